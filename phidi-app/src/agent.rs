@@ -0,0 +1,215 @@
+use std::rc::Rc;
+
+use floem::{
+    keyboard::Modifiers,
+    reactive::{RwSignal, Scope, SignalGet, SignalUpdate, SignalWith},
+};
+use phidi_core::{command::EditCommand, mode::Mode};
+use phidi_rpc::agent::{AgentEvent, AgentSessionId};
+use phidi_xi_rope::Rope;
+
+use crate::{
+    command::{CommandExecuted, CommandKind},
+    editor::EditorData,
+    keypress::{KeyPressFocus, condition::Condition},
+    main_split::Editors,
+    window_tab::CommonData,
+};
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum AgentEntry {
+    User(String),
+    Assistant(String),
+    Tool {
+        id: String,
+        name: String,
+        arguments: String,
+        /// The output of the tool and whether it failed, once it has run.
+        result: Option<(String, bool)>,
+    },
+    Approval {
+        id: String,
+        description: String,
+        approved: Option<bool>,
+    },
+    Error(String),
+}
+
+#[derive(Clone, Debug)]
+pub struct AgentData {
+    pub session: RwSignal<AgentSessionId>,
+    pub editor: EditorData,
+    pub entries: RwSignal<im::Vector<AgentEntry>>,
+    /// Whether the agent is working on a message.
+    pub running: RwSignal<bool>,
+    pub common: Rc<CommonData>,
+}
+
+impl KeyPressFocus for AgentData {
+    fn get_mode(&self) -> Mode {
+        Mode::Insert
+    }
+
+    fn check_condition(&self, condition: Condition) -> bool {
+        matches!(condition, Condition::PanelFocus)
+    }
+
+    fn run_command(
+        &self,
+        command: &crate::command::PhidiCommand,
+        count: Option<usize>,
+        mods: Modifiers,
+    ) -> CommandExecuted {
+        match &command.kind {
+            CommandKind::Workbench(_) => {}
+            CommandKind::Scroll(_) => {}
+            CommandKind::Focus(_) => {}
+            CommandKind::Edit(EditCommand::InsertNewLine) => {
+                self.send();
+                return CommandExecuted::Yes;
+            }
+            CommandKind::Edit(_)
+            | CommandKind::Move(_)
+            | CommandKind::MultiSelection(_) => {
+                return self.editor.run_command(command, count, mods);
+            }
+            CommandKind::MotionMode(_) => {}
+        }
+        CommandExecuted::No
+    }
+
+    fn receive_char(&self, c: &str) {
+        self.editor.receive_char(c);
+    }
+}
+
+impl AgentData {
+    pub fn new(cx: Scope, editors: Editors, common: Rc<CommonData>) -> Self {
+        Self {
+            session: cx.create_rw_signal(AgentSessionId::next()),
+            editor: editors.make_local(cx, common.clone()),
+            entries: cx.create_rw_signal(im::Vector::new()),
+            running: cx.create_rw_signal(false),
+            common,
+        }
+    }
+
+    /// Send the content of the input to the agent.
+    pub fn send(&self) {
+        if self.running.get_untracked() {
+            return;
+        }
+        let message = self.editor.doc().buffer.with_untracked(|b| b.to_string());
+        let message = message.trim();
+        if message.is_empty() {
+            return;
+        }
+
+        let settings = self.common.config.get_untracked().agent.settings();
+        self.common.proxy.agent_send(
+            self.session.get_untracked(),
+            settings,
+            message.to_string(),
+        );
+        self.entries.update(|entries| {
+            entries.push_back(AgentEntry::User(message.to_string()))
+        });
+        self.running.set(true);
+        self.editor.doc().reload(Rope::from(""), true);
+    }
+
+    pub fn stop(&self) {
+        self.common.proxy.agent_cancel(self.session.get_untracked());
+    }
+
+    /// Forget the current conversation and start a new one.
+    pub fn new_conversation(&self) {
+        self.common.proxy.agent_close(self.session.get_untracked());
+        self.session.set(AgentSessionId::next());
+        self.entries.set(im::Vector::new());
+        self.running.set(false);
+    }
+
+    pub fn approve(&self, id: &str, approved: bool) {
+        self.common.proxy.agent_approve(
+            self.session.get_untracked(),
+            id.to_string(),
+            approved,
+        );
+        self.entries.update(|entries| {
+            for entry in entries.iter_mut() {
+                if let AgentEntry::Approval {
+                    id: entry_id,
+                    approved: answer,
+                    ..
+                } = entry
+                {
+                    if entry_id == id {
+                        *answer = Some(approved);
+                    }
+                }
+            }
+        });
+    }
+
+    pub fn handle_event(&self, session: AgentSessionId, event: &AgentEvent) {
+        // Events of a conversation that has been closed can still arrive.
+        if session != self.session.get_untracked() {
+            return;
+        }
+        match event {
+            AgentEvent::Message { content } => {
+                self.push(AgentEntry::Assistant(content.clone()));
+            }
+            AgentEvent::ToolCall {
+                id,
+                name,
+                arguments,
+            } => {
+                self.push(AgentEntry::Tool {
+                    id: id.clone(),
+                    name: name.clone(),
+                    arguments: arguments.clone(),
+                    result: None,
+                });
+            }
+            AgentEvent::ToolResult {
+                id,
+                content,
+                is_error,
+            } => {
+                self.entries.update(|entries| {
+                    let tool =
+                        entries.iter_mut().rev().find_map(|entry| match entry {
+                            AgentEntry::Tool {
+                                id: tool_id,
+                                result,
+                                ..
+                            } if tool_id == id => Some(result),
+                            _ => None,
+                        });
+                    if let Some(result) = tool {
+                        *result = Some((content.clone(), *is_error));
+                    }
+                });
+            }
+            AgentEvent::ApprovalRequest { id, description } => {
+                self.push(AgentEntry::Approval {
+                    id: id.clone(),
+                    description: description.clone(),
+                    approved: None,
+                });
+            }
+            AgentEvent::Error { message } => {
+                self.push(AgentEntry::Error(message.clone()));
+            }
+            AgentEvent::Done => {
+                self.running.set(false);
+            }
+        }
+    }
+
+    fn push(&self, entry: AgentEntry) {
+        self.entries.update(|entries| entries.push_back(entry));
+    }
+}
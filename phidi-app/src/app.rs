@@ -80,7 +80,7 @@
     db::PhidiDb,
     debug::RunDebugMode,
     editor::{
-        diff::diff_show_more_section_view,
+        diff::{diff_hunk_actions_view, diff_show_more_section_view},
         location::{EditorLocation, EditorPosition},
         view::editor_container_view,
     },
@@ -1386,6 +1386,15 @@ fn editor_tab_content(
                             &diff_editor_data.left,
                             &diff_editor_data.right,
                         ),
+                        {
+                            let proposals = window_tab_data.proposals.clone();
+                            let right_doc = diff_editor_data.right.doc_signal();
+                            diff_hunk_actions_view(
+                                &diff_editor_data.left,
+                                &diff_editor_data.right,
+                                move || proposals.is_review_doc(&right_doc.get()),
+                            )
+                        },
                     ))
                     .style(|s: Style| s.size_full())
                     .on_cleanup(move || {
@@ -2508,6 +2517,37 @@ fn palette_item(
                 .style(|s| s.align_items(Some(AlignItems::Center)).max_width_full()),
             )
         }
+        PaletteItemContent::SCMBranch { branch } => {
+            let text = item.filter_text;
+            let indices = item.indices;
+            let mut hint = branch.upstream.clone().unwrap_or_default();
+            if branch.ahead > 0 {
+                hint.push_str(&format!(" ↑{}", branch.ahead));
+            }
+            if branch.behind > 0 {
+                hint.push_str(&format!(" ↓{}", branch.behind));
+            }
+            if branch.is_head {
+                hint.push_str(" (current)");
+            }
+            container(
+                stack((
+                    focus_text(
+                        move || text.clone(),
+                        move || indices.clone(),
+                        move || config.get().color(PhidiColor::EDITOR_FOCUS),
+                    )
+                    .style(|s| s.margin_right(6.0).max_width_full()),
+                    label(move || hint.trim().to_string()).style(move |s| {
+                        s.color(config.get().color(PhidiColor::EDITOR_DIM))
+                            .min_width(0.0)
+                            .flex_grow(1.0)
+                            .flex_basis(0.0)
+                    }),
+                ))
+                .style(|s| s.align_items(Some(AlignItems::Center)).max_width_full()),
+            )
+        }
         PaletteItemContent::PaletteHelp { .. }
         | PaletteItemContent::Command { .. } => {
             let text = item.filter_text;
@@ -2562,6 +2602,7 @@ fn palette_item(
         | PaletteItemContent::LineEnding { .. }
         | PaletteItemContent::ColorTheme { .. }
         | PaletteItemContent::SCMReference { .. }
+        | PaletteItemContent::SCMStash { .. }
         | PaletteItemContent::TerminalProfile { .. }
         | PaletteItemContent::IconTheme { .. } => {
             let text = item.filter_text;
@@ -2575,6 +2616,26 @@ fn palette_item(
                 .style(|s| s.align_items(Some(AlignItems::Center)).max_width_full()),
             )
         }
+        PaletteItemContent::ColorPresentation {
+            label: text, color, ..
+        } => {
+            let text = text.clone();
+            let swatch = Color::from_rgba8(
+                (color.red * 255.0).round() as u8,
+                (color.green * 255.0).round() as u8,
+                (color.blue * 255.0).round() as u8,
+                (color.alpha * 255.0).round() as u8,
+            );
+            container(
+                stack((
+                    label(|| "■".to_string()).style(move |s| {
+                        s.color(swatch).margin_right(6.0).selectable(false)
+                    }),
+                    label(move || text.clone()),
+                ))
+                .style(|s| s.align_items(Some(AlignItems::Center)).max_width_full()),
+            )
+        }
         #[cfg(windows)]
         PaletteItemContent::WslHost { .. } => {
             let text = item.filter_text;
@@ -2607,10 +2668,12 @@ fn palette_input(window_tab_data: Rc<WindowTabData>) -> impl View {
     let focus = window_tab_data.common.focus;
     let is_focused = move || focus.get() == Focus::Palette;
 
+    let palette = window_tab_data.palette.clone();
     let input = TextInputBuilder::new()
         .is_focused(is_focused)
         .build_editor(editor)
-        .placeholder(move || window_tab_data.palette.placeholder_text().to_owned())
+        .placeholder(move || window_tab_data.palette.placeholder_text())
+        .masked(move || palette.is_masked())
         .style(|s| s.width_full());
 
     container(container(input).style(move |s| {
@@ -3182,10 +3245,18 @@ fn code_action(window_tab_data: Rc<WindowTabData>) -> impl View {
                 },
                 move |(i, _item)| (request_id(), *i),
                 move |(i, item)| {
-                    container(
-                        text(item.title().replace('\n', " "))
-                            .style(|s| s.text_ellipsis().min_width(0.0)),
-                    )
+                    let source = item.source.clone().unwrap_or_default();
+                    let has_source = !source.is_empty();
+                    stack((
+                        text(item.title().replace('\n', " ")).style(|s| {
+                            s.text_ellipsis().min_width(0.0).flex_grow(1.0)
+                        }),
+                        text(source).style(move |s| {
+                            s.apply_if(!has_source, |s| s.hide())
+                                .margin_left(10.0)
+                                .color(config.get().color(PhidiColor::EDITOR_DIM))
+                        }),
+                    ))
                     .on_click_stop(move |_| {
                         let code_action = code_action.get_untracked();
                         code_action.active.set(i);
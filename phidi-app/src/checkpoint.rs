@@ -0,0 +1,131 @@
+//! Checkpoints of the workspace. They are taken and restored by the proxy,
+//! which sends the updated list whenever it changes, including when the agent
+//! takes one before it gets to work.
+
+use std::{path::PathBuf, rc::Rc};
+
+use floem::{
+    ext_event::create_ext_action,
+    reactive::{RwSignal, Scope, SignalUpdate},
+};
+use phidi_rpc::{
+    checkpoint::{Checkpoint, RestoreResult},
+    proxy::ProxyResponse,
+};
+
+use crate::{
+    alert::AlertButton, command::InternalCommand, listener::Listener,
+    window_tab::CommonData,
+};
+
+#[derive(Clone)]
+pub struct CheckpointData {
+    pub checkpoints: RwSignal<im::Vector<Checkpoint>>,
+    pub common: Rc<CommonData>,
+}
+
+impl CheckpointData {
+    pub fn new(cx: Scope, common: Rc<CommonData>) -> Self {
+        Self {
+            checkpoints: cx.create_rw_signal(im::Vector::new()),
+            common,
+        }
+    }
+
+    pub fn load(&self) {
+        let checkpoints = self.checkpoints;
+        let send = create_ext_action(self.common.scope, move |result| {
+            if let Ok(ProxyResponse::ListCheckpointsResponse { checkpoints: list }) =
+                result
+            {
+                checkpoints.set(list.into());
+            }
+        });
+        self.common
+            .proxy
+            .list_checkpoints(move |result| send(result));
+    }
+
+    pub fn set(&self, checkpoints: &[Checkpoint]) {
+        self.checkpoints.set(checkpoints.iter().cloned().collect());
+    }
+
+    pub fn create(&self) {
+        self.common
+            .proxy
+            .create_checkpoint("Manual checkpoint".to_string(), |_| {});
+    }
+
+    pub fn delete(&self, id: &str) {
+        self.common.proxy.delete_checkpoint(id.to_string(), |_| {});
+    }
+
+    /// Ask for confirmation, then roll the workspace back to the checkpoint.
+    pub fn restore(&self, checkpoint: &Checkpoint) {
+        let internal_command = self.common.internal_command;
+        let restore = {
+            let common = self.common.clone();
+            let id = checkpoint.id.clone();
+            move || {
+                internal_command.send(InternalCommand::HideAlert);
+                let workspace = common.workspace.path.clone();
+                let send = create_ext_action(common.scope, move |result| {
+                    if let Ok(ProxyResponse::RestoreCheckpointResponse { result }) =
+                        result
+                    {
+                        report_conflicts(internal_command, &result, workspace);
+                    }
+                });
+                common
+                    .proxy
+                    .restore_checkpoint(id.clone(), move |result| send(result));
+            }
+        };
+        internal_command.send(InternalCommand::ShowAlert {
+            title: format!("Restore \"{}\"?", checkpoint.label),
+            msg: "Files are written back to how they were at the checkpoint. \
+                  The current state is saved as a checkpoint first."
+                .to_string(),
+            buttons: vec![AlertButton {
+                text: "Restore".to_string(),
+                action: Rc::new(restore),
+            }],
+        });
+    }
+}
+
+fn report_conflicts(
+    internal_command: Listener<InternalCommand>,
+    result: &RestoreResult,
+    workspace: Option<PathBuf>,
+) {
+    if result.conflicts.is_empty() {
+        return;
+    }
+    let files = result
+        .conflicts
+        .iter()
+        .map(|path| {
+            workspace
+                .as_deref()
+                .and_then(|workspace| path.strip_prefix(workspace).ok())
+                .unwrap_or(path)
+                .display()
+                .to_string()
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    internal_command.send(InternalCommand::ShowAlert {
+        title: "Some files weren't restored".to_string(),
+        msg: format!(
+            "These files have unsaved changes, save or revert them and restore \
+             again:\n{files}"
+        ),
+        buttons: vec![AlertButton {
+            text: "OK".to_string(),
+            action: Rc::new(move || {
+                internal_command.send(InternalCommand::HideAlert);
+            }),
+        }],
+    });
+}
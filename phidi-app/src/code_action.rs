@@ -7,7 +7,7 @@
 };
 use lsp_types::CodeActionOrCommand;
 use phidi_core::{command::FocusCommand, mode::Mode, movement::Movement};
-use phidi_rpc::plugin::PluginId;
+use phidi_rpc::{plugin::PluginId, proxy::SourcedCodeAction};
 
 use crate::{
     command::{CommandExecuted, CommandKind, InternalCommand},
@@ -25,6 +25,8 @@ pub enum CodeActionStatus {
 pub struct ScoredCodeActionItem {
     pub item: CodeActionOrCommand,
     pub plugin_id: PluginId,
+    /// The name of the server, when the actions are from more than one.
+    pub source: Option<String>,
     pub score: i64,
     pub indices: Vec<usize>,
 }
@@ -166,8 +168,7 @@ pub fn previous_page(&self) {
 
     pub fn show(
         &mut self,
-        plugin_id: PluginId,
-        code_actions: im::Vector<CodeActionOrCommand>,
+        code_actions: im::Vector<SourcedCodeAction>,
         offset: usize,
         mouse_click: bool,
     ) {
@@ -179,8 +180,9 @@ pub fn show(
         self.items = code_actions
             .into_iter()
             .map(|code_action| ScoredCodeActionItem {
-                item: code_action,
-                plugin_id,
+                item: code_action.action,
+                plugin_id: code_action.plugin_id,
+                source: code_action.source,
                 score: 0,
                 indices: Vec::new(),
             })
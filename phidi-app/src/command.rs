@@ -14,7 +14,8 @@
 use phidi_rpc::{
     dap_types::{DapId, RunDebugConfig},
     plugin::{PluginId, VoltID},
-    proxy::ProxyStatus,
+    proxy::{ProxyStatus, SourcedCodeAction},
+    source_control::FileDiff,
     terminal::{TermId, TerminalProfile},
 };
 use serde_json::Value;
@@ -187,6 +188,10 @@ pub enum PhidiWorkbenchCommand {
     #[strum(message = "Show Call Hierarchy")]
     ShowCallHierarchy,
 
+    #[strum(serialize = "show_type_hierarchy")]
+    #[strum(message = "Show Type Hierarchy")]
+    ShowTypeHierarchy,
+
     #[strum(serialize = "find_references")]
     #[strum(message = "Find References")]
     FindReferences,
@@ -466,6 +471,10 @@ pub enum PhidiWorkbenchCommand {
     #[strum(serialize = "toggle_search_focus")]
     ToggleSearchFocus,
 
+    #[strum(message = "Toggle Commit History Focus")]
+    #[strum(serialize = "toggle_git_log_focus")]
+    ToggleGitLogFocus,
+
     // Visual toggle commands
     #[strum(serialize = "toggle_terminal_visual")]
     ToggleTerminalVisual,
@@ -516,6 +525,102 @@ pub enum PhidiWorkbenchCommand {
     #[strum(serialize = "source_control_discard_workspace_changes")]
     SourceControlDiscardWorkspaceChanges,
 
+    #[strum(message = "Source Control: Stage File")]
+    #[strum(serialize = "source_control_stage_active_file")]
+    SourceControlStageActiveFile,
+
+    #[strum(message = "Source Control: Unstage File")]
+    #[strum(serialize = "source_control_unstage_active_file")]
+    SourceControlUnstageActiveFile,
+
+    #[strum(message = "Source Control: Stage Selected Lines")]
+    #[strum(serialize = "source_control_stage_selected_lines")]
+    SourceControlStageSelectedLines,
+
+    #[strum(message = "Source Control: Unstage Selected Lines")]
+    #[strum(serialize = "source_control_unstage_selected_lines")]
+    SourceControlUnstageSelectedLines,
+
+    #[strum(message = "Source Control: Stage All Changes")]
+    #[strum(serialize = "source_control_stage_all")]
+    SourceControlStageAll,
+
+    #[strum(message = "Source Control: Unstage All Changes")]
+    #[strum(serialize = "source_control_unstage_all")]
+    SourceControlUnstageAll,
+
+    #[strum(message = "Source Control: Show Commit of Line")]
+    #[strum(serialize = "source_control_show_line_commit")]
+    SourceControlShowLineCommit,
+
+    #[strum(message = "Source Control: Toggle Blame Gutter")]
+    #[strum(serialize = "source_control_toggle_blame_gutter")]
+    SourceControlToggleBlameGutter,
+
+    #[strum(message = "Source Control: Show File History")]
+    #[strum(serialize = "source_control_show_file_history")]
+    SourceControlShowFileHistory,
+
+    #[strum(message = "Source Control: Create Branch")]
+    #[strum(serialize = "source_control_create_branch")]
+    SourceControlCreateBranch,
+
+    #[strum(message = "Source Control: Rename Branch")]
+    #[strum(serialize = "source_control_rename_branch")]
+    SourceControlRenameBranch,
+
+    #[strum(message = "Source Control: Delete Branch")]
+    #[strum(serialize = "source_control_delete_branch")]
+    SourceControlDeleteBranch,
+
+    #[strum(message = "Source Control: Merge Branch")]
+    #[strum(serialize = "source_control_merge_branch")]
+    SourceControlMergeBranch,
+
+    #[strum(message = "Source Control: Create Tag")]
+    #[strum(serialize = "source_control_create_tag")]
+    SourceControlCreateTag,
+
+    #[strum(message = "Source Control: Create Annotated Tag")]
+    #[strum(serialize = "source_control_create_annotated_tag")]
+    SourceControlCreateAnnotatedTag,
+
+    #[strum(message = "Source Control: Stash Changes")]
+    #[strum(serialize = "source_control_stash")]
+    SourceControlStash,
+
+    #[strum(message = "Source Control: Stash Changes Including Untracked Files")]
+    #[strum(serialize = "source_control_stash_include_untracked")]
+    SourceControlStashIncludeUntracked,
+
+    #[strum(message = "Source Control: Apply Stash")]
+    #[strum(serialize = "source_control_stash_apply")]
+    SourceControlStashApply,
+
+    #[strum(message = "Source Control: Pop Stash")]
+    #[strum(serialize = "source_control_stash_pop")]
+    SourceControlStashPop,
+
+    #[strum(message = "Source Control: Drop Stash")]
+    #[strum(serialize = "source_control_stash_drop")]
+    SourceControlStashDrop,
+
+    #[strum(message = "Source Control: Fetch")]
+    #[strum(serialize = "source_control_fetch")]
+    SourceControlFetch,
+
+    #[strum(message = "Source Control: Pull")]
+    #[strum(serialize = "source_control_pull")]
+    SourceControlPull,
+
+    #[strum(message = "Source Control: Pull (Rebase)")]
+    #[strum(serialize = "source_control_pull_rebase")]
+    SourceControlPullRebase,
+
+    #[strum(message = "Source Control: Push")]
+    #[strum(serialize = "source_control_push")]
+    SourceControlPush,
+
     #[strum(serialize = "export_current_theme_settings")]
     #[strum(message = "Export current settings to a theme file")]
     ExportCurrentThemeSettings,
@@ -532,6 +637,14 @@ pub enum PhidiWorkbenchCommand {
     #[strum(message = "Change current file line ending")]
     ChangeFileLineEnding,
 
+    #[strum(serialize = "format_selection")]
+    #[strum(message = "Format Selection")]
+    FormatSelection,
+
+    #[strum(serialize = "show_color_picker")]
+    #[strum(message = "Show Color Picker")]
+    ShowColorPicker,
+
     #[strum(serialize = "next_editor_tab")]
     #[strum(message = "Next Editor Tab")]
     NextEditorTab,
@@ -589,6 +702,18 @@ pub enum PhidiWorkbenchCommand {
     #[strum(serialize = "diff_files")]
     DiffFiles,
 
+    #[strum(message = "Copy Context Pack as Markdown")]
+    #[strum(serialize = "copy_context_pack")]
+    CopyContextPack,
+
+    #[strum(message = "Copy Context Pack as JSON")]
+    #[strum(serialize = "copy_context_pack_json")]
+    CopyContextPackJson,
+
+    #[strum(message = "Save Context Pack")]
+    #[strum(serialize = "save_context_pack")]
+    SaveContextPack,
+
     #[strum(serialize = "quit")]
     #[strum(message = "Quit Editor")]
     Quit,
@@ -618,6 +743,16 @@ pub enum InternalCommand {
     OpenFileChanges {
         path: PathBuf,
     },
+    /// Open the changes a commit made to a file
+    OpenCommitFileChanges {
+        path: PathBuf,
+        commit: String,
+    },
+    /// Open the changes a commit made to one of the files it changed
+    OpenCommitChanges {
+        commit: String,
+        diff: FileDiff,
+    },
     ReloadFileExplorer,
     /// Test whether a file/directory can be created at that path
     TestPathCreation {
@@ -690,8 +825,7 @@ pub enum InternalCommand {
     ShowCodeActions {
         offset: usize,
         mouse_click: bool,
-        plugin_id: PluginId,
-        code_actions: im::Vector<CodeActionOrCommand>,
+        code_actions: im::Vector<SourcedCodeAction>,
     },
     RunCodeAction {
         plugin_id: PluginId,
@@ -788,7 +922,7 @@ pub enum InternalCommand {
         tab_index: usize,
         terminal_index: usize,
     },
-    CallHierarchyIncoming {
+    HierarchyChildren {
         item_id: ViewId,
     },
     StopTerminal {
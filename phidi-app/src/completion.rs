@@ -1,4 +1,6 @@
-use std::{borrow::Cow, path::PathBuf, str::FromStr, sync::Arc};
+use std::{
+    borrow::Cow, collections::HashSet, path::PathBuf, str::FromStr, sync::Arc,
+};
 
 use floem::{
     peniko::kurbo::Rect,
@@ -6,8 +8,8 @@
     views::editor::{id::EditorId, text::Document},
 };
 use lsp_types::{
-    CompletionItem, CompletionResponse, CompletionTextEdit, InsertTextFormat,
-    Position,
+    CompletionItem, CompletionItemKind, CompletionResponse, CompletionTextEdit,
+    InsertTextFormat, Position,
 };
 use nucleo::Utf32Str;
 use phidi_core::{
@@ -106,17 +108,25 @@ pub fn receive(
             // TODO: Possibly handle the 'is_incomplete' field on List.
             CompletionResponse::List(list) => &list.items,
         };
-        let items: im::Vector<ScoredCompletionItem> = items
-            .iter()
-            .map(|i| ScoredCompletionItem {
-                item: i.to_owned(),
-                plugin_id,
-                score: 0,
-                label_score: 0,
-                indices: Vec::new(),
-            })
-            .collect();
-        self.input_items.insert(input.to_string(), items);
+        // Several language servers can answer the same request, so their items
+        // are merged, leaving out those another server already offered.
+        let existing = self.input_items.entry(input.to_string()).or_default();
+        let new_items: Vec<ScoredCompletionItem> = {
+            let mut offered: HashSet<_> =
+                existing.iter().map(|i| completion_key(&i.item)).collect();
+            items
+                .iter()
+                .filter(|&i| offered.insert(completion_key(i)))
+                .map(|i| ScoredCompletionItem {
+                    item: i.to_owned(),
+                    plugin_id,
+                    score: 0,
+                    label_score: 0,
+                    indices: Vec::new(),
+                })
+                .collect()
+        };
+        existing.extend(new_items);
         self.filter_items();
     }
 
@@ -415,3 +425,77 @@ fn completion_lens_text(
         Some(Some(item.to_string()))
     }
 }
+
+/// What makes two completion items the same: their label, their kind and the
+/// text they insert.
+fn completion_key(
+    item: &CompletionItem,
+) -> (&str, Option<CompletionItemKind>, &str) {
+    let inserted_text = match &item.text_edit {
+        Some(CompletionTextEdit::Edit(edit)) => Some(edit.new_text.as_str()),
+        Some(CompletionTextEdit::InsertAndReplace(edit)) => {
+            Some(edit.new_text.as_str())
+        }
+        None => item.insert_text.as_deref(),
+    };
+
+    (&item.label, item.kind, inserted_text.unwrap_or(&item.label))
+}
+
+#[cfg(test)]
+mod tests {
+    use lsp_types::{
+        CompletionItem, CompletionItemKind, CompletionTextEdit, Position, Range,
+        TextEdit,
+    };
+
+    use super::completion_key;
+
+    fn item(
+        label: &str,
+        kind: CompletionItemKind,
+        insert_text: Option<&str>,
+    ) -> CompletionItem {
+        CompletionItem {
+            label: label.to_string(),
+            kind: Some(kind),
+            insert_text: insert_text.map(|t| t.to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_completion_key() {
+        let function = CompletionItemKind::FUNCTION;
+        let variable = CompletionItemKind::VARIABLE;
+
+        // Without any text to insert, the label is what is inserted.
+        assert_eq!(
+            completion_key(&item("len", function, None)),
+            completion_key(&item("len", function, Some("len")))
+        );
+        assert_ne!(
+            completion_key(&item("len", function, None)),
+            completion_key(&item("len", variable, None))
+        );
+        assert_ne!(
+            completion_key(&item("len", function, Some("len()"))),
+            completion_key(&item("len", function, None))
+        );
+        assert_ne!(
+            completion_key(&item("len", function, None)),
+            completion_key(&item("size", function, Some("len")))
+        );
+
+        // A text edit takes precedence over the insert text.
+        let mut edited = item("len", function, Some("size"));
+        edited.text_edit = Some(CompletionTextEdit::Edit(TextEdit::new(
+            Range::new(Position::new(0, 0), Position::new(0, 1)),
+            "len".to_string(),
+        )));
+        assert_eq!(
+            completion_key(&edited),
+            completion_key(&item("len", function, None))
+        );
+    }
+}
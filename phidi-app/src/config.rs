@@ -12,12 +12,13 @@
 use parking_lot::RwLock;
 use phidi_core::directory::Directory;
 use phidi_proxy::plugin::wasi::find_all_volts;
-use phidi_rpc::plugin::VoltID;
+use phidi_rpc::plugin::{LspServerConfig, VoltID};
 use serde::Deserialize;
 use strum::VariantNames;
 use tracing::error;
 
 use self::{
+    agent::AgentConfig,
     color::PhidiColor,
     color_theme::{ColorThemeConfig, ThemeColor, ThemeColorPreference},
     core::CoreConfig,
@@ -30,6 +31,7 @@
 };
 use crate::workspace::{PhidiWorkspace, PhidiWorkspaceType};
 
+pub mod agent;
 pub mod color;
 pub mod color_theme;
 pub mod core;
@@ -100,12 +102,18 @@ pub struct PhidiConfig {
     pub ui: UIConfig,
     pub editor: EditorConfig,
     pub terminal: TerminalConfig,
+    pub agent: AgentConfig,
     #[serde(default)]
     pub color_theme: ColorThemeConfig,
     #[serde(default)]
     pub icon_theme: IconThemeConfig,
     #[serde(flatten)]
     pub plugins: HashMap<String, HashMap<String, serde_json::Value>>,
+    /// The `[lsp.<name>]` tables of the user settings, read from the file as
+    /// they are since the environment and the initialization options are
+    /// case sensitive. The proxy adds the ones of the workspace settings.
+    #[serde(skip)]
+    pub lsp_servers: HashMap<String, LspServerConfig>,
     #[serde(skip)]
     pub color: ThemeColor,
     #[serde(skip)]
@@ -143,6 +151,9 @@ pub fn load(
             }
         };
 
+        phidi_config.plugins.remove("lsp");
+        phidi_config.lsp_servers = Self::load_lsp_servers();
+
         phidi_config.available_color_themes =
             Self::load_color_themes(disabled_volts, extra_plugin_paths);
         phidi_config.available_icon_themes =
@@ -249,6 +260,27 @@ fn default_config() -> config::Config {
             .unwrap()
     }
 
+    fn load_lsp_servers() -> HashMap<String, LspServerConfig> {
+        #[derive(Deserialize)]
+        struct LspSettings {
+            #[serde(default)]
+            lsp: HashMap<String, LspServerConfig>,
+        }
+
+        let Some(content) = Self::settings_file()
+            .and_then(|path| std::fs::read_to_string(path).ok())
+        else {
+            return HashMap::new();
+        };
+        match toml::from_str::<LspSettings>(&content) {
+            Ok(settings) => settings.lsp,
+            Err(err) => {
+                error!("Failed to read the language servers of the settings: {err}");
+                HashMap::new()
+            }
+        }
+    }
+
     fn default_phidi_config() -> PhidiConfig {
         let mut default_phidi_config: PhidiConfig =
             DEFAULT_CONFIG.clone().try_deserialize().expect("Failed to deserialize default config, this likely indicates a missing or misnamed field in settings.toml");
@@ -297,6 +329,7 @@ fn resolve_theme(&mut self, workspace: &PhidiWorkspace) {
                 self.icon_theme.path = icon_theme_path.clone().unwrap_or_default();
             }
             self.plugins = new.plugins;
+            self.plugins.remove("lsp");
         }
         self.resolve_colors(Some(&default_phidi_config));
         self.update_id();
@@ -910,6 +943,14 @@ pub fn get_dropdown_info(&self, kind: &str, key: &str) -> Option<DropdownInfo> {
                     .unwrap_or(0),
                 items: self.wrap_style_list.clone(),
             }),
+            ("editor", "format-on-save-mode") => Some(DropdownInfo {
+                active_index: self.editor.format_on_save_mode as usize,
+                items: editor::FormatOnSaveMode::VARIANTS
+                    .iter()
+                    .map(|s| s.to_string())
+                    .sorted()
+                    .collect(),
+            }),
             ("ui", "tab-close-button") => Some(DropdownInfo {
                 active_index: self.ui.tab_close_button as usize,
                 items: ui::TabCloseButton::VARIANTS
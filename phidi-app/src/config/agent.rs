@@ -0,0 +1,41 @@
+use phidi_rpc::agent::AgentSettings;
+use serde::{Deserialize, Serialize};
+use structdesc::FieldNames;
+
+#[derive(FieldNames, Debug, Clone, Deserialize, Serialize, Default)]
+#[serde(rename_all = "kebab-case")]
+pub struct AgentConfig {
+    #[field_names(
+        desc = "The base url of an OpenAI compatible api, e.g. https://api.openai.com/v1 or http://localhost:11434/v1"
+    )]
+    pub endpoint: String,
+    #[field_names(desc = "The model the agent uses")]
+    pub model: String,
+    #[field_names(
+        desc = "The api key of the endpoint. If empty, the OPENAI_API_KEY environment variable is used."
+    )]
+    pub api_key: String,
+    #[field_names(
+        desc = "The maximum number of model requests the agent makes to answer a message"
+    )]
+    pub max_steps: usize,
+    #[field_names(
+        desc = "The approximate number of tokens a context pack may take, 0 for no limit"
+    )]
+    pub context_pack_token_budget: usize,
+    #[field_names(
+        desc = "Serve the workspace tools to external agents over the Model Context Protocol, for `phidi-proxy --mcp` to connect to. Takes effect for newly opened workspaces."
+    )]
+    pub mcp_server: bool,
+}
+
+impl AgentConfig {
+    pub fn settings(&self) -> AgentSettings {
+        AgentSettings {
+            endpoint: self.endpoint.clone(),
+            model: self.model.clone(),
+            api_key: self.api_key.clone(),
+            max_steps: self.max_steps,
+        }
+    }
+}
@@ -71,6 +71,8 @@ impl PhidiColor {
     pub const EDITOR_DEBUG_BREAK_LINE: &'static str = "editor.debug_break_line";
     pub const EDITOR_CURRENT_LINE: &'static str = "editor.current_line";
     pub const EDITOR_LINK: &'static str = "editor.link";
+    pub const EDITOR_HIGHLIGHT_READ: &'static str = "editor.highlight_read";
+    pub const EDITOR_HIGHLIGHT_WRITE: &'static str = "editor.highlight_write";
     pub const EDITOR_VISIBLE_WHITESPACE: &'static str = "editor.visible_whitespace";
     pub const EDITOR_INDENT_GUIDE: &'static str = "editor.indent_guide";
     pub const EDITOR_DRAG_DROP_BACKGROUND: &'static str =
@@ -57,6 +57,27 @@ fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
     }
 }
 
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Deserialize,
+    Serialize,
+    Default,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    strum_macros::VariantNames,
+)]
+pub enum FormatOnSaveMode {
+    /// Format the whole file
+    #[default]
+    File,
+    /// Format only the lines changed since the last commit
+    Modifications,
+}
+
 #[derive(FieldNames, Debug, Clone, Deserialize, Serialize, Default)]
 #[serde(rename_all = "kebab-case")]
 pub struct EditorConfig {
@@ -134,6 +155,14 @@ pub struct EditorConfig {
         desc = "Whether it should format the document on save (if there is an available formatter)"
     )]
     pub format_on_save: bool,
+    #[field_names(
+        desc = "Whether format on save formats the whole file or only the lines modified since the last commit (the whole file when it isn't in a git repository)"
+    )]
+    pub format_on_save_mode: FormatOnSaveMode,
+    #[field_names(
+        desc = "Whether the language server should format the line after typing one of its trigger characters"
+    )]
+    pub format_on_type: bool,
 
     #[field_names(
         desc = "Whether newlines should be automatically converted to the current line ending"
@@ -143,12 +172,27 @@ pub struct EditorConfig {
     #[field_names(desc = "If matching brackets are highlighted")]
     pub highlight_matching_brackets: bool,
 
+    #[field_names(
+        desc = "If the occurrences of the symbol under the cursor are highlighted. The language server tells reads and writes apart when it supports it."
+    )]
+    pub highlight_selection_occurrences: bool,
+
     #[field_names(desc = "If scope lines are highlighted")]
     pub highlight_scope_lines: bool,
 
     #[field_names(desc = "If inlay hints should be displayed")]
     pub enable_inlay_hints: bool,
 
+    #[field_names(
+        desc = "If a swatch should be displayed before the colors the language server finds"
+    )]
+    pub enable_color_decorators: bool,
+
+    #[field_names(
+        desc = "If editing the name of a tag also edits the name of its closing or opening tag (or other ranges the language server links together)"
+    )]
+    pub linked_editing: bool,
+
     #[field_names(
         desc = "Set the inlay hint font family. If empty, it uses the editor font family."
     )]
@@ -196,6 +240,14 @@ pub struct EditorConfig {
         desc = "Set the completion lens font size. If 0 it uses the inlay hint font size."
     )]
     pub completion_lens_font_size: usize,
+    #[field_names(
+        desc = "If the author and the commit which last changed the line of the cursor should be displayed after it"
+    )]
+    pub enable_inline_blame: bool,
+    #[field_names(
+        desc = "If the author and the date of the commit which last changed each part of the file should be displayed in the gutter"
+    )]
+    pub show_blame_gutter: bool,
     #[field_names(
         desc = "Set the cursor blink interval (in milliseconds). Set to 0 to completely disable."
     )]
@@ -262,6 +314,11 @@ pub fn line_height(&self) -> usize {
         (line_height.round() as usize).max(self.font_size)
     }
 
+    /// Whether the blame of the files is displayed anywhere.
+    pub fn blame_shown(&self) -> bool {
+        self.enable_inline_blame || self.show_blame_gutter
+    }
+
     pub fn inlay_hint_font_size(&self) -> usize {
         if self.inlay_hint_font_size < 5
             || self.inlay_hint_font_size > self.font_size
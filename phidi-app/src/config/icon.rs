@@ -115,6 +115,14 @@ impl PhidiIcons {
 
     pub const IMPLEMENTATION: &'static str = "implementation";
 
+    pub const PROPOSALS: &'static str = "proposals";
+
+    pub const AGENT: &'static str = "agent";
+
+    pub const CHECKPOINTS: &'static str = "checkpoints";
+
+    pub const GIT_LOG: &'static str = "git_log";
+
     pub const SYMBOL_KIND_ARRAY: &'static str = "symbol_kind.array";
     pub const SYMBOL_KIND_BOOLEAN: &'static str = "symbol_kind.boolean";
     pub const SYMBOL_KIND_CLASS: &'static str = "symbol_kind.class";
@@ -54,6 +54,16 @@ pub struct UIConfig {
     #[field_names(desc = "Trim whitespace from search results")]
     pub trim_search_results_whitespace: bool,
 
+    #[field_names(
+        desc = "Set the maximum number of global search results. If 0, there is no limit"
+    )]
+    pub search_max_results: usize,
+
+    #[field_names(
+        desc = "Set the number of lines shown before and after each global search result"
+    )]
+    pub search_context_lines: usize,
+
     #[field_names(desc = "Set the line height for list items")]
     list_line_height: usize,
 
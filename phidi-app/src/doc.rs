@@ -1,13 +1,14 @@
 use std::{
     borrow::Cow,
     cell::RefCell,
-    collections::HashMap,
+    cmp,
+    collections::{HashMap, HashSet},
     ops::Range,
     path::{Path, PathBuf},
     rc::Rc,
     sync::{
         Arc,
-        atomic::{self, AtomicUsize},
+        atomic::{self, AtomicU64, AtomicUsize},
     },
     time::Duration,
 };
@@ -35,8 +36,9 @@
 };
 use itertools::Itertools;
 use lsp_types::{
-    CodeActionOrCommand, CodeLens, Diagnostic, DiagnosticSeverity,
-    DocumentSymbolResponse, InlayHint, InlayHintLabel, TextEdit,
+    CodeLens, ColorInformation, Diagnostic, DiagnosticSeverity,
+    DocumentHighlightKind, DocumentLink, DocumentSymbolResponse, InlayHint,
+    InlayHintLabel, Position, TextEdit,
 };
 use phidi_core::{
     buffer::{
@@ -60,9 +62,11 @@
     word::{CharClassification, WordCursor, get_char_property},
 };
 use phidi_rpc::{
+    RpcError,
     buffer::BufferId,
     plugin::PluginId,
-    proxy::ProxyResponse,
+    proxy::{ProxyResponse, SourcedCodeAction},
+    source_control::BlameHunk,
     style::{LineStyle, LineStyles, Style},
 };
 use phidi_xi_rope::{
@@ -74,7 +78,7 @@
 
 use crate::{
     command::{CommandKind, PhidiCommand},
-    config::{PhidiConfig, color::PhidiColor},
+    config::{PhidiConfig, color::PhidiColor, editor::FormatOnSaveMode},
     editor::{EditorData, compute_screen_lines, gutter::FoldingRanges},
     find::{Find, FindProgress, FindResult},
     history::DocumentHistory,
@@ -88,6 +92,14 @@
     workspace::PhidiWorkspace,
 };
 
+/// Documents with at least this many lines get the semantic styles of the
+/// visible lines before those of the whole document.
+const LARGE_DOC_LINES: usize = 5000;
+
+/// How long the buffer has to stay unchanged before it is blamed again, as
+/// blaming walks the history of the file.
+const BLAME_DELAY: Duration = Duration::from_millis(500);
+
 #[derive(Clone, Debug)]
 pub struct DiagnosticData {
     pub expanded: RwSignal<bool>,
@@ -155,9 +167,8 @@ pub struct DocInfo {
     pub cursor_offset: usize,
 }
 
-/// (Offset -> (Plugin the code actions are from, Code Actions))
-pub type CodeActions =
-    im::HashMap<usize, (PluginId, im::Vector<CodeActionOrCommand>)>;
+/// (Offset -> Code Actions, with the plugins they are from)
+pub type CodeActions = im::HashMap<usize, im::Vector<SourcedCodeAction>>;
 
 pub type AllCodeLens = im::HashMap<usize, (PluginId, usize, im::Vector<CodeLens>)>;
 
@@ -172,8 +183,16 @@ pub struct Doc {
     pub buffer: RwSignal<Buffer>,
     pub syntax: RwSignal<Syntax>,
     semantic_styles: RwSignal<Option<Spans<Style>>>,
+    /// The id of the semantic styles, to get the next ones as a delta of them.
+    semantic_result_id: RwSignal<Option<String>>,
     /// Inlay hints for the document
     pub inlay_hints: RwSignal<Option<Spans<InlayHint>>>,
+    /// Occurrences of the symbol under the cursor, and whether they read or write it
+    pub document_highlights: RwSignal<Vec<(Range<usize>, DocumentHighlightKind)>>,
+    /// Links in the document, followed with Ctrl+click
+    pub document_links: RwSignal<Option<Spans<DocumentLink>>>,
+    /// Colors in the document, shown with a swatch in front of them
+    pub document_colors: RwSignal<Option<Spans<ColorInformation>>>,
     /// Current completion lens text, if any.
     /// This will be displayed even on views that are not focused.
     pub completion_lens: RwSignal<Option<String>>,
@@ -196,6 +215,10 @@ pub struct Doc {
     /// Stores information about different versions of the document from source control.
     histories: RwSignal<im::HashMap<String, DocumentHistory>>,
     pub head_changes: RwSignal<im::Vector<DiffLines>>,
+    /// Who last changed the lines of the buffer, when the blame is shown.
+    pub blame: RwSignal<Option<im::Vector<BlameHunk>>>,
+    /// The characters after which the language servers format on type.
+    pub on_type_formatting_triggers: RwSignal<HashSet<String>>,
 
     line_styles: Rc<RefCell<LineStyles>>,
     pub parser: Rc<RefCell<BracketParser>>,
@@ -237,7 +260,11 @@ pub fn new(
                 config.editor.bracket_colorization_limit,
             ))),
             semantic_styles: cx.create_rw_signal(None),
+            semantic_result_id: cx.create_rw_signal(None),
             inlay_hints: cx.create_rw_signal(None),
+            document_highlights: cx.create_rw_signal(Vec::new()),
+            document_links: cx.create_rw_signal(None),
+            document_colors: cx.create_rw_signal(None),
             diagnostics,
             completion_lens: cx.create_rw_signal(None),
             completion_pos: cx.create_rw_signal((0, 0)),
@@ -251,6 +278,8 @@ pub fn new(
             loaded: cx.create_rw_signal(false),
             histories: cx.create_rw_signal(im::HashMap::new()),
             head_changes: cx.create_rw_signal(im::Vector::new()),
+            blame: cx.create_rw_signal(None),
+            on_type_formatting_triggers: cx.create_rw_signal(HashSet::new()),
             sticky_headers: Rc::new(RefCell::new(HashMap::new())),
             code_actions: cx.create_rw_signal(im::HashMap::new()),
             find_result: FindResult::new(cx),
@@ -287,7 +316,11 @@ pub fn new_content(
                 config.editor.bracket_colorization_limit,
             ))),
             semantic_styles: cx.create_rw_signal(None),
+            semantic_result_id: cx.create_rw_signal(None),
             inlay_hints: cx.create_rw_signal(None),
+            document_highlights: cx.create_rw_signal(Vec::new()),
+            document_links: cx.create_rw_signal(None),
+            document_colors: cx.create_rw_signal(None),
             diagnostics: DiagnosticData {
                 expanded: cx.create_rw_signal(true),
                 diagnostics: cx.create_rw_signal(im::Vector::new()),
@@ -301,6 +334,8 @@ pub fn new_content(
             content: cx.create_rw_signal(content),
             histories: cx.create_rw_signal(im::HashMap::new()),
             head_changes: cx.create_rw_signal(im::Vector::new()),
+            blame: cx.create_rw_signal(None),
+            on_type_formatting_triggers: cx.create_rw_signal(HashSet::new()),
             sticky_headers: Rc::new(RefCell::new(HashMap::new())),
             loaded: cx.create_rw_signal(true),
             find_result: FindResult::new(cx),
@@ -338,7 +373,11 @@ pub fn new_history(
                 config.editor.bracket_colorization_limit,
             ))),
             semantic_styles: cx.create_rw_signal(None),
+            semantic_result_id: cx.create_rw_signal(None),
             inlay_hints: cx.create_rw_signal(None),
+            document_highlights: cx.create_rw_signal(Vec::new()),
+            document_links: cx.create_rw_signal(None),
+            document_colors: cx.create_rw_signal(None),
             diagnostics: DiagnosticData {
                 expanded: cx.create_rw_signal(true),
                 diagnostics: cx.create_rw_signal(im::Vector::new()),
@@ -354,6 +393,8 @@ pub fn new_history(
             loaded: cx.create_rw_signal(true),
             histories: cx.create_rw_signal(im::HashMap::new()),
             head_changes: cx.create_rw_signal(im::Vector::new()),
+            blame: cx.create_rw_signal(None),
+            on_type_formatting_triggers: cx.create_rw_signal(HashSet::new()),
             code_actions: cx.create_rw_signal(im::HashMap::new()),
             find_result: FindResult::new(cx),
             preedit: PreeditData::new(cx),
@@ -601,6 +642,7 @@ pub fn apply_deltas(&self, deltas: &[(Rope, RopeDelta, InvalLines)]) {
             for (i, (_, delta, inval)) in deltas.iter().enumerate() {
                 self.update_styles(delta);
                 self.update_inlay_hints(delta);
+                self.update_document_decorations(delta);
                 self.update_diagnostics(delta);
                 self.update_completion_lens(delta);
                 self.update_find_result(delta);
@@ -647,8 +689,11 @@ pub fn line_ending(&self) -> LineEnding {
         batch(|| {
             self.trigger_syntax_change(edits);
             self.trigger_head_change();
+            self.get_blame();
             self.check_auto_save();
             self.get_inlay_hints();
+            self.get_document_links();
+            self.get_document_colors();
             self.find_result.reset();
             self.get_semantic_styles();
             self.do_bracket_colorization();
@@ -708,6 +753,7 @@ fn check_auto_save(&self) {
             let scope = self.scope;
             let proxy = self.common.proxy.clone();
             let format = config.editor.format_on_save;
+            let format_mode = config.editor.format_on_save_mode;
             exec_after(
                 Duration::from_millis(config.editor.autosave_interval),
                 move |_| {
@@ -723,7 +769,8 @@ fn check_auto_save(&self) {
                         return;
                     }
 
-                    if format {
+                    let ranges = doc.format_on_save_ranges(format_mode);
+                    if format && ranges.as_ref().is_none_or(|r| !r.is_empty()) {
                         let send = create_ext_action(scope, move |result| {
                             let current_rev = doc.rev();
                             if current_rev != rev {
@@ -737,9 +784,15 @@ fn check_auto_save(&self) {
                             }
                             doc.save(|| {});
                         });
-                        proxy.get_document_formatting(path, move |result| {
+                        let f = move |result| {
                             send(result);
-                        });
+                        };
+                        match ranges {
+                            Some(ranges) => {
+                                proxy.get_document_range_formatting(path, ranges, f)
+                            }
+                            None => proxy.get_document_formatting(path, f),
+                        }
                     } else {
                         doc.save(|| {});
                     }
@@ -775,6 +828,23 @@ fn update_inlay_hints(&self, delta: &RopeDelta) {
         });
     }
 
+    /// Move the links and colors along with an edit. The occurrence
+    /// highlights are dropped, they're requested again as the cursor moves.
+    fn update_document_decorations(&self, delta: &RopeDelta) {
+        self.document_highlights
+            .update(|highlights| highlights.clear());
+        self.document_links.update(|links| {
+            if let Some(links) = links.as_mut() {
+                links.apply_shape(delta);
+            }
+        });
+        self.document_colors.update(|colors| {
+            if let Some(colors) = colors.as_mut() {
+                colors.apply_shape(delta);
+            }
+        });
+    }
+
     pub fn trigger_syntax_change(&self, edits: Option<SmallVec<[SyntaxEdit; 3]>>) {
         let (rev, text) =
             self.buffer.with_untracked(|b| (b.rev(), b.text().clone()));
@@ -857,7 +927,9 @@ pub fn line_style(&self, line: usize) -> Arc<Vec<LineStyle>> {
         self.line_styles.borrow().get(&line).cloned().unwrap()
     }
 
-    /// Request semantic styles for the buffer from the LSP through the proxy.
+    /// Request semantic styles for the buffer from the LSP through the proxy,
+    /// as a delta of the current ones if there are some. A large document
+    /// without any gets those of the visible lines first.
     pub fn get_semantic_styles(&self) {
         if !self.loaded() {
             return;
@@ -870,50 +942,99 @@ pub fn get_semantic_styles(&self) {
                 return;
             };
 
-        let (atomic_rev, rev, len) = self
+        let (atomic_rev, rev, num_lines) = self
             .buffer
-            .with_untracked(|b| (b.atomic_rev(), b.rev(), b.len()));
+            .with_untracked(|b| (b.atomic_rev(), b.rev(), b.num_lines()));
+        let has_styles = self.semantic_styles.with_untracked(|s| s.is_some());
+
+        if !has_styles && num_lines >= LARGE_DOC_LINES {
+            if let Some((start, end)) = self.visible_lines() {
+                let send = self.receive_semantic_styles(false);
+                let atomic_rev = atomic_rev.clone();
+                let range = lsp_types::Range {
+                    start: Position::new(start as u32, 0),
+                    end: Position::new(end as u32 + 1, 0),
+                };
+                self.common.proxy.get_semantic_tokens_range(
+                    path.clone(),
+                    range,
+                    move |result| {
+                        build_semantic_styles(result, atomic_rev, rev, send)
+                    },
+                );
+            }
+        }
 
+        let send = self.receive_semantic_styles(true);
+        let result_id = if has_styles {
+            self.semantic_result_id.get_untracked()
+        } else {
+            None
+        };
+        self.common
+            .proxy
+            .get_semantic_tokens(path, result_id, move |result| {
+                build_semantic_styles(result, atomic_rev, rev, send)
+            });
+    }
+
+    /// The lines visible in the editors of the document.
+    fn visible_lines(&self) -> Option<(usize, usize)> {
+        self.editors.with_editors_untracked(|editors| {
+            editors
+                .values()
+                .filter(|editor| editor.doc().buffer_id == self.buffer_id)
+                .filter_map(|editor| {
+                    editor.screen_lines().with_untracked(|screen_lines| {
+                        Some((
+                            screen_lines.lines.first()?.line,
+                            screen_lines.lines.last()?.line,
+                        ))
+                    })
+                })
+                .reduce(|(start, end), (other_start, other_end)| {
+                    (start.min(other_start), end.max(other_end))
+                })
+        })
+    }
+
+    /// Apply semantic styles built from a response of the proxy, replacing
+    /// only those in their range if they have one.
+    fn receive_semantic_styles(
+        &self,
+        is_delta_base: bool,
+    ) -> impl FnOnce(Option<BuiltSemanticStyles>) + Send + 'static {
         let doc = self.clone();
-        let send = create_ext_action(self.scope, move |styles| {
-            if let Some(styles) = styles {
-                if doc.buffer.with_untracked(|b| b.rev()) == rev {
-                    doc.semantic_styles.set(Some(styles));
-                    doc.clear_style_cache();
-                }
+        create_ext_action(self.scope, move |styles: Option<BuiltSemanticStyles>| {
+            let Some(styles) = styles else {
+                return;
+            };
+            if doc.buffer.with_untracked(|b| b.rev()) != styles.rev {
+                return;
             }
-        });
-
-        self.common.proxy.get_semantic_tokens(path, move |result| {
-            if let Ok(ProxyResponse::GetSemanticTokens { styles }) = result {
-                if styles.styles.is_empty() {
-                    send(None);
-                    return;
+            batch(|| {
+                match styles.range {
+                    Some(range) => {
+                        let len = doc.buffer.with_untracked(|b| b.len());
+                        doc.semantic_styles.update(|current| {
+                            current
+                                .get_or_insert_with(|| {
+                                    SpansBuilder::new(len).build()
+                                })
+                                .edit(
+                                    Interval::new(range.start, range.end),
+                                    styles.spans,
+                                );
+                        });
+                    }
+                    None => doc.semantic_styles.set(Some(styles.spans)),
                 }
-                if atomic_rev.load(atomic::Ordering::Acquire) != rev {
-                    send(None);
-                    return;
+                if is_delta_base {
+                    doc.semantic_result_id.set(styles.result_id);
                 }
-                std::thread::spawn(move || {
-                    let mut styles_span = SpansBuilder::new(len);
-                    for style in styles.styles {
-                        if atomic_rev.load(atomic::Ordering::Acquire) != rev {
-                            send(None);
-                            return;
-                        }
-                        styles_span.add_span(
-                            Interval::new(style.start, style.end),
-                            style.style,
-                        );
-                    }
-
-                    let styles = styles_span.build();
-                    send(Some(styles));
-                });
-            } else {
-                send(None);
-            }
-        });
+                doc.clear_style_cache();
+            });
+        })
     }
 
     pub fn get_code_lens(&self) {
@@ -1049,6 +1170,128 @@ pub fn get_inlay_hints(&self) {
         });
     }
 
+    /// Request the links in the buffer from the LSP through the proxy.
+    pub fn get_document_links(&self) {
+        if !self.loaded() {
+            return;
+        }
+
+        let path =
+            if let DocContent::File { path, .. } = self.content.get_untracked() {
+                path
+            } else {
+                return;
+            };
+
+        let (buffer, rev) = self.buffer.with_untracked(|b| (b.clone(), b.rev()));
+
+        let doc = self.clone();
+        let send = create_ext_action(self.scope, move |links| {
+            if doc.buffer.with_untracked(|b| b.rev()) == rev {
+                doc.document_links.set(Some(links));
+                doc.clear_text_cache();
+            }
+        });
+
+        self.common.proxy.get_document_links(path, move |result| {
+            if let Ok(ProxyResponse::GetDocumentLinks { links }) = result {
+                send(ranges_to_spans(&buffer, links, |link| link.range));
+            }
+        });
+    }
+
+    /// Request the colors in the buffer from the LSP through the proxy.
+    pub fn get_document_colors(&self) {
+        if !self.loaded() {
+            return;
+        }
+
+        let path =
+            if let DocContent::File { path, .. } = self.content.get_untracked() {
+                path
+            } else {
+                return;
+            };
+
+        let (buffer, rev) = self.buffer.with_untracked(|b| (b.clone(), b.rev()));
+
+        let doc = self.clone();
+        let send = create_ext_action(self.scope, move |colors| {
+            if doc.buffer.with_untracked(|b| b.rev()) == rev {
+                doc.document_colors.set(Some(colors));
+                doc.clear_text_cache();
+            }
+        });
+
+        self.common.proxy.get_document_colors(path, move |result| {
+            if let Ok(ProxyResponse::GetDocumentColors { colors }) = result {
+                send(ranges_to_spans(&buffer, colors, |color| color.range));
+            }
+        });
+    }
+
+    /// The link at the offset, if any.
+    pub fn document_link_at(&self, offset: usize) -> Option<DocumentLink> {
+        self.document_links.with_untracked(|links| {
+            links
+                .as_ref()?
+                .iter()
+                .find(|(iv, _)| iv.start <= offset && offset < iv.end)
+                .map(|(_, link)| link.clone())
+        })
+    }
+
+    /// The color at the offset, if any, along with its range in the buffer.
+    pub fn document_color_at(
+        &self,
+        offset: usize,
+    ) -> Option<(Range<usize>, ColorInformation)> {
+        self.document_colors.with_untracked(|colors| {
+            colors
+                .as_ref()?
+                .iter()
+                .find(|(iv, _)| iv.start <= offset && offset <= iv.end)
+                .map(|(iv, color)| (iv.start..iv.end, color.clone()))
+        })
+    }
+
+    /// The whole word occurrences of the word at the offset, for when no
+    /// language server can tell the occurrences of the symbol there. Only the
+    /// visible lines, and a screen above and below them, are looked through.
+    pub fn word_occurrences(
+        &self,
+        offset: usize,
+    ) -> Vec<(Range<usize>, DocumentHighlightKind)> {
+        let (start_line, end_line) = self
+            .visible_lines()
+            .map(|(start, end)| {
+                let height = end - start + 1;
+                (start.saturating_sub(height), end + height)
+            })
+            .unwrap_or((0, usize::MAX));
+        self.buffer.with_untracked(|buffer| {
+            let (start, end) = buffer.select_word(offset);
+            let word = buffer.slice_to_cow(start..end);
+            let last_line = buffer.last_line();
+            let from = buffer.offset_of_line(start_line.min(last_line));
+            let to = if end_line >= last_line {
+                buffer.len()
+            } else {
+                buffer.offset_of_line(end_line + 1)
+            };
+            let text = buffer.text().slice_to_cow(from..to);
+            word_occurrences(&text, &word)
+                .into_iter()
+                .map(|range| {
+                    (
+                        range.start + from..range.end + from,
+                        DocumentHighlightKind::TEXT,
+                    )
+                })
+                .collect()
+        })
+    }
+
     pub fn diagnostics(&self) -> &DiagnosticData {
         &self.diagnostics
     }
@@ -1318,6 +1561,41 @@ pub fn head_changes(&self) -> RwSignal<im::Vector<DiffLines>> {
         self.head_changes
     }
 
+    /// The lines changed since the `head` version of the file, to format only
+    /// what was modified. `None` when there's no `head` version to compare
+    /// with, such as when the file isn't in a git repository.
+    pub fn head_modified_ranges(&self) -> Option<Vec<lsp_types::Range>> {
+        let head = self.histories.with_untracked(|histories| {
+            histories
+                .get("head")
+                .map(|history| history.buffer.text().clone())
+        })?;
+        // Diffed again rather than read from `head_changes`, which is updated
+        // in the background and may not have the last edit yet.
+        self.buffer.with_untracked(|buffer| {
+            let changes = rope_diff(
+                head,
+                buffer.text().clone(),
+                buffer.rev(),
+                buffer.atomic_rev(),
+                None,
+            )?;
+            Some(modified_ranges(buffer, &changes))
+        })
+    }
+
+    /// What format on save should format: `None` for the whole file, or the
+    /// ranges to format, which may be none at all.
+    pub fn format_on_save_ranges(
+        &self,
+        mode: FormatOnSaveMode,
+    ) -> Option<Vec<lsp_types::Range>> {
+        match mode {
+            FormatOnSaveMode::File => None,
+            FormatOnSaveMode::Modifications => self.head_modified_ranges(),
+        }
+    }
+
     /// Retrieve the `head` version of the buffer
     pub fn retrieve_head(&self) {
         if let DocContent::File { path, .. } = self.content.get_untracked() {
@@ -1355,6 +1633,64 @@ pub fn retrieve_head(&self) {
         }
     }
 
+    /// Request who last changed each line of the buffer, unsaved changes
+    /// included, if the blame is shown, once the buffer stops changing.
+    pub fn get_blame(&self) {
+        if !self.loaded() || !self.common.config.get_untracked().editor.blame_shown()
+        {
+            return;
+        }
+
+        let path =
+            if let DocContent::File { path, .. } = self.content.get_untracked() {
+                path
+            } else {
+                return;
+            };
+
+        let rev = self.rev();
+        let doc = self.clone();
+        let proxy = self.common.proxy.clone();
+        exec_after(BLAME_DELAY, move |_| {
+            let current_rev = doc
+                .buffer
+                .try_with_untracked(|b| b.as_ref().map(|b| b.rev()));
+            if current_rev != Some(rev) {
+                return;
+            }
+
+            let send = create_ext_action(doc.scope, move |hunks| {
+                if doc.rev() == rev {
+                    doc.blame.set(Some(hunks));
+                }
+            });
+            proxy.git_blame(path, move |result| {
+                if let Ok(ProxyResponse::GitBlame { hunks }) = result {
+                    send(im::Vector::from(hunks));
+                }
+            });
+        });
+    }
+
+    /// The part of the blame which `line` is in.
+    pub fn blame_of_line(&self, line: usize) -> Option<BlameHunk> {
+        self.blame.with_untracked(|blame| {
+            let blame = blame.as_ref()?;
+            let index = blame
+                .binary_search_by(|hunk| {
+                    if hunk.lines.end <= line {
+                        cmp::Ordering::Less
+                    } else if hunk.lines.start > line {
+                        cmp::Ordering::Greater
+                    } else {
+                        cmp::Ordering::Equal
+                    }
+                })
+                .ok()?;
+            blame.get(index).cloned()
+        })
+    }
+
     pub fn trigger_head_change(&self) {
         let history = if let Some(text) =
             self.histories.with_untracked(|histories| {
@@ -1709,6 +2045,29 @@ fn phantom_text(
         // overall.
         let mut text: SmallVec<[PhantomText; 6]> = hints.collect();
 
+        if config.editor.enable_color_decorators {
+            self.document_colors.with_untracked(|colors| {
+                let Some(colors) = colors.as_ref() else {
+                    return;
+                };
+                for (iv, color) in colors.iter_chunks(start_offset..end_offset) {
+                    if iv.start < start_offset || iv.start >= end_offset {
+                        continue;
+                    }
+                    text.push(PhantomText {
+                        kind: PhantomTextKind::InlayHint,
+                        col: iv.start - start_offset,
+                        text: "■ ".to_string(),
+                        affinity: Some(CursorAffinity::Forward),
+                        fg: Some(swatch_color(&color.color)),
+                        font_size: None,
+                        bg: None,
+                        under_line: None,
+                    });
+                }
+            });
+        }
+
         // If error lens is enabled, and the diagnostics field is filled, then get the diagnostics
         // that end on this line which have a severity worse than HINT and convert them into
         // PhantomText instances
@@ -2099,6 +2458,31 @@ fn apply_layout_styles(
                 });
         });
 
+        doc.document_links.with_untracked(|links| {
+            let Some(links) = links.as_ref() else {
+                return;
+            };
+            let color = config.color(PhidiColor::EDITOR_LINK);
+            for (iv, _) in links.iter_chunks(start_offset..end_offset) {
+                if iv.start() > end_offset || iv.end() < start_offset {
+                    continue;
+                }
+                let start = iv.start().saturating_sub(start_offset);
+                let end = iv.end().min(end_offset) - start_offset;
+                let start = phantom_text.col_after(start, true);
+                let end = phantom_text.col_after(end, false);
+                let styles = extra_styles_for_range(
+                    layout,
+                    start,
+                    end,
+                    None,
+                    Some(color),
+                    None,
+                );
+                layout_line.extra_style.extend(styles);
+            }
+        });
+
         // Add the styling for the diagnostic severity, if applicable
         if let Some(max_severity) = max_severity {
             let theme_prop = if max_severity == DiagnosticSeverity::ERROR {
@@ -2237,3 +2621,179 @@ fn extra_styles_for_range(
             })
         })
 }
+
+/// Turn items with an LSP range into spans over the buffer, dropping the ones
+/// that overlap an earlier item.
+fn ranges_to_spans<T: Clone>(
+    buffer: &Buffer,
+    mut items: Vec<T>,
+    range: impl Fn(&T) -> lsp_types::Range,
+) -> Spans<T> {
+    let len = buffer.len();
+    items.sort_by(|left, right| range(left).start.cmp(&range(right).start));
+
+    let mut spans = SpansBuilder::new(len);
+    let mut last_end = 0;
+    for item in items {
+        let range = range(&item);
+        let start = buffer.offset_of_position(&range.start).min(len);
+        let end = buffer.offset_of_position(&range.end).min(len);
+        if start < last_end || start >= end {
+            continue;
+        }
+        last_end = end;
+        spans.add_span(Interval::new(start, end), item);
+    }
+    spans.build()
+}
+
+fn swatch_color(color: &lsp_types::Color) -> Color {
+    let channel = |c: f32| (c.clamp(0.0, 1.0) * 255.0).round() as u8;
+    Color::from_rgba8(
+        channel(color.red),
+        channel(color.green),
+        channel(color.blue),
+        channel(color.alpha),
+    )
+}
+
+/// The ranges of the lines added or changed in the buffer, going by the diff
+/// of its text against another version of it.
+fn modified_ranges(buffer: &Buffer, changes: &[DiffLines]) -> Vec<lsp_types::Range> {
+    changes
+        .iter()
+        .filter_map(|change| match change {
+            DiffLines::Right(lines) => Some(lsp_types::Range {
+                start: buffer.offset_to_position(buffer.offset_of_line(lines.start)),
+                end: buffer.offset_to_position(buffer.offset_of_line(lines.end)),
+            }),
+            _ => None,
+        })
+        .collect()
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// The ranges of `word` in `text` which aren't part of a longer word. Only
+/// words made of alphanumerics and underscores are looked for.
+fn word_occurrences(text: &str, word: &str) -> Vec<Range<usize>> {
+    if word.is_empty() || !word.chars().all(is_word_char) {
+        return Vec::new();
+    }
+    text.match_indices(word)
+        .filter(|(start, _)| {
+            let end = start + word.len();
+            !text[..*start].chars().next_back().is_some_and(is_word_char)
+                && !text[end..].chars().next().is_some_and(is_word_char)
+        })
+        .map(|(start, _)| start..start + word.len())
+        .collect()
+}
+
+/// Semantic styles ready to go into a document.
+struct BuiltSemanticStyles {
+    rev: u64,
+    spans: Spans<Style>,
+    range: Option<Range<usize>>,
+    result_id: Option<String>,
+}
+
+/// Build the spans of the semantic styles in the response, in a thread since
+/// there can be a lot of them, unless the document changed in the meantime.
+fn build_semantic_styles(
+    result: Result<ProxyResponse, RpcError>,
+    atomic_rev: Arc<AtomicU64>,
+    rev: u64,
+    send: impl FnOnce(Option<BuiltSemanticStyles>) + Send + 'static,
+) {
+    let Ok(ProxyResponse::GetSemanticTokens { styles }) = result else {
+        send(None);
+        return;
+    };
+    // A delta can remove all the styles of its range, but no styles at all for
+    // the document leaves the syntax highlighting in place.
+    if (styles.range.is_none() && styles.styles.is_empty())
+        || atomic_rev.load(atomic::Ordering::Acquire) != rev
+    {
+        send(None);
+        return;
+    }
+    std::thread::spawn(move || {
+        let offset = styles.range.as_ref().map_or(0, |range| range.start);
+        let len = styles
+            .range
+            .as_ref()
+            .map_or(styles.len, |range| range.end - range.start);
+        let mut styles_span = SpansBuilder::new(len);
+        for style in styles.styles {
+            if atomic_rev.load(atomic::Ordering::Acquire) != rev {
+                send(None);
+                return;
+            }
+            // Tokens reaching out of the range are cut at its ends.
+            let start = style.start.saturating_sub(offset).min(len);
+            let end = style.end.saturating_sub(offset).min(len);
+            styles_span.add_span(Interval::new(start, end), style.style);
+        }
+
+        send(Some(BuiltSemanticStyles {
+            rev,
+            spans: styles_span.build(),
+            range: styles.range,
+            result_id: styles.result_id,
+        }));
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, atomic::AtomicU64};
+
+    use lsp_types::{Position, Range};
+    use phidi_core::buffer::{Buffer, diff::rope_diff};
+    use phidi_xi_rope::Rope;
+
+    use super::{modified_ranges, word_occurrences};
+
+    #[test]
+    fn test_modified_ranges() {
+        let head = Rope::from("a\nb\nc\nd\n");
+        let buffer = Buffer::new("new\na\nb\nchanged\nd\nadded\n");
+        let changes = rope_diff(
+            head,
+            buffer.text().clone(),
+            0,
+            Arc::new(AtomicU64::new(0)),
+            None,
+        )
+        .unwrap();
+        // The lines are those of the buffer, after the line added before them.
+        let line =
+            |start, end| Range::new(Position::new(start, 0), Position::new(end, 0));
+        assert_eq!(
+            modified_ranges(&buffer, &changes),
+            vec![line(0, 1), line(3, 4), line(5, 6)]
+        );
+
+        let unchanged = Buffer::new("a\nb\nc\nd\n");
+        let changes = rope_diff(
+            Rope::from("a\nb\nc\nd\n"),
+            unchanged.text().clone(),
+            0,
+            Arc::new(AtomicU64::new(0)),
+            None,
+        )
+        .unwrap();
+        assert!(modified_ranges(&unchanged, &changes).is_empty());
+    }
+
+    #[test]
+    fn test_word_occurrences() {
+        let text = "let foo = foo_bar + foo;\nfoo(afoo);";
+        assert_eq!(word_occurrences(text, "foo"), vec![4..7, 20..23, 25..28]);
+        assert!(word_occurrences(text, "+").is_empty());
+        assert!(word_occurrences(text, "").is_empty());
+    }
+}
@@ -7,7 +7,6 @@
 };
 
 use floem::{
-    ViewId,
     action::{TimerToken, exec_after, show_context_menu},
     ext_event::create_ext_action,
     keyboard::Modifiers,
@@ -33,9 +32,10 @@
 };
 use itertools::Itertools;
 use lsp_types::{
-    CodeActionResponse, CompletionItem, CompletionTextEdit, GotoDefinitionResponse,
-    HoverContents, InlayHint, InlayHintLabel, InlineCompletionTriggerKind, Location,
-    MarkedString, MarkupKind, Range, TextEdit,
+    CompletionItem, CompletionTextEdit, DocumentHighlightKind,
+    GotoDefinitionResponse, HoverContents, InlayHint, InlayHintLabel,
+    InlineCompletionTriggerKind, Location, MarkedString, MarkupKind, Range,
+    TextEdit, Url,
 };
 use nucleo::Utf32Str;
 use phidi_core::{
@@ -54,7 +54,10 @@
     rope_text_pos::RopeTextPosition,
     selection::{InsertDrift, SelRegion, Selection},
 };
-use phidi_rpc::{buffer::BufferId, plugin::PluginId, proxy::ProxyResponse};
+use phidi_rpc::{
+    buffer::BufferId,
+    proxy::{ProxyResponse, SourcedCodeAction},
+};
 use phidi_xi_rope::{Rope, RopeDelta, Transformer};
 use serde::{Deserialize, Serialize};
 use view::StickyHeaderInfo;
@@ -73,13 +76,14 @@
     id::{DiffEditorId, EditorTabId},
     inline_completion::{InlineCompletionItem, InlineCompletionStatus},
     keypress::{KeyPressFocus, condition::Condition},
+    listener::Listener,
     lsp::path_from_url,
     main_split::{Editors, MainSplitData, SplitDirection, SplitMoveDirection},
     markdown::{
         MarkdownContent, from_marked_string, from_plaintext, parse_markdown,
     },
     panel::{
-        call_hierarchy_view::CallHierarchyItemData,
+        call_hierarchy_view::{HierarchyItem, HierarchyKind},
         implementation_view::{init_implementation_root, map_to_location},
         kind::PanelKind,
     },
@@ -218,6 +222,9 @@ pub struct EditorData {
     pub on_screen_find: RwSignal<OnScreenFind>,
     pub last_inline_find: RwSignal<Option<(InlineFindDirection, String)>>,
     pub find_focus: RwSignal<bool>,
+    /// The number of carets of the linked editing session, which edits the
+    /// ranges linked to the one the cursor is in all at once.
+    pub linked_editing: RwSignal<Option<usize>>,
     pub editor: Rc<Editor>,
     pub kind: RwSignal<EditorViewKind>,
     pub sticky_header_height: RwSignal<f64>,
@@ -257,6 +264,7 @@ fn new(
             }),
             last_inline_find: cx.create_rw_signal(None),
             find_focus: cx.create_rw_signal(false),
+            linked_editing: cx.create_rw_signal(None),
             editor: Rc::new(editor),
             kind: cx.create_rw_signal(EditorViewKind::Normal),
             sticky_header_height: cx.create_rw_signal(0.0),
@@ -428,6 +436,7 @@ fn run_edit_command(&self, cmd: &EditCommand) -> CommandExecuted {
             .config
             .with_untracked(|config| config.editor.smart_tab);
         let doc_before_edit = text.text().clone();
+        self.check_linked_editing(None, Some(cmd));
         let mut cursor = self.editor.cursor.get_untracked();
         let mut register = self.common.register.get_untracked();
 
@@ -468,6 +477,9 @@ fn run_edit_command(&self, cmd: &EditCommand) -> CommandExecuted {
         }
 
         self.apply_deltas(&deltas);
+        if *cmd == EditCommand::InsertNewLine {
+            self.format_on_type("\n");
+        }
         if let EditCommand::NormalMode = cmd {
             self.snippet.set(None);
             self.quit_on_screen_find();
@@ -1389,7 +1401,6 @@ pub fn call_hierarchy(&self, window_tab_data: WindowTabData) {
             let position = buffer.offset_to_position(offset);
             (start_position, position)
         });
-        let scope = window_tab_data.scope;
         let range = Range {
             start: _start_position,
             end: position,
@@ -1403,24 +1414,48 @@ pub fn call_hierarchy(&self, window_tab_data: WindowTabData) {
                 }) = result
                 {
                     if let Some(item) = items.and_then(|x| x.into_iter().next()) {
-                        let root = scope.create_rw_signal(CallHierarchyItemData {
-                            view_id: ViewId::new(),
-                            item: Rc::new(item),
-                            from_range: range,
-                            init: false,
-                            open: scope.create_rw_signal(true),
-                            children: scope.create_rw_signal(Vec::with_capacity(0)),
-                        });
-                        let item = root;
-                        window_tab_data.call_hierarchy_data.root.update(|x| {
-                            *x = Some(root);
-                        });
+                        window_tab_data.call_hierarchy_data.set_root(
+                            HierarchyItem::Call(item),
+                            range,
+                            HierarchyKind::IncomingCalls,
+                        );
                         window_tab_data.show_panel(PanelKind::CallHierarchy);
-                        window_tab_data.common.internal_command.send(
-                            InternalCommand::CallHierarchyIncoming {
-                                item_id: item.get_untracked().view_id,
-                            },
+                    }
+                }
+            }),
+        );
+    }
+
+    pub fn type_hierarchy(&self, window_tab_data: WindowTabData) {
+        let doc = self.doc();
+        let Some(path) = doc
+            .loaded()
+            .then(|| doc.content.with_untracked(|c| c.path().cloned()))
+            .flatten()
+        else {
+            return;
+        };
+
+        let offset = self.cursor().with_untracked(|c| c.offset());
+        let range = doc.buffer.with_untracked(|buffer| {
+            let start_offset = buffer.prev_code_boundary(offset);
+            Range {
+                start: buffer.offset_to_position(start_offset),
+                end: buffer.offset_to_position(offset),
+            }
+        });
+        self.common.proxy.show_type_hierarchy(
+            path,
+            range.end,
+            create_ext_action(self.scope, move |result| {
+                if let Ok(ProxyResponse::TypeHierarchyResponse { items }) = result {
+                    if let Some(item) = items.and_then(|x| x.into_iter().next()) {
+                        window_tab_data.call_hierarchy_data.set_root(
+                            HierarchyItem::Type(item),
+                            range,
+                            HierarchyKind::Supertypes,
                         );
+                        window_tab_data.show_panel(PanelKind::CallHierarchy);
                     }
                 }
             }),
@@ -2240,7 +2275,7 @@ pub fn get_code_actions(&self) {
 
         // insert some empty data, so that we won't make the request again
         doc.code_actions().update(|c| {
-            c.insert(offset, (PluginId(0), im::Vector::new()));
+            c.insert(offset, im::Vector::new());
         });
 
         let (position, rev, diagnostics) = doc.buffer.with_untracked(|buffer| {
@@ -2262,28 +2297,23 @@ pub fn get_code_actions(&self) {
             (position, rev, diagnostics)
         });
 
-        let send = create_ext_action(
-            self.scope,
-            move |resp: (PluginId, CodeActionResponse)| {
+        let send =
+            create_ext_action(self.scope, move |actions: Vec<SourcedCodeAction>| {
                 if doc.rev() == rev {
                     doc.code_actions().update(|c| {
-                        c.insert(offset, (resp.0, resp.1.into()));
+                        c.insert(offset, actions.into());
                     });
                 }
-            },
-        );
+            });
 
         self.common.proxy.get_code_actions(
             path,
             position,
             diagnostics,
             move |result| {
-                if let Ok(ProxyResponse::GetCodeActionsResponse {
-                    plugin_id,
-                    resp,
-                }) = result
+                if let Ok(ProxyResponse::GetCodeActionsResponse { actions }) = result
                 {
-                    send((plugin_id, resp))
+                    send(actions)
                 }
             },
         );
@@ -2295,13 +2325,12 @@ pub fn show_code_actions(&self, mouse_click: bool) {
         let code_actions = doc
             .code_actions()
             .with_untracked(|c| c.get(&offset).cloned());
-        if let Some((plugin_id, code_actions)) = code_actions {
+        if let Some(code_actions) = code_actions {
             if !code_actions.is_empty() {
                 self.common.internal_command.send(
                     InternalCommand::ShowCodeActions {
                         offset,
                         mouse_click,
-                        plugin_id,
                         code_actions,
                     },
                 );
@@ -2346,7 +2375,10 @@ pub fn save(
         }
 
         let rev = doc.rev();
-        let format_on_save = allow_formatting && config.editor.format_on_save;
+        let ranges = doc.format_on_save_ranges(config.editor.format_on_save_mode);
+        let format_on_save = allow_formatting
+            && config.editor.format_on_save
+            && ranges.as_ref().is_none_or(|ranges| !ranges.is_empty());
         if format_on_save {
             let editor = self.clone();
             let send = create_ext_action(self.scope, move |result| {
@@ -2364,11 +2396,17 @@ pub fn save(
             let (tx, rx) = crossbeam_channel::bounded(1);
             let proxy = self.common.proxy.clone();
             std::thread::spawn(move || {
-                proxy.get_document_formatting(path, move |result| {
+                let f = move |result| {
                     if let Err(err) = tx.send(result) {
                         tracing::error!("{:?}", err);
                     }
-                });
+                };
+                match ranges {
+                    Some(ranges) => {
+                        proxy.get_document_range_formatting(path, ranges, f)
+                    }
+                    None => proxy.get_document_formatting(path, f),
+                }
                 let result = rx.recv_timeout(std::time::Duration::from_secs(1));
                 send(result);
             });
@@ -2409,6 +2447,187 @@ pub fn format(&self) {
         }
     }
 
+    /// Format the selections, or the line of the cursor when nothing is
+    /// selected.
+    pub fn format_selection(&self) {
+        let doc = self.doc();
+        let rev = doc.rev();
+        let DocContent::File { path, .. } = doc.content.get_untracked() else {
+            return;
+        };
+        let cursor = self.cursor().get_untracked();
+        let ranges = doc.buffer.with_untracked(|buffer| {
+            let regions = match &cursor.mode {
+                CursorMode::Normal(offset) => {
+                    vec![phidi_core::selection::SelRegion::caret(*offset)]
+                }
+                _ => cursor.edit_selection(buffer).regions().to_vec(),
+            };
+            regions
+                .into_iter()
+                .map(|region| {
+                    let (start, end) = if region.is_caret() {
+                        let line = buffer.line_of_offset(region.start);
+                        (
+                            buffer.offset_of_line(line),
+                            buffer.offset_of_line(line + 1),
+                        )
+                    } else {
+                        (region.min(), region.max())
+                    };
+                    lsp_types::Range {
+                        start: buffer.offset_to_position(start),
+                        end: buffer.offset_to_position(end),
+                    }
+                })
+                .collect::<Vec<_>>()
+        });
+
+        let editor = self.clone();
+        let send = create_ext_action(self.scope, move |result| {
+            if let Ok(ProxyResponse::GetDocumentFormatting { edits }) = result {
+                if editor.doc().rev() == rev {
+                    editor.do_text_edit(&edits);
+                }
+            }
+        });
+        self.common.proxy.get_document_range_formatting(
+            path,
+            ranges,
+            move |result| send(result),
+        );
+    }
+
+    /// Stage or unstage the changes on the lines of the selections, or on the
+    /// line of the cursor when nothing is selected.
+    pub fn stage_selected_lines(&self, stage: bool) {
+        let doc = self.doc();
+        let DocContent::File { path, .. } = doc.content.get_untracked() else {
+            return;
+        };
+        let cursor = self.cursor().get_untracked();
+        let lines = doc.buffer.with_untracked(|buffer| {
+            let regions = match &cursor.mode {
+                CursorMode::Normal(offset) => {
+                    vec![phidi_core::selection::SelRegion::caret(*offset)]
+                }
+                _ => cursor.edit_selection(buffer).regions().to_vec(),
+            };
+            regions
+                .into_iter()
+                .map(|region| {
+                    let start = buffer.line_of_offset(region.min());
+                    let end = buffer.line_of_offset(region.max());
+                    // A selection which ends at the start of a line leaves it out.
+                    if end > start && buffer.offset_of_line(end) == region.max() {
+                        start..end
+                    } else {
+                        start..end + 1
+                    }
+                })
+                .collect::<Vec<_>>()
+        });
+        if stage {
+            self.common.proxy.git_stage_lines(path, lines);
+        } else {
+            self.common.proxy.git_unstage_lines(path, lines);
+        }
+    }
+
+    /// Open the changes of the commit which last changed the line of the
+    /// cursor.
+    pub fn show_line_commit(&self) {
+        let doc = self.doc();
+        let DocContent::File { path, .. } = doc.content.get_untracked() else {
+            return;
+        };
+        let offset = self.cursor().with_untracked(|c| c.offset());
+        let line = doc
+            .buffer
+            .with_untracked(|buffer| buffer.line_of_offset(offset));
+        if let Some(commit) = doc.blame_of_line(line).and_then(|hunk| hunk.commit) {
+            self.common.internal_command.send(
+                InternalCommand::OpenCommitFileChanges {
+                    path,
+                    commit: commit.id,
+                },
+            );
+        }
+    }
+
+    /// Offer to stage or unstage the change drawn in the gutter at `pos`,
+    /// which is in the coordinates of the editor's text.
+    pub fn gutter_right_click(&self, pos: Point) {
+        let doc = self.doc();
+        let DocContent::File { path, .. } = doc.content.get_untracked() else {
+            return;
+        };
+        let mode = self.cursor().with_untracked(|c| c.get_mode());
+        let (offset, _) = self.editor.offset_of_point(mode, pos);
+        let line = doc
+            .buffer
+            .with_untracked(|buffer| buffer.line_of_offset(offset));
+        let Some(lines) = doc
+            .head_changes()
+            .with_untracked(|changes| gutter::head_change_lines(changes, line))
+        else {
+            return;
+        };
+
+        let stage = {
+            let proxy = self.common.proxy.clone();
+            let path = path.clone();
+            let lines = lines.clone();
+            move || proxy.git_stage_lines(path.clone(), vec![lines.clone()])
+        };
+        let unstage = {
+            let proxy = self.common.proxy.clone();
+            move || proxy.git_unstage_lines(path.clone(), vec![lines.clone()])
+        };
+        let menu = Menu::new("")
+            .entry(MenuItem::new("Stage Change").action(stage))
+            .entry(MenuItem::new("Unstage Change").action(unstage));
+        show_context_menu(menu, None);
+    }
+
+    /// Ask the language server to format after typing `ch`, if it is one of
+    /// its trigger characters.
+    fn format_on_type(&self, ch: &str) {
+        if !self.common.config.get_untracked().editor.format_on_type {
+            return;
+        }
+        let doc = self.doc();
+        if !doc
+            .on_type_formatting_triggers
+            .with_untracked(|triggers| triggers.contains(ch))
+        {
+            return;
+        }
+        let rev = doc.rev();
+        let DocContent::File { path, .. } = doc.content.get_untracked() else {
+            return;
+        };
+        let offset = self.cursor().with_untracked(|c| c.offset());
+        let position = doc
+            .buffer
+            .with_untracked(|buffer| buffer.offset_to_position(offset));
+
+        let editor = self.clone();
+        let send = create_ext_action(self.scope, move |result| {
+            if let Ok(ProxyResponse::GetDocumentFormatting { edits }) = result {
+                if editor.doc().rev() == rev {
+                    editor.do_text_edit(&edits);
+                }
+            }
+        });
+        self.common.proxy.on_type_formatting(
+            path,
+            position,
+            ch.to_string(),
+            move |result| send(result),
+        );
+    }
+
     fn search_whole_word_forward(&self, mods: Modifiers) {
         let offset = self.cursor().with_untracked(|c| c.offset());
         let (word, buffer) = self.doc().buffer.with_untracked(|buffer| {
@@ -2708,6 +2927,9 @@ pub fn pointer_down(&self, pointer_event: &PointerInputEvent) {
                     || (cfg!(not(target_os = "macos"))
                         && pointer_event.modifiers.control())
                 {
+                    if self.open_document_link(pointer_event.pos) {
+                        return;
+                    }
                     let rs = self.find_hint(pointer_event.pos);
                     match rs {
                         FindHintRs::NoMatchBreak
@@ -2748,6 +2970,270 @@ pub fn pointer_down(&self, pointer_event: &PointerInputEvent) {
         }
     }
 
+    /// Follow the document link under the pointer, resolving it first if the
+    /// server left its target out. Returns whether there was a link there.
+    fn open_document_link(&self, pos: Point) -> bool {
+        let mode = self.cursor().with_untracked(|c| c.get_mode());
+        let (offset, is_inside) = self.editor.offset_of_point(mode, pos);
+        if !is_inside {
+            return false;
+        }
+        let doc = self.doc();
+        let Some(link) = doc.document_link_at(offset) else {
+            return false;
+        };
+
+        let internal_command = self.common.internal_command;
+        if let Some(target) = link.target.clone() {
+            open_link_target(internal_command, target);
+            return true;
+        }
+        let Some(path) = doc.content.get_untracked().path().cloned() else {
+            return true;
+        };
+        let send = create_ext_action(self.scope, move |result| {
+            if let Ok(ProxyResponse::ResolveDocumentLink { link }) = result {
+                if let Some(target) = link.target {
+                    open_link_target(internal_command, target);
+                }
+            }
+        });
+        self.common
+            .proxy
+            .resolve_document_link(path, link, move |result| send(result));
+        true
+    }
+
+    /// Ask the language server for the occurrences of the symbol under the
+    /// cursor, or look for the word under it when no server knows.
+    pub fn get_document_highlights(&self) {
+        let doc = self.doc();
+        let config = self.common.config.get_untracked();
+        if !config.editor.highlight_selection_occurrences
+            || doc.content.with_untracked(|content| content.is_local())
+        {
+            doc.document_highlights
+                .update(|highlights| highlights.clear());
+            return;
+        }
+
+        let offset = self.cursor().with_untracked(|c| c.offset());
+        let Some(path) = doc.content.get_untracked().path().cloned() else {
+            let highlights = doc.word_occurrences(offset);
+            doc.document_highlights.set(highlights);
+            return;
+        };
+        let (rev, position) = doc
+            .buffer
+            .with_untracked(|b| (b.rev(), b.offset_to_position(offset)));
+
+        let editor = self.clone();
+        let send = create_ext_action(self.scope, move |result| {
+            let doc = editor.doc();
+            if doc.rev() != rev
+                || editor.cursor().with_untracked(|c| c.offset()) != offset
+            {
+                return;
+            }
+            let highlights = match result {
+                Ok(ProxyResponse::GetDocumentHighlights { highlights }) => {
+                    doc.buffer.with_untracked(|b| {
+                        highlights
+                            .into_iter()
+                            .map(|highlight| {
+                                let start =
+                                    b.offset_of_position(&highlight.range.start);
+                                let end = b.offset_of_position(&highlight.range.end);
+                                (
+                                    start..end,
+                                    highlight
+                                        .kind
+                                        .unwrap_or(DocumentHighlightKind::TEXT),
+                                )
+                            })
+                            .collect()
+                    })
+                }
+                _ => doc.word_occurrences(offset),
+            };
+            doc.document_highlights.set(highlights);
+        });
+        self.common
+            .proxy
+            .get_document_highlights(path, position, move |result| {
+                send(result);
+            });
+    }
+
+    /// Start, keep up or end linked editing at the cursor. The ranges come
+    /// from the language server, or are the names of the opening and closing
+    /// tags in the syntax tree, and each gets a caret so they're edited
+    /// together.
+    pub fn update_linked_editing(&self) {
+        let doc = self.doc();
+        let cursor = self.cursor().get_untracked();
+        let CursorMode::Insert(selection) = &cursor.mode else {
+            self.end_linked_editing();
+            return;
+        };
+        if !self.common.config.get_untracked().editor.linked_editing
+            || doc.content.with_untracked(|content| content.is_local())
+        {
+            self.end_linked_editing();
+            return;
+        }
+        let carets = selection.regions().iter().all(|region| region.is_caret());
+        match self.linked_editing.get_untracked() {
+            // The selection is the user's own.
+            None if !carets || selection.len() != 1 => return,
+            Some(count) if !carets || selection.len() != count => {
+                self.linked_editing.set(None);
+                return;
+            }
+            _ => {}
+        }
+
+        let offset = cursor.offset();
+        let rev = doc.rev();
+        let Some(path) = doc.content.get_untracked().path().cloned() else {
+            let ranges = self.linked_tag_names(offset);
+            self.set_linked_editing(rev, offset, ranges);
+            return;
+        };
+        let position = doc
+            .buffer
+            .with_untracked(|buffer| buffer.offset_to_position(offset));
+        let editor = self.clone();
+        let send = create_ext_action(self.scope, move |result| {
+            let ranges = match result {
+                Ok(ProxyResponse::GetLinkedEditingRanges {
+                    ranges: Some(ranges),
+                }) => editor.doc().buffer.with_untracked(|buffer| {
+                    ranges
+                        .ranges
+                        .iter()
+                        .map(|range| {
+                            buffer.offset_of_position(&range.start)
+                                ..buffer.offset_of_position(&range.end)
+                        })
+                        .collect()
+                }),
+                _ => editor.linked_tag_names(offset),
+            };
+            editor.set_linked_editing(rev, offset, ranges);
+        });
+        self.common
+            .proxy
+            .get_linked_editing_ranges(path, position, move |result| {
+                send(result);
+            });
+    }
+
+    fn linked_tag_names(&self, offset: usize) -> Vec<std::ops::Range<usize>> {
+        self.doc()
+            .syntax()
+            .with_untracked(|syntax| syntax.linked_tag_names(offset))
+            .unwrap_or_default()
+    }
+
+    fn set_linked_editing(
+        &self,
+        rev: u64,
+        offset: usize,
+        ranges: Vec<std::ops::Range<usize>>,
+    ) {
+        if self.doc().rev() != rev
+            || self.cursor().with_untracked(|c| c.offset()) != offset
+        {
+            return;
+        }
+        let Some(primary) = ranges
+            .iter()
+            .find(|range| range.start <= offset && offset <= range.end)
+            .cloned()
+        else {
+            self.end_linked_editing();
+            return;
+        };
+        if ranges.len() < 2 {
+            self.end_linked_editing();
+            return;
+        }
+
+        let column = offset - primary.start;
+        let mut selection = Selection::new();
+        for range in ranges.iter().filter(|range| **range != primary) {
+            let offset = (range.start + column).min(range.end);
+            selection.add_region(SelRegion::caret(offset));
+        }
+        // Added last so it stays the cursor offset.
+        selection.add_region(SelRegion::caret(offset));
+
+        let unchanged = self.cursor().with_untracked(|cursor| match &cursor.mode {
+            CursorMode::Insert(current) => current.regions() == selection.regions(),
+            _ => false,
+        });
+        if !unchanged {
+            self.cursor().update(|cursor| cursor.set_insert(selection));
+        }
+        self.linked_editing.set(Some(ranges.len()));
+    }
+
+    fn end_linked_editing(&self) {
+        if self.linked_editing.get_untracked().is_none() {
+            return;
+        }
+        self.linked_editing.set(None);
+        self.cursor().update(|cursor| {
+            if let CursorMode::Insert(_) = cursor.mode {
+                let offset = cursor.offset();
+                cursor.set_insert(Selection::caret(offset));
+            }
+        });
+    }
+
+    /// Linked editing is for names: an edit which doesn't keep to the name,
+    /// like typing a space after a tag name, ends it first so it's only made
+    /// once.
+    fn check_linked_editing(
+        &self,
+        inserted: Option<&str>,
+        cmd: Option<&EditCommand>,
+    ) {
+        if self.linked_editing.get_untracked().is_none() {
+            return;
+        }
+        let keeps_to_name = match (inserted, cmd) {
+            (Some(inserted), _) => inserted.chars().all(is_name_char),
+            (_, Some(EditCommand::DeleteBackward)) => {
+                let offset = self.cursor().with_untracked(|c| c.offset());
+                self.doc().buffer.with_untracked(|buffer| {
+                    let text = buffer.text();
+                    text.prev_codepoint_offset(offset)
+                        .and_then(|prev| {
+                            text.slice_to_cow(prev..offset).chars().next()
+                        })
+                        .is_some_and(is_name_char)
+                })
+            }
+            (_, Some(EditCommand::DeleteForward)) => {
+                let offset = self.cursor().with_untracked(|c| c.offset());
+                self.doc().buffer.with_untracked(|buffer| {
+                    let text = buffer.text();
+                    text.next_codepoint_offset(offset)
+                        .and_then(|next| {
+                            text.slice_to_cow(offset..next).chars().next()
+                        })
+                        .is_some_and(is_name_char)
+                })
+            }
+            _ => false,
+        };
+        if !keeps_to_name {
+            self.end_linked_editing();
+        }
+    }
+
     fn find_hint(&self, pos: Point) -> FindHintRs {
         let rs = self.editor.line_col_of_point_with_phantom(pos);
         let line = rs.0 as u32;
@@ -2934,6 +3420,9 @@ fn right_click(&self, pointer_event: &PointerInputEvent) {
                     Some(CommandKind::Workbench(
                         PhidiWorkbenchCommand::ShowCallHierarchy,
                     )),
+                    Some(CommandKind::Workbench(
+                        PhidiWorkbenchCommand::ShowTypeHierarchy,
+                    )),
                     Some(CommandKind::Workbench(
                         PhidiWorkbenchCommand::FindReferences,
                     )),
@@ -2954,6 +3443,9 @@ fn right_click(&self, pointer_event: &PointerInputEvent) {
                     Some(CommandKind::Workbench(
                         PhidiWorkbenchCommand::SourceControlOpenActiveFileRemoteUrl,
                     )),
+                    Some(CommandKind::Workbench(
+                        PhidiWorkbenchCommand::SourceControlShowLineCommit,
+                    )),
                     None,
                     Some(CommandKind::Edit(EditCommand::ClipboardCut)),
                     Some(CommandKind::Edit(EditCommand::ClipboardCopy)),
@@ -2980,6 +3472,17 @@ fn right_click(&self, pointer_event: &PointerInputEvent) {
                 PhidiWorkbenchCommand::GoToLocation,
             )));
         }
+        if doc.document_color_at(offset).is_some() {
+            cmds.splice(
+                0..0,
+                [
+                    Some(CommandKind::Workbench(
+                        PhidiWorkbenchCommand::ShowColorPicker,
+                    )),
+                    None,
+                ],
+            );
+        }
         let phidi_command = self.common.phidi_command;
         for cmd in cmds {
             if let Some(cmd) = cmd {
@@ -3383,6 +3886,7 @@ fn receive_char(&self, c: &str) {
         } else {
             // normal editor receive char
             if self.get_mode() == Mode::Insert {
+                self.check_linked_editing(Some(c), None);
                 let mut cursor = self.cursor().get_untracked();
                 let deltas = self.doc().do_insert(
                     &mut cursor,
@@ -3405,6 +3909,7 @@ fn receive_char(&self, c: &str) {
                 );
 
                 self.apply_deltas(&deltas);
+                self.format_on_type(c);
             } else if let Some(direction) = self.inline_find.get_untracked() {
                 self.inline_find(direction.clone(), c);
                 self.last_inline_find.set(Some((direction, c.to_string())));
@@ -3920,3 +4425,38 @@ fn find_hint(mut pre_hint_len: u32, index: u32, hint: &InlayHint) -> FindHintRs
         }
     }
 }
+
+/// Whether the character can be part of the name of a tag, as edited together
+/// with the name of its pair.
+fn is_name_char(c: char) -> bool {
+    c.is_alphanumeric() || matches!(c, '-' | '_' | ':' | '.')
+}
+
+/// Open the target of a document link, in the editor when it's a file.
+fn open_link_target(internal_command: Listener<InternalCommand>, target: Url) {
+    if target.scheme() != "file" {
+        internal_command.send(InternalCommand::OpenWebUri {
+            uri: target.to_string(),
+        });
+        return;
+    }
+    let Ok(path) = target.to_file_path() else {
+        return;
+    };
+    // Servers point at a line with a `#L<line>` fragment, as in `#L12` or
+    // `#L12,4`.
+    let position = target
+        .fragment()
+        .and_then(|fragment| fragment.strip_prefix('L'))
+        .and_then(|line| line.split([',', '-']).next()?.parse::<usize>().ok())
+        .map(|line| EditorPosition::Line(line.saturating_sub(1)));
+    internal_command.send(InternalCommand::JumpToLocation {
+        location: EditorLocation {
+            path,
+            position,
+            scroll_offset: None,
+            ignore_unconfirmed: true,
+            same_editor_tab: false,
+        },
+    });
+}
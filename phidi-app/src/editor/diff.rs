@@ -1,4 +1,4 @@
-use std::{rc::Rc, sync::atomic};
+use std::{ops::Range, rc::Rc, sync::atomic};
 
 use floem::{
     View,
@@ -10,9 +10,13 @@
         Decorators, clip, dyn_stack, editor::id::EditorId, empty, label, stack, svg,
     },
 };
-use phidi_core::buffer::{
-    diff::{DiffExpand, DiffLines, expand_diff_lines, rope_diff},
-    rope_text::RopeText,
+use phidi_core::{
+    buffer::{
+        diff::{DiffExpand, DiffLines, expand_diff_lines, rope_diff},
+        rope_text::RopeText,
+    },
+    editor::EditType,
+    selection::Selection,
 };
 use phidi_rpc::{buffer::BufferId, proxy::ProxyResponse};
 use phidi_xi_rope::Rope;
@@ -305,6 +309,201 @@ fn listen_diff_changes(&self) {
     }
 }
 
+/// A block of consecutive changed lines, as line ranges into the left and the
+/// right document. One of the ranges is empty for pure additions or deletions.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DiffHunk {
+    pub left: Range<usize>,
+    pub right: Range<usize>,
+}
+
+pub fn diff_hunks(changes: &[DiffLines]) -> Vec<DiffHunk> {
+    let mut hunks = Vec::new();
+    let mut hunk: Option<DiffHunk> = None;
+    let mut left_line = 0;
+    let mut right_line = 0;
+    for change in changes {
+        match change {
+            DiffLines::Both(info) => {
+                hunks.extend(hunk.take());
+                left_line = info.left.end;
+                right_line = info.right.end;
+            }
+            DiffLines::Left(range) => {
+                let hunk = hunk.get_or_insert(DiffHunk {
+                    left: range.start..range.start,
+                    right: right_line..right_line,
+                });
+                hunk.left.end = range.end;
+                left_line = range.end;
+            }
+            DiffLines::Right(range) => {
+                let hunk = hunk.get_or_insert(DiffHunk {
+                    left: left_line..left_line,
+                    right: range.start..range.start,
+                });
+                hunk.right.end = range.end;
+                right_line = range.end;
+            }
+        }
+    }
+    hunks.extend(hunk);
+    hunks
+}
+
+/// Replaces `lines` of `doc` with `source_lines` of `source`.
+pub fn replace_hunk_lines(
+    doc: &Doc,
+    lines: Range<usize>,
+    source: &Doc,
+    source_lines: Range<usize>,
+) {
+    let text = source.buffer.with_untracked(|buffer| {
+        let start = buffer.offset_of_line(source_lines.start);
+        let end = buffer.offset_of_line(source_lines.end);
+        buffer.text().slice_to_cow(start..end).to_string()
+    });
+    let selection = doc.buffer.with_untracked(|buffer| {
+        Selection::region(
+            buffer.offset_of_line(lines.start),
+            buffer.offset_of_line(lines.end),
+        )
+    });
+    doc.do_raw_edit(&[(selection, text.as_str())], EditType::Other);
+}
+
+/// Accept and reject buttons on every hunk of a diff editor. Accepting a hunk
+/// copies it from the right document into the left one, rejecting it restores
+/// the left document's lines in the right one.
+pub fn diff_hunk_actions_view(
+    left_editor: &EditorData,
+    right_editor: &EditorData,
+    enabled: impl Fn() -> bool + 'static,
+) -> impl View + use<> {
+    let left_editor = left_editor.clone();
+    let right_editor = right_editor.clone();
+    let right_editor_view = right_editor.kind;
+    let right_screen_lines = right_editor.screen_lines();
+    let viewport = right_editor.viewport();
+    let config = right_editor.common.config;
+
+    let each_fn = move || {
+        if !enabled() {
+            return Vec::new();
+        }
+        right_editor_view.with(|editor_view| {
+            if let EditorViewKind::Diff(diff_info) = editor_view {
+                diff_hunks(&diff_info.changes)
+            } else {
+                Vec::new()
+            }
+        })
+    };
+
+    let key_fn = |hunk: &DiffHunk| (hunk.left.clone(), hunk.right.clone());
+
+    let view_fn = move |hunk: DiffHunk| {
+        let action = |name: &'static str| {
+            label(move || name.to_string()).style(move |s| {
+                let config = config.get();
+                s.padding_horiz(6.0)
+                    .border_radius(6.0)
+                    .pointer_events_auto()
+                    .background(config.color(PhidiColor::PANEL_BACKGROUND))
+                    .hover(|s| {
+                        s.cursor(CursorStyle::Pointer).background(
+                            config.color(PhidiColor::PANEL_HOVERED_BACKGROUND),
+                        )
+                    })
+            })
+        };
+        let accept = {
+            let hunk = hunk.clone();
+            let left_doc = left_editor.doc_signal();
+            let right_doc = right_editor.doc_signal();
+            move || {
+                replace_hunk_lines(
+                    &left_doc.get_untracked(),
+                    hunk.left.clone(),
+                    &right_doc.get_untracked(),
+                    hunk.right.clone(),
+                );
+            }
+        };
+        let reject = {
+            let hunk = hunk.clone();
+            let left_doc = left_editor.doc_signal();
+            let right_doc = right_editor.doc_signal();
+            move || {
+                replace_hunk_lines(
+                    &right_doc.get_untracked(),
+                    hunk.right.clone(),
+                    &left_doc.get_untracked(),
+                    hunk.left.clone(),
+                );
+            }
+        };
+
+        stack((
+            action("Accept")
+                .on_event_stop(EventListener::PointerDown, move |_| {})
+                .on_click_stop(move |_| accept()),
+            action("Reject")
+                .on_event_stop(EventListener::PointerDown, move |_| {})
+                .on_click_stop(move |_| reject())
+                .style(|s| s.margin_left(6.0)),
+        ))
+        .style(move |s| {
+            let right_screen_lines = right_screen_lines.get();
+
+            // Pure deletions have no lines of their own on the right, so the
+            // buttons go below the line above them.
+            let (line, below) = if hunk.right.is_empty() && hunk.right.start > 0 {
+                (hunk.right.start - 1, true)
+            } else {
+                (hunk.right.start, false)
+            };
+            let Some(line_info) = right_screen_lines.info_for_line(line) else {
+                return s.hide();
+            };
+
+            let config = config.get();
+            let line_height = config.editor.line_height();
+
+            let mut y = line_info.y - viewport.get().y0;
+            if below {
+                y += line_height as f64;
+            }
+
+            s.absolute()
+                .width_pct(100.0)
+                .height(line_height as f32)
+                .justify_end()
+                .items_center()
+                .padding_right(20.0)
+                .margin_top(y)
+        })
+    };
+
+    stack((
+        empty().style(move |s| {
+            s.height(config.get().editor.line_height() as f32 + 1.0)
+        }),
+        clip(
+            dyn_stack(each_fn, key_fn, view_fn)
+                .style(|s| s.flex_col().size_pct(100.0, 100.0)),
+        )
+        .style(|s| s.size_pct(100.0, 100.0)),
+    ))
+    .style(|s| {
+        s.absolute()
+            .flex_col()
+            .size_pct(100.0, 100.0)
+            .pointer_events_none()
+    })
+    .debug_name("Diff Hunk Actions")
+}
+
 #[derive(Clone, PartialEq)]
 struct DiffShowMoreSection {
     left_actual_line: usize,
@@ -550,3 +749,57 @@ pub fn diff_show_more_section_view(
     })
     .debug_name("Diff Show More Section")
 }
+
+#[cfg(test)]
+mod tests {
+    use phidi_core::buffer::diff::{DiffBothInfo, DiffLines};
+
+    use super::{DiffHunk, diff_hunks};
+
+    fn both(
+        left: std::ops::Range<usize>,
+        right: std::ops::Range<usize>,
+    ) -> DiffLines {
+        DiffLines::Both(DiffBothInfo {
+            left,
+            right,
+            skip: None,
+        })
+    }
+
+    #[test]
+    fn test_diff_hunks() {
+        let changes = vec![
+            both(0..2, 0..2),
+            DiffLines::Left(2..3),
+            DiffLines::Right(2..4),
+            both(3..5, 4..6),
+            DiffLines::Right(6..7),
+            both(5..6, 7..8),
+            DiffLines::Left(6..8),
+        ];
+        assert_eq!(
+            diff_hunks(&changes),
+            vec![
+                DiffHunk {
+                    left: 2..3,
+                    right: 2..4,
+                },
+                DiffHunk {
+                    left: 5..5,
+                    right: 6..7,
+                },
+                DiffHunk {
+                    left: 6..8,
+                    right: 8..8,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_diff_hunks_unchanged() {
+        assert!(diff_hunks(&[both(0..3, 0..3)]).is_empty());
+        assert!(diff_hunks(&[]).is_empty());
+    }
+}
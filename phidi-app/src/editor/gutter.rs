@@ -1,3 +1,5 @@
+use std::ops::Range;
+
 use floem::{
     Renderer, View, ViewId,
     context::PaintCx,
@@ -6,7 +8,10 @@
     text::{Attrs, AttrsList, FamilyOwned, TextLayout},
 };
 use im::HashMap;
-use phidi_core::{buffer::rope_text::RopeText, mode::Mode};
+use phidi_core::{
+    buffer::{diff::DiffLines, rope_text::RopeText},
+    mode::Mode,
+};
 use serde::{Deserialize, Serialize};
 
 use super::{EditorData, view::changes_colors_screen};
@@ -211,6 +216,44 @@ fn debug_name(&self) -> std::borrow::Cow<'static, str> {
     }
 }
 
+/// The lines of the change to the file shown in the gutter at `line`, in the
+/// way the proxy takes them for staging. A deletion, which is drawn between
+/// two lines, is the empty range at the line after it.
+pub fn head_change_lines(
+    changes: &im::Vector<DiffLines>,
+    line: usize,
+) -> Option<Range<usize>> {
+    let mut new_line = 0;
+    let mut deletion = None;
+    let mut changes = changes.iter().peekable();
+    while let Some(change) = changes.next() {
+        match change {
+            DiffLines::Both(info) => {
+                new_line += info.right.len();
+            }
+            DiffLines::Left(_) => {
+                // A deletion followed by additions is shown as one change.
+                if matches!(changes.peek(), Some(DiffLines::Right(_))) {
+                    continue;
+                }
+                if line == new_line || line + 1 == new_line {
+                    deletion = Some(new_line..new_line);
+                }
+            }
+            DiffLines::Right(range) => {
+                if range.contains(&line) {
+                    return Some(range.clone());
+                }
+                new_line = range.end;
+            }
+        }
+        if new_line > line + 1 {
+            break;
+        }
+    }
+    deletion
+}
+
 #[derive(Default, Clone)]
 pub struct FoldingRanges(pub Vec<FoldingRange>);
 
@@ -1,10 +1,11 @@
 use std::{
     cmp, collections::BTreeMap, ops::DerefMut, path::PathBuf, rc::Rc, sync::Arc,
+    time::Duration,
 };
 
 use floem::{
     Renderer, View, ViewId,
-    action::{set_ime_allowed, set_ime_cursor_area},
+    action::{exec_after, set_ime_allowed, set_ime_cursor_area},
     context::{PaintCx, StyleCx},
     event::{Event, EventListener, EventPropagation},
     keyboard::Modifiers,
@@ -20,6 +21,7 @@
     },
     style::{CursorColor, CursorStyle, Style, TextColor},
     taffy::prelude::NodeId,
+    text::{Attrs, AttrsList, FamilyOwned, TextLayout},
     views::{
         Decorators, clip, container, dyn_stack,
         editor::{
@@ -41,7 +43,7 @@
     },
 };
 use itertools::Itertools;
-use lsp_types::CodeLens;
+use lsp_types::{CodeLens, DocumentHighlightKind};
 use phidi_core::{
     buffer::{Buffer, diff::DiffLines, rope_text::RopeText},
     cursor::{CursorAffinity, CursorMode},
@@ -169,9 +171,32 @@ pub fn editor_view(
         hide_cursor.track();
         let occurrences = doc.with(|doc| doc.find_result.occurrences);
         occurrences.track();
+        doc.with(|doc| doc.document_highlights).track();
         id.request_paint();
     });
 
+    {
+        let editor = e_data.clone();
+        let cursor = e_data.cursor();
+        create_effect(move |_| {
+            let offset = cursor.with(|c| c.offset());
+            if !is_active.get() {
+                return;
+            }
+            // Wait for the cursor to settle rather than asking for every
+            // step of it.
+            let editor = editor.clone();
+            exec_after(Duration::from_millis(100), move |_| {
+                if editor.cursor().try_get_untracked().map(|c| c.offset())
+                    == Some(offset)
+                {
+                    editor.get_document_highlights();
+                    editor.update_linked_editing();
+                }
+            });
+        });
+    }
+
     create_effect(move |last_rev| {
         let buffer = doc.with(|doc| doc.buffer);
         let rev = buffer.with(|buffer| buffer.rev());
@@ -478,6 +503,103 @@ fn paint_current_line(
         });
     }
 
+    /// Paint the occurrences of the symbol under the cursor, telling writes
+    /// apart from reads.
+    fn paint_document_highlights(
+        &self,
+        cx: &mut PaintCx,
+        screen_lines: &ScreenLines,
+    ) {
+        if screen_lines.lines.is_empty() {
+            return;
+        }
+
+        let min_vline = *screen_lines.lines.first().unwrap();
+        let max_vline = *screen_lines.lines.last().unwrap();
+        let min_line = screen_lines.info(min_vline).unwrap().vline_info.rvline.line;
+        let max_line = screen_lines.info(max_vline).unwrap().vline_info.rvline.line;
+
+        let e_data = &self.editor;
+        let ed = &e_data.editor;
+        let doc = e_data.doc();
+
+        let config = self.editor.common.config.get_untracked();
+        let line_height = config.editor.line_height() as f64;
+
+        let start = ed.offset_of_line(min_line);
+        let end = ed.offset_of_line(max_line + 1);
+
+        doc.document_highlights.with_untracked(|highlights| {
+            // A lone occurrence is the one under the cursor, there's nothing to
+            // point out.
+            if highlights.len() < 2 {
+                return;
+            }
+            for (range, kind) in highlights {
+                if range.end < start || range.start > end {
+                    continue;
+                }
+                let color = if *kind == DocumentHighlightKind::WRITE {
+                    config.color(PhidiColor::EDITOR_HIGHLIGHT_WRITE)
+                } else {
+                    config.color(PhidiColor::EDITOR_HIGHLIGHT_READ)
+                };
+                self.paint_region(
+                    cx,
+                    ed,
+                    &SelRegion::new(range.start, range.end, None),
+                    color,
+                    false,
+                    screen_lines,
+                    line_height,
+                );
+            }
+        });
+    }
+
+    /// Paint who last changed the line of the cursor after the end of it.
+    fn paint_inline_blame(&self, cx: &mut PaintCx, screen_lines: &ScreenLines) {
+        let config = self.editor.common.config.get_untracked();
+        if !config.editor.enable_inline_blame {
+            return;
+        }
+
+        let e_data = &self.editor;
+        let ed = &e_data.editor;
+        let doc = e_data.doc();
+        let offset = e_data.cursor().with_untracked(|c| c.offset());
+        let (line, line_end) = doc.buffer.with_untracked(|buffer| {
+            let line = buffer.line_of_offset(offset);
+            (line, buffer.line_end_offset(line, true))
+        });
+        let Some(hunk) = doc.blame_of_line(line) else {
+            return;
+        };
+        // The annotation goes on the last visual line of a wrapped line.
+        let rvline = ed.rvline_of_offset(line_end, CursorAffinity::Backward);
+        let Some(info) = screen_lines.info(rvline) else {
+            return;
+        };
+
+        let family: Vec<FamilyOwned> =
+            FamilyOwned::parse_list(&config.editor.font_family).collect();
+        let attrs = Attrs::new()
+            .family(&family)
+            .color(config.color(PhidiColor::EDITOR_DIM))
+            .font_size(config.editor.inlay_hint_font_size() as f32);
+        let mut text_layout = TextLayout::new();
+        text_layout.set_text(
+            &crate::source_control::inline_blame_text(&hunk),
+            AttrsList::new(attrs),
+            None,
+        );
+
+        let line_height = config.editor.line_height() as f64;
+        let x = ed.text_layout(line).text.size().width + 30.0;
+        let y = info.vline_y + (line_height - text_layout.size().height) / 2.0;
+        cx.draw_text(&text_layout, Point::new(x, y));
+    }
+
     fn paint_find(&self, cx: &mut PaintCx, screen_lines: &ScreenLines) {
         let find_visual = self.editor.common.find.visual.get_untracked();
         if !find_visual && self.editor.on_screen_find.with_untracked(|f| !f.active) {
@@ -513,11 +635,12 @@ fn paint_find(&self, cx: &mut PaintCx, screen_lines: &ScreenLines) {
             for region in occurrences.with_untracked(|selection| {
                 selection.regions_in_range(start, end).to_vec()
             }) {
-                self.paint_find_region(
+                self.paint_region(
                     cx,
                     ed,
                     &region,
                     color,
+                    true,
                     screen_lines,
                     line_height,
                 );
@@ -527,11 +650,12 @@ fn paint_find(&self, cx: &mut PaintCx, screen_lines: &ScreenLines) {
         self.editor.on_screen_find.with_untracked(|find| {
             if find.active {
                 for region in &find.regions {
-                    self.paint_find_region(
+                    self.paint_region(
                         cx,
                         ed,
                         region,
                         color,
+                        true,
                         screen_lines,
                         line_height,
                     );
@@ -540,12 +664,15 @@ fn paint_find(&self, cx: &mut PaintCx, screen_lines: &ScreenLines) {
         });
     }
 
-    fn paint_find_region(
+    /// Paint the outline of the region, or fill it if `stroke` is false.
+    #[allow(clippy::too_many_arguments)]
+    fn paint_region(
         &self,
         cx: &mut PaintCx,
         ed: &Editor,
         region: &SelRegion,
         color: Color,
+        stroke: bool,
         screen_lines: &ScreenLines,
         line_height: f64,
     ) {
@@ -601,7 +728,11 @@ fn paint_find_region(
                 let rect = Size::new(x1 - x0, line_height)
                     .to_rect()
                     .with_origin(Point::new(x0, line_info.vline_y));
-                cx.stroke(&rect, color, &Stroke::new(1.0));
+                if stroke {
+                    cx.stroke(&rect, color, &Stroke::new(1.0));
+                } else {
+                    cx.fill(&rect, color, 0.0);
+                }
             }
         }
     }
@@ -1143,6 +1274,7 @@ fn paint(&mut self, cx: &mut PaintCx) {
         let screen_lines = ed.screen_lines.get_untracked();
         self.paint_diff_sections(cx, viewport, &screen_lines, &config);
         let screen_lines = ed.screen_lines.get_untracked();
+        self.paint_document_highlights(cx, &screen_lines);
         self.paint_find(cx, &screen_lines);
         let screen_lines = ed.screen_lines.get_untracked();
         self.paint_bracket_highlights_scope_lines(cx, viewport, &screen_lines);
@@ -1156,6 +1288,8 @@ fn paint(&mut self, cx: &mut PaintCx) {
             &screen_lines,
         );
         let screen_lines = ed.screen_lines.get_untracked();
+        self.paint_inline_blame(cx, &screen_lines);
+        let screen_lines = ed.screen_lines.get_untracked();
         self.paint_sticky_headers(cx, viewport, &screen_lines);
         self.paint_scroll_bar(cx, viewport, is_local, config);
     }
@@ -1308,6 +1442,7 @@ pub fn editor_container_view(
     stack((
         editor_breadcrumbs(workspace, editor.get_untracked(), config),
         stack((
+            editor_gutter_blame(editor),
             editor_gutter(window_tab_data.clone(), editor),
             editor_gutter_folding_range(
                 window_tab_data.clone(),
@@ -1808,7 +1943,7 @@ fn editor_gutter_code_actions(
             cursor.with(|cursor| (cursor.offset(), cursor.affinity));
         let has_code_actions = doc
             .code_actions()
-            .with(|c| c.get(&offset).map(|c| !c.1.is_empty()).unwrap_or(false));
+            .with(|c| c.get(&offset).map(|c| !c.is_empty()).unwrap_or(false));
         if has_code_actions {
             let vline = ed.vline_of_offset(offset, affinity);
             Some(vline)
@@ -1872,6 +2007,83 @@ fn editor_gutter_code_actions(
     .debug_name("Code Action LightBulb")
 }
 
+/// Who last changed each part of the file, at its first line, in front of the
+/// line numbers. Clicking one opens the changes of its commit.
+fn editor_gutter_blame(e_data: RwSignal<EditorData>) -> impl View {
+    let (ed, doc, config, internal_command) = e_data.with_untracked(|e| {
+        (
+            e.editor.clone(),
+            e.doc_signal(),
+            e.common.config,
+            e.common.internal_command,
+        )
+    });
+    let viewport = ed.viewport;
+    let screen_lines = ed.screen_lines;
+
+    clip(
+        dyn_stack(
+            move || doc.get().blame.get().unwrap_or_default(),
+            |hunk| hunk.clone(),
+            move |hunk| {
+                let text = crate::source_control::gutter_blame_text(&hunk);
+                let line = hunk.lines.start;
+                let commit = hunk.commit.map(|commit| commit.id);
+                let has_commit = commit.is_some();
+                label(move || text.clone())
+                    .on_click_stop(move |_| {
+                        let path = doc
+                            .get_untracked()
+                            .content
+                            .get_untracked()
+                            .path()
+                            .cloned();
+                        if let (Some(path), Some(commit)) = (path, commit.clone()) {
+                            internal_command.send(
+                                InternalCommand::OpenCommitFileChanges {
+                                    path,
+                                    commit,
+                                },
+                            );
+                        }
+                    })
+                    .style(move |s| {
+                        let line_y = screen_lines
+                            .with(|s| s.info_for_line(line))
+                            .map(|l| l.y)
+                            .unwrap_or(-100.0);
+                        let config = config.get();
+                        s.absolute()
+                            .width_pct(100.0)
+                            .height(config.editor.line_height() as f32)
+                            .items_center()
+                            .padding_horiz(10.0)
+                            .margin_top(line_y as f32 - viewport.get().y0 as f32)
+                            .text_ellipsis()
+                            .color(config.color(PhidiColor::EDITOR_DIM))
+                            .selectable(false)
+                            .apply_if(has_commit, |s| {
+                                s.hover(|s| {
+                                    s.cursor(CursorStyle::Pointer).color(
+                                        config.color(PhidiColor::EDITOR_FOREGROUND),
+                                    )
+                                })
+                            })
+                    })
+            },
+        )
+        .style(|s| s.size_pct(100.0, 100.0)),
+    )
+    .style(move |s| {
+        let config = config.get();
+        let is_file = doc.get().content.with(|content| content.is_file());
+        s.width(config.editor.font_size() as f32 * 16.0)
+            .height_pct(100.0)
+            .apply_if(!config.editor.show_blame_gutter || !is_file, |s| s.hide())
+    })
+    .debug_name("Blame Gutter")
+}
+
 fn editor_gutter(
     window_tab_data: Rc<WindowTabData>,
     e_data: RwSignal<EditorData>,
@@ -1924,6 +2136,17 @@ fn editor_gutter(
                             scroll_delta.set(pointer_event.delta);
                         }
                     })
+                    .on_event_cont(EventListener::PointerDown, move |event| {
+                        if let Event::PointerDown(pointer_event) = event {
+                            if pointer_event.button.is_secondary() {
+                                let y = pointer_event.pos.y
+                                    + viewport.get_untracked().y0;
+                                e_data
+                                    .get_untracked()
+                                    .gutter_right_click(Point::new(0.0, y));
+                            }
+                        }
+                    })
                     .style(|s| s.size_pct(100.0, 100.0)),
                 editor_gutter_code_actions(e_data, gutter_width, icon_padding),
             ))
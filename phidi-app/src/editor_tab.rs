@@ -265,6 +265,13 @@ pub fn view_info(
                 let diff_editor_data = diff_editors
                     .with(|diff_editors| diff_editors.get(&diff_editor_id).cloned());
                 let confirmed = diff_editor_data.as_ref().map(|d| d.confirmed);
+                // Both sides are old versions for the changes of a commit.
+                let history = diff_editor_data.as_ref().and_then(|data| {
+                    data.right.doc_signal().with(|doc| match doc.content.get() {
+                        DocContent::History(history) => Some(history),
+                        _ => None,
+                    })
+                });
 
                 let info = diff_editor_data
                     .map(|diff_editor_data| {
@@ -325,12 +332,32 @@ pub fn view_info(
                             left_is_pristine && right_is_pristine,
                         )
                     }
-                    [None, None] => (
-                        config.ui_svg(PhidiIcons::FILE),
-                        Some(config.color(PhidiColor::PHIDI_ICON_ACTIVE)),
-                        "local".to_string(),
-                        true,
-                    ),
+                    [None, None] => match history {
+                        Some(history) => {
+                            let (svg, color) = config.file_svg(&history.path);
+                            let version =
+                                &history.version[..history.version.len().min(7)];
+                            (
+                                svg,
+                                color,
+                                format!(
+                                    "{} ({version})",
+                                    history
+                                        .path
+                                        .file_name()
+                                        .unwrap_or_default()
+                                        .to_string_lossy()
+                                ),
+                                true,
+                            )
+                        }
+                        None => (
+                            config.ui_svg(PhidiIcons::FILE),
+                            Some(config.color(PhidiColor::PHIDI_ICON_ACTIVE)),
+                            "local".to_string(),
+                            true,
+                        ),
+                    },
                 };
                 EditorTabChildViewInfo {
                     icon,
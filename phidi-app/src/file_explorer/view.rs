@@ -1,4 +1,8 @@
-use std::{path::Path, rc::Rc, sync::Arc};
+use std::{
+    path::{Path, PathBuf},
+    rc::Rc,
+    sync::Arc,
+};
 
 use floem::{
     View,
@@ -15,10 +19,11 @@
         virtual_stack,
     },
 };
+use indexmap::IndexMap;
 use phidi_core::selection::Selection;
 use phidi_rpc::{
     file::{FileNodeViewData, FileNodeViewKind, Naming},
-    source_control::FileDiffKind,
+    source_control::{FileDiff, FileDiffKind},
 };
 use phidi_xi_rope::Rope;
 
@@ -128,7 +133,7 @@ fn file_node_text_color(
     node: FileNodeViewData,
     source_control: SourceControlData,
 ) -> Color {
-    let diff = source_control.file_diffs.with(|file_diffs| {
+    let file_diff_kind = |file_diffs: &IndexMap<PathBuf, FileDiff>| {
         let FileNodeViewKind::Path(path) = &node.kind else {
             return None;
         };
@@ -139,9 +144,13 @@ fn file_node_text_color(
                 .find(|p| p.as_path().starts_with(path))
                 .map(|_| FileDiffKind::Modified)
         } else {
-            file_diffs.get(path).map(|(diff, _)| diff.kind())
+            file_diffs.get(path).map(|diff| diff.kind())
         }
-    });
+    };
+    let diff = source_control
+        .file_diffs
+        .with(file_diff_kind)
+        .or_else(|| source_control.staged_diffs.with(file_diff_kind));
 
     let color = match diff {
         Some(FileDiffKind::Modified | FileDiffKind::Renamed) => {
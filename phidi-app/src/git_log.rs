@@ -0,0 +1,428 @@
+//! The history of the repository for the git log panel. Commits are loaded a
+//! page at a time, and the lanes of the commit graph are laid out as the pages
+//! come in, so that a new page carries on from the lanes of the last one.
+
+use std::{
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+use floem::{
+    ext_event::create_ext_action,
+    keyboard::Modifiers,
+    reactive::{RwSignal, Scope, SignalGet, SignalUpdate, SignalWith},
+};
+use phidi_core::{command::EditCommand, mode::Mode};
+use phidi_rpc::{
+    proxy::ProxyResponse,
+    source_control::{FileDiff, GitLogFilter, LogEntry},
+};
+use phidi_xi_rope::Rope;
+
+use crate::{
+    command::{CommandExecuted, CommandKind, InternalCommand},
+    editor::EditorData,
+    keypress::{KeyPressFocus, condition::Condition},
+    main_split::Editors,
+    window_tab::CommonData,
+};
+
+/// How many commits are loaded at a time.
+const PAGE_SIZE: usize = 200;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LogFilterField {
+    Branch,
+    Path,
+    Author,
+    Message,
+}
+
+/// How the commit graph is drawn in the row of a commit. Lanes are the columns
+/// of the graph, counted from the left.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct GraphRow {
+    /// The lane of the commit itself.
+    pub lane: usize,
+    /// The lines from the top of the row to its middle, as the lane at the top
+    /// and the lane in the middle.
+    pub top: Vec<(usize, usize)>,
+    /// The lines from the middle of the row to its bottom, as the lane in the
+    /// middle and the lane at the bottom.
+    pub bottom: Vec<(usize, usize)>,
+    /// How many lanes the row needs.
+    pub width: usize,
+}
+
+/// The lanes of the graph between two rows, each waiting for the commit with
+/// the id in it.
+#[derive(Clone, Debug, Default)]
+pub struct CommitGraph {
+    lanes: Vec<Option<String>>,
+}
+
+impl CommitGraph {
+    /// Lay out the next commit, the commits coming newest first.
+    pub fn row(&mut self, id: &str, parents: &[String]) -> GraphRow {
+        let waiting = |lane: &Option<String>| lane.as_deref() == Some(id);
+        let lane = self
+            .lanes
+            .iter()
+            .position(waiting)
+            .or_else(|| self.lanes.iter().position(|lane| lane.is_none()))
+            .unwrap_or(self.lanes.len());
+
+        let mut top = Vec::new();
+        let mut bottom = Vec::new();
+        for (i, waiting_for) in self.lanes.iter().enumerate() {
+            match waiting_for {
+                Some(_) if waiting(waiting_for) => top.push((i, lane)),
+                Some(_) => {
+                    top.push((i, i));
+                    bottom.push((i, i));
+                }
+                None => {}
+            }
+        }
+        // Every lane which led to the commit ends here.
+        for waiting_for in self.lanes.iter_mut() {
+            if waiting(waiting_for) {
+                *waiting_for = None;
+            }
+        }
+        if lane == self.lanes.len() {
+            self.lanes.push(None);
+        }
+
+        for (i, parent) in parents.iter().enumerate() {
+            let parent_lane = if i == 0 {
+                self.lanes[lane] = Some(parent.clone());
+                lane
+            } else if let Some(existing) = self
+                .lanes
+                .iter()
+                .position(|lane| lane.as_ref() == Some(parent))
+            {
+                existing
+            } else if let Some(free) =
+                self.lanes.iter().position(|lane| lane.is_none())
+            {
+                self.lanes[free] = Some(parent.clone());
+                free
+            } else {
+                self.lanes.push(Some(parent.clone()));
+                self.lanes.len() - 1
+            };
+            if !bottom.contains(&(lane, parent_lane)) {
+                bottom.push((lane, parent_lane));
+            }
+        }
+
+        let width = self.lanes.len();
+        while self.lanes.last().is_some_and(|lane| lane.is_none()) {
+            self.lanes.pop();
+        }
+        GraphRow {
+            lane,
+            top,
+            bottom,
+            width,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LogRow {
+    pub entry: LogEntry,
+    pub graph: GraphRow,
+}
+
+#[derive(Clone, Debug)]
+pub struct GitLogData {
+    pub branch_editor: EditorData,
+    pub path_editor: EditorData,
+    pub author_editor: EditorData,
+    pub message_editor: EditorData,
+    /// The filter input which has the keyboard focus.
+    pub active_filter: RwSignal<LogFilterField>,
+    pub rows: RwSignal<im::Vector<LogRow>>,
+    pub has_more: RwSignal<bool>,
+    pub loading: RwSignal<bool>,
+    pub error: RwSignal<Option<String>>,
+    /// The commit whose changed files are shown, with the files once they are
+    /// loaded.
+    pub expanded: RwSignal<Option<(String, Option<im::Vector<FileDiff>>)>>,
+    filter: RwSignal<GitLogFilter>,
+    graph: RwSignal<CommitGraph>,
+    /// Bumped for every new filter, so that pages for an old one are dropped.
+    query_id: RwSignal<usize>,
+    pub common: Rc<CommonData>,
+}
+
+impl KeyPressFocus for GitLogData {
+    fn get_mode(&self) -> Mode {
+        Mode::Insert
+    }
+
+    fn check_condition(&self, condition: Condition) -> bool {
+        matches!(condition, Condition::PanelFocus)
+    }
+
+    fn run_command(
+        &self,
+        command: &crate::command::PhidiCommand,
+        count: Option<usize>,
+        mods: Modifiers,
+    ) -> CommandExecuted {
+        match &command.kind {
+            CommandKind::Workbench(_) => {}
+            CommandKind::Scroll(_) => {}
+            CommandKind::Focus(_) => {}
+            CommandKind::Edit(EditCommand::InsertNewLine) => {
+                self.apply_filter();
+                return CommandExecuted::Yes;
+            }
+            CommandKind::Edit(_)
+            | CommandKind::Move(_)
+            | CommandKind::MultiSelection(_) => {
+                return self.active_editor().run_command(command, count, mods);
+            }
+            CommandKind::MotionMode(_) => {}
+        }
+        CommandExecuted::No
+    }
+
+    fn receive_char(&self, c: &str) {
+        self.active_editor().receive_char(c);
+    }
+}
+
+impl GitLogData {
+    pub fn new(cx: Scope, editors: Editors, common: Rc<CommonData>) -> Self {
+        Self {
+            branch_editor: editors.make_local(cx, common.clone()),
+            path_editor: editors.make_local(cx, common.clone()),
+            author_editor: editors.make_local(cx, common.clone()),
+            message_editor: editors.make_local(cx, common.clone()),
+            active_filter: cx.create_rw_signal(LogFilterField::Message),
+            rows: cx.create_rw_signal(im::Vector::new()),
+            has_more: cx.create_rw_signal(false),
+            loading: cx.create_rw_signal(false),
+            error: cx.create_rw_signal(None),
+            expanded: cx.create_rw_signal(None),
+            filter: cx.create_rw_signal(GitLogFilter::default()),
+            graph: cx.create_rw_signal(CommitGraph::default()),
+            query_id: cx.create_rw_signal(0),
+            common,
+        }
+    }
+
+    pub fn editor(&self, field: LogFilterField) -> &EditorData {
+        match field {
+            LogFilterField::Branch => &self.branch_editor,
+            LogFilterField::Path => &self.path_editor,
+            LogFilterField::Author => &self.author_editor,
+            LogFilterField::Message => &self.message_editor,
+        }
+    }
+
+    fn active_editor(&self) -> &EditorData {
+        self.editor(self.active_filter.get_untracked())
+    }
+
+    /// Load the log again from the start with the filter in the inputs.
+    pub fn apply_filter(&self) {
+        let text = |field| {
+            let text = self
+                .editor(field)
+                .doc()
+                .buffer
+                .with_untracked(|buffer| buffer.to_string());
+            let text = text.trim();
+            (!text.is_empty()).then(|| text.to_string())
+        };
+        let filter = GitLogFilter {
+            branch: text(LogFilterField::Branch),
+            path: text(LogFilterField::Path)
+                .map(|path| self.workspace_path().join(path)),
+            author: text(LogFilterField::Author),
+            message: text(LogFilterField::Message),
+        };
+        self.filter.set(filter);
+        self.reload();
+    }
+
+    /// Filter the log down to the commits which changed the file.
+    pub fn show_file_history(&self, path: &Path) {
+        let workspace_path = self.workspace_path();
+        let path = path.strip_prefix(&workspace_path).unwrap_or(path);
+        self.path_editor
+            .doc()
+            .reload(Rope::from(path.to_string_lossy()), true);
+        self.apply_filter();
+    }
+
+    pub fn reload(&self) {
+        self.query_id.update(|id| *id += 1);
+        self.rows.set(im::Vector::new());
+        self.graph.set(CommitGraph::default());
+        self.has_more.set(false);
+        self.loading.set(false);
+        self.error.set(None);
+        self.expanded.set(None);
+        self.load_more();
+    }
+
+    pub fn load_more(&self) {
+        if self.loading.get_untracked() {
+            return;
+        }
+        self.loading.set(true);
+
+        let filter = self.filter.get_untracked();
+        // The parents of the commits which are filtered out aren't in the log,
+        // so the graph only joins the commits up when none are left out.
+        let connected = filter.path.is_none()
+            && filter.author.is_none()
+            && filter.message.is_none();
+        let after = self.rows.with_untracked(|rows| {
+            rows.last().map(|row| row.entry.commit.id.clone())
+        });
+        let query_id = self.query_id.get_untracked();
+        let current_query_id = self.query_id;
+        let rows = self.rows;
+        let graph = self.graph;
+        let has_more = self.has_more;
+        let loading = self.loading;
+        let error = self.error;
+        let send = create_ext_action(self.common.scope, move |result| {
+            if current_query_id.get_untracked() != query_id {
+                return;
+            }
+            loading.set(false);
+            match result {
+                Ok(ProxyResponse::GitLog {
+                    commits,
+                    has_more: more,
+                }) => {
+                    let new_rows = graph
+                        .try_update(|graph| {
+                            commits
+                                .into_iter()
+                                .map(|entry| {
+                                    let parents: &[String] =
+                                        if connected { &entry.parents } else { &[] };
+                                    let row = graph.row(&entry.commit.id, parents);
+                                    LogRow { entry, graph: row }
+                                })
+                                .collect::<Vec<_>>()
+                        })
+                        .unwrap_or_default();
+                    rows.update(|rows| rows.extend(new_rows));
+                    has_more.set(more);
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    error.set(Some(e.message));
+                }
+            }
+        });
+        self.common
+            .proxy
+            .git_log(filter, after, PAGE_SIZE, move |result| {
+                send(result);
+            });
+    }
+
+    /// Show the files which the commit changed, or hide them if they're shown.
+    pub fn toggle_expanded(&self, commit: &str) {
+        let is_expanded = self.expanded.with_untracked(|expanded| {
+            expanded.as_ref().is_some_and(|(id, _)| id == commit)
+        });
+        if is_expanded {
+            self.expanded.set(None);
+            return;
+        }
+
+        self.expanded.set(Some((commit.to_string(), None)));
+        let expanded = self.expanded;
+        let id = commit.to_string();
+        let send = create_ext_action(self.common.scope, move |result| {
+            if let Ok(ProxyResponse::GitCommitChanges { diffs }) = result {
+                expanded.update(|expanded| {
+                    if let Some((commit, files)) = expanded {
+                        if *commit == id {
+                            *files = Some(diffs.into());
+                        }
+                    }
+                });
+            }
+        });
+        self.common
+            .proxy
+            .git_commit_changes(commit.to_string(), move |result| {
+                send(result);
+            });
+    }
+
+    pub fn open_file_changes(&self, commit: &str, diff: FileDiff) {
+        self.common
+            .internal_command
+            .send(InternalCommand::OpenCommitChanges {
+                commit: commit.to_string(),
+                diff,
+            });
+    }
+
+    fn workspace_path(&self) -> PathBuf {
+        self.common.workspace.path.clone().unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CommitGraph, GraphRow};
+
+    #[test]
+    fn test_commit_graph() {
+        // d merges c into b, which both come from a.
+        let mut graph = CommitGraph::default();
+        let ids =
+            |ids: &[&str]| ids.iter().map(|id| id.to_string()).collect::<Vec<_>>();
+        let rows = [
+            graph.row("d", &ids(&["b", "c"])),
+            graph.row("c", &ids(&["a"])),
+            graph.row("b", &ids(&["a"])),
+            graph.row("a", &[]),
+        ];
+        assert_eq!(
+            rows,
+            [
+                GraphRow {
+                    lane: 0,
+                    top: vec![],
+                    bottom: vec![(0, 0), (0, 1)],
+                    width: 2,
+                },
+                GraphRow {
+                    lane: 1,
+                    top: vec![(0, 0), (1, 1)],
+                    bottom: vec![(0, 0), (1, 1)],
+                    width: 2,
+                },
+                GraphRow {
+                    lane: 0,
+                    top: vec![(0, 0), (1, 1)],
+                    bottom: vec![(1, 1), (0, 0)],
+                    width: 2,
+                },
+                GraphRow {
+                    lane: 0,
+                    top: vec![(0, 0), (1, 0)],
+                    bottom: vec![],
+                    width: 2,
+                },
+            ]
+        );
+    }
+}
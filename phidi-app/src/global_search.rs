@@ -1,14 +1,21 @@
-use std::{ops::Range, path::PathBuf, rc::Rc};
+use std::{
+    collections::{HashMap, HashSet},
+    ops::Range,
+    path::PathBuf,
+    rc::Rc,
+};
 
 use floem::{
     ext_event::create_ext_action,
     keyboard::Modifiers,
+    prelude::SignalTrack,
     reactive::{Memo, RwSignal, Scope, SignalGet, SignalUpdate, SignalWith},
     views::VirtualVector,
 };
 use indexmap::IndexMap;
+use lsp_types::{Url, WorkspaceEdit};
 use phidi_core::{mode::Mode, selection::Selection};
-use phidi_rpc::proxy::{ProxyResponse, SearchMatch};
+use phidi_rpc::proxy::{ProxyResponse, SearchMatch, SearchOptions};
 use phidi_xi_rope::Rope;
 
 use crate::{
@@ -23,14 +30,31 @@
 pub struct SearchMatchData {
     pub expanded: RwSignal<bool>,
     pub matches: RwSignal<im::Vector<SearchMatch>>,
+    /// The matches left out of a replace, by their line and start column.
+    pub excluded: RwSignal<HashSet<(usize, usize)>>,
     pub line_height: Memo<f64>,
 }
 
 impl SearchMatchData {
+    pub fn is_excluded(&self, m: &SearchMatch) -> bool {
+        self.excluded
+            .with(|excluded| excluded.contains(&(m.line, m.start)))
+    }
+
+    pub fn toggle_excluded(&self, m: &SearchMatch) {
+        self.excluded.update(|excluded| {
+            if !excluded.remove(&(m.line, m.start)) {
+                excluded.insert((m.line, m.start));
+            }
+        });
+    }
+
     pub fn height(&self) -> f64 {
         let line_height = self.line_height.get();
         let count = if self.expanded.get() {
-            self.matches.with(|m| m.len()) + 1
+            self.matches
+                .with(|m| m.iter().map(match_lines).sum::<usize>())
+                + 1
         } else {
             1
         };
@@ -38,10 +62,39 @@ pub fn height(&self) -> f64 {
     }
 }
 
+/// The number of lines a match takes up in the results, with the lines of
+/// context around it.
+pub fn match_lines(m: &SearchMatch) -> usize {
+    m.context_before.len() + 1 + m.context_after.len()
+}
+
+/// The inputs of the search panel.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SearchInput {
+    Search,
+    Replace,
+    Include,
+    Exclude,
+}
+
 #[derive(Clone, Debug)]
 pub struct GlobalSearchData {
     pub editor: EditorData,
+    pub replace_editor: EditorData,
+    pub replace_active: RwSignal<bool>,
+    /// Globs of the files to search, and `type:<name>` for file types.
+    pub include_editor: EditorData,
+    /// Globs of the files to leave out.
+    pub exclude_editor: EditorData,
+    pub focused_input: RwSignal<SearchInput>,
     pub search_result: RwSignal<IndexMap<PathBuf, SearchMatchData>>,
+    /// Whether there are more results than the maximum.
+    pub limit_hit: RwSignal<bool>,
+    /// The revisions of the open files when they were searched, which a
+    /// replace checks the files against.
+    pub revisions: RwSignal<HashMap<PathBuf, u64>>,
+    /// Set to search again, when open files changed since they were searched.
+    pub refresh: RwSignal<()>,
     pub main_split: MainSplitData,
     pub common: Rc<CommonData>,
 }
@@ -68,7 +121,7 @@ fn run_command(
             CommandKind::Edit(_)
             | CommandKind::Move(_)
             | CommandKind::MultiSelection(_) => {
-                return self.editor.run_command(command, count, mods);
+                return self.focused_editor().run_command(command, count, mods);
             }
             CommandKind::MotionMode(_) => {}
         }
@@ -76,7 +129,7 @@ fn run_command(
     }
 
     fn receive_char(&self, c: &str) {
-        self.editor.receive_char(c);
+        self.focused_editor().receive_char(c);
     }
 }
 
@@ -87,7 +140,9 @@ fn total_len(&self) -> usize {
                 .iter()
                 .map(|(_, data)| {
                     if data.expanded.get() {
-                        data.matches.with(|m| m.len()) + 1
+                        data.matches
+                            .with(|m| m.iter().map(match_lines).sum::<usize>())
+                            + 1
                     } else {
                         1
                     }
@@ -108,11 +163,22 @@ impl GlobalSearchData {
     pub fn new(cx: Scope, main_split: MainSplitData) -> Self {
         let common = main_split.common.clone();
         let editor = main_split.editors.make_local(cx, common.clone());
+        let replace_editor = main_split.editors.make_local(cx, common.clone());
+        let include_editor = main_split.editors.make_local(cx, common.clone());
+        let exclude_editor = main_split.editors.make_local(cx, common.clone());
         let search_result = cx.create_rw_signal(IndexMap::new());
 
         let global_search = Self {
             editor,
+            replace_editor,
+            replace_active: cx.create_rw_signal(false),
+            include_editor,
+            exclude_editor,
+            focused_input: cx.create_rw_signal(SearchInput::Search),
             search_result,
+            limit_hit: cx.create_rw_signal(false),
+            revisions: cx.create_rw_signal(HashMap::new()),
+            refresh: cx.create_rw_signal(()),
             main_split,
             common,
         };
@@ -120,22 +186,59 @@ pub fn new(cx: Scope, main_split: MainSplitData) -> Self {
         {
             let global_search = global_search.clone();
             let buffer = global_search.editor.doc().buffer;
+            let replace_buffer = global_search.replace_editor.doc().buffer;
+            let replace_active = global_search.replace_active;
+            let include_buffer = global_search.include_editor.doc().buffer;
+            let exclude_buffer = global_search.exclude_editor.doc().buffer;
             cx.create_effect(move |_| {
+                global_search.refresh.track();
                 let pattern = buffer.with(|buffer| buffer.to_string());
                 if pattern.is_empty() {
                     global_search.search_result.update(|r| r.clear());
+                    global_search.limit_hit.set(false);
                     return;
                 }
                 let case_sensitive = global_search.common.find.case_sensitive(true);
                 let whole_word = global_search.common.find.whole_words.get();
                 let is_regex = global_search.common.find.is_regex.get();
+                // The replacement is previewed while the replace input is shown.
+                let replacement = if replace_active.get() {
+                    Some(replace_buffer.with(|buffer| buffer.to_string()))
+                } else {
+                    None
+                };
+                let (include, file_types) =
+                    parse_include(&include_buffer.with(|buffer| buffer.to_string()));
+                let (max_results, context_lines) =
+                    global_search.common.config.with(|config| {
+                        (
+                            config.ui.search_max_results,
+                            config.ui.search_context_lines,
+                        )
+                    });
+                let options = SearchOptions {
+                    include,
+                    exclude: split_globs(
+                        &exclude_buffer.with(|buffer| buffer.to_string()),
+                    )
+                    .map(str::to_string)
+                    .collect(),
+                    file_types,
+                    max_results: (max_results > 0).then_some(max_results),
+                    context_lines,
+                };
                 let send = {
                     let global_search = global_search.clone();
                     create_ext_action(cx, move |result| {
-                        if let Ok(ProxyResponse::GlobalSearchResponse { matches }) =
-                            result
+                        if let Ok(ProxyResponse::GlobalSearchResponse {
+                            matches,
+                            limit_hit,
+                            revisions,
+                        }) = result
                         {
                             global_search.update_matches(matches);
+                            global_search.limit_hit.set(limit_hit);
+                            global_search.revisions.set(revisions);
                         }
                     })
                 };
@@ -144,6 +247,8 @@ pub fn new(cx: Scope, main_split: MainSplitData) -> Self {
                     case_sensitive,
                     whole_word,
                     is_regex,
+                    replacement,
+                    options,
                     move |result| {
                         send(result);
                     },
@@ -178,10 +283,23 @@ fn update_matches(&self, matches: IndexMap<PathBuf, Vec<SearchMatch>>) {
                                     .common
                                     .scope
                                     .create_rw_signal(im::Vector::new()),
+                                excluded: self
+                                    .common
+                                    .scope
+                                    .create_rw_signal(HashSet::new()),
                                 line_height: self.common.ui_line_height,
                             }
                         });
 
+                    // Keep the exclusions of the matches that are still there,
+                    // e.g. when only the replacement changed.
+                    match_data.excluded.update(|excluded| {
+                        excluded.retain(|(line, start)| {
+                            matches
+                                .iter()
+                                .any(|m| m.line == *line && m.start == *start)
+                        })
+                    });
                     match_data.matches.set(matches.into());
 
                     (path, match_data)
@@ -190,11 +308,154 @@ fn update_matches(&self, matches: IndexMap<PathBuf, Vec<SearchMatch>>) {
         );
     }
 
+    fn focused_editor(&self) -> &EditorData {
+        match self.focused_input.get_untracked() {
+            SearchInput::Search => &self.editor,
+            SearchInput::Replace => &self.replace_editor,
+            SearchInput::Include => &self.include_editor,
+            SearchInput::Exclude => &self.exclude_editor,
+        }
+    }
+
+    /// Replace the matches shown in the results that aren't excluded, which
+    /// leaves out any past the result limit. Files that are open are edited,
+    /// so the replacement can be undone, and the others are written by the
+    /// proxy.
+    pub fn replace_all(&self) {
+        let pattern = self.editor.doc().buffer.with_untracked(|b| b.to_string());
+        if pattern.is_empty() {
+            return;
+        }
+        let replacement = self
+            .replace_editor
+            .doc()
+            .buffer
+            .with_untracked(|b| b.to_string());
+        let search_result = self.search_result;
+        let files = search_result
+            .get_untracked()
+            .iter()
+            .filter_map(|(path, match_data)| {
+                let excluded = match_data.excluded.get_untracked();
+                let included = match_data
+                    .matches
+                    .get_untracked()
+                    .iter()
+                    .map(|m| (m.line, m.start))
+                    .filter(|m| !excluded.contains(m))
+                    .collect::<Vec<_>>();
+                (!included.is_empty()).then(|| (path.clone(), included))
+            })
+            .collect::<IndexMap<_, _>>();
+        if files.is_empty() {
+            return;
+        }
+
+        let revisions = self.revisions.get_untracked();
+
+        let send = {
+            let main_split = self.main_split.clone();
+            let refresh = self.refresh;
+            create_ext_action(self.common.scope, move |result| {
+                let Ok(ProxyResponse::GlobalReplaceResponse {
+                    edits, stale, ..
+                }) = result
+                else {
+                    return;
+                };
+                // Files whose document is gone by now are opened again to be
+                // edited, like those of a workspace edit.
+                let mut closed = HashMap::new();
+                for (path, edits) in edits {
+                    let doc = main_split
+                        .docs
+                        .with_untracked(|docs| docs.get(&path).cloned());
+                    if let Some(doc) = doc {
+                        doc.do_text_edit(&edits);
+                    } else if let Ok(url) = Url::from_file_path(&path) {
+                        closed.insert(url, edits);
+                    }
+                }
+                if !closed.is_empty() {
+                    main_split.apply_workspace_edit(&WorkspaceEdit {
+                        changes: Some(closed),
+                        ..Default::default()
+                    });
+                }
+                // Only the excluded matches are left.
+                search_result.update(|search_result| {
+                    search_result.retain(|_, match_data| {
+                        let excluded = match_data.excluded.get_untracked();
+                        match_data.matches.update(|matches| {
+                            matches.retain(|m| excluded.contains(&(m.line, m.start)))
+                        });
+                        !excluded.is_empty()
+                    })
+                });
+                // The matches of files edited since the search are left alone,
+                // and are found again where they are now.
+                if !stale.is_empty() {
+                    refresh.set(());
+                }
+            })
+        };
+        let find = &self.common.find;
+        self.common.proxy.global_replace(
+            pattern,
+            find.case_sensitive(false),
+            find.whole_words.get_untracked(),
+            find.is_regex.get_untracked(),
+            replacement,
+            files,
+            revisions,
+            move |result| send(result),
+        );
+    }
+
     pub fn set_pattern(&self, pattern: String) {
         let pattern_len = pattern.len();
+        self.focused_input.set(SearchInput::Search);
         self.editor.doc().reload(Rope::from(pattern), true);
         self.editor
             .cursor()
             .update(|cursor| cursor.set_insert(Selection::region(0, pattern_len)));
     }
 }
+
+fn split_globs(input: &str) -> impl Iterator<Item = &str> {
+    input
+        .split(',')
+        .map(str::trim)
+        .filter(|glob| !glob.is_empty())
+}
+
+/// The globs and the file types, given as `type:<name>`, of the files to
+/// include in a search.
+fn parse_include(input: &str) -> (Vec<String>, Vec<String>) {
+    let mut globs = Vec::new();
+    let mut file_types = Vec::new();
+    for glob in split_globs(input) {
+        match glob.strip_prefix("type:") {
+            Some(file_type) => file_types.push(file_type.trim().to_string()),
+            None => globs.push(glob.to_string()),
+        }
+    }
+    (globs, file_types)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_include;
+
+    #[test]
+    fn test_parse_include() {
+        assert_eq!(
+            parse_include("src/**, type:rust,, *.toml "),
+            (
+                vec!["src/**".to_string(), "*.toml".to_string()],
+                vec!["rust".to_string()]
+            )
+        );
+        assert_eq!(parse_include(""), (Vec::new(), Vec::new()));
+    }
+}
@@ -1,6 +1,8 @@
 pub mod about;
+pub mod agent;
 pub mod alert;
 pub mod app;
+pub mod checkpoint;
 pub mod code_action;
 pub mod code_lens;
 pub mod command;
@@ -14,6 +16,7 @@
 pub mod file_explorer;
 pub mod find;
 pub mod focus_text;
+pub mod git_log;
 pub mod global_search;
 pub mod history;
 pub mod hover;
@@ -28,6 +31,7 @@
 pub mod palette;
 pub mod panel;
 pub mod plugin;
+pub mod proposal;
 pub mod proxy;
 pub mod rename;
 pub mod settings;
@@ -16,7 +16,7 @@
 use itertools::Itertools;
 use lsp_types::{
     CodeAction, CodeActionOrCommand, DiagnosticSeverity, DocumentChangeOperation,
-    DocumentChanges, OneOf, Position, TextEdit, Url, WorkspaceEdit,
+    DocumentChanges, OneOf, Position, Range, TextEdit, Url, WorkspaceEdit,
 };
 use phidi_core::{
     buffer::rope_text::RopeText, command::FocusCommand, cursor::Cursor,
@@ -24,9 +24,11 @@
 };
 use phidi_rpc::{
     buffer::BufferId,
+    context_pack::{ContextPackFormat, ContextPackRequest},
     core::FileChanged,
     plugin::{PluginId, VoltID},
     proxy::ProxyResponse,
+    source_control::FileDiff,
 };
 use phidi_xi_rope::{Rope, spans::SpansBuilder};
 use serde::{Deserialize, Serialize};
@@ -728,10 +730,84 @@ pub fn open_file_changes(&self, path: PathBuf) {
         );
     }
 
+    /// Open a diff of the file in the commit against it in the parent of the
+    /// commit, where it had its old path if the commit renamed it.
+    pub fn open_commit_file_changes(&self, path: PathBuf, commit: String) {
+        let id = commit.clone();
+        let main_split = self.clone();
+        let send = create_ext_action(self.scope, move |diffs: Vec<FileDiff>| {
+            let old_path = diffs
+                .into_iter()
+                .find_map(|diff| match diff {
+                    FileDiff::Renamed(new, old) if new == path => Some(old),
+                    _ => None,
+                })
+                .unwrap_or_else(|| path.clone());
+            let left = main_split.history_doc(old_path, format!("{commit}^"));
+            let right = main_split.history_doc(path, commit);
+            main_split.open_diff_docs(left, right);
+        });
+        self.common
+            .proxy
+            .git_commit_changes(id, move |result| match result {
+                Ok(ProxyResponse::GitCommitChanges { diffs }) => send(diffs),
+                _ => send(Vec::new()),
+            });
+    }
+
+    /// Open a diff of one of the files the commit changed, in place of the
+    /// diff of another one unless that was kept open.
+    pub fn open_commit_changes(&self, commit: String, diff: FileDiff) {
+        if self.common.focus.get_untracked() != Focus::Workbench {
+            self.common.focus.set(Focus::Workbench);
+        }
+        let (left_path, right_path) = match diff {
+            FileDiff::Renamed(new, old) => (old, new),
+            FileDiff::Modified(path)
+            | FileDiff::Added(path)
+            | FileDiff::Deleted(path) => (path.clone(), path),
+        };
+        let left = self.history_doc(left_path, format!("{commit}^"));
+        let right = self.history_doc(right_path, commit);
+        self.open_diff_docs(left, right);
+    }
+
+    /// A read only document of the file in a revision, which is filled in
+    /// when the proxy has read it from the repository.
+    fn history_doc(&self, path: PathBuf, version: String) -> Rc<Doc> {
+        let doc = Rc::new(Doc::new_history(
+            self.scope,
+            DocContent::History(DocHistory {
+                path: path.clone(),
+                version: version.clone(),
+            }),
+            self.editors,
+            self.common.clone(),
+        ));
+        let send = {
+            let doc = doc.clone();
+            create_ext_action(self.scope, move |result| {
+                if let Ok(ProxyResponse::GitFileAtRevision { content }) = result {
+                    doc.init_content(Rope::from(content));
+                }
+            })
+        };
+        self.common
+            .proxy
+            .git_file_at_revision(path, version, move |result| {
+                send(result);
+            });
+        doc
+    }
+
     pub fn open_diff_files(&self, left_path: PathBuf, right_path: PathBuf) {
         let [left, right] =
             [left_path, right_path].map(|path| self.get_doc(path, None).0);
 
+        self.open_diff_docs(left, right);
+    }
+
+    pub fn open_diff_docs(&self, left: Rc<Doc>, right: Rc<Doc>) {
         self.get_editor_tab_child(
             EditorTabChildSource::DiffEditor { left, right },
             false,
@@ -804,15 +880,22 @@ fn get_editor_tab_child(
             *editor_tab
         };
 
+        let is_same_doc = |a: &Rc<Doc>, b: &Rc<Doc>| {
+            let content = a.content.get_untracked();
+            // Local documents have no identity besides the document itself
+            if content.is_local() {
+                Rc::ptr_eq(a, b)
+            } else {
+                content == b.content.get_untracked()
+            }
+        };
         let is_same_diff_editor =
             |diff_editor_id: &DiffEditorId, left: &Rc<Doc>, right: &Rc<Doc>| {
                 diff_editors
                     .get(diff_editor_id)
                     .map(|diff_editor| {
-                        left.content.get_untracked()
-                            == diff_editor.left.doc().content.get_untracked()
-                            && right.content.get_untracked()
-                                == diff_editor.right.doc().content.get_untracked()
+                        is_same_doc(left, &diff_editor.left.doc())
+                            && is_same_doc(right, &diff_editor.right.doc())
                     })
                     .unwrap_or(false)
             };
@@ -2259,6 +2342,30 @@ pub fn apply_workspace_edit(&self, edit: &WorkspaceEdit) {
         }
     }
 
+    /// The first open document whose version has moved on from the one the
+    /// edit was made for.
+    pub fn outdated_document(&self, edit: &WorkspaceEdit) -> Option<Url> {
+        let edits = match edit.document_changes.as_ref()? {
+            DocumentChanges::Edits(edits) => edits.iter().collect::<Vec<_>>(),
+            DocumentChanges::Operations(ops) => ops
+                .iter()
+                .filter_map(|op| match op {
+                    DocumentChangeOperation::Edit(edit) => Some(edit),
+                    DocumentChangeOperation::Op(_) => None,
+                })
+                .collect(),
+        };
+        edits.into_iter().find_map(|edit| {
+            let document = &edit.text_document;
+            let version = document.version?;
+            let path = document.uri.to_file_path().ok()?;
+            let rev = self
+                .docs
+                .with_untracked(|docs| docs.get(&path).map(|doc| doc.rev()))?;
+            (rev != version as u64).then(|| document.uri.clone())
+        })
+    }
+
     pub fn next_error(&self) {
         let file_diagnostics =
             self.file_diagnostics_items(DiagnosticSeverity::ERROR);
@@ -2337,6 +2444,75 @@ fn file_diagnostics_items(
             .collect()
     }
 
+    /// The selection of the active editor, the open files and the
+    /// diagnostics, for the proxy to make a context pack of.
+    pub fn context_pack_request(
+        &self,
+        format: ContextPackFormat,
+    ) -> ContextPackRequest {
+        let selection = self.active_editor.get_untracked().and_then(|editor| {
+            let doc = editor.doc();
+            let path = doc.content.get_untracked().path().cloned()?;
+            let range = doc.buffer.with_untracked(|buffer| {
+                let selection =
+                    editor.cursor().with_untracked(|c| c.edit_selection(buffer));
+                let region = selection.first()?;
+                Some(Range::new(
+                    buffer.offset_to_position(region.min()),
+                    buffer.offset_to_position(region.max()),
+                ))
+            })?;
+            Some((path, range))
+        });
+        // Docs stay loaded after their editors are closed, so only the files
+        // an editor tab shows are open.
+        let editors = self.editors;
+        let open_files = self
+            .editor_tabs
+            .get_untracked()
+            .values()
+            .flat_map(|editor_tab| {
+                editor_tab.with_untracked(|editor_tab| {
+                    editor_tab
+                        .children
+                        .iter()
+                        .filter_map(|(_, _, child)| match child {
+                            EditorTabChild::Editor(editor_id) => {
+                                editors.editor_untracked(*editor_id)
+                            }
+                            _ => None,
+                        })
+                        .filter_map(|editor| {
+                            editor.doc().content.get_untracked().path().cloned()
+                        })
+                        .collect::<Vec<_>>()
+                })
+            })
+            .unique()
+            .sorted()
+            .collect();
+        let diagnostics = self
+            .diagnostics
+            .get_untracked()
+            .into_iter()
+            .map(|(path, diagnostic)| {
+                let diagnostics = diagnostic.diagnostics.get_untracked();
+                (path, diagnostics.into_iter().collect::<Vec<_>>())
+            })
+            .filter(|(_, diagnostics)| !diagnostics.is_empty())
+            .sorted_by_key(|(path, _)| path.clone())
+            .collect();
+        let config = self.common.config.get_untracked();
+        ContextPackRequest {
+            selection,
+            open_files,
+            diagnostics,
+            files_exclude: config.editor.files_exclude.clone(),
+            token_budget: config.agent.context_pack_token_budget,
+            format,
+        }
+    }
+
     pub fn get_diagnostic_data(&self, path: &Path) -> DiagnosticData {
         if let Some(d) = self.diagnostics.with_untracked(|d| d.get(path).cloned()) {
             d
@@ -2996,7 +3172,9 @@ pub fn get_active_editor(&self) -> Option<EditorData> {
     }
 }
 
-fn workspace_edits(edit: &WorkspaceEdit) -> Option<HashMap<Url, Vec<TextEdit>>> {
+pub(crate) fn workspace_edits(
+    edit: &WorkspaceEdit,
+) -> Option<HashMap<Url, Vec<TextEdit>>> {
     if let Some(changes) = edit.changes.as_ref() {
         return Some(changes.clone());
     }
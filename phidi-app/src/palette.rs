@@ -22,14 +22,17 @@
 };
 use im::Vector;
 use itertools::Itertools;
-use lsp_types::{DocumentSymbol, DocumentSymbolResponse};
+use lsp_types::{DocumentSymbol, DocumentSymbolResponse, TextEdit};
 use nucleo::Utf32Str;
 use phidi_core::{
     buffer::rope_text::RopeText, command::FocusCommand, language::PhidiLanguage,
     line_ending::LineEnding, mode::Mode, movement::Movement, selection::Selection,
     syntax::Syntax,
 };
-use phidi_rpc::proxy::ProxyResponse;
+use phidi_rpc::{
+    proxy::ProxyResponse,
+    source_control::{BranchInfo, GitCredentials, RemoteOperation, StashEntry},
+};
 use phidi_xi_rope::Rope;
 use strum::{EnumMessage, IntoEnumIterator};
 use tracing::error;
@@ -39,6 +42,7 @@
     kind::PaletteKind,
 };
 use crate::{
+    alert::AlertButton,
     command::{
         CommandExecuted, CommandKind, InternalCommand, PhidiCommand, WindowCommand,
     },
@@ -68,6 +72,85 @@ pub enum PaletteStatus {
     Done,
 }
 
+/// A branch, tag or stash operation that the palette asks input for.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ScmAction {
+    CreateBranch,
+    /// Renaming asks for the branch first, then for its new name.
+    RenameBranch {
+        branch: Option<String>,
+    },
+    DeleteBranch,
+    MergeBranch,
+    /// Annotated tags ask for the name first, then for the message.
+    CreateTag {
+        annotated: bool,
+        name: Option<String>,
+    },
+    Stash {
+        include_untracked: bool,
+    },
+    ApplyStash,
+    PopStash,
+    DropStash,
+    /// The remote wants a username and password, which are asked one after
+    /// the other before the operation is started again.
+    Credentials {
+        operation: RemoteOperation,
+        url: String,
+        username: Option<String>,
+    },
+}
+
+impl ScmAction {
+    fn palette_kind(&self) -> PaletteKind {
+        match self {
+            ScmAction::RenameBranch { branch: None }
+            | ScmAction::DeleteBranch
+            | ScmAction::MergeBranch => PaletteKind::SCMBranches,
+            ScmAction::ApplyStash | ScmAction::PopStash | ScmAction::DropStash => {
+                PaletteKind::SCMStashes
+            }
+            ScmAction::CreateBranch
+            | ScmAction::RenameBranch { .. }
+            | ScmAction::CreateTag { .. }
+            | ScmAction::Stash { .. }
+            | ScmAction::Credentials { .. } => PaletteKind::SCMInput,
+        }
+    }
+
+    fn placeholder_text(&self) -> String {
+        let text = match self {
+            ScmAction::CreateBranch => "Type the name of the new branch",
+            ScmAction::RenameBranch { branch: None } => {
+                "Select the branch to rename"
+            }
+            ScmAction::RenameBranch { .. } => "Type the new name of the branch",
+            ScmAction::DeleteBranch => "Select the branch to delete",
+            ScmAction::MergeBranch => {
+                "Select the branch to merge into the current branch"
+            }
+            ScmAction::CreateTag { name: None, .. } => "Type the name of the tag",
+            ScmAction::CreateTag { .. } => "Type the message of the tag",
+            ScmAction::Stash { .. } => {
+                "Type a message for the stash, or leave it empty"
+            }
+            ScmAction::ApplyStash => "Select the stash to apply",
+            ScmAction::PopStash => "Select the stash to pop",
+            ScmAction::DropStash => "Select the stash to drop",
+            ScmAction::Credentials {
+                url,
+                username: None,
+                ..
+            } => return format!("Type the username for {url}"),
+            ScmAction::Credentials { url, .. } => {
+                return format!("Type the password or access token for {url}");
+            }
+        };
+        text.to_string()
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct PaletteInput {
     pub input: String,
@@ -107,6 +190,7 @@ pub struct PaletteData {
     pub source_control: SourceControlData,
     pub common: Rc<CommonData>,
     left_diff_path: RwSignal<Option<PathBuf>>,
+    scm_action: RwSignal<Option<ScmAction>>,
 }
 
 impl std::fmt::Debug for PaletteData {
@@ -214,6 +298,7 @@ pub fn new(
 
         let clicked_index = cx.create_rw_signal(Option::<usize>::None);
         let left_diff_path = cx.create_rw_signal(None);
+        let scm_action = cx.create_rw_signal(None);
 
         let palette = Self {
             run_id_counter,
@@ -238,6 +323,7 @@ pub fn new(
             source_control,
             common,
             left_diff_path,
+            scm_action,
         };
 
         {
@@ -295,10 +381,14 @@ pub fn new(
                         .unwrap();
                     if let Some(new_kind) = new_kind {
                         palette.run_inner(new_kind);
-                    } else if input
-                        .with_untracked(|i| i.kind == PaletteKind::WorkspaceSymbol)
-                    {
-                        palette.run_inner(PaletteKind::WorkspaceSymbol);
+                    } else if let Some(kind) = input.with_untracked(|i| {
+                        matches!(
+                            i.kind,
+                            PaletteKind::WorkspaceSymbol | PaletteKind::ColorPicker
+                        )
+                        .then_some(i.kind)
+                    }) {
+                        palette.run_inner(kind);
                     }
                 }
                 Some(new_input)
@@ -341,9 +431,30 @@ pub fn run(&self, kind: PaletteKind) {
             .update(|cursor| cursor.set_insert(Selection::caret(symbol.len())));
     }
 
+    /// Start the palette to ask for what a branch, tag or stash operation needs.
+    pub fn run_scm_action(&self, action: ScmAction) {
+        let kind = action.palette_kind();
+        self.scm_action.set(Some(action));
+        self.run(kind);
+    }
+
+    /// Whether the input is a password, which isn't shown.
+    pub fn is_masked(&self) -> bool {
+        self.kind.get() == PaletteKind::SCMInput
+            && self.scm_action.with(|action| {
+                matches!(
+                    action,
+                    Some(ScmAction::Credentials {
+                        username: Some(_),
+                        ..
+                    })
+                )
+            })
+    }
+
     /// Get the placeholder text to use in the palette input field.
-    pub fn placeholder_text(&self) -> &'static str {
-        match self.kind.get() {
+    pub fn placeholder_text(&self) -> String {
+        let text = match self.kind.get() {
             PaletteKind::SshHost => {
                 "Type [user@]host or select a previously connected workspace below"
             }
@@ -354,8 +465,22 @@ pub fn placeholder_text(&self) -> &'static str {
                     "Seleft left file"
                 }
             }
+            PaletteKind::ColorPicker => {
+                "Type a color like #ff8800, or pick how to write the current one"
+            }
+            PaletteKind::SCMBranches
+            | PaletteKind::SCMStashes
+            | PaletteKind::SCMInput => {
+                return self.scm_action.with(|action| {
+                    action
+                        .as_ref()
+                        .map(ScmAction::placeholder_text)
+                        .unwrap_or_default()
+                });
+            }
             _ => "",
-        }
+        };
+        text.to_string()
     }
 
     /// Execute the internal behavior of the palette for the given kind. This ignores updating and
@@ -415,7 +540,19 @@ fn run_inner(&self, kind: PaletteKind) {
             PaletteKind::SCMReferences => {
                 self.get_scm_references();
             }
+            PaletteKind::SCMBranches => {
+                self.get_scm_branches();
+            }
+            PaletteKind::SCMStashes => {
+                self.get_scm_stashes();
+            }
+            PaletteKind::SCMInput => {
+                self.items.update(|items| items.clear());
+            }
             PaletteKind::TerminalProfile => self.get_terminal_profiles(),
+            PaletteKind::ColorPicker => {
+                self.get_color_presentations();
+            }
         }
     }
 
@@ -1047,6 +1184,87 @@ fn get_line_endings(&self) {
         self.items.set(items);
     }
 
+    /// Initialize the palette with the ways the language server offers to
+    /// write the color under the cursor, or the color typed in instead.
+    fn get_color_presentations(&self) {
+        let Some(editor) = self.main_split.active_editor.get_untracked() else {
+            return;
+        };
+        let doc = editor.doc();
+        let Some(path) = doc.content.with_untracked(|c| c.path().cloned()) else {
+            return;
+        };
+        let offset = editor.cursor().with_untracked(|c| c.offset());
+        let input = self.input.get_untracked().input;
+        let color = doc.document_color_at(offset).and_then(|(range, info)| {
+            let color = if input.trim().is_empty() {
+                info.color
+            } else {
+                parse_hex_color(&input)?
+            };
+            Some((range, color))
+        });
+        let Some((range, color)) = color else {
+            self.items.update(|items| items.clear());
+            return;
+        };
+        let range = doc.buffer.with_untracked(|buffer| lsp_types::Range {
+            start: buffer.offset_to_position(range.start),
+            end: buffer.offset_to_position(range.end),
+        });
+
+        let set_items = self.items.write_only();
+        let send = create_ext_action(self.common.scope, move |result| {
+            if let Ok(ProxyResponse::GetColorPresentations { presentations }) =
+                result
+            {
+                let items = presentations
+                    .into_iter()
+                    .map(|presentation| {
+                        let mut edits =
+                            vec![presentation.text_edit.unwrap_or_else(|| {
+                                TextEdit {
+                                    range,
+                                    new_text: presentation.label.clone(),
+                                }
+                            })];
+                        edits.extend(
+                            presentation.additional_text_edits.unwrap_or_default(),
+                        );
+                        // The items all write the typed color, so they match the
+                        // input whatever their label.
+                        let filter_text = if input.trim().is_empty() {
+                            presentation.label.clone()
+                        } else {
+                            input.clone()
+                        };
+                        PaletteItem {
+                            content: PaletteItemContent::ColorPresentation {
+                                label: presentation.label,
+                                color,
+                                edits,
+                            },
+                            filter_text,
+                            score: 0,
+                            indices: Vec::new(),
+                        }
+                    })
+                    .collect();
+                set_items.set(items);
+            } else {
+                set_items.update(|items| items.clear());
+            }
+        });
+        self.common.proxy.get_color_presentations(
+            path,
+            color,
+            range,
+            move |result| {
+                send(result);
+            },
+        );
+    }
+
     fn get_scm_references(&self) {
         let branches = self.source_control.branches.get_untracked();
         let tags = self.source_control.tags.get_untracked();
@@ -1074,6 +1292,63 @@ fn get_scm_references(&self) {
         self.items.set(items);
     }
 
+    fn get_scm_branches(&self) {
+        // The checked out branch can't be deleted or merged into itself.
+        let skip_head = self.scm_action.with_untracked(|action| {
+            matches!(
+                action,
+                Some(ScmAction::DeleteBranch | ScmAction::MergeBranch)
+            )
+        });
+        let set_items = self.items.write_only();
+        let send = create_ext_action(self.common.scope, move |result| {
+            if let Ok(ProxyResponse::GitBranches { branches }) = result {
+                let items = branches
+                    .into_iter()
+                    .filter(|branch| !(skip_head && branch.is_head))
+                    .map(|branch| PaletteItem {
+                        filter_text: branch.name.clone(),
+                        content: PaletteItemContent::SCMBranch { branch },
+                        score: 0,
+                        indices: Vec::new(),
+                    })
+                    .collect();
+                set_items.set(items);
+            } else {
+                set_items.update(|items| items.clear());
+            }
+        });
+        self.common.proxy.git_branches(move |result| {
+            send(result);
+        });
+    }
+
+    fn get_scm_stashes(&self) {
+        let set_items = self.items.write_only();
+        let send = create_ext_action(self.common.scope, move |result| {
+            if let Ok(ProxyResponse::GitStashes { stashes }) = result {
+                let items = stashes
+                    .into_iter()
+                    .map(|stash| PaletteItem {
+                        filter_text: format!(
+                            "stash@{{{}}}: {}",
+                            stash.index, stash.message
+                        ),
+                        content: PaletteItemContent::SCMStash { stash },
+                        score: 0,
+                        indices: Vec::new(),
+                    })
+                    .collect();
+                set_items.set(items);
+            } else {
+                set_items.update(|items| items.clear());
+            }
+        });
+        self.common.proxy.git_stashes(move |result| {
+            send(result);
+        });
+    }
+
     fn get_terminal_profiles(&self) {
         let profiles = self.common.config.get().terminal.profiles.clone();
         let mut items: im::Vector<PaletteItem> = im::Vector::new();
@@ -1302,6 +1577,13 @@ fn select(&self) {
                     }
                     doc.trigger_syntax_change(None);
                 }
+                PaletteItemContent::ColorPresentation { edits, .. } => {
+                    if let Some(editor) =
+                        self.main_split.active_editor.get_untracked()
+                    {
+                        editor.do_text_edit(edits);
+                    }
+                }
                 PaletteItemContent::LineEnding { kind } => {
                     let Some(editor) = self.main_split.active_editor.get_untracked()
                     else {
@@ -1323,6 +1605,12 @@ fn select(&self) {
                         data: Some(serde_json::json!(name.to_owned())),
                     });
                 }
+                PaletteItemContent::SCMBranch { branch } => {
+                    self.select_scm_branch(branch);
+                }
+                PaletteItemContent::SCMStash { stash } => {
+                    self.select_scm_stash(stash);
+                }
                 PaletteItemContent::TerminalProfile { name: _, profile } => self
                     .common
                     .internal_command
@@ -1330,6 +1618,9 @@ fn select(&self) {
                         profile: Some(profile.to_owned()),
                     }),
             }
+        } else if self.kind.get_untracked() == PaletteKind::SCMInput {
+            let input = self.input.with_untracked(|input| input.input.clone());
+            self.submit_scm_input(&input);
         } else if self.kind.get_untracked() == PaletteKind::SshHost {
             let input = self.input.with_untracked(|input| input.input.clone());
             let ssh = SshHost::from_string(&input);
@@ -1345,6 +1636,119 @@ fn select(&self) {
         }
     }
 
+    fn select_scm_branch(&self, branch: &BranchInfo) {
+        let Some(action) = self.scm_action.try_update(Option::take).flatten() else {
+            return;
+        };
+        let name = branch.name.clone();
+        match action {
+            ScmAction::RenameBranch { branch: None } => {
+                self.run_scm_action(ScmAction::RenameBranch { branch: Some(name) });
+            }
+            ScmAction::DeleteBranch if branch.merged => {
+                self.common.proxy.git_delete_branch(name, false);
+            }
+            ScmAction::DeleteBranch => {
+                let internal_command = self.common.internal_command;
+                let proxy = self.common.proxy.clone();
+                let title = format!("Delete \"{name}\"?");
+                let delete = move || {
+                    internal_command.send(InternalCommand::HideAlert);
+                    proxy.git_delete_branch(name.clone(), true);
+                };
+                internal_command.send(InternalCommand::ShowAlert {
+                    title,
+                    msg: "The branch isn't merged into the current branch, its \
+                          commits will be lost."
+                        .to_string(),
+                    buttons: vec![AlertButton {
+                        text: "Delete".to_string(),
+                        action: Rc::new(delete),
+                    }],
+                });
+            }
+            ScmAction::MergeBranch => {
+                self.common.proxy.git_merge(name);
+            }
+            _ => {}
+        }
+    }
+
+    fn select_scm_stash(&self, stash: &StashEntry) {
+        let Some(action) = self.scm_action.try_update(Option::take).flatten() else {
+            return;
+        };
+        match action {
+            ScmAction::ApplyStash => self.common.proxy.git_stash_apply(stash.index),
+            ScmAction::PopStash => self.common.proxy.git_stash_pop(stash.index),
+            ScmAction::DropStash => self.common.proxy.git_stash_drop(stash.index),
+            _ => {}
+        }
+    }
+
+    fn submit_scm_input(&self, input: &str) {
+        let Some(action) = self.scm_action.try_update(Option::take).flatten() else {
+            return;
+        };
+        // Passwords are taken as they are typed in.
+        let password = input.to_string();
+        let input = input.trim();
+        let proxy = &self.common.proxy;
+        match action {
+            ScmAction::Stash { include_untracked } => {
+                let message = (!input.is_empty()).then(|| input.to_string());
+                proxy.git_stash_push(message, include_untracked);
+            }
+            _ if input.is_empty() => {}
+            ScmAction::CreateBranch => {
+                proxy.git_create_branch(input.to_string(), true);
+            }
+            ScmAction::RenameBranch { branch: Some(from) } => {
+                proxy.git_rename_branch(from, input.to_string());
+            }
+            ScmAction::CreateTag {
+                annotated: true,
+                name: None,
+            } => {
+                self.run_scm_action(ScmAction::CreateTag {
+                    annotated: true,
+                    name: Some(input.to_string()),
+                });
+            }
+            ScmAction::CreateTag {
+                annotated: true,
+                name: Some(name),
+            } => {
+                proxy.git_create_tag(name, Some(input.to_string()));
+            }
+            ScmAction::CreateTag {
+                annotated: false, ..
+            } => {
+                proxy.git_create_tag(input.to_string(), None);
+            }
+            ScmAction::Credentials {
+                operation,
+                url,
+                username: None,
+            } => {
+                self.run_scm_action(ScmAction::Credentials {
+                    operation,
+                    url,
+                    username: Some(input.to_string()),
+                });
+            }
+            ScmAction::Credentials {
+                operation,
+                username: Some(username),
+                ..
+            } => {
+                let credentials = GitCredentials { username, password };
+                proxy.git_remote_operation(operation, Some(credentials));
+            }
+            _ => {}
+        }
+    }
+
     /// Update the preview for the currently active palette item, if it has one.
     fn preview(&self) {
         if self.status.get_untracked() == PaletteStatus::Inactive {
@@ -1394,6 +1798,7 @@ fn preview(&self) {
                 PaletteItemContent::WslHost { .. } => {}
                 PaletteItemContent::Language { .. } => {}
                 PaletteItemContent::LineEnding { .. } => {}
+                PaletteItemContent::ColorPresentation { .. } => {}
                 PaletteItemContent::Reference { location, .. } => {
                     self.has_preview.set(true);
                     let (doc, new_doc) =
@@ -1460,6 +1865,8 @@ fn preview(&self) {
                         save: false,
                     }),
                 PaletteItemContent::SCMReference { .. } => {}
+                PaletteItemContent::SCMBranch { .. } => {}
+                PaletteItemContent::SCMStash { .. } => {}
                 PaletteItemContent::TerminalProfile { .. } => {}
             }
         }
@@ -1477,6 +1884,7 @@ fn cancel(&self) {
         }
 
         self.left_diff_path.set(None);
+        self.scm_action.set(None);
         self.close();
     }
 
@@ -1698,3 +2106,37 @@ fn receive_char(&self, c: &str) {
         self.input_editor.receive_char(c);
     }
 }
+
+/// Parse a color written as `#rgb`, `#rrggbb` or `#rrggbbaa`, the `#` being
+/// optional.
+fn parse_hex_color(input: &str) -> Option<lsp_types::Color> {
+    let hex = input.trim();
+    let hex = hex.strip_prefix('#').unwrap_or(hex);
+    if !hex.is_ascii() {
+        return None;
+    }
+    let channel = |s: &str| u8::from_str_radix(s, 16).ok().map(|c| c as f32 / 255.0);
+    let (red, green, blue, alpha) = match hex.len() {
+        3 => {
+            let short = |i: usize| channel(&hex[i..i + 1].repeat(2));
+            (short(0)?, short(1)?, short(2)?, 1.0)
+        }
+        6 | 8 => (
+            channel(&hex[0..2])?,
+            channel(&hex[2..4])?,
+            channel(&hex[4..6])?,
+            if hex.len() == 8 {
+                channel(&hex[6..8])?
+            } else {
+                1.0
+            },
+        ),
+        _ => return None,
+    };
+    Some(lsp_types::Color {
+        red,
+        green,
+        blue,
+        alpha,
+    })
+}
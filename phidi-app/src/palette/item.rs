@@ -1,8 +1,11 @@
 use std::path::PathBuf;
 
-use lsp_types::{Range, SymbolKind};
+use lsp_types::{Color, Range, SymbolKind, TextEdit};
 use phidi_core::line_ending::LineEnding;
-use phidi_rpc::dap_types::RunDebugConfig;
+use phidi_rpc::{
+    dap_types::RunDebugConfig,
+    source_control::{BranchInfo, StashEntry},
+};
 
 use crate::{
     command::{PhidiCommand, PhidiWorkbenchCommand},
@@ -80,8 +83,20 @@ pub enum PaletteItemContent {
     SCMReference {
         name: String,
     },
+    SCMBranch {
+        branch: BranchInfo,
+    },
+    SCMStash {
+        stash: StashEntry,
+    },
     TerminalProfile {
         name: String,
         profile: phidi_rpc::terminal::TerminalProfile,
     },
+    ColorPresentation {
+        label: String,
+        color: Color,
+        /// The edits that write the color this way.
+        edits: Vec<TextEdit>,
+    },
 }
@@ -21,9 +21,13 @@ pub enum PaletteKind {
     Language,
     LineEnding,
     SCMReferences,
+    SCMBranches,
+    SCMStashes,
+    SCMInput,
     TerminalProfile,
     DiffFiles,
     HelpAndFile,
+    ColorPicker,
 }
 
 impl PaletteKind {
@@ -47,8 +51,12 @@ pub fn symbol(&self) -> &'static str {
             | PaletteKind::Language
             | PaletteKind::LineEnding
             | PaletteKind::SCMReferences
+            | PaletteKind::SCMBranches
+            | PaletteKind::SCMStashes
+            | PaletteKind::SCMInput
             | PaletteKind::HelpAndFile
-            | PaletteKind::DiffFiles => "",
+            | PaletteKind::DiffFiles
+            | PaletteKind::ColorPicker => "",
             #[cfg(windows)]
             PaletteKind::WslHost => "",
         }
@@ -101,8 +109,13 @@ pub fn command(self) -> Option<PhidiWorkbenchCommand> {
             PaletteKind::SCMReferences => {
                 Some(PhidiWorkbenchCommand::PaletteSCMReferences)
             }
+            // Opened by the branch, tag and stash source control commands
+            PaletteKind::SCMBranches
+            | PaletteKind::SCMStashes
+            | PaletteKind::SCMInput => None,
             PaletteKind::TerminalProfile => None, // InternalCommand::NewTerminal
             PaletteKind::DiffFiles => Some(PhidiWorkbenchCommand::DiffFiles),
+            PaletteKind::ColorPicker => Some(PhidiWorkbenchCommand::ShowColorPicker),
         }
     }
 
@@ -129,8 +142,13 @@ pub fn get_input<'a>(&self, input: &'a str) -> &'a str {
             | PaletteKind::IconTheme
             | PaletteKind::Language
             | PaletteKind::LineEnding
-            | PaletteKind::SCMReferences | PaletteKind::HelpAndFile
-            | PaletteKind::DiffFiles => input,
+            | PaletteKind::SCMReferences
+            | PaletteKind::SCMBranches
+            | PaletteKind::SCMStashes
+            | PaletteKind::SCMInput
+            | PaletteKind::HelpAndFile
+            | PaletteKind::DiffFiles
+            | PaletteKind::ColorPicker => input,
             PaletteKind::PaletteHelp
             | PaletteKind::Command
             | PaletteKind::Workspace
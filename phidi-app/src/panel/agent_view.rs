@@ -0,0 +1,281 @@
+use std::{rc::Rc, sync::Arc};
+
+use floem::{
+    View,
+    event::EventListener,
+    reactive::{ReadSignal, SignalGet, SignalUpdate},
+    style::CursorStyle,
+    views::{
+        Decorators, container, dyn_container, dyn_stack, empty, label, rich_text,
+        scroll, stack,
+    },
+};
+
+use super::{kind::PanelKind, position::PanelPosition};
+use crate::{
+    agent::{AgentData, AgentEntry},
+    config::{PhidiConfig, color::PhidiColor},
+    markdown::{MarkdownContent, parse_markdown},
+    text_input::TextInputBuilder,
+    window_tab::{Focus, WindowTabData},
+};
+
+/// How many lines of a tool's output are shown.
+const TOOL_RESULT_LINES: usize = 6;
+
+pub fn agent_panel(
+    window_tab_data: Rc<WindowTabData>,
+    _position: PanelPosition,
+) -> impl View {
+    let agent = window_tab_data.agent.clone();
+    let config = agent.common.config;
+    let focus = agent.common.focus;
+    let is_focused = move || focus.get() == Focus::Panel(PanelKind::Agent);
+
+    stack((
+        header(agent.clone(), config),
+        scroll(
+            dyn_stack(
+                {
+                    let agent = agent.clone();
+                    move || agent.entries.get().into_iter().enumerate()
+                },
+                |(i, entry)| (*i, entry.clone()),
+                {
+                    let agent = agent.clone();
+                    move |(_, entry)| entry_view(agent.clone(), entry, config)
+                },
+            )
+            .style(|s| s.flex_col().width_pct(100.0).padding(10.0)),
+        )
+        .style(|s| s.width_pct(100.0).flex_grow(1.0).flex_basis(0.0)),
+        container(
+            TextInputBuilder::new()
+                .is_focused(is_focused)
+                .build_editor(agent.editor.clone())
+                .placeholder(|| "Ask the agent, Enter to send".to_string())
+                .on_event_cont(EventListener::PointerDown, move |_| {
+                    focus.set(Focus::Panel(PanelKind::Agent));
+                })
+                .style(move |s| {
+                    s.width_pct(100.0)
+                        .border(1.0)
+                        .border_radius(6.0)
+                        .border_color(config.get().color(PhidiColor::PHIDI_BORDER))
+                }),
+        )
+        .style(|s| s.width_pct(100.0).padding(10.0)),
+    ))
+    .style(|s| s.absolute().size_pct(100.0, 100.0).flex_col())
+    .debug_name("Agent Panel")
+}
+
+fn header(agent: AgentData, config: ReadSignal<Arc<PhidiConfig>>) -> impl View {
+    let running = agent.running;
+    let model = move || {
+        let config = config.get();
+        if config.agent.model.is_empty() {
+            "No model configured".to_string()
+        } else {
+            config.agent.model.clone()
+        }
+    };
+    stack((
+        label(model).style(move |s| {
+            s.color(config.get().color(PhidiColor::EDITOR_DIM))
+                .min_width(0.0)
+                .flex_grow(1.0)
+                .text_ellipsis()
+                .selectable(false)
+        }),
+        {
+            let agent = agent.clone();
+            button("Stop", move || agent.stop(), config)
+                .style(move |s| s.apply_if(!running.get(), |s| s.hide()))
+        },
+        button("New Conversation", move || agent.new_conversation(), config),
+    ))
+    .style(|s| s.width_pct(100.0).items_center().padding_horiz(10.0))
+}
+
+fn button(
+    name: &'static str,
+    on_click: impl Fn() + 'static,
+    config: ReadSignal<Arc<PhidiConfig>>,
+) -> impl View {
+    label(move || name.to_string())
+        .on_click_stop(move |_| on_click())
+        .style(move |s| {
+            let config = config.get();
+            s.margin_left(6.0)
+                .padding_horiz(6.0)
+                .border_radius(6.0)
+                .selectable(false)
+                .hover(|s| {
+                    s.cursor(CursorStyle::Pointer).background(
+                        config.color(PhidiColor::PANEL_HOVERED_BACKGROUND),
+                    )
+                })
+        })
+}
+
+fn entry_view(
+    agent: AgentData,
+    entry: AgentEntry,
+    config: ReadSignal<Arc<PhidiConfig>>,
+) -> impl View {
+    let view = match entry {
+        AgentEntry::User(text) => markdown_view(text, config)
+            .style(move |s| {
+                s.padding(6.0)
+                    .border_radius(6.0)
+                    .background(config.get().color(PhidiColor::EDITOR_BACKGROUND))
+            })
+            .into_any(),
+        AgentEntry::Assistant(text) => markdown_view(text, config).into_any(),
+        AgentEntry::Tool {
+            name,
+            arguments,
+            result,
+            ..
+        } => tool_view(name, arguments, result, config).into_any(),
+        AgentEntry::Approval {
+            id,
+            description,
+            approved,
+        } => approval_view(agent, id, description, approved, config).into_any(),
+        AgentEntry::Error(message) => label(move || message.clone())
+            .style(move |s| s.color(config.get().color(PhidiColor::PHIDI_ERROR)))
+            .into_any(),
+    };
+    view.style(|s| s.width_pct(100.0).margin_bottom(6.0))
+}
+
+fn markdown_view(text: String, config: ReadSignal<Arc<PhidiConfig>>) -> impl View {
+    dyn_stack(
+        move || {
+            let config = config.get();
+            parse_markdown(&text, 1.5, &config)
+                .into_iter()
+                .enumerate()
+                .collect::<Vec<_>>()
+        },
+        |(i, _)| *i,
+        move |(_, content)| match content {
+            MarkdownContent::Text(text_layout) => container(
+                rich_text(move || text_layout.clone()).style(|s| s.max_width_full()),
+            )
+            .style(|s| s.max_width_full()),
+            MarkdownContent::Image { .. } => container(empty()),
+            MarkdownContent::Separator => container(empty().style(move |s| {
+                s.width_full()
+                    .margin_vert(5.0)
+                    .height(1.0)
+                    .background(config.get().color(PhidiColor::PHIDI_BORDER))
+            })),
+        },
+    )
+    .style(|s| s.flex_col().width_pct(100.0))
+}
+
+fn tool_view(
+    name: String,
+    arguments: String,
+    result: Option<(String, bool)>,
+    config: ReadSignal<Arc<PhidiConfig>>,
+) -> impl View {
+    let call = format!("{name} {arguments}");
+    stack((
+        label(move || call.clone()).style(|s| {
+            s.width_pct(100.0)
+                .min_width(0.0)
+                .text_ellipsis()
+                .font_bold()
+        }),
+        dyn_container(
+            move || result.clone(),
+            move |result| match result {
+                Some((output, is_error)) => {
+                    let lines = output.lines().count();
+                    let mut text = output
+                        .lines()
+                        .take(TOOL_RESULT_LINES)
+                        .collect::<Vec<_>>()
+                        .join("\n");
+                    if lines > TOOL_RESULT_LINES {
+                        text.push_str(&format!(
+                            "\n... {} more lines",
+                            lines - TOOL_RESULT_LINES
+                        ));
+                    }
+                    label(move || text.clone())
+                        .style(move |s| {
+                            let config = config.get();
+                            s.color(if is_error {
+                                config.color(PhidiColor::PHIDI_ERROR)
+                            } else {
+                                config.color(PhidiColor::EDITOR_DIM)
+                            })
+                            .font_family(config.editor.font_family.clone())
+                        })
+                        .into_any()
+                }
+                None => label(|| "running...".to_string())
+                    .style(move |s| {
+                        s.color(config.get().color(PhidiColor::EDITOR_DIM))
+                    })
+                    .into_any(),
+            },
+        ),
+    ))
+    .style(move |s| {
+        s.flex_col()
+            .padding(6.0)
+            .border(1.0)
+            .border_radius(6.0)
+            .border_color(config.get().color(PhidiColor::PHIDI_BORDER))
+    })
+}
+
+fn approval_view(
+    agent: AgentData,
+    id: String,
+    description: String,
+    approved: Option<bool>,
+    config: ReadSignal<Arc<PhidiConfig>>,
+) -> impl View {
+    let answer = match approved {
+        None => {
+            let allow = {
+                let agent = agent.clone();
+                let id = id.clone();
+                move || agent.approve(&id, true)
+            };
+            let deny = move || agent.approve(&id, false);
+            stack((button("Allow", allow, config), button("Deny", deny, config)))
+                .into_any()
+        }
+        Some(approved) => {
+            label(move || if approved { "Allowed" } else { "Denied" }.to_string())
+                .style(move |s| s.color(config.get().color(PhidiColor::EDITOR_DIM)))
+                .into_any()
+        }
+    };
+    stack((
+        label(move || description.clone()).style(|s| {
+            s.min_width(0.0)
+                .flex_grow(1.0)
+                .text_ellipsis()
+                .selectable(false)
+        }),
+        answer,
+    ))
+    .style(move |s| {
+        let config = config.get();
+        s.items_center()
+            .padding(6.0)
+            .border(1.0)
+            .border_radius(6.0)
+            .border_color(config.color(PhidiColor::PHIDI_WARN))
+    })
+}
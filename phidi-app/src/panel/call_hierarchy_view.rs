@@ -9,7 +9,7 @@
         virtual_stack,
     },
 };
-use lsp_types::{CallHierarchyItem, Range};
+use lsp_types::{CallHierarchyItem, Range, SymbolKind, TypeHierarchyItem, Url};
 
 use super::position::PanelPosition;
 use crate::{
@@ -19,17 +19,132 @@
     window_tab::{CommonData, WindowTabData},
 };
 
+/// What the children of an item in the hierarchy panel are.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HierarchyKind {
+    IncomingCalls,
+    OutgoingCalls,
+    Supertypes,
+    Subtypes,
+}
+
+impl HierarchyKind {
+    /// The other direction of the same hierarchy.
+    pub fn toggled(self) -> Self {
+        match self {
+            HierarchyKind::IncomingCalls => HierarchyKind::OutgoingCalls,
+            HierarchyKind::OutgoingCalls => HierarchyKind::IncomingCalls,
+            HierarchyKind::Supertypes => HierarchyKind::Subtypes,
+            HierarchyKind::Subtypes => HierarchyKind::Supertypes,
+        }
+    }
+
+    pub fn title(self) -> &'static str {
+        match self {
+            HierarchyKind::IncomingCalls => "Incoming Calls",
+            HierarchyKind::OutgoingCalls => "Outgoing Calls",
+            HierarchyKind::Supertypes => "Supertypes",
+            HierarchyKind::Subtypes => "Subtypes",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum HierarchyItem {
+    Call(CallHierarchyItem),
+    Type(TypeHierarchyItem),
+}
+
+impl HierarchyItem {
+    pub fn name(&self) -> &str {
+        match self {
+            HierarchyItem::Call(item) => &item.name,
+            HierarchyItem::Type(item) => &item.name,
+        }
+    }
+
+    pub fn kind(&self) -> SymbolKind {
+        match self {
+            HierarchyItem::Call(item) => item.kind,
+            HierarchyItem::Type(item) => item.kind,
+        }
+    }
+
+    pub fn detail(&self) -> Option<&str> {
+        match self {
+            HierarchyItem::Call(item) => item.detail.as_deref(),
+            HierarchyItem::Type(item) => item.detail.as_deref(),
+        }
+    }
+
+    pub fn uri(&self) -> &Url {
+        match self {
+            HierarchyItem::Call(item) => &item.uri,
+            HierarchyItem::Type(item) => &item.uri,
+        }
+    }
+
+    pub fn selection_range(&self) -> Range {
+        match self {
+            HierarchyItem::Call(item) => item.selection_range,
+            HierarchyItem::Type(item) => item.selection_range,
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct CallHierarchyData {
     pub root: RwSignal<Option<RwSignal<CallHierarchyItemData>>>,
+    pub kind: RwSignal<HierarchyKind>,
     pub common: Rc<CommonData>,
     pub scroll_to_line: RwSignal<Option<f64>>,
 }
 
+impl CallHierarchyData {
+    /// Show the hierarchy of the item, which is at `from_range`.
+    pub fn set_root(
+        &self,
+        item: HierarchyItem,
+        from_range: Range,
+        kind: HierarchyKind,
+    ) {
+        let scope = self.common.scope;
+        let root = scope.create_rw_signal(CallHierarchyItemData {
+            view_id: ViewId::new(),
+            item: Rc::new(item),
+            from_range,
+            init: false,
+            open: scope.create_rw_signal(true),
+            children: scope.create_rw_signal(Vec::with_capacity(0)),
+        });
+        self.kind.set(kind);
+        self.root.set(Some(root));
+        self.common
+            .internal_command
+            .send(InternalCommand::HierarchyChildren {
+                item_id: root.get_untracked().view_id,
+            });
+    }
+
+    /// Switch between incoming and outgoing calls, or supertypes and
+    /// subtypes, of the same root.
+    pub fn toggle_kind(&self) {
+        self.kind.update(|kind| *kind = kind.toggled());
+        if let Some(root) = self.root.get_untracked() {
+            let data = root.get_untracked();
+            self.set_root(
+                data.item.as_ref().clone(),
+                data.from_range,
+                self.kind.get_untracked(),
+            );
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct CallHierarchyItemData {
     pub view_id: ViewId,
-    pub item: Rc<CallHierarchyItem>,
+    pub item: Rc<HierarchyItem>,
     pub from_range: Range,
     pub init: bool,
     pub open: RwSignal<bool>,
@@ -130,14 +245,43 @@ pub fn show_hierarchy_panel(
     let config = call_hierarchy_data.common.config;
     let ui_line_height = call_hierarchy_data.common.ui_line_height;
     let scroll_to_line = call_hierarchy_data.scroll_to_line;
-    scroll(
+    let kind = call_hierarchy_data.kind;
+    let root = call_hierarchy_data.root;
+    let header = stack((
+        label(move || kind.get().title())
+            .style(|s| s.flex_grow(1.0).min_width(0.0).selectable(false)),
+        {
+            let call_hierarchy_data = call_hierarchy_data.clone();
+            label(move || format!("Show {}", kind.get().toggled().title()))
+                .on_click_stop(move |_| call_hierarchy_data.toggle_kind())
+                .style(move |s| {
+                    let config = config.get();
+                    s.padding_horiz(6.0)
+                        .border_radius(6.0)
+                        .selectable(false)
+                        .hover(|s| {
+                            s.cursor(CursorStyle::Pointer).background(
+                                config.color(PhidiColor::PANEL_HOVERED_BACKGROUND),
+                            )
+                        })
+                })
+        },
+    ))
+    .style(move |s| {
+        s.width_pct(100.0)
+            .items_center()
+            .padding_horiz(10.0)
+            .height(ui_line_height.get())
+            .apply_if(root.with(|root| root.is_none()), |s| s.hide())
+    });
+    let tree = scroll(
         virtual_stack(
             move || VirtualList::new(call_hierarchy_data.root.get()),
             move |(_, _, item)| item.get_untracked().view_id,
             move |(_, level, rw_data)| {
                 let data = rw_data.get_untracked();
                 let open = data.open;
-                let kind = data.item.kind;
+                let kind = data.item.kind();
                 stack((
                     container(
                         svg(move || {
@@ -164,7 +308,7 @@ pub fn show_hierarchy_panel(
                             });
                             if !rw_data.get_untracked().init {
                                 window_tab_data.common.internal_command.send(
-                                    InternalCommand::CallHierarchyIncoming {
+                                    InternalCommand::HierarchyChildren {
                                         item_id: rw_data.get_untracked().view_id,
                                     },
                                 );
@@ -186,10 +330,10 @@ pub fn show_hierarchy_panel(
                                     config.color(PhidiColor::PHIDI_ICON_ACTIVE)
                                 }))
                         }),
-                    data.item.name.clone().into_view(),
-                    if data.item.detail.is_some() {
+                    data.item.name().to_string().into_view(),
+                    if data.item.detail().is_some() {
                         label(move || {
-                            data.item.detail.clone().unwrap_or_default().replace('\n', "↵")
+                            data.item.detail().unwrap_or_default().replace('\n', "↵")
                         }).style(move |s| s.margin_left(6.0)
                                                 .color(config.get().color(PhidiColor::EDITOR_DIM))
                         ).into_any()
@@ -217,11 +361,11 @@ pub fn show_hierarchy_panel(
                     move |_| {
                         if !rw_data.get_untracked().init {
                             window_tab_data.common.internal_command.send(
-                                InternalCommand::CallHierarchyIncoming { item_id: rw_data.get_untracked().view_id },
+                                InternalCommand::HierarchyChildren { item_id: rw_data.get_untracked().view_id },
                             );
                         }
                         let data = data.get_untracked();
-                        if let Ok(path) = data.item.uri.to_file_path() {
+                        if let Ok(path) = data.item.uri().to_file_path() {
                             window_tab_data
                                 .common
                                 .internal_command
@@ -247,5 +391,26 @@ pub fn show_hierarchy_panel(
         } else {
             None
         }
-    })
+    });
+    stack((
+        header,
+        container(tree).style(|s| s.width_pct(100.0).flex_grow(1.0).flex_basis(0.0)),
+    ))
+    .style(|s| s.flex_col().size_pct(100.0, 100.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::HierarchyKind;
+
+    #[test]
+    fn test_toggled() {
+        for (kind, other) in [
+            (HierarchyKind::IncomingCalls, HierarchyKind::OutgoingCalls),
+            (HierarchyKind::Supertypes, HierarchyKind::Subtypes),
+        ] {
+            assert_eq!(kind.toggled(), other);
+            assert_eq!(other.toggled(), kind);
+        }
+    }
 }
@@ -0,0 +1,133 @@
+use std::{rc::Rc, sync::Arc};
+
+use chrono::{Local, TimeZone};
+use floem::{
+    View,
+    reactive::{ReadSignal, SignalGet},
+    style::CursorStyle,
+    views::{Decorators, container, dyn_stack, label, scroll, stack},
+};
+use phidi_rpc::checkpoint::Checkpoint;
+
+use super::position::PanelPosition;
+use crate::{
+    checkpoint::CheckpointData,
+    config::{PhidiConfig, color::PhidiColor},
+    window_tab::WindowTabData,
+};
+
+pub fn checkpoints_panel(
+    window_tab_data: Rc<WindowTabData>,
+    _position: PanelPosition,
+) -> impl View {
+    let config = window_tab_data.common.config;
+    let checkpoints = window_tab_data.checkpoints.clone();
+    checkpoints.load();
+    stack((
+        {
+            let checkpoints = checkpoints.clone();
+            stack((
+                label(|| "Checkpoints".to_string())
+                    .style(|s| s.flex_grow(1.0).min_width(0.0).selectable(false)),
+                action_button(
+                    "Create Checkpoint",
+                    move || checkpoints.create(),
+                    config,
+                ),
+            ))
+            .style(move |s| {
+                s.width_pct(100.0)
+                    .items_center()
+                    .padding_horiz(10.0)
+                    .background(config.get().color(PhidiColor::EDITOR_BACKGROUND))
+            })
+        },
+        container(
+            scroll(
+                dyn_stack(
+                    move || checkpoints.checkpoints.get(),
+                    |checkpoint| checkpoint.id.clone(),
+                    move |checkpoint| {
+                        checkpoint_view(
+                            window_tab_data.checkpoints.clone(),
+                            checkpoint,
+                            config,
+                        )
+                    },
+                )
+                .style(|s| s.flex_col().width_pct(100.0).line_height(1.8)),
+            )
+            .style(|s| s.absolute().size_pct(100.0, 100.0)),
+        )
+        .style(|s| s.width_pct(100.0).flex_grow(1.0).flex_basis(0.0)),
+    ))
+    .style(|s| s.flex_col().size_pct(100.0, 100.0).line_height(1.8))
+    .debug_name("Checkpoints Panel")
+}
+
+fn action_button(
+    name: &'static str,
+    on_click: impl Fn() + 'static,
+    config: ReadSignal<Arc<PhidiConfig>>,
+) -> impl View {
+    label(move || name.to_string())
+        .on_click_stop(move |_| on_click())
+        .style(move |s| {
+            let config = config.get();
+            s.margin_left(6.0)
+                .padding_horiz(6.0)
+                .border_radius(6.0)
+                .selectable(false)
+                .hover(|s| {
+                    s.cursor(CursorStyle::Pointer).background(
+                        config.color(PhidiColor::PANEL_HOVERED_BACKGROUND),
+                    )
+                })
+        })
+}
+
+fn checkpoint_view(
+    checkpoints: CheckpointData,
+    checkpoint: Checkpoint,
+    config: ReadSignal<Arc<PhidiConfig>>,
+) -> impl View {
+    let time = Local
+        .timestamp_opt(checkpoint.time, 0)
+        .single()
+        .map(|time| time.format("%Y-%m-%d %H:%M:%S").to_string())
+        .unwrap_or_default();
+    let title = checkpoint.label.clone();
+    let restore = {
+        let checkpoints = checkpoints.clone();
+        let checkpoint = checkpoint.clone();
+        move || checkpoints.restore(&checkpoint)
+    };
+    let delete = move || checkpoints.delete(&checkpoint.id);
+    stack((
+        label(move || title.clone()).style(|s| {
+            s.margin_right(6.0)
+                .min_width(0.0)
+                .text_ellipsis()
+                .selectable(false)
+        }),
+        label(move || time.clone()).style(move |s| {
+            s.color(config.get().color(PhidiColor::EDITOR_DIM))
+                .min_width(0.0)
+                .flex_grow(1.0)
+                .text_ellipsis()
+                .selectable(false)
+        }),
+        action_button("Restore", restore, config),
+        action_button("Delete", delete, config),
+    ))
+    .style(move |s| {
+        let config = config.get();
+        s.width_pct(100.0)
+            .min_width(0.0)
+            .items_center()
+            .padding_horiz(10.0)
+            .hover(|s| {
+                s.background(config.color(PhidiColor::PANEL_HOVERED_BACKGROUND))
+            })
+    })
+}
@@ -29,6 +29,8 @@ pub fn default_panel_order() -> PanelOrder {
             PanelKind::Plugin,
             PanelKind::SourceControl,
             PanelKind::Debug,
+            PanelKind::Checkpoints,
+            PanelKind::GitLog,
         ],
     );
     order.insert(
@@ -39,12 +41,13 @@ pub fn default_panel_order() -> PanelOrder {
             PanelKind::Problem,
             PanelKind::CallHierarchy,
             PanelKind::References,
-            PanelKind::Implementation
+            PanelKind::Implementation,
+            PanelKind::Proposals
         ],
     );
     order.insert(
         PanelPosition::RightTop,
-        im::vector![PanelKind::DocumentSymbol,],
+        im::vector![PanelKind::DocumentSymbol, PanelKind::Agent],
     );
 
     order
@@ -57,6 +60,7 @@ pub enum PanelSection {
     Error,
     Warn,
     Changes,
+    StagedChanges,
     Installed,
     Available,
     Process,
@@ -0,0 +1,394 @@
+use std::{rc::Rc, sync::Arc};
+
+use floem::{
+    Renderer, View, ViewId,
+    context::PaintCx,
+    event::EventListener,
+    kurbo::Stroke,
+    peniko::kurbo::{Circle, Line, Point},
+    reactive::{Memo, ReadSignal, SignalGet, SignalUpdate, SignalWith},
+    style::{CursorStyle, Style},
+    views::{
+        Decorators, container, dyn_container, dyn_stack, empty, label, scroll,
+        stack, stack_from_iter, svg,
+    },
+};
+use phidi_rpc::source_control::FileDiff;
+
+use super::{kind::PanelKind, position::PanelPosition};
+use crate::{
+    config::{PhidiConfig, color::PhidiColor, icon::PhidiIcons},
+    git_log::{GitLogData, GraphRow, LogFilterField, LogRow},
+    source_control::relative_time,
+    text_input::TextInputBuilder,
+    window_tab::{Focus, WindowTabData},
+};
+
+/// The width of a lane of the commit graph.
+const LANE_WIDTH: f64 = 12.0;
+
+/// The colors the lanes of the graph cycle through.
+const LANE_COLORS: [&str; 6] = [
+    PhidiColor::TERMINAL_BLUE,
+    PhidiColor::TERMINAL_GREEN,
+    PhidiColor::TERMINAL_YELLOW,
+    PhidiColor::TERMINAL_MAGENTA,
+    PhidiColor::TERMINAL_CYAN,
+    PhidiColor::TERMINAL_RED,
+];
+
+pub fn git_log_panel(
+    window_tab_data: Rc<WindowTabData>,
+    _position: PanelPosition,
+) -> impl View {
+    let config = window_tab_data.common.config;
+    let git_log = window_tab_data.git_log.clone();
+    if git_log.rows.with_untracked(|rows| rows.is_empty()) {
+        git_log.reload();
+    }
+    let error = git_log.error;
+    let has_more = git_log.has_more;
+    let loading = git_log.loading;
+    stack((
+        {
+            let git_log = git_log.clone();
+            stack((
+                label(|| "Commit History".to_string())
+                    .style(|s| s.flex_grow(1.0).min_width(0.0).selectable(false)),
+                action_button("Refresh", move || git_log.reload(), config),
+            ))
+            .style(move |s| {
+                s.width_pct(100.0)
+                    .items_center()
+                    .padding_horiz(10.0)
+                    .background(config.get().color(PhidiColor::EDITOR_BACKGROUND))
+            })
+        },
+        stack((
+            filter_input(git_log.clone(), LogFilterField::Message, "Message"),
+            filter_input(git_log.clone(), LogFilterField::Author, "Author"),
+            filter_input(git_log.clone(), LogFilterField::Path, "Path"),
+            filter_input(git_log.clone(), LogFilterField::Branch, "Branch"),
+        ))
+        .style(|s| s.flex_col().width_pct(100.0).padding(10.0).gap(4.0)),
+        label(move || error.get().unwrap_or_default()).style(move |s| {
+            s.padding_horiz(10.0)
+                .color(config.get().color(PhidiColor::PHIDI_ERROR))
+                .apply_if(error.with(|error| error.is_none()), |s| s.hide())
+        }),
+        container(
+            scroll(
+                stack((
+                    {
+                        let git_log = git_log.clone();
+                        dyn_stack(
+                            move || git_log.rows.get(),
+                            |row| row.entry.commit.id.clone(),
+                            move |row| commit_view(git_log.clone(), row),
+                        )
+                        .style(|s| s.flex_col().width_pct(100.0))
+                    },
+                    {
+                        let git_log = git_log.clone();
+                        action_button(
+                            "Load More",
+                            move || git_log.load_more(),
+                            config,
+                        )
+                        .style(move |s| {
+                            s.margin_vert(6.0)
+                                .apply_if(!has_more.get() || loading.get(), |s| {
+                                    s.hide()
+                                })
+                        })
+                    },
+                ))
+                .style(|s| s.flex_col().width_pct(100.0)),
+            )
+            .style(|s| s.absolute().size_pct(100.0, 100.0)),
+        )
+        .style(|s| s.width_pct(100.0).flex_grow(1.0).flex_basis(0.0)),
+    ))
+    .style(|s| s.flex_col().size_pct(100.0, 100.0).line_height(1.8))
+    .debug_name("Commit History Panel")
+}
+
+fn filter_input(
+    git_log: GitLogData,
+    field: LogFilterField,
+    placeholder: &'static str,
+) -> impl View {
+    let config = git_log.common.config;
+    let focus = git_log.common.focus;
+    let active_filter = git_log.active_filter;
+    let is_focused = move || {
+        focus.get() == Focus::Panel(PanelKind::GitLog)
+            && active_filter.get() == field
+    };
+    TextInputBuilder::new()
+        .is_focused(is_focused)
+        .build_editor(git_log.editor(field).clone())
+        .placeholder(move || format!("{placeholder}, Enter to filter"))
+        .on_event_cont(EventListener::PointerDown, move |_| {
+            active_filter.set(field);
+            focus.set(Focus::Panel(PanelKind::GitLog));
+        })
+        .style(move |s| {
+            s.width_pct(100.0)
+                .border(1.0)
+                .border_radius(6.0)
+                .border_color(config.get().color(PhidiColor::PHIDI_BORDER))
+        })
+}
+
+fn action_button(
+    name: &'static str,
+    on_click: impl Fn() + 'static,
+    config: ReadSignal<Arc<PhidiConfig>>,
+) -> impl View {
+    label(move || name.to_string())
+        .on_click_stop(move |_| on_click())
+        .style(move |s| {
+            let config = config.get();
+            s.margin_left(6.0)
+                .padding_horiz(6.0)
+                .border_radius(6.0)
+                .selectable(false)
+                .hover(|s| {
+                    s.cursor(CursorStyle::Pointer).background(
+                        config.color(PhidiColor::PANEL_HOVERED_BACKGROUND),
+                    )
+                })
+        })
+}
+
+/// A commit with its part of the graph, and the files it changed under it
+/// when it is expanded.
+fn commit_view(git_log: GitLogData, row: LogRow) -> impl View {
+    let config = git_log.common.config;
+    let ui_line_height = git_log.common.ui_line_height;
+    let commit = row.entry.commit;
+    let expanded = git_log.expanded;
+    let is_expanded = {
+        let id = commit.id.clone();
+        move || {
+            expanded.with(|expanded| matches!(expanded, Some((c, _)) if *c == id))
+        }
+    };
+    let summary = commit.summary.clone();
+    let author = commit.author.clone();
+    let time = relative_time(commit.time);
+    let id = commit.id.clone();
+
+    stack((
+        commit_graph(row.graph, config, ui_line_height),
+        stack((
+            stack((
+                label(move || summary.clone()).style(|s| {
+                    s.flex_grow(1.0)
+                        .flex_basis(0.0)
+                        .min_width(0.0)
+                        .text_ellipsis()
+                        .selectable(false)
+                }),
+                label(move || format!("{author}, {time}")).style(move |s| {
+                    s.margin_left(6.0)
+                        .max_width_pct(50.0)
+                        .text_ellipsis()
+                        .color(config.get().color(PhidiColor::EDITOR_DIM))
+                        .selectable(false)
+                }),
+            ))
+            .on_click_stop({
+                let git_log = git_log.clone();
+                let id = id.clone();
+                move |_| git_log.toggle_expanded(&id)
+            })
+            .style(move |s| {
+                s.width_pct(100.0)
+                    .min_width(0.0)
+                    .height(ui_line_height.get() as f32)
+                    .items_center()
+                    .cursor(CursorStyle::Pointer)
+            }),
+            {
+                let git_log = git_log.clone();
+                let expanded_id = id.clone();
+                dyn_container(
+                    move || {
+                        expanded.with(|expanded| match expanded {
+                            Some((commit, files)) if *commit == expanded_id => {
+                                Some(files.clone())
+                            }
+                            _ => None,
+                        })
+                    },
+                    move |files| match files {
+                        Some(files) => {
+                            commit_files_view(git_log.clone(), &id, files).into_any()
+                        }
+                        None => empty().into_any(),
+                    },
+                )
+                .style(|s| s.width_pct(100.0))
+            },
+        ))
+        .style(|s| s.flex_col().flex_grow(1.0).flex_basis(0.0).min_width(0.0)),
+    ))
+    .style(move |s| {
+        let config = config.get();
+        s.width_pct(100.0)
+            .padding_horiz(10.0)
+            .apply_if(is_expanded(), |s| {
+                s.background(config.color(PhidiColor::PANEL_CURRENT_BACKGROUND))
+            })
+            .hover(|s| {
+                s.background(config.color(PhidiColor::PANEL_HOVERED_BACKGROUND))
+            })
+    })
+}
+
+/// The files a commit changed, each opening its diff. `None` while they are
+/// loading.
+fn commit_files_view(
+    git_log: GitLogData,
+    commit: &str,
+    files: Option<im::Vector<FileDiff>>,
+) -> impl View {
+    let config = git_log.common.config;
+    let workspace_path = git_log.common.workspace.path.clone().unwrap_or_default();
+    let Some(files) = files else {
+        return label(|| "Loading...".to_string())
+            .style(move |s| {
+                s.color(config.get().color(PhidiColor::EDITOR_DIM))
+                    .selectable(false)
+            })
+            .into_any();
+    };
+
+    let file_views = files.into_iter().map(|diff| {
+        let path = match &diff {
+            FileDiff::Renamed(new, _) => new.clone(),
+            diff => diff.path().clone(),
+        };
+        let relative = path
+            .strip_prefix(&workspace_path)
+            .unwrap_or(&path)
+            .to_string_lossy()
+            .to_string();
+        let icon = match &diff {
+            FileDiff::Modified(_) => PhidiIcons::SCM_DIFF_MODIFIED,
+            FileDiff::Added(_) => PhidiIcons::SCM_DIFF_ADDED,
+            FileDiff::Deleted(_) => PhidiIcons::SCM_DIFF_REMOVED,
+            FileDiff::Renamed(_, _) => PhidiIcons::SCM_DIFF_RENAMED,
+        };
+        let color = match &diff {
+            FileDiff::Added(_) => PhidiColor::SOURCE_CONTROL_ADDED,
+            FileDiff::Deleted(_) => PhidiColor::SOURCE_CONTROL_REMOVED,
+            FileDiff::Modified(_) | FileDiff::Renamed(_, _) => {
+                PhidiColor::SOURCE_CONTROL_MODIFIED
+            }
+        };
+        let git_log = git_log.clone();
+        let commit = commit.to_string();
+        stack((
+            svg(move || config.get().ui_svg(icon)).style(move |s| {
+                let config = config.get();
+                let size = config.ui.icon_size() as f32;
+                s.min_width(size)
+                    .size(size, size)
+                    .margin_right(6.0)
+                    .color(config.color(color))
+            }),
+            label(move || relative.clone())
+                .style(|s| s.min_width(0.0).text_ellipsis().selectable(false)),
+        ))
+        .on_click_stop(move |_| git_log.open_file_changes(&commit, diff.clone()))
+        .style(move |s| {
+            s.width_pct(100.0)
+                .items_center()
+                .cursor(CursorStyle::Pointer)
+                .hover(|s| {
+                    s.background(
+                        config.get().color(PhidiColor::PANEL_HOVERED_BACKGROUND),
+                    )
+                })
+        })
+        .into_any()
+    });
+    stack_from_iter(file_views)
+        .style(|s| s.flex_col().width_pct(100.0))
+        .into_any()
+}
+
+fn commit_graph(
+    row: GraphRow,
+    config: ReadSignal<Arc<PhidiConfig>>,
+    line_height: Memo<f64>,
+) -> impl View {
+    let width = (row.width.max(1) as f64 * LANE_WIDTH) as f32;
+    CommitGraphView {
+        id: ViewId::new(),
+        row,
+        config,
+        line_height,
+    }
+    .style(move |s: Style| s.width(width).min_width(width).margin_right(6.0))
+}
+
+/// Paints the part of the commit graph in the row of a commit. The lanes
+/// which go on to the next row carry on down past the first line, over the
+/// changed files of an expanded commit.
+pub struct CommitGraphView {
+    id: ViewId,
+    row: GraphRow,
+    config: ReadSignal<Arc<PhidiConfig>>,
+    line_height: Memo<f64>,
+}
+
+impl View for CommitGraphView {
+    fn id(&self) -> ViewId {
+        self.id
+    }
+
+    fn paint(&mut self, cx: &mut PaintCx) {
+        let config = self.config.get_untracked();
+        let line_height = self.line_height.get_untracked();
+        let height = self
+            .id
+            .get_layout()
+            .map(|layout| layout.size.height as f64)
+            .unwrap_or(line_height);
+        let middle = line_height / 2.0;
+        let x = |lane: usize| lane as f64 * LANE_WIDTH + LANE_WIDTH / 2.0;
+        let color =
+            |lane: usize| config.color(LANE_COLORS[lane % LANE_COLORS.len()]);
+        let stroke = Stroke::new(1.5);
+
+        for &(from, to) in &self.row.top {
+            let line =
+                Line::new(Point::new(x(from), 0.0), Point::new(x(to), middle));
+            cx.stroke(&line, color(from), &stroke);
+        }
+        for &(from, to) in &self.row.bottom {
+            let line = Line::new(
+                Point::new(x(from), middle),
+                Point::new(x(to), line_height),
+            );
+            cx.stroke(&line, color(to), &stroke);
+            if height > line_height {
+                let line = Line::new(
+                    Point::new(x(to), line_height),
+                    Point::new(x(to), height),
+                );
+                cx.stroke(&line, color(to), &stroke);
+            }
+        }
+        let lane = self.row.lane;
+        cx.fill(
+            &Circle::new(Point::new(x(lane), middle), 4.0),
+            color(lane),
+            0.0,
+        );
+    }
+}
@@ -1,12 +1,15 @@
-use std::{path::PathBuf, rc::Rc, sync::Arc};
+use std::{ops::Range, path::PathBuf, rc::Rc, sync::Arc};
 
 use floem::{
     View,
     event::EventListener,
     reactive::{ReadSignal, SignalGet, SignalUpdate},
     style::{CursorStyle, Style},
-    views::{Decorators, container, label, scroll, stack, svg, virtual_stack},
+    views::{
+        Decorators, container, dyn_stack, label, scroll, stack, svg, virtual_stack,
+    },
 };
+use phidi_rpc::proxy::SearchMatch;
 use phidi_xi_rope::find::CaseMatching;
 
 use super::{kind::PanelKind, position::PanelPosition};
@@ -14,9 +17,12 @@
     app::clickable_icon,
     command::InternalCommand,
     config::{PhidiConfig, color::PhidiColor, icon::PhidiIcons},
-    editor::location::{EditorLocation, EditorPosition},
+    editor::{
+        EditorData,
+        location::{EditorLocation, EditorPosition},
+    },
     focus_text::focus_text,
-    global_search::{GlobalSearchData, SearchMatchData},
+    global_search::{GlobalSearchData, SearchInput, SearchMatchData, match_lines},
     listener::Listener,
     text_input::TextInputBuilder,
     window_tab::{Focus, WindowTabData},
@@ -36,14 +42,41 @@ pub fn global_search_panel(
     let whole_word = global_search.common.find.whole_words;
     let is_regex = global_search.common.find.is_regex;
 
+    let replace_editor = global_search.replace_editor.clone();
+    let replace_active = global_search.replace_active;
+    let focused_input = global_search.focused_input;
+    let limit_hit = global_search.limit_hit;
+
     let focus = global_search.common.focus;
-    let is_focused = move || focus.get() == Focus::Panel(PanelKind::Search);
+    let is_focused = move |input: SearchInput| {
+        move || {
+            focus.get() == Focus::Panel(PanelKind::Search)
+                && focused_input.get() == input
+        }
+    };
 
     stack((
-        container(
+        stack((
+            clickable_icon(
+                move || {
+                    if replace_active.get() {
+                        PhidiIcons::ITEM_OPENED
+                    } else {
+                        PhidiIcons::ITEM_CLOSED
+                    }
+                },
+                move || {
+                    replace_active.update(|active| *active = !*active);
+                },
+                || false,
+                || false,
+                || "Toggle Replace",
+                config,
+            )
+            .style(|s| s.margin_right(6.0)),
             stack((
                 TextInputBuilder::new()
-                    .is_focused(is_focused)
+                    .is_focused(is_focused(SearchInput::Search))
                     .build_editor(editor.clone())
                     .style(|s| s.width_pct(100.0)),
                 clickable_icon(
@@ -90,23 +123,117 @@ pub fn global_search_panel(
             ))
             .on_event_cont(EventListener::PointerDown, move |_| {
                 focus.set(Focus::Panel(PanelKind::Search));
+                focused_input.set(SearchInput::Search);
             })
             .style(move |s| {
-                s.width_pct(100.0)
+                s.flex_grow(1.0)
+                    .min_width(0.0)
                     .padding_right(6.0)
                     .items_center()
                     .border(1.0)
                     .border_radius(6.0)
                     .border_color(config.get().color(PhidiColor::PHIDI_BORDER))
             }),
-        )
-        .style(|s| s.width_pct(100.0).padding(10.0)),
+        ))
+        .style(|s| s.width_pct(100.0).items_center().padding(10.0)),
+        stack((
+            TextInputBuilder::new()
+                .is_focused(is_focused(SearchInput::Replace))
+                .build_editor(replace_editor)
+                .placeholder(|| "Replace".to_string())
+                .style(|s| s.width_pct(100.0)),
+            {
+                let global_search = global_search.clone();
+                clickable_icon(
+                    || PhidiIcons::SEARCH_REPLACE_ALL,
+                    move || global_search.replace_all(),
+                    || false,
+                    || false,
+                    || "Replace All",
+                    config,
+                )
+                .style(|s| s.padding_vert(4.0))
+            },
+        ))
+        .on_event_cont(EventListener::PointerDown, move |_| {
+            focus.set(Focus::Panel(PanelKind::Search));
+            focused_input.set(SearchInput::Replace);
+        })
+        .style(move |s| {
+            let config = config.get();
+            let icon_size = config.ui.icon_size() as f32;
+            s.margin_left(10.0 + icon_size + 8.0 + 6.0)
+                .margin_right(10.0)
+                .margin_bottom(10.0)
+                .padding_right(6.0)
+                .items_center()
+                .border(1.0)
+                .border_radius(6.0)
+                .border_color(config.color(PhidiColor::PHIDI_BORDER))
+                .apply_if(!replace_active.get(), |s| s.hide())
+        }),
+        filter_input(
+            global_search.include_editor.clone(),
+            SearchInput::Include,
+            "Files to include, e.g. src/**, type:rust",
+            is_focused(SearchInput::Include),
+            &global_search,
+        ),
+        filter_input(
+            global_search.exclude_editor.clone(),
+            SearchInput::Exclude,
+            "Files to exclude, e.g. *.lock, tests/**",
+            is_focused(SearchInput::Exclude),
+            &global_search,
+        ),
+        label(move || {
+            let max_results = config.get().ui.search_max_results;
+            format!("Showing the first {max_results} results")
+        })
+        .style(move |s| {
+            s.padding_horiz(10.0)
+                .margin_bottom(6.0)
+                .color(config.get().color(PhidiColor::EDITOR_DIM))
+                .selectable(false)
+                .apply_if(!limit_hit.get(), |s| s.hide())
+        }),
         search_result(workspace, global_search, internal_command, config),
     ))
     .style(|s| s.absolute().size_pct(100.0, 100.0).flex_col())
     .debug_name("Global Search Panel")
 }
 
+/// An input of comma separated globs narrowing down the files searched.
+fn filter_input(
+    editor: EditorData,
+    input: SearchInput,
+    placeholder: &'static str,
+    is_focused: impl Fn() -> bool + 'static,
+    global_search: &GlobalSearchData,
+) -> impl View {
+    let config = global_search.common.config;
+    let focus = global_search.common.focus;
+    let focused_input = global_search.focused_input;
+    TextInputBuilder::new()
+        .is_focused(is_focused)
+        .build_editor(editor)
+        .placeholder(move || placeholder.to_string())
+        .on_event_cont(EventListener::PointerDown, move |_| {
+            focus.set(Focus::Panel(PanelKind::Search));
+            focused_input.set(input);
+        })
+        .style(move |s| {
+            let config = config.get();
+            let icon_size = config.ui.icon_size() as f32;
+            s.margin_left(10.0 + icon_size + 8.0 + 6.0)
+                .margin_right(10.0)
+                .margin_bottom(10.0)
+                .border(1.0)
+                .border_radius(6.0)
+                .border_color(config.color(PhidiColor::PHIDI_BORDER))
+        })
+}
+
 fn search_result(
     workspace: Arc<PhidiWorkspace>,
     global_search_data: GlobalSearchData,
@@ -114,6 +241,7 @@ fn search_result(
     config: ReadSignal<Arc<PhidiConfig>>,
 ) -> impl View {
     let ui_line_height = global_search_data.common.ui_line_height;
+    let replace_active = global_search_data.replace_active;
     container({
         scroll({
             virtual_stack(
@@ -215,84 +343,151 @@ fn search_result(
                                     im::Vector::new()
                                 }
                             },
-                            |m| (m.line, m.start, m.end),
+                            // The replacement is part of the key to update the
+                            // preview when it changes.
+                            |m| (m.line, m.start, m.end, m.replacement.clone()),
                             move |m| {
                                 let path = full_path.clone();
                                 let line_number = m.line;
-                                let start = m.start;
-                                let end = m.end;
-                                let line_content = m.line_content.clone();
-
-                                focus_text(
-                                    move || {
-                                        let config = config.get();
-                                        let content = if config
-                                            .ui
-                                            .trim_search_results_whitespace
-                                        {
-                                            m.line_content.trim()
-                                        } else {
-                                            &m.line_content
-                                        };
-                                        format!("{}: {content}", m.line,)
-                                    },
+                                let context_before = context_view(
+                                    m.context_before.clone(),
+                                    line_number - m.context_before.len(),
+                                    config,
+                                );
+                                let context_after = context_view(
+                                    m.context_after.clone(),
+                                    line_number + 1,
+                                    config,
+                                );
+                                let match_data = match_data.clone();
+                                let is_excluded = {
+                                    let match_data = match_data.clone();
+                                    let m = m.clone();
+                                    move || match_data.is_excluded(&m)
+                                };
+                                let content = {
+                                    let m = m.clone();
+                                    let is_excluded = is_excluded.clone();
                                     move || {
-                                        let config = config.get();
-                                        let mut offset = if config
-                                            .ui
-                                            .trim_search_results_whitespace
+                                        match_content(
+                                            &m,
+                                            replace_active.get() && !is_excluded(),
+                                        )
+                                    }
+                                };
+
+                                let match_view = stack((
+                                    focus_text(
                                         {
-                                            line_content.trim_start().len() as i32
-                                                - line_content.len() as i32
-                                        } else {
-                                            0
-                                        };
-                                        offset +=
-                                            line_number.to_string().len() as i32 + 2;
+                                            let content = content.clone();
+                                            move || {
+                                                let config = config.get();
+                                                let (content, _) = content();
+                                                let content = if config
+                                                    .ui
+                                                    .trim_search_results_whitespace
+                                                {
+                                                    content.trim()
+                                                } else {
+                                                    &content
+                                                };
+                                                format!("{line_number}: {content}")
+                                            }
+                                        },
+                                        move || {
+                                            let config = config.get();
+                                            let (content, range) = content();
+                                            let mut offset = if config
+                                                .ui
+                                                .trim_search_results_whitespace
+                                            {
+                                                content.trim_start().len() as i32
+                                                    - content.len() as i32
+                                            } else {
+                                                0
+                                            };
+                                            offset += line_number.to_string().len()
+                                                as i32
+                                                + 2;
 
-                                        ((start as i32 + offset) as usize
-                                            ..(end as i32 + offset) as usize)
-                                            .collect()
-                                    },
-                                    move || {
-                                        config.get().color(PhidiColor::EDITOR_FOCUS)
+                                            ((range.start as i32 + offset) as usize
+                                                ..(range.end as i32 + offset)
+                                                    as usize)
+                                                .collect()
+                                        },
+                                        move || {
+                                            config
+                                                .get()
+                                                .color(PhidiColor::EDITOR_FOCUS)
+                                        },
+                                    )
+                                    .style(|s| s.flex_grow(1.0).min_width(0.0)),
+                                    {
+                                        let is_excluded = is_excluded.clone();
+                                        clickable_icon(
+                                            || PhidiIcons::CLOSE,
+                                            move || match_data.toggle_excluded(&m),
+                                            is_excluded,
+                                            || false,
+                                            || "Exclude from Replace",
+                                            config,
+                                        )
+                                        .style(move |s| {
+                                            s.margin_right(6.0).apply_if(
+                                                !replace_active.get(),
+                                                |s| s.hide(),
+                                            )
+                                        })
                                     },
-                                )
+                                ))
                                 .style(move |s| {
                                     let config = config.get();
                                     let icon_size = config.ui.icon_size() as f32;
-                                    s.margin_left(10.0 + icon_size + 6.0).hover(
-                                        |s| {
+                                    s.margin_left(10.0 + icon_size + 6.0)
+                                        .items_center()
+                                        .apply_if(
+                                            replace_active.get() && is_excluded(),
+                                            |s| {
+                                                s.color(
+                                                    config.color(
+                                                        PhidiColor::EDITOR_DIM,
+                                                    ),
+                                                )
+                                            },
+                                        )
+                                        .hover(|s| {
                                             s.cursor(CursorStyle::Pointer)
                                                 .background(config.color(
                                                 PhidiColor::PANEL_HOVERED_BACKGROUND,
                                             ))
-                                        },
-                                    )
+                                        })
                                 })
-                                .on_click_stop(
-                                    move |_| {
-                                        internal_command.send(
-                                            InternalCommand::JumpToLocation {
-                                                location: EditorLocation {
-                                                    path: path.clone(),
-                                                    position: Some(
-                                                        EditorPosition::Line(
-                                                            line_number
-                                                                .saturating_sub(1),
-                                                        ),
+                                .on_click_stop(move |_| {
+                                    internal_command.send(
+                                        InternalCommand::JumpToLocation {
+                                            location: EditorLocation {
+                                                path: path.clone(),
+                                                position: Some(
+                                                    EditorPosition::Line(
+                                                        line_number
+                                                            .saturating_sub(1),
                                                     ),
-                                                    scroll_offset: None,
-                                                    ignore_unconfirmed: false,
-                                                    same_editor_tab: false,
-                                                },
+                                                ),
+                                                scroll_offset: None,
+                                                ignore_unconfirmed: false,
+                                                same_editor_tab: false,
                                             },
-                                        );
-                                    },
-                                )
+                                        },
+                                    );
+                                });
+
+                                stack((context_before, match_view, context_after))
+                                    .style(|s| s.flex_col())
                             },
                         )
-                        .item_size_fixed(move || ui_line_height.get())
+                        .item_size_fn(move |m: &SearchMatch| {
+                            ui_line_height.get() * match_lines(m) as f64
+                        })
                         .style(|s| s.flex_col()),
                     ))
                     .style(|s| s.flex_col())
@@ -307,3 +502,53 @@ fn search_result(
     })
     .style(|s| s.size_pct(100.0, 100.0))
 }
+
+/// The lines of context around a match, numbered from `first_line`.
+fn context_view(
+    lines: Vec<String>,
+    first_line: usize,
+    config: ReadSignal<Arc<PhidiConfig>>,
+) -> impl View {
+    dyn_stack(
+        move || lines.clone().into_iter().enumerate(),
+        |(i, _)| *i,
+        move |(i, line)| {
+            label(move || {
+                let config = config.get();
+                let line = if config.ui.trim_search_results_whitespace {
+                    line.trim()
+                } else {
+                    &line
+                };
+                format!("{}: {line}", first_line + i)
+            })
+            .style(move |s| {
+                let config = config.get();
+                let icon_size = config.ui.icon_size() as f32;
+                s.margin_left(10.0 + icon_size + 6.0)
+                    .color(config.color(PhidiColor::EDITOR_DIM))
+                    .text_ellipsis()
+                    .selectable(false)
+            })
+        },
+    )
+    .style(|s| s.flex_col())
+}
+
+/// The line of a match as shown in the results, with the replacement in place
+/// of the match if `replaced`, and the range to highlight in it.
+fn match_content(m: &SearchMatch, replaced: bool) -> (String, Range<usize>) {
+    // Long lines are shortened, and then the columns of the match don't point
+    // into the content.
+    let parts = m
+        .line_content
+        .get(..m.start)
+        .zip(m.line_content.get(m.end..));
+    match (&m.replacement, parts) {
+        (Some(replacement), Some((before, after))) if replaced => (
+            format!("{before}{replacement}{after}"),
+            m.start..m.start + replacement.len(),
+        ),
+        _ => (m.line_content.clone(), m.start..m.end),
+    }
+}
@@ -19,6 +19,10 @@ pub enum PanelKind {
     DocumentSymbol,
     References,
     Implementation,
+    Proposals,
+    Agent,
+    Checkpoints,
+    GitLog,
 }
 
 impl PanelKind {
@@ -35,6 +39,10 @@ pub fn svg_name(&self) -> &'static str {
             PanelKind::DocumentSymbol => PhidiIcons::DOCUMENT_SYMBOL,
             PanelKind::References => PhidiIcons::REFERENCES,
             PanelKind::Implementation => PhidiIcons::IMPLEMENTATION,
+            PanelKind::Proposals => PhidiIcons::PROPOSALS,
+            PanelKind::Agent => PhidiIcons::AGENT,
+            PanelKind::Checkpoints => PhidiIcons::CHECKPOINTS,
+            PanelKind::GitLog => PhidiIcons::GIT_LOG,
         }
     }
 
@@ -61,6 +69,10 @@ pub fn default_position(&self) -> PanelPosition {
             PanelKind::DocumentSymbol => PanelPosition::RightTop,
             PanelKind::References => PanelPosition::BottomLeft,
             PanelKind::Implementation => PanelPosition::BottomLeft,
+            PanelKind::Proposals => PanelPosition::BottomLeft,
+            PanelKind::Agent => PanelPosition::RightTop,
+            PanelKind::Checkpoints => PanelPosition::LeftTop,
+            PanelKind::GitLog => PanelPosition::LeftTop,
         }
     }
 }
@@ -1,13 +1,17 @@
+pub mod agent_view;
 pub mod call_hierarchy_view;
+pub mod checkpoints_view;
 pub mod data;
 pub mod debug_view;
 pub mod document_symbol;
+pub mod git_log_view;
 pub mod global_search_view;
 pub mod implementation_view;
 pub mod kind;
 pub mod plugin_view;
 pub mod position;
 pub mod problem_view;
+pub mod proposals_view;
 pub mod references_view;
 pub mod source_control_view;
 pub mod style;
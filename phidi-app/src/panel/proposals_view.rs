@@ -0,0 +1,235 @@
+use std::{path::PathBuf, rc::Rc, sync::Arc};
+
+use floem::{
+    View,
+    reactive::{ReadSignal, SignalGet, SignalWith},
+    style::{CursorStyle, Style},
+    views::{Decorators, container, dyn_stack, label, scroll, stack, svg},
+};
+use phidi_rpc::proposal::ProposalId;
+
+use super::position::PanelPosition;
+use crate::{
+    config::{PhidiConfig, color::PhidiColor},
+    proposal::ProposalData,
+    window_tab::WindowTabData,
+};
+
+pub fn proposals_panel(
+    window_tab_data: Rc<WindowTabData>,
+    _position: PanelPosition,
+) -> impl View {
+    let config = window_tab_data.common.config;
+    let proposals = window_tab_data.proposals.clone();
+    let workspace = window_tab_data.workspace.clone();
+    container(
+        scroll(
+            dyn_stack(
+                move || {
+                    proposals.proposals.with(|proposals| {
+                        proposals
+                            .iter()
+                            .map(|(id, proposal)| {
+                                (
+                                    *id,
+                                    proposal.title.clone(),
+                                    proposal.source.clone(),
+                                    proposal
+                                        .files
+                                        .iter()
+                                        .map(|(path, file)| {
+                                            (path.clone(), file.edits.len())
+                                        })
+                                        .collect::<Vec<_>>(),
+                                )
+                            })
+                            .collect::<Vec<_>>()
+                    })
+                },
+                |(id, _, _, files)| (*id, files.clone()),
+                move |(id, title, source, files)| {
+                    let proposals = window_tab_data.proposals.clone();
+                    let workspace_path = workspace.path.clone();
+                    stack((
+                        proposal_header(
+                            proposals.clone(),
+                            id,
+                            title,
+                            source,
+                            config,
+                        ),
+                        stack(
+                            files
+                                .into_iter()
+                                .map(|(path, edits)| {
+                                    let display = workspace_path
+                                        .as_ref()
+                                        .and_then(|w| path.strip_prefix(w).ok())
+                                        .unwrap_or(&path)
+                                        .to_path_buf();
+                                    file_view(
+                                        proposals.clone(),
+                                        id,
+                                        path,
+                                        display,
+                                        edits,
+                                        config,
+                                    )
+                                })
+                                .collect::<Vec<_>>(),
+                        )
+                        .style(|s| s.flex_col().width_pct(100.0)),
+                    ))
+                    .style(|s| s.flex_col().width_pct(100.0))
+                },
+            )
+            .style(|s| s.flex_col().width_pct(100.0).line_height(1.8)),
+        )
+        .style(|s| s.absolute().size_pct(100.0, 100.0)),
+    )
+    .style(|s| s.size_pct(100.0, 100.0))
+    .debug_name("Proposals Panel")
+}
+
+fn action_button(
+    name: &'static str,
+    on_click: impl Fn() + 'static,
+    config: ReadSignal<Arc<PhidiConfig>>,
+) -> impl View {
+    label(move || name.to_string())
+        .on_click_stop(move |_| on_click())
+        .style(move |s| {
+            let config = config.get();
+            s.margin_left(6.0)
+                .padding_horiz(6.0)
+                .border_radius(6.0)
+                .selectable(false)
+                .hover(|s| {
+                    s.cursor(CursorStyle::Pointer).background(
+                        config.color(PhidiColor::PANEL_HOVERED_BACKGROUND),
+                    )
+                })
+        })
+}
+
+fn proposal_header(
+    proposals: ProposalData,
+    id: ProposalId,
+    title: String,
+    source: String,
+    config: ReadSignal<Arc<PhidiConfig>>,
+) -> impl View {
+    let accept = {
+        let proposals = proposals.clone();
+        move || proposals.accept(id)
+    };
+    let reject = move || proposals.reject(id);
+    stack((
+        label(move || title.clone()).style(|s| {
+            s.margin_right(6.0)
+                .min_width(0.0)
+                .text_ellipsis()
+                .selectable(false)
+        }),
+        label(move || source.clone()).style(move |s| {
+            s.color(config.get().color(PhidiColor::EDITOR_DIM))
+                .min_width(0.0)
+                .flex_grow(1.0)
+                .text_ellipsis()
+                .selectable(false)
+        }),
+        action_button("Accept All", accept, config),
+        action_button("Reject All", reject, config),
+    ))
+    .style(move |s| {
+        s.width_pct(100.0)
+            .items_center()
+            .padding_horiz(10.0)
+            .background(config.get().color(PhidiColor::EDITOR_BACKGROUND))
+    })
+}
+
+fn file_view(
+    proposals: ProposalData,
+    id: ProposalId,
+    path: PathBuf,
+    display: PathBuf,
+    edits: usize,
+    config: ReadSignal<Arc<PhidiConfig>>,
+) -> impl View {
+    let file_name = display
+        .file_name()
+        .and_then(|s| s.to_str())
+        .unwrap_or("")
+        .to_string();
+    let folder = display
+        .parent()
+        .and_then(|s| s.to_str())
+        .unwrap_or("")
+        .to_string();
+    let style_path = display.clone();
+
+    let review = {
+        let proposals = proposals.clone();
+        let path = path.clone();
+        move || proposals.review(id, &path)
+    };
+    let accept = {
+        let proposals = proposals.clone();
+        let path = path.clone();
+        move || proposals.accept_file(id, &path)
+    };
+    let reject = move || proposals.reject_file(id, &path);
+
+    stack((
+        svg(move || config.get().file_svg(&display).0).style(move |s| {
+            let config = config.get();
+            let size = config.ui.icon_size() as f32;
+            let color = config.file_svg(&style_path).1;
+            s.min_width(size)
+                .size(size, size)
+                .margin_right(6.0)
+                .apply_opt(color, Style::color)
+        }),
+        label(move || file_name.clone()).style(|s| {
+            s.margin_right(6.0)
+                .min_width(0.0)
+                .text_ellipsis()
+                .selectable(false)
+        }),
+        label(move || folder.clone()).style(move |s| {
+            s.color(config.get().color(PhidiColor::EDITOR_DIM))
+                .margin_right(6.0)
+                .min_width(0.0)
+                .text_ellipsis()
+                .selectable(false)
+        }),
+        label(move || {
+            if edits == 1 {
+                "1 edit".to_string()
+            } else {
+                format!("{edits} edits")
+            }
+        })
+        .style(move |s| {
+            s.color(config.get().color(PhidiColor::EDITOR_DIM))
+                .flex_grow(1.0)
+                .selectable(false)
+        }),
+        action_button("Accept", accept, config),
+        action_button("Reject", reject, config),
+    ))
+    .on_click_stop(move |_| review())
+    .style(move |s| {
+        let config = config.get();
+        s.width_pct(100.0)
+            .min_width(0.0)
+            .items_center()
+            .padding_left(10.0 + config.ui.icon_size() as f32 + 6.0)
+            .padding_right(10.0)
+            .hover(|s| {
+                s.cursor(CursorStyle::Pointer)
+                    .background(config.color(PhidiColor::PANEL_HOVERED_BACKGROUND))
+            })
+    })
+}
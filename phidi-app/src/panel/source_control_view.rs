@@ -23,10 +23,10 @@
     view::foldable_panel_section,
 };
 use crate::{
+    app::clickable_icon,
     command::{CommandKind, InternalCommand, PhidiCommand, PhidiWorkbenchCommand},
     config::{color::PhidiColor, icon::PhidiIcons},
     editor::view::editor_view,
-    settings::checkbox,
     source_control::SourceControlData,
     window_tab::{Focus, WindowTabData},
 };
@@ -180,13 +180,40 @@ pub fn source_control_panel(
             },
         ))
         .style(|s| s.flex_col().width_pct(100.0).padding(10.0)),
-        foldable_panel_section(
-            text("Changes"),
-            file_diffs_view(source_control),
-            window_tab_data.panel.section_open(PanelSection::Changes),
-            config,
-        )
-        .style(|s| s.flex_col().size_pct(100.0, 100.0)),
+        {
+            let staged_diffs = source_control.staged_diffs;
+            let open = window_tab_data
+                .panel
+                .section_open(PanelSection::StagedChanges);
+            foldable_panel_section(
+                text("Staged Changes"),
+                file_diffs_view(source_control.clone(), true),
+                open,
+                config,
+            )
+            .style(move |s| {
+                s.flex_col()
+                    .width_pct(100.0)
+                    .apply_if(open.get(), |s| s.flex_grow(1.0).flex_basis(0.0))
+                    .apply_if(staged_diffs.with(|diffs| diffs.is_empty()), |s| {
+                        s.hide()
+                    })
+            })
+        },
+        {
+            let open = window_tab_data.panel.section_open(PanelSection::Changes);
+            foldable_panel_section(
+                text("Changes"),
+                file_diffs_view(source_control, false),
+                open,
+                config,
+            )
+            .style(move |s| {
+                s.flex_col()
+                    .width_pct(100.0)
+                    .apply_if(open.get(), |s| s.flex_grow(1.0).flex_basis(0.0))
+            })
+        },
     ))
     .on_event_stop(EventListener::PointerDown, move |_| {
         if focus.get_untracked() != Focus::Panel(PanelKind::SourceControl) {
@@ -197,8 +224,14 @@ pub fn source_control_panel(
     .debug_name("Source Control Panel")
 }
 
-fn file_diffs_view(source_control: SourceControlData) -> impl View {
-    let file_diffs = source_control.file_diffs;
+/// The staged changes, or those which aren't, with a button to move each to
+/// the other list.
+fn file_diffs_view(source_control: SourceControlData, staged: bool) -> impl View {
+    let file_diffs = if staged {
+        source_control.staged_diffs
+    } else {
+        source_control.file_diffs
+    };
     let config = source_control.common.config;
     let workspace = source_control.common.workspace.clone();
     let panel_rect = create_rw_signal(Rect::ZERO);
@@ -206,10 +239,13 @@ fn file_diffs_view(source_control: SourceControlData) -> impl View {
     let phidi_command = source_control.common.phidi_command;
     let internal_command = source_control.common.internal_command;
 
-    let view_fn = move |(path, (diff, checked)): (PathBuf, (FileDiff, bool))| {
+    let view_fn = move |(path, diff): (PathBuf, FileDiff)| {
         let diff_for_style = diff.clone();
         let full_path = path.clone();
         let diff_for_menu = diff.clone();
+        let diff_for_stage = diff.clone();
+        let source_control_for_stage = source_control.clone();
+        let source_control_for_menu = source_control.clone();
         let path_for_click = full_path.clone();
 
         let path = if let Some(workspace_path) = workspace.path.as_ref() {
@@ -231,13 +267,33 @@ fn file_diffs_view(source_control: SourceControlData) -> impl View {
             .to_string();
         let style_path = path.clone();
         stack((
-            checkbox(move || checked, config).on_click_stop(move |_| {
-                file_diffs.update(|diffs| {
-                    if let Some((_, checked)) = diffs.get_mut(&full_path) {
-                        *checked = !*checked;
+            clickable_icon(
+                move || {
+                    if staged {
+                        PhidiIcons::SCM_CHANGE_REMOVE
+                    } else {
+                        PhidiIcons::SCM_CHANGE_ADD
                     }
-                });
-            }),
+                },
+                move || {
+                    let diffs = [diff_for_stage.clone()];
+                    if staged {
+                        source_control_for_stage.unstage(&diffs);
+                    } else {
+                        source_control_for_stage.stage(&diffs);
+                    }
+                },
+                || false,
+                || false,
+                move || {
+                    if staged {
+                        "Unstage Changes"
+                    } else {
+                        "Stage Changes"
+                    }
+                },
+                config,
+            ),
             svg(move || config.get().file_svg(&path).0).style(move |s| {
                 let config = config.get();
                 let size = config.ui.icon_size() as f32;
@@ -311,21 +367,34 @@ fn file_diffs_view(source_control: SourceControlData) -> impl View {
             });
         })
         .on_event_cont(EventListener::PointerDown, move |event| {
-            let diff_for_menu = diff_for_menu.clone();
+            let diff_for_discard = diff_for_menu.clone();
 
             let discard = move || {
                 phidi_command.send(PhidiCommand {
                     kind: CommandKind::Workbench(
                         PhidiWorkbenchCommand::SourceControlDiscardTargetFileChanges,
                     ),
-                    data: Some(serde_json::json!(diff_for_menu.clone())),
+                    data: Some(serde_json::json!(diff_for_discard.clone())),
                 });
             };
 
             if let Event::PointerDown(pointer_event) = event {
                 if pointer_event.button.is_secondary() {
-                    let menu = Menu::new("")
-                        .entry(MenuItem::new("Discard Changes").action(discard));
+                    let source_control = source_control_for_menu.clone();
+                    let diffs = [diff_for_menu.clone()];
+                    let menu = if staged {
+                        Menu::new("").entry(
+                            MenuItem::new("Unstage Changes")
+                                .action(move || source_control.unstage(&diffs)),
+                        )
+                    } else {
+                        Menu::new("")
+                            .entry(
+                                MenuItem::new("Stage Changes")
+                                    .action(move || source_control.stage(&diffs)),
+                            )
+                            .entry(MenuItem::new("Discard Changes").action(discard))
+                    };
                     show_context_menu(menu, None);
                 }
             }
@@ -348,9 +417,7 @@ fn file_diffs_view(source_control: SourceControlData) -> impl View {
         scroll({
             dyn_stack(
                 move || file_diffs.get(),
-                |(path, (diff, checked))| {
-                    (path.to_path_buf(), diff.clone(), *checked)
-                },
+                |(path, diff)| (path.to_path_buf(), diff.clone()),
                 view_fn,
             )
             .style(|s| s.line_height(1.6).flex_col().width_pct(100.0))
@@ -17,12 +17,16 @@
 };
 
 use super::{
+    agent_view::agent_panel,
+    checkpoints_view::checkpoints_panel,
     debug_view::debug_panel,
+    git_log_view::git_log_panel,
     global_search_view::global_search_panel,
     kind::PanelKind,
     plugin_view::plugin_panel,
     position::{PanelContainerPosition, PanelPosition},
     problem_view::problem_panel,
+    proposals_view::proposals_panel,
     source_control_view::source_control_panel,
     terminal_view::terminal_panel,
 };
@@ -507,6 +511,18 @@ fn panel_view(
                     implementation_panel(window_tab_data.clone(), position)
                         .into_any()
                 }
+                PanelKind::Proposals => {
+                    proposals_panel(window_tab_data.clone(), position).into_any()
+                }
+                PanelKind::Agent => {
+                    agent_panel(window_tab_data.clone(), position).into_any()
+                }
+                PanelKind::Checkpoints => {
+                    checkpoints_panel(window_tab_data.clone(), position).into_any()
+                }
+                PanelKind::GitLog => {
+                    git_log_panel(window_tab_data.clone(), position).into_any()
+                }
             };
             view.style(|s| s.size_pct(100.0, 100.0))
         },
@@ -563,6 +579,10 @@ fn panel_picker(
                 PanelKind::DocumentSymbol => "Document Symbol",
                 PanelKind::References => "References",
                 PanelKind::Implementation => "Implementation",
+                PanelKind::Proposals => "Proposed Changes",
+                PanelKind::Agent => "Agent",
+                PanelKind::Checkpoints => "Checkpoints",
+                PanelKind::GitLog => "Commit History",
             };
             let icon = p.svg_name();
             let is_active = {
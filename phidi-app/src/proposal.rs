@@ -0,0 +1,412 @@
+//! Edits proposed by agents and plugins. Nothing is applied until it has been
+//! reviewed: every file of a proposal can be opened in a diff editor against
+//! its current document, where single hunks can be accepted, rejected or
+//! edited before they land in the document.
+
+use std::{
+    collections::HashMap,
+    ops::Range,
+    path::{Path, PathBuf},
+    rc::Rc,
+    sync::{Arc, atomic::AtomicU64},
+};
+
+use floem::{
+    prelude::SignalTrack,
+    reactive::{RwSignal, Scope, SignalGet, SignalUpdate, SignalWith},
+};
+use indexmap::IndexMap;
+use lsp_types::{TextEdit, Url, WorkspaceEdit};
+use phidi_core::{
+    buffer::diff::rope_diff, editor::EditType, selection::Selection, syntax::Syntax,
+};
+use phidi_rpc::proposal::{EditProposal, ProposalId};
+use phidi_xi_rope::Rope;
+
+use crate::{
+    doc::Doc,
+    editor::diff::{DiffHunk, diff_hunks},
+    main_split::{MainSplitData, workspace_edits},
+    window_tab::CommonData,
+};
+
+#[derive(Clone)]
+pub struct ProposedFile {
+    pub edits: Vec<TextEdit>,
+    /// The document holding the proposed content, created when the file is
+    /// first opened for review. It's the right side of the diff editor.
+    pub review: Option<Rc<Doc>>,
+    /// The content of the document the review was built on, which tells the
+    /// proposed changes apart from the ones made to the document since.
+    pub base: Option<Rope>,
+}
+
+#[derive(Clone)]
+pub struct ProposalItem {
+    pub title: String,
+    pub source: String,
+    pub files: IndexMap<PathBuf, ProposedFile>,
+}
+
+#[derive(Clone)]
+pub struct ProposalData {
+    pub scope: Scope,
+    pub proposals: RwSignal<IndexMap<ProposalId, ProposalItem>>,
+    pub main_split: MainSplitData,
+    pub common: Rc<CommonData>,
+}
+
+impl ProposalData {
+    pub fn new(cx: Scope, main_split: MainSplitData) -> Self {
+        let common = main_split.common.clone();
+        Self {
+            scope: cx,
+            proposals: cx.create_rw_signal(IndexMap::new()),
+            main_split,
+            common,
+        }
+    }
+
+    /// Stage a proposal. Only text edits can be reviewed, resource
+    /// operations in the edit are ignored.
+    pub fn add(&self, proposal: &EditProposal) {
+        let mut files: IndexMap<PathBuf, ProposedFile> =
+            workspace_edits(&proposal.edit)
+                .unwrap_or_default()
+                .into_iter()
+                .filter_map(|(url, edits)| {
+                    let path = url.to_file_path().ok()?;
+                    Some((
+                        path,
+                        ProposedFile {
+                            edits,
+                            review: None,
+                            base: None,
+                        },
+                    ))
+                })
+                .collect();
+        if files.is_empty() {
+            return;
+        }
+        files.sort_keys();
+
+        self.proposals.update(|proposals| {
+            proposals.insert(
+                proposal.id,
+                ProposalItem {
+                    title: proposal.title.clone(),
+                    source: proposal.source.clone(),
+                    files,
+                },
+            );
+        });
+    }
+
+    fn file(&self, id: ProposalId, path: &Path) -> Option<ProposedFile> {
+        self.proposals
+            .with_untracked(|proposals| proposals.get(&id)?.files.get(path).cloned())
+    }
+
+    /// Whether the document is the proposed side of a review.
+    pub fn is_review_doc(&self, doc: &Rc<Doc>) -> bool {
+        self.proposals.with(|proposals| {
+            proposals.values().any(|proposal| {
+                proposal.files.values().any(|file| {
+                    file.review
+                        .as_ref()
+                        .map(|review| Rc::ptr_eq(review, doc))
+                        .unwrap_or(false)
+                })
+            })
+        })
+    }
+
+    /// Open the diff editor reviewing the proposed changes of one file.
+    pub fn review(&self, id: ProposalId, path: &Path) {
+        let Some(file) = self.file(id, path) else {
+            return;
+        };
+        let (left, _) = self.main_split.get_doc(path.to_path_buf(), None);
+        let right = if let Some(review) = file.review {
+            review
+        } else {
+            let review = self.review_doc(id, path, left.clone(), file.edits);
+            self.proposals.update(|proposals| {
+                if let Some(file) = proposals
+                    .get_mut(&id)
+                    .and_then(|proposal| proposal.files.get_mut(path))
+                {
+                    file.review = Some(review.clone());
+                }
+            });
+            review
+        };
+        self.main_split.open_diff_docs(left, right);
+    }
+
+    fn review_doc(
+        &self,
+        id: ProposalId,
+        path: &Path,
+        left: Rc<Doc>,
+        edits: Vec<TextEdit>,
+    ) -> Rc<Doc> {
+        let cx = self.scope;
+        let right = Rc::new(Doc::new_local(
+            cx,
+            self.main_split.editors,
+            self.common.clone(),
+        ));
+        right.set_syntax(Syntax::init(path));
+
+        // The proposed content is built on top of the current document, which
+        // might still be loading.
+        let ready = cx.create_rw_signal(false);
+        {
+            let proposals = self.proposals;
+            let path = path.to_path_buf();
+            let left = left.clone();
+            let right = right.clone();
+            cx.create_effect(move |initialized: Option<bool>| {
+                if initialized == Some(true) {
+                    return true;
+                }
+                left.buffer.track();
+                if !left.loaded() {
+                    return false;
+                }
+                let base = left.buffer.with_untracked(|b| b.text().clone());
+                right.init_content(base.clone());
+                right.do_text_edit(&edits);
+                proposals.update(|proposals| {
+                    if let Some(file) = proposals
+                        .get_mut(&id)
+                        .and_then(|proposal| proposal.files.get_mut(&path))
+                    {
+                        file.base = Some(base);
+                    }
+                });
+                ready.set(true);
+                true
+            });
+        }
+
+        // Once every hunk has been accepted or rejected both sides are equal
+        // and there is nothing left to review.
+        {
+            let proposals = self.clone();
+            let path = path.to_path_buf();
+            let right = right.clone();
+            cx.create_effect(move |resolved: Option<bool>| {
+                if resolved == Some(true) || !ready.get() {
+                    return resolved.unwrap_or(false);
+                }
+                let left_text = left.buffer.with(|b| b.to_string());
+                let right_text = right.buffer.with(|b| b.to_string());
+                if left_text == right_text {
+                    proposals.remove_file(id, &path);
+                    true
+                } else {
+                    false
+                }
+            });
+        }
+
+        right
+    }
+
+    /// Apply the remaining changes of one file to its document.
+    pub fn accept_file(&self, id: ProposalId, path: &Path) {
+        let Some(file) = self.file(id, path) else {
+            return;
+        };
+        let (left, _) = self.main_split.get_doc(path.to_path_buf(), None);
+        match (file.review, file.base) {
+            (Some(review), Some(base)) if left.loaded() => {
+                // The review may have been edited, so it's the reviewed
+                // content that lands rather than the original edits, on top of
+                // whatever the document has been changed to since.
+                let review = review.buffer.with_untracked(|b| b.text().clone());
+                let current = left.buffer.with_untracked(|b| b.text().clone());
+                let edits = rebase_review(&base, &review, &current);
+                if !edits.is_empty() {
+                    let edits: Vec<_> = edits
+                        .iter()
+                        .map(|(range, text)| {
+                            (
+                                Selection::region(range.start, range.end),
+                                text.as_str(),
+                            )
+                        })
+                        .collect();
+                    left.do_raw_edit(&edits, EditType::Other);
+                }
+            }
+            _ => {
+                if let Ok(url) = Url::from_file_path(path) {
+                    self.main_split.apply_workspace_edit(&WorkspaceEdit {
+                        changes: Some(HashMap::from([(url, file.edits)])),
+                        ..Default::default()
+                    });
+                }
+            }
+        }
+        self.remove_file(id, path);
+    }
+
+    pub fn reject_file(&self, id: ProposalId, path: &Path) {
+        self.remove_file(id, path);
+    }
+
+    pub fn accept(&self, id: ProposalId) {
+        let paths = self.proposals.with_untracked(|proposals| {
+            proposals
+                .get(&id)
+                .map(|proposal| proposal.files.keys().cloned().collect::<Vec<_>>())
+                .unwrap_or_default()
+        });
+        for path in paths {
+            self.accept_file(id, &path);
+        }
+    }
+
+    pub fn reject(&self, id: ProposalId) {
+        self.proposals.update(|proposals| {
+            proposals.shift_remove(&id);
+        });
+    }
+
+    fn remove_file(&self, id: ProposalId, path: &Path) {
+        self.proposals.update(|proposals| {
+            let Some(proposal) = proposals.get_mut(&id) else {
+                return;
+            };
+            proposal.files.shift_remove(path);
+            if proposal.files.is_empty() {
+                proposals.shift_remove(&id);
+            }
+        });
+    }
+}
+
+/// The line hunks turning `left` into `right`.
+fn line_hunks(left: &Rope, right: &Rope) -> Vec<DiffHunk> {
+    rope_diff(
+        left.clone(),
+        right.clone(),
+        0,
+        Arc::new(AtomicU64::new(0)),
+        None,
+    )
+    .map(|changes| diff_hunks(&changes))
+    .unwrap_or_default()
+}
+
+/// Whether two line ranges touch the same lines, counting an empty range as
+/// the position it's inserted at.
+fn lines_overlap(a: &Range<usize>, b: &Range<usize>) -> bool {
+    a.start < b.end.max(b.start + 1) && b.start < a.end.max(a.start + 1)
+}
+
+/// The edits, as offset ranges of `current` and their text, that bring the
+/// changes `review` proposes to `base` into `current`, the document's content
+/// which may have changed since the review was built on `base`.
+///
+/// Hunks that were already accepted into the document, or whose lines have
+/// been edited there since, are left as the document has them.
+fn rebase_review(
+    base: &Rope,
+    review: &Rope,
+    current: &Rope,
+) -> Vec<(Range<usize>, String)> {
+    let changed = line_hunks(base, current);
+    line_hunks(base, review)
+        .into_iter()
+        .filter(|proposed| {
+            !changed
+                .iter()
+                .any(|change| lines_overlap(&change.left, &proposed.left))
+        })
+        .map(|proposed| {
+            // Shift the lines by what the document's own changes before the
+            // hunk added or removed.
+            let shift = changed
+                .iter()
+                .filter(|change| change.left.end <= proposed.left.start)
+                .map(|change| {
+                    change.right.len() as isize - change.left.len() as isize
+                })
+                .sum::<isize>();
+            let line = |line: usize| (line as isize + shift) as usize;
+            let start = current.offset_of_line(line(proposed.left.start));
+            let end = current.offset_of_line(line(proposed.left.end));
+            let text = review
+                .slice_to_cow(
+                    review.offset_of_line(proposed.right.start)
+                        ..review.offset_of_line(proposed.right.end),
+                )
+                .to_string();
+            (start..end, text)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use phidi_xi_rope::Rope;
+
+    use super::rebase_review;
+
+    /// `current` with the edits rebased from the review applied.
+    fn accept(base: &str, review: &str, current: &str) -> String {
+        let mut text = current.to_string();
+        let edits = rebase_review(
+            &Rope::from(base),
+            &Rope::from(review),
+            &Rope::from(current),
+        );
+        for (range, new_text) in edits.into_iter().rev() {
+            text.replace_range(range, &new_text);
+        }
+        text
+    }
+
+    #[test]
+    fn test_accept_unchanged_document() {
+        assert_eq!(
+            accept("a\nb\nc\n", "a\nB\nc\nd\n", "a\nb\nc\n"),
+            "a\nB\nc\nd\n"
+        );
+    }
+
+    #[test]
+    fn test_accept_after_document_changed() {
+        // Lines were added before and after the proposed change, and the one
+        // after it was edited, all of which is kept.
+        assert_eq!(
+            accept(
+                "a\nb\nc\nd\ne\n",
+                "a\nb\nB\nc\nd\ne\n",
+                "z\na\nb\nc\nd\nE\nf\n",
+            ),
+            "z\na\nb\nB\nc\nd\nE\nf\n"
+        );
+        // A proposed change to lines edited in the document since loses to
+        // the edit.
+        assert_eq!(
+            accept("a\nb\nc\n", "a\nB\nc\n", "a\nmine\nc\n"),
+            "a\nmine\nc\n"
+        );
+    }
+
+    #[test]
+    fn test_accept_part_of_the_hunks() {
+        let base = "a\nb\nc\nd\ne\n";
+        // The change of `b` was accepted on its own, the one of `d` rejected,
+        // which restored it in the review, and only the one of `e` remains.
+        let review = "a\nB\nc\nd\nE\n";
+        let current = "a\nB\nc\nd\ne\n";
+        assert_eq!(accept(base, review, current), "a\nB\nc\nd\nE\n");
+    }
+}
@@ -8,8 +8,9 @@
 use floem::{ext_event::create_signal_from_channel, reactive::ReadSignal};
 use phidi_proxy::dispatch::Dispatcher;
 use phidi_rpc::{
-    core::{CoreHandler, CoreNotification, CoreRpcHandler},
-    plugin::VoltID,
+    RequestId,
+    core::{CoreHandler, CoreNotification, CoreRequest, CoreRpcHandler},
+    plugin::{LspServerConfig, VoltID},
     proxy::{ProxyRpcHandler, ProxyStatus},
     terminal::TermId,
 };
@@ -28,6 +29,7 @@
 
 pub struct Proxy {
     pub tx: Sender<CoreNotification>,
+    pub request_tx: Sender<(RequestId, CoreRequest)>,
     pub term_tx: Sender<(TermId, TermEvent)>,
 }
 
@@ -36,6 +38,7 @@ pub struct ProxyData {
     pub proxy_rpc: ProxyRpcHandler,
     pub core_rpc: CoreRpcHandler,
     pub notification: ReadSignal<Option<CoreNotification>>,
+    pub request: ReadSignal<Option<(RequestId, CoreRequest)>>,
 }
 
 impl ProxyData {
@@ -50,6 +53,8 @@ pub fn new_proxy(
     disabled_volts: Vec<VoltID>,
     extra_plugin_paths: Vec<PathBuf>,
     plugin_configurations: HashMap<String, HashMap<String, serde_json::Value>>,
+    lsp_servers: HashMap<String, LspServerConfig>,
+    mcp_server: bool,
     term_tx: Sender<(TermId, TermEvent)>,
 ) -> ProxyData {
     let proxy_rpc = ProxyRpcHandler::new();
@@ -69,6 +74,8 @@ pub fn new_proxy(
                     disabled_volts,
                     extra_plugin_paths,
                     plugin_configurations,
+                    lsp_servers,
+                    mcp_server,
                     1,
                     1,
                 );
@@ -113,12 +120,17 @@ pub fn new_proxy(
     }
 
     let (tx, rx) = std::sync::mpsc::channel();
+    let (request_tx, request_rx) = std::sync::mpsc::channel();
     {
         let core_rpc = core_rpc.clone();
         std::thread::Builder::new()
             .name("CoreRpcHandler".to_owned())
             .spawn(move || {
-                let mut proxy = Proxy { tx, term_tx };
+                let mut proxy = Proxy {
+                    tx,
+                    request_tx,
+                    term_tx,
+                };
                 core_rpc.mainloop(&mut proxy);
                 core_rpc.notification(CoreNotification::ProxyStatus {
                     status: ProxyStatus::Disconnected,
@@ -128,11 +140,13 @@ pub fn new_proxy(
     };
 
     let notification = create_signal_from_channel(rx);
+    let request = create_signal_from_channel(request_rx);
 
     ProxyData {
         proxy_rpc,
         core_rpc,
         notification,
+        request,
     }
 }
 
@@ -152,11 +166,10 @@ fn handle_notification(&mut self, rpc: phidi_rpc::core::CoreNotification) {
         }
     }
 
-    fn handle_request(
-        &mut self,
-        _id: phidi_rpc::RequestId,
-        _rpc: phidi_rpc::core::CoreRequest,
-    ) {
+    fn handle_request(&mut self, id: RequestId, rpc: CoreRequest) {
+        if let Err(err) = self.request_tx.send((id, rpc)) {
+            tracing::error!("{:?}", err);
+        }
     }
 }
 
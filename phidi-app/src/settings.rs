@@ -29,9 +29,9 @@
 use crate::{
     command::CommandExecuted,
     config::{
-        DropdownInfo, PhidiConfig, color::PhidiColor, core::CoreConfig,
-        editor::EditorConfig, icon::PhidiIcons, terminal::TerminalConfig,
-        ui::UIConfig,
+        DropdownInfo, PhidiConfig, agent::AgentConfig, color::PhidiColor,
+        core::CoreConfig, editor::EditorConfig, icon::PhidiIcons,
+        terminal::TerminalConfig, ui::UIConfig,
     },
     keypress::KeyPressFocus,
     main_split::Editors,
@@ -181,6 +181,12 @@ fn into_settings_map(
                     &TerminalConfig::DESCS[..],
                     into_settings_map(&config.terminal),
                 ),
+                (
+                    "Agent",
+                    &AgentConfig::FIELDS[..],
+                    &AgentConfig::DESCS[..],
+                    into_settings_map(&config.agent),
+                ),
             ] {
                 let pos = cx.create_rw_signal(Point::new(0.0, item_height_accum));
                 data_items.push_back(SettingsItem {
@@ -1,12 +1,14 @@
 use std::{path::PathBuf, rc::Rc};
 
+use chrono::{Local, TimeZone, Utc};
+
 use floem::{
     keyboard::Modifiers,
     reactive::{RwSignal, Scope, SignalWith},
 };
 use indexmap::IndexMap;
 use phidi_core::mode::Mode;
-use phidi_rpc::source_control::FileDiff;
+use phidi_rpc::source_control::{BlameHunk, FileDiff};
 
 use crate::{
     command::{CommandExecuted, CommandKind},
@@ -18,8 +20,10 @@
 
 #[derive(Clone, Debug)]
 pub struct SourceControlData {
-    // VCS modified files & whether they should be included in the next commit
-    pub file_diffs: RwSignal<IndexMap<PathBuf, (FileDiff, bool)>>,
+    // VCS modified files which aren't staged
+    pub file_diffs: RwSignal<IndexMap<PathBuf, FileDiff>>,
+    // VCS modified files in the index, which go into the next commit
+    pub staged_diffs: RwSignal<IndexMap<PathBuf, FileDiff>>,
     pub branch: RwSignal<String>,
     pub branches: RwSignal<im::Vector<String>>,
     pub tags: RwSignal<im::Vector<String>>,
@@ -64,6 +68,7 @@ impl SourceControlData {
     pub fn new(cx: Scope, editors: Editors, common: Rc<CommonData>) -> Self {
         Self {
             file_diffs: cx.create_rw_signal(IndexMap::new()),
+            staged_diffs: cx.create_rw_signal(IndexMap::new()),
             branch: cx.create_rw_signal("".to_string()),
             branches: cx.create_rw_signal(im::Vector::new()),
             tags: cx.create_rw_signal(im::Vector::new()),
@@ -73,18 +78,15 @@ pub fn new(cx: Scope, editors: Editors, common: Rc<CommonData>) -> Self {
     }
 
     pub fn commit(&self) {
-        let diffs: Vec<FileDiff> = self.file_diffs.with_untracked(|file_diffs| {
-            file_diffs
-                .iter()
-                .filter_map(
-                    |(_, (diff, checked))| {
-                        if *checked { Some(diff) } else { None }
-                    },
-                )
-                .cloned()
-                .collect()
-        });
-        if diffs.is_empty() {
+        // Without anything staged, all of the changes are committed.
+        let has_staged = self.staged_diffs.with_untracked(|diffs| !diffs.is_empty());
+        let diffs: Vec<FileDiff> = if has_staged {
+            Vec::new()
+        } else {
+            self.file_diffs
+                .with_untracked(|diffs| diffs.values().cloned().collect())
+        };
+        if !has_staged && diffs.is_empty() {
             return;
         }
 
@@ -101,4 +103,95 @@ pub fn commit(&self) {
         self.editor.reset();
         self.common.proxy.git_commit(message.to_string(), diffs);
     }
+
+    pub fn stage(&self, diffs: &[FileDiff]) {
+        self.common.proxy.git_stage_files(diff_paths(diffs));
+    }
+
+    pub fn unstage(&self, diffs: &[FileDiff]) {
+        self.common.proxy.git_unstage_files(diff_paths(diffs));
+    }
+
+    pub fn stage_all(&self) {
+        let diffs = self
+            .file_diffs
+            .with_untracked(|diffs| diffs.values().cloned().collect::<Vec<_>>());
+        if !diffs.is_empty() {
+            self.stage(&diffs);
+        }
+    }
+
+    pub fn unstage_all(&self) {
+        let diffs = self
+            .staged_diffs
+            .with_untracked(|diffs| diffs.values().cloned().collect::<Vec<_>>());
+        if !diffs.is_empty() {
+            self.unstage(&diffs);
+        }
+    }
+}
+
+/// The paths of the files of the changes, which for a rename are both the old
+/// and the new one.
+fn diff_paths(diffs: &[FileDiff]) -> Vec<PathBuf> {
+    diffs
+        .iter()
+        .flat_map(|diff| match diff {
+            FileDiff::Modified(path)
+            | FileDiff::Added(path)
+            | FileDiff::Deleted(path) => vec![path.clone()],
+            FileDiff::Renamed(new_path, old_path) => {
+                vec![new_path.clone(), old_path.clone()]
+            }
+        })
+        .collect()
+}
+
+/// The blame of the line of the cursor, shown after it.
+pub fn inline_blame_text(hunk: &BlameHunk) -> String {
+    match &hunk.commit {
+        Some(commit) => format!(
+            "{}, {} \u{2022} {}",
+            commit.author,
+            relative_time(commit.time),
+            commit.summary
+        ),
+        None => "You, not committed yet".to_string(),
+    }
+}
+
+/// The blame of a part of the file, shown in the gutter at its first line.
+pub fn gutter_blame_text(hunk: &BlameHunk) -> String {
+    match &hunk.commit {
+        Some(commit) => {
+            let date = Local
+                .timestamp_opt(commit.time, 0)
+                .single()
+                .map(|time| time.format("%Y-%m-%d").to_string())
+                .unwrap_or_default();
+            format!("{date} {}", commit.author)
+        }
+        None => "Not committed yet".to_string(),
+    }
+}
+
+/// How long ago `time`, in seconds since the Unix epoch, was, in the largest
+/// unit which fits.
+pub fn relative_time(time: i64) -> String {
+    const MINUTE: i64 = 60;
+    const HOUR: i64 = 60 * MINUTE;
+    const DAY: i64 = 24 * HOUR;
+    const MONTH: i64 = 30 * DAY;
+    const YEAR: i64 = 365 * DAY;
+
+    let seconds = (Utc::now().timestamp() - time).max(0);
+    let (amount, unit) = match seconds {
+        seconds if seconds < MINUTE => return "just now".to_string(),
+        seconds if seconds < HOUR => (seconds / MINUTE, "minute"),
+        seconds if seconds < DAY => (seconds / HOUR, "hour"),
+        seconds if seconds < MONTH => (seconds / DAY, "day"),
+        seconds if seconds < YEAR => (seconds / MONTH, "month"),
+        seconds => (seconds / YEAR, "year"),
+    };
+    format!("{amount} {unit}{} ago", if amount == 1 { "" } else { "s" })
 }
@@ -58,11 +58,14 @@ pub fn status(
     });
     let branch = source_control.branch;
     let file_diffs = source_control.file_diffs;
+    let staged_diffs = source_control.staged_diffs;
     let branch = move || {
         format!(
             "{}{}",
             branch.get(),
-            if file_diffs.with(|diffs| diffs.is_empty()) {
+            if file_diffs.with(|diffs| diffs.is_empty())
+                && staged_diffs.with(|diffs| diffs.is_empty())
+            {
                 ""
             } else {
                 "*"
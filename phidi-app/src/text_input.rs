@@ -218,7 +218,9 @@ fn text_input_full<T: KeyPressFocus + 'static>(
         offset: 0,
         preedit_range: None,
         layout_rect: Rect::ZERO,
+        value: "".to_string(),
         content: "".to_string(),
+        masked: false,
         focus: false,
         text_node: None,
         text_layout: create_rw_signal(None),
@@ -300,6 +302,9 @@ fn text_input_full<T: KeyPressFocus + 'static>(
     })
 }
 
+/// Shown instead of each character of a masked input.
+const MASK: char = '•';
+
 enum TextInputState {
     Content {
         text: String,
@@ -308,11 +313,16 @@ enum TextInputState {
     },
     Focus(bool),
     Placeholder(String),
+    Masked(bool),
 }
 
 pub struct TextInput {
     id: ViewId,
+    /// The text of the input, with the preedit text.
+    value: String,
+    /// What is shown, which is the value unless the input is masked.
     content: String,
+    masked: bool,
     offset: usize,
     preedit_range: Option<(usize, usize)>,
     editor: EditorData,
@@ -342,6 +352,17 @@ pub fn placeholder(self, placeholder: impl Fn() -> String + 'static) -> Self {
         self
     }
 
+    /// Show a bullet instead of each character while `masked` is true, like
+    /// password fields do.
+    pub fn masked(self, masked: impl Fn() -> bool + 'static) -> Self {
+        let id = self.id;
+        create_effect(move |_| {
+            let masked = masked();
+            id.update_state(TextInputState::Masked(masked));
+        });
+        self
+    }
+
     pub fn on_cursor_pos(mut self, cursor_pos: impl Fn(Point) + 'static) -> Self {
         self.on_cursor_pos = Some(Box::new(cursor_pos));
         self
@@ -413,6 +434,37 @@ fn set_text_layout(&mut self) {
         self.placeholder_text_layout = Some(placeholder_text_layout);
     }
 
+    fn update_content(&mut self) {
+        self.content = if self.masked {
+            MASK.to_string().repeat(self.value.chars().count())
+        } else {
+            self.value.clone()
+        };
+        self.text_layout.set(None);
+    }
+
+    /// The offset in the shown content of an offset in the value.
+    fn content_offset(&self, offset: usize) -> usize {
+        if self.masked {
+            let offset = offset.min(self.value.len());
+            self.value[..offset].chars().count() * MASK.len_utf8()
+        } else {
+            offset
+        }
+    }
+
+    /// The offset in the value of an offset in the shown content.
+    fn value_offset(&self, offset: usize) -> usize {
+        if self.masked {
+            self.value
+                .char_indices()
+                .nth(offset / MASK.len_utf8())
+                .map_or(self.value.len(), |(i, _)| i)
+        } else {
+            offset
+        }
+    }
+
     fn hit_index(&self, _cx: &mut EventCx, point: Point) -> usize {
         self.text_layout.with_untracked(|text_layout| {
             if let Some(text_layout) = text_layout.as_ref() {
@@ -426,7 +478,7 @@ fn hit_index(&self, _cx: &mut EventCx, point: Point) -> usize {
                     };
                 let hit =
                     text_layout.hit_point(Point::new(point.x - padding_left, 0.0));
-                hit.index.min(self.content.len())
+                self.value_offset(hit.index.min(self.content.len()))
             } else {
                 0
             }
@@ -533,10 +585,10 @@ fn update(
                     offset,
                     preedit_range,
                 } => {
-                    self.content = text;
+                    self.value = text;
                     self.offset = offset;
                     self.preedit_range = preedit_range;
-                    self.text_layout.set(None);
+                    self.update_content();
                 }
                 TextInputState::Focus(focus) => {
                     self.focus = focus;
@@ -545,6 +597,10 @@ fn update(
                     self.placeholder = placeholder;
                     self.placeholder_text_layout = None;
                 }
+                TextInputState::Masked(masked) => {
+                    self.masked = masked;
+                    self.update_content();
+                }
             }
             self.id.request_layout();
         }
@@ -575,6 +631,7 @@ fn layout(
                 let text_layout = text_layout.as_ref().unwrap();
 
                 let offset = self.cursor().get_untracked().offset();
+                let offset = self.content_offset(offset);
                 let cursor_point = text_layout.hit_position(offset).point;
                 if cursor_point != self.cursor_pos {
                     self.cursor_pos = cursor_point;
@@ -628,7 +685,8 @@ fn compute_layout(
         self.layout_rect = size
             .to_rect()
             .with_origin(Point::new(location.x as f64, location.y as f64));
-        let offset = self.cursor().with_untracked(|c| c.offset());
+        let offset =
+            self.content_offset(self.cursor().with_untracked(|c| c.offset()));
         let cursor_line = self.text_layout.with_untracked(|text_layout| {
             let hit_position = text_layout.as_ref().unwrap().hit_position(offset);
             let point = Point::new(location.x as f64, location.y as f64)
@@ -675,7 +733,7 @@ fn event_before_children(
                     });
                 } else if pointer.button.is_primary() && pointer.count == 3 {
                     self.cursor().update(|cursor| {
-                        cursor.set_insert(Selection::region(0, self.content.len()));
+                        cursor.set_insert(Selection::region(0, self.value.len()));
                     });
                 }
                 cx.update_active(self.id);
@@ -721,8 +779,10 @@ fn paint(&mut self, cx: &mut floem::context::PaintCx) {
             if let CursorMode::Insert(selection) = &cursor.mode {
                 for region in selection.regions() {
                     if !region.is_caret() {
-                        let min = text_layout.hit_position(region.min()).point.x;
-                        let max = text_layout.hit_position(region.max()).point.x;
+                        let min = self.content_offset(region.min());
+                        let max = self.content_offset(region.max());
+                        let min = text_layout.hit_position(min).point.x;
+                        let max = text_layout.hit_position(max).point.x;
                         cx.fill(
                             &Rect::ZERO
                                 .with_size(Size::new(max - min, height))
@@ -741,6 +801,8 @@ fn paint(&mut self, cx: &mut floem::context::PaintCx) {
             }
 
             if let Some((start, end)) = self.preedit_range {
+                let start = self.content_offset(start);
+                let end = self.content_offset(end);
                 let start_position = text_layout.hit_position(start);
                 let start_point = start_position.point
                     + self.layout_rect.origin().to_vec2()
@@ -772,7 +834,8 @@ fn paint(&mut self, cx: &mut floem::context::PaintCx) {
             {
                 cx.clip(&self.text_rect.inflate(2.0, 2.0));
 
-                let hit_position = text_layout.hit_position(self.offset);
+                let offset = self.content_offset(self.offset);
+                let hit_position = text_layout.hit_position(offset);
                 let cursor_point = hit_position.point
                     + self.layout_rect.origin().to_vec2()
                     - self.text_viewport.origin().to_vec2();
@@ -13,9 +13,9 @@
 use alacritty_terminal::vte::ansi::Handler;
 use floem::{
     ViewId,
-    action::{TimerToken, open_file, remove_overlay},
+    action::{TimerToken, open_file, remove_overlay, save_as},
     ext_event::{create_ext_action, create_signal_from_channel},
-    file::FileDialogOptions,
+    file::{FileDialogOptions, FileInfo},
     keyboard::Modifiers,
     kurbo::Size,
     peniko::kurbo::{Point, Rect, Vec2},
@@ -25,7 +25,10 @@
         WriteSignal, use_context,
     },
     text::{Attrs, AttrsList, FamilyOwned, LineHeightValue, TextLayout},
-    views::editor::core::buffer::rope_text::RopeText,
+    views::editor::{
+        core::{buffer::rope_text::RopeText, register::Clipboard},
+        text::SystemClipboard,
+    },
 };
 use im::HashMap;
 use indexmap::IndexMap;
@@ -39,13 +42,14 @@
     mode::Mode, register::Register,
 };
 use phidi_rpc::{
-    RpcError,
-    core::CoreNotification,
+    RequestId, RpcError,
+    context_pack::ContextPackFormat,
+    core::{CoreNotification, CoreRequest, CoreResponse},
     dap_types::{ConfigSource, RunDebugConfig},
     file::{Naming, PathObject},
     plugin::PluginId,
-    proxy::{ProxyResponse, ProxyRpcHandler, ProxyStatus},
-    source_control::FileDiff,
+    proxy::{ProxyResponse, ProxyRpcHandler, ProxyStatus, SourcedCodeAction},
+    source_control::{FileDiff, PullMode, RemoteOperation},
     terminal::TermId,
 };
 use serde_json::Value;
@@ -53,7 +57,9 @@
 
 use crate::{
     about::AboutData,
+    agent::AgentData,
     alert::{AlertBoxData, AlertButton},
+    checkpoint::CheckpointData,
     code_action::{CodeActionData, CodeActionStatus},
     command::{
         CommandExecuted, CommandKind, InternalCommand, PhidiCommand,
@@ -68,6 +74,7 @@
     editor_tab::EditorTabChild,
     file_explorer::data::FileExplorerData,
     find::Find,
+    git_log::GitLogData,
     global_search::GlobalSearchData,
     hover::HoverData,
     id::WindowTabId,
@@ -76,14 +83,19 @@
     listener::Listener,
     lsp::path_from_url,
     main_split::{MainSplitData, SplitData, SplitDirection, SplitMoveDirection},
-    palette::{DEFAULT_RUN_TOML, PaletteData, PaletteStatus, kind::PaletteKind},
+    palette::{
+        DEFAULT_RUN_TOML, PaletteData, PaletteStatus, ScmAction, kind::PaletteKind,
+    },
     panel::{
-        call_hierarchy_view::{CallHierarchyData, CallHierarchyItemData},
+        call_hierarchy_view::{
+            CallHierarchyData, CallHierarchyItemData, HierarchyItem, HierarchyKind,
+        },
         data::{PanelData, PanelSection, default_panel_order},
         kind::PanelKind,
         position::PanelContainerPosition,
     },
     plugin::PluginData,
+    proposal::ProposalData,
     proxy::{ProxyData, new_proxy},
     rename::RenameData,
     source_control::SourceControlData,
@@ -182,6 +194,10 @@ pub struct WindowTabData {
     pub rename: RenameData,
     pub global_search: GlobalSearchData,
     pub call_hierarchy_data: CallHierarchyData,
+    pub proposals: ProposalData,
+    pub agent: AgentData,
+    pub checkpoints: CheckpointData,
+    pub git_log: GitLogData,
     pub about_data: AboutData,
     pub alert_data: AlertBoxData,
     pub layout_rect: RwSignal<Rect>,
@@ -334,6 +350,8 @@ pub fn new(
             all_disabled_volts,
             window_common.extra_plugin_paths.as_ref().clone(),
             config.plugins.clone(),
+            config.lsp_servers.clone(),
+            config.agent.mcp_server,
             term_tx.clone(),
         );
         let (config, set_config) = cx.create_signal(Arc::new(config));
@@ -511,6 +529,10 @@ pub fn new(
 
         let rename = RenameData::new(cx, main_split.editors, common.clone());
         let global_search = GlobalSearchData::new(cx, main_split.clone());
+        let proposals = ProposalData::new(cx, main_split.clone());
+        let agent = AgentData::new(cx, main_split.editors, common.clone());
+        let checkpoints = CheckpointData::new(cx, common.clone());
+        let git_log = GitLogData::new(cx, main_split.editors, common.clone());
 
         let plugin = PluginData::new(
             cx,
@@ -560,9 +582,14 @@ pub fn new(
             global_search,
             call_hierarchy_data: CallHierarchyData {
                 root: cx.create_rw_signal(None),
+                kind: cx.create_rw_signal(HierarchyKind::IncomingCalls),
                 common: common.clone(),
                 scroll_to_line: cx.create_rw_signal(None),
             },
+            proposals,
+            agent,
+            checkpoints,
+            git_log,
             about_data,
             alert_data,
             layout_rect: cx.create_rw_signal(Rect::ZERO),
@@ -626,6 +653,18 @@ pub fn new(
             });
         }
 
+        {
+            let window_tab_data = window_tab_data.clone();
+            let request = window_tab_data.proxy.request;
+            cx.create_effect(move |_| {
+                request.with(|rpc| {
+                    if let Some((id, rpc)) = rpc.as_ref() {
+                        window_tab_data.handle_core_request(*id, rpc);
+                    }
+                });
+            });
+        }
+
         window_tab_data
     }
 
@@ -659,7 +698,21 @@ pub fn reload_config(&self) {
                 change_plugins.push(key.clone());
             }
         }
+        let blame_was_shown =
+            self.common.config.get_untracked().editor.blame_shown();
         self.set_config.set(Arc::new(config.clone()));
+        if !blame_was_shown && config.editor.blame_shown() {
+            self.main_split.docs.with_untracked(|docs| {
+                for doc in docs.values() {
+                    doc.get_blame();
+                }
+            });
+        }
+        // The proxy restarts the servers whose settings changed, including the
+        // ones of the workspace settings which it reads itself.
+        self.common
+            .proxy
+            .update_lsp_servers(config.lsp_servers.clone());
         if !change_plugins.is_empty() {
             self.common
                 .proxy
@@ -713,6 +766,15 @@ pub fn run_phidi_command(&self, cmd: PhidiCommand) {
         }
     }
 
+    /// The path of the file of the active editor.
+    fn active_file_path(&self) -> Option<PathBuf> {
+        let editor_data = self.main_split.active_editor.get_untracked()?;
+        match editor_data.doc().content.get_untracked() {
+            DocContent::File { path, .. } => Some(path),
+            _ => None,
+        }
+    }
+
     pub fn run_workbench_command(
         &self,
         cmd: PhidiWorkbenchCommand,
@@ -1139,6 +1201,16 @@ pub fn run_workbench_command(
             ChangeFileLineEnding => {
                 self.palette.run(PaletteKind::LineEnding);
             }
+            ShowColorPicker => {
+                self.palette.run(PaletteKind::ColorPicker);
+            }
+            FormatSelection => {
+                if let Some(editor_data) =
+                    self.main_split.active_editor.get_untracked()
+                {
+                    editor_data.format_selection();
+                }
+            }
             DiffFiles => self.palette.run(PaletteKind::DiffFiles),
 
             // ==== Running / Debugging ====
@@ -1267,6 +1339,9 @@ pub fn run_workbench_command(
             ToggleSearchFocus => {
                 self.toggle_panel_focus(PanelKind::Search);
             }
+            ToggleGitLogFocus => {
+                self.toggle_panel_focus(PanelKind::GitLog);
+            }
             ToggleTerminalVisual => {
                 self.toggle_panel_visual(PanelKind::Terminal);
             }
@@ -1345,6 +1420,128 @@ pub fn run_workbench_command(
             SourceControlDiscardWorkspaceChanges => {
                 // TODO:
             }
+            SourceControlStageActiveFile => {
+                if let Some(path) = self.active_file_path() {
+                    self.common.proxy.git_stage_files(vec![path]);
+                }
+            }
+            SourceControlUnstageActiveFile => {
+                if let Some(path) = self.active_file_path() {
+                    self.common.proxy.git_unstage_files(vec![path]);
+                }
+            }
+            SourceControlStageSelectedLines => {
+                if let Some(editor_data) =
+                    self.main_split.active_editor.get_untracked()
+                {
+                    editor_data.stage_selected_lines(true);
+                }
+            }
+            SourceControlUnstageSelectedLines => {
+                if let Some(editor_data) =
+                    self.main_split.active_editor.get_untracked()
+                {
+                    editor_data.stage_selected_lines(false);
+                }
+            }
+            SourceControlStageAll => {
+                self.source_control.stage_all();
+            }
+            SourceControlUnstageAll => {
+                self.source_control.unstage_all();
+            }
+            SourceControlShowLineCommit => {
+                if let Some(editor_data) =
+                    self.main_split.active_editor.get_untracked()
+                {
+                    editor_data.show_line_commit();
+                }
+            }
+            SourceControlToggleBlameGutter => {
+                let show =
+                    !self.common.config.get_untracked().editor.show_blame_gutter;
+                PhidiConfig::update_file(
+                    "editor",
+                    "show-blame-gutter",
+                    toml_edit::Value::from(show),
+                );
+            }
+            SourceControlShowFileHistory => {
+                if let Some(path) = self.active_file_path() {
+                    self.git_log.show_file_history(&path);
+                    self.show_panel(PanelKind::GitLog);
+                }
+            }
+            SourceControlCreateBranch => {
+                self.palette.run_scm_action(ScmAction::CreateBranch);
+            }
+            SourceControlRenameBranch => {
+                self.palette
+                    .run_scm_action(ScmAction::RenameBranch { branch: None });
+            }
+            SourceControlDeleteBranch => {
+                self.palette.run_scm_action(ScmAction::DeleteBranch);
+            }
+            SourceControlMergeBranch => {
+                self.palette.run_scm_action(ScmAction::MergeBranch);
+            }
+            SourceControlCreateTag => {
+                self.palette.run_scm_action(ScmAction::CreateTag {
+                    annotated: false,
+                    name: None,
+                });
+            }
+            SourceControlCreateAnnotatedTag => {
+                self.palette.run_scm_action(ScmAction::CreateTag {
+                    annotated: true,
+                    name: None,
+                });
+            }
+            SourceControlStash => {
+                self.palette.run_scm_action(ScmAction::Stash {
+                    include_untracked: false,
+                });
+            }
+            SourceControlStashIncludeUntracked => {
+                self.palette.run_scm_action(ScmAction::Stash {
+                    include_untracked: true,
+                });
+            }
+            SourceControlStashApply => {
+                self.palette.run_scm_action(ScmAction::ApplyStash);
+            }
+            SourceControlStashPop => {
+                self.palette.run_scm_action(ScmAction::PopStash);
+            }
+            SourceControlStashDrop => {
+                self.palette.run_scm_action(ScmAction::DropStash);
+            }
+            SourceControlFetch => {
+                self.common
+                    .proxy
+                    .git_remote_operation(RemoteOperation::Fetch, None);
+            }
+            SourceControlPull => {
+                self.common.proxy.git_remote_operation(
+                    RemoteOperation::Pull {
+                        mode: PullMode::FastForward,
+                    },
+                    None,
+                );
+            }
+            SourceControlPullRebase => {
+                self.common.proxy.git_remote_operation(
+                    RemoteOperation::Pull {
+                        mode: PullMode::Rebase,
+                    },
+                    None,
+                );
+            }
+            SourceControlPush => {
+                self.common
+                    .proxy
+                    .git_remote_operation(RemoteOperation::Push, None);
+            }
 
             // ==== UI ====
             ShowAbout => {
@@ -1429,6 +1626,33 @@ pub fn run_workbench_command(
             NextError => {
                 self.main_split.next_error();
             }
+            CopyContextPack => {
+                self.export_context_pack(ContextPackFormat::Markdown, None);
+            }
+            CopyContextPackJson => {
+                self.export_context_pack(ContextPackFormat::Json, None);
+            }
+            SaveContextPack => {
+                let window_tab_data = self.clone();
+                save_as(
+                    FileDialogOptions::new().title("Save Context Pack"),
+                    move |file: Option<FileInfo>| {
+                        let Some(path) = file.and_then(|mut file| file.path.pop())
+                        else {
+                            return;
+                        };
+                        let format = if path
+                            .extension()
+                            .is_some_and(|extension| extension == "json")
+                        {
+                            ContextPackFormat::Json
+                        } else {
+                            ContextPackFormat::Markdown
+                        };
+                        window_tab_data.export_context_pack(format, Some(path));
+                    },
+                );
+            }
             PreviousError => {}
             Quit => {
                 floem::quit_app();
@@ -1498,6 +1722,13 @@ pub fn run_workbench_command(
                     editor_data.call_hierarchy(self.clone());
                 }
             }
+            ShowTypeHierarchy => {
+                if let Some(editor_data) =
+                    self.main_split.active_editor.get_untracked()
+                {
+                    editor_data.type_hierarchy(self.clone());
+                }
+            }
             FindReferences => {
                 if let Some(editor_data) =
                     self.main_split.active_editor.get_untracked()
@@ -1640,6 +1871,12 @@ pub fn run_internal_command(&self, cmd: InternalCommand) {
             InternalCommand::OpenFileChanges { path } => {
                 self.main_split.open_file_changes(path);
             }
+            InternalCommand::OpenCommitFileChanges { path, commit } => {
+                self.main_split.open_commit_file_changes(path, commit);
+            }
+            InternalCommand::OpenCommitChanges { commit, diff } => {
+                self.main_split.open_commit_changes(commit, diff);
+            }
             InternalCommand::ReloadFileExplorer => {
                 self.file_explorer.reload();
             }
@@ -1845,11 +2082,10 @@ pub fn run_internal_command(&self, cmd: InternalCommand) {
             InternalCommand::ShowCodeActions {
                 offset,
                 mouse_click,
-                plugin_id,
                 code_actions,
             } => {
                 let mut code_action = self.code_action.get_untracked();
-                code_action.show(plugin_id, code_actions, offset, mouse_click);
+                code_action.show(code_actions, offset, mouse_click);
                 self.code_action.set(code_action);
             }
             InternalCommand::RunCodeAction { plugin_id, action } => {
@@ -2073,18 +2309,44 @@ pub fn run_internal_command(&self, cmd: InternalCommand) {
                     self.palette.run(PaletteKind::RunAndDebug);
                 }
             }
-            InternalCommand::CallHierarchyIncoming { item_id } => {
-                self.call_hierarchy_incoming(item_id);
+            InternalCommand::HierarchyChildren { item_id } => {
+                self.hierarchy_children(item_id);
             }
         }
     }
 
+    fn handle_core_request(&self, id: RequestId, rpc: &CoreRequest) {
+        let response = match rpc {
+            CoreRequest::ApplyWorkspaceEdit { edit } => {
+                let failure_reason = self
+                    .main_split
+                    .outdated_document(edit)
+                    .map(|uri| format!("{uri} has changed since the edit was made"));
+                if failure_reason.is_none() {
+                    self.main_split.apply_workspace_edit(edit);
+                }
+                CoreResponse::ApplyWorkspaceEditResponse {
+                    applied: failure_reason.is_none(),
+                    failure_reason,
+                }
+            }
+        };
+        self.proxy.core_rpc.handle_response(id, Ok(response));
+    }
+
     fn handle_core_notification(&self, rpc: &CoreNotification) {
         let cx = self.scope;
         match rpc {
             CoreNotification::ProxyStatus { status } => {
                 self.common.proxy_status.set(Some(status.to_owned()));
             }
+            CoreNotification::GitCredentialsRequired { operation, url } => {
+                self.palette.run_scm_action(ScmAction::Credentials {
+                    operation: *operation,
+                    url: url.clone(),
+                    username: None,
+                });
+            }
             CoreNotification::DiffInfo { diff } => {
                 self.source_control.branch.set(diff.head.clone());
                 self.source_control
@@ -2093,22 +2355,23 @@ fn handle_core_notification(&self, rpc: &CoreNotification) {
                 self.source_control
                     .tags
                     .set(diff.tags.iter().cloned().collect());
-                self.source_control.file_diffs.update(|file_diffs| {
-                    *file_diffs = diff
-                        .diffs
+                self.source_control.file_diffs.set(
+                    diff.unstaged
                         .iter()
-                        .cloned()
-                        .map(|diff| {
-                            let checked =
-                                file_diffs.get(diff.path()).is_none_or(|(_, c)| *c);
-                            (diff.path().clone(), (diff, checked))
-                        })
-                        .collect();
-                });
+                        .map(|diff| (diff.path().clone(), diff.clone()))
+                        .collect(),
+                );
+                self.source_control.staged_diffs.set(
+                    diff.staged
+                        .iter()
+                        .map(|diff| (diff.path().clone(), diff.clone()))
+                        .collect(),
+                );
 
                 let docs = self.main_split.docs.get_untracked();
                 for (_, doc) in docs {
                     doc.retrieve_head();
+                    doc.get_blame();
                 }
             }
             CoreNotification::CompletionResponse {
@@ -2259,6 +2522,13 @@ fn handle_core_notification(&self, rpc: &CoreNotification) {
             CoreNotification::OpenFileChanged { path, content } => {
                 self.main_split.open_file_changed(path, content);
             }
+            CoreNotification::EditProposed { proposal } => {
+                self.proposals.add(proposal);
+                self.show_panel(PanelKind::Proposals);
+            }
+            CoreNotification::ApplyWorkspaceEdit { edit } => {
+                self.main_split.apply_workspace_edit(&edit);
+            }
             CoreNotification::VoltInstalled { volt, icon } => {
                 self.plugin.volt_installed(volt, icon);
             }
@@ -2324,6 +2594,23 @@ fn handle_core_notification(&self, rpc: &CoreNotification) {
             CoreNotification::WorkspaceFileChange => {
                 self.file_explorer.reload();
             }
+            CoreNotification::AgentEvent { session, event } => {
+                self.agent.handle_event(*session, event);
+            }
+            CoreNotification::CheckpointsChanged { checkpoints } => {
+                self.checkpoints.set(checkpoints);
+            }
+            CoreNotification::OnTypeFormattingTriggers { path, characters } => {
+                if let Some(doc) = self
+                    .main_split
+                    .docs
+                    .with_untracked(|docs| docs.get(path).cloned())
+                {
+                    doc.on_type_formatting_triggers.update(|triggers| {
+                        triggers.extend(characters.iter().cloned())
+                    });
+                }
+            }
             _ => {}
         }
     }
@@ -2355,6 +2642,12 @@ pub fn key_down<'a>(&self, event: impl Into<EventRef<'a>> + Copy) -> bool {
             Focus::Panel(PanelKind::SourceControl) => {
                 Some(keypress.key_down(event, &self.source_control))
             }
+            Focus::Panel(PanelKind::Agent) => {
+                Some(keypress.key_down(event, &self.agent))
+            }
+            Focus::Panel(PanelKind::GitLog) => {
+                Some(keypress.key_down(event, &self.git_log))
+            }
             _ => None,
         };
 
@@ -2636,14 +2929,18 @@ fn toggle_panel_focus(&self, kind: PanelKind) {
             | PanelKind::CallHierarchy
             | PanelKind::DocumentSymbol
             | PanelKind::References
-            | PanelKind::Implementation => {
+            | PanelKind::Implementation
+            | PanelKind::Proposals
+            | PanelKind::Checkpoints => {
                 // Some panels don't accept focus (yet). Fall back to visibility check
                 // in those cases.
                 self.panel.is_panel_visible(&kind)
             }
-            PanelKind::Terminal | PanelKind::SourceControl | PanelKind::Search => {
-                self.is_panel_focused(kind)
-            }
+            PanelKind::Terminal
+            | PanelKind::SourceControl
+            | PanelKind::Search
+            | PanelKind::Agent
+            | PanelKind::GitLog => self.is_panel_focused(kind),
         };
         if should_hide {
             self.hide_panel(kind);
@@ -2918,17 +3215,51 @@ pub fn show_code_lens(
             .send(InternalCommand::ShowCodeActions {
                 offset,
                 mouse_click,
-                plugin_id,
                 code_actions: lens
                     .into_iter()
                     .filter_map(|lens| {
-                        Some(CodeActionOrCommand::Command(lens.command?))
+                        Some(SourcedCodeAction {
+                            plugin_id,
+                            source: None,
+                            action: CodeActionOrCommand::Command(lens.command?),
+                        })
                     })
                     .collect(),
             });
     }
 
-    pub fn call_hierarchy_incoming(&self, item_id: ViewId) {
+    /// Have the proxy make a context pack and copy it to the clipboard, or
+    /// write it to `path`.
+    pub fn export_context_pack(
+        &self,
+        format: ContextPackFormat,
+        path: Option<PathBuf>,
+    ) {
+        let request = self.main_split.context_pack_request(format);
+        let send = create_ext_action(self.scope, move |result| {
+            let pack = match result {
+                Ok(ProxyResponse::ContextPackResponse { pack }) => pack,
+                Ok(_) => return,
+                Err(err) => {
+                    error!("Failed to make a context pack: {}", err.message);
+                    return;
+                }
+            };
+            if let Some(path) = &path {
+                if let Err(err) = std::fs::write(path, &pack.content) {
+                    error!("Failed to write the context pack: {}", err);
+                }
+            } else {
+                let mut clipboard = SystemClipboard::new();
+                clipboard.put_string(&pack.content);
+            }
+        });
+        self.common
+            .proxy
+            .context_pack(request, move |result| send(result));
+    }
+
+    pub fn hierarchy_children(&self, item_id: ViewId) {
         let Some(root) = self.call_hierarchy_data.root.get_untracked() else {
             return;
         };
@@ -2936,49 +3267,92 @@ pub fn call_hierarchy_incoming(&self, item_id: ViewId) {
             return;
         };
         let root_item = item;
-        let path: PathBuf = item.get_untracked().item.uri.to_file_path().unwrap();
+        let hierarchy_item = item.get_untracked().item;
+        let Ok(path) = hierarchy_item.uri().to_file_path() else {
+            return;
+        };
         let scope = self.scope;
         let send =
-            create_ext_action(scope, move |_rs: Result<ProxyResponse, RpcError>| {
-                match _rs {
+            create_ext_action(scope, move |rs: Result<ProxyResponse, RpcError>| {
+                // The children, with the range to jump to for each.
+                let children: Vec<(HierarchyItem, lsp_types::Range)> = match rs {
                     Ok(ProxyResponse::CallHierarchyIncomingResponse { items }) => {
-                        if let Some(items) = items {
-                            let mut item_children = Vec::new();
-                            for x in items {
-                                let item = Rc::new(x.from);
-                                for range in x.from_ranges {
-                                    item_children.push(scope.create_rw_signal(
-                                        CallHierarchyItemData {
-                                            view_id: floem::ViewId::new(),
-                                            item: item.clone(),
-                                            from_range: range,
-                                            init: false,
-                                            open: scope.create_rw_signal(false),
-                                            children:
-                                                scope.create_rw_signal(Vec::new()),
-                                        },
-                                    ))
-                                }
-                            }
-                            root_item.update(|x| {
-                                x.init = true;
-                                x.children.update(|children| {
-                                    *children = item_children;
+                        items
+                            .unwrap_or_default()
+                            .into_iter()
+                            .flat_map(|x| {
+                                let item = x.from;
+                                x.from_ranges.into_iter().map(move |range| {
+                                    (HierarchyItem::Call(item.clone()), range)
                                 })
-                            });
-                        }
+                            })
+                            .collect()
                     }
+                    // The ranges of outgoing calls are in the caller, so jump
+                    // to the callee instead.
+                    Ok(ProxyResponse::CallHierarchyOutgoingResponse { items }) => {
+                        items
+                            .unwrap_or_default()
+                            .into_iter()
+                            .map(|x| {
+                                let range = x.to.selection_range;
+                                (HierarchyItem::Call(x.to), range)
+                            })
+                            .collect()
+                    }
+                    Ok(ProxyResponse::TypeHierarchyResponse { items }) => items
+                        .unwrap_or_default()
+                        .into_iter()
+                        .map(|x| {
+                            let range = x.selection_range;
+                            (HierarchyItem::Type(x), range)
+                        })
+                        .collect(),
                     Err(err) => {
                         tracing::error!("{:?}", err);
+                        return;
                     }
-                    Ok(_) => {}
-                }
+                    Ok(_) => return,
+                };
+                let item_children = children
+                    .into_iter()
+                    .map(|(item, range)| {
+                        scope.create_rw_signal(CallHierarchyItemData {
+                            view_id: floem::ViewId::new(),
+                            item: Rc::new(item),
+                            from_range: range,
+                            init: false,
+                            open: scope.create_rw_signal(false),
+                            children: scope.create_rw_signal(Vec::new()),
+                        })
+                    })
+                    .collect();
+                root_item.update(|x| {
+                    x.init = true;
+                    x.children.update(|children| {
+                        *children = item_children;
+                    })
+                });
             });
-        self.common.proxy.call_hierarchy_incoming(
-            path,
-            item.get_untracked().item.as_ref().clone(),
-            send,
-        );
+        let proxy = &self.common.proxy;
+        match (
+            self.call_hierarchy_data.kind.get_untracked(),
+            hierarchy_item.as_ref().clone(),
+        ) {
+            (HierarchyKind::IncomingCalls, HierarchyItem::Call(item)) => {
+                proxy.call_hierarchy_incoming(path, item, send);
+            }
+            (HierarchyKind::OutgoingCalls, HierarchyItem::Call(item)) => {
+                proxy.call_hierarchy_outgoing(path, item, send);
+            }
+            (HierarchyKind::Supertypes, HierarchyItem::Type(item)) => {
+                proxy.type_hierarchy_supertypes(path, item, send);
+            }
+            (HierarchyKind::Subtypes, HierarchyItem::Type(item)) => {
+                proxy.type_hierarchy_subtypes(path, item, send);
+            }
+            _ => {}
+        }
     }
 }
 
@@ -0,0 +1,559 @@
+//! A symbol graph of the workspace built with tree-sitter tags queries
+//! (`tags.scm`), so that navigation works for languages without a language
+//! server, or before the language server has finished indexing.
+//!
+//! Every file contributes the definitions and references matched by the
+//! query. The parent of a tag is the innermost definition containing it,
+//! which is what the call edges are derived from. The `tags.scm` queries
+//! don't capture imports, so the patterns of [`import_query`] are appended to
+//! them.
+
+use std::{
+    cell::RefCell,
+    cmp::Reverse,
+    collections::{HashMap, HashSet},
+    fs, io,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::SystemTime,
+};
+
+use lsp_types::Range;
+use phidi_rpc::code_graph::{CodeGraphItem, CodeGraphQuery};
+use phidi_xi_rope::Rope;
+use serde::{Deserialize, Serialize};
+use tree_sitter::{Query, QueryCursor};
+
+use crate::{
+    buffer::rope_text::RopeTextRef,
+    language::PhidiLanguage,
+    rope_text_pos::RopeTextPosition,
+    syntax::{
+        SyntaxLayers,
+        highlight::{HighlightConfiguration, get_highlight_config},
+        util::RopeProvider,
+    },
+};
+
+/// Bumped whenever the extracted data changes, so that persisted graphs are
+/// rebuilt instead of being reused.
+const CODE_GRAPH_VERSION: u32 = 2;
+
+/// Files larger than this are most likely generated and aren't indexed.
+const MAX_FILE_SIZE: u64 = 1024 * 1024;
+
+thread_local! {
+    static TAGS_QUERIES: RefCell<HashMap<PhidiLanguage, Option<Arc<Query>>>> =
+        RefCell::new(HashMap::new());
+}
+
+fn get_tags_query(
+    language: PhidiLanguage,
+) -> Option<(Arc<HighlightConfiguration>, Arc<Query>)> {
+    let config = get_highlight_config(language).ok()?;
+    let query = TAGS_QUERIES.with(|queries| {
+        queries
+            .borrow_mut()
+            .entry(language)
+            .or_insert_with(|| {
+                let source = language.get_tags_query()?;
+                let with_imports = format!("{source}\n{}", import_query(language));
+                // An older grammar may not have the nodes the imports are
+                // matched on, which shouldn't cost the rest of the tags.
+                let query =
+                    Query::new(&config.language, &with_imports).or_else(|err| {
+                        tracing::error!("{:?} {:?}", language, err);
+                        Query::new(&config.language, &source)
+                    });
+                match query {
+                    Ok(query) => Some(Arc::new(query)),
+                    Err(err) => {
+                        tracing::error!("{:?} {:?}", language, err);
+                        None
+                    }
+                }
+            })
+            .clone()
+    })?;
+    Some((config, query))
+}
+
+/// Patterns capturing the imports of the language as `@reference.import`, with
+/// the imported module as the `@name`.
+fn import_query(language: PhidiLanguage) -> &'static str {
+    match language {
+        PhidiLanguage::Rust => {
+            "(use_declaration argument: (_) @name) @reference.import"
+        }
+        PhidiLanguage::Python => {
+            "(import_statement
+               name: [(dotted_name) @name
+                      (aliased_import name: (dotted_name) @name)])
+               @reference.import
+             (import_from_statement module_name: (_) @name) @reference.import"
+        }
+        PhidiLanguage::Javascript
+        | PhidiLanguage::Jsx
+        | PhidiLanguage::Typescript
+        | PhidiLanguage::Tsx => {
+            "(import_statement source: (string (string_fragment) @name))
+               @reference.import"
+        }
+        PhidiLanguage::Go => {
+            "(import_spec
+               path: (interpreted_string_literal
+                       (interpreted_string_literal_content) @name))
+               @reference.import"
+        }
+        PhidiLanguage::C | PhidiLanguage::Cpp => {
+            "(preproc_include
+               path: [(string_literal (string_content) @name)
+                      (system_lib_string) @name])
+               @reference.import"
+        }
+        PhidiLanguage::Java => {
+            "(import_declaration [(identifier) (scoped_identifier)] @name)
+               @reference.import"
+        }
+        _ => "",
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Tag {
+    pub name: String,
+    /// The suffix of the capture, e.g. `function` for `@definition.function`.
+    pub kind: String,
+    pub is_definition: bool,
+    /// The range of the tag's name.
+    pub range: Range,
+    /// The index of the innermost definition of the file containing the tag.
+    pub parent: Option<usize>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileTags {
+    pub mod_time: Option<SystemTime>,
+    pub tags: Vec<Tag>,
+}
+
+impl FileTags {
+    /// Extract the tags of a file. Returns `None` if the file's language has
+    /// no tags query, or if the file can't be read.
+    pub fn read(path: &Path) -> Option<FileTags> {
+        let language = PhidiLanguage::from_path_raw(path)?;
+        get_tags_query(language)?;
+        let metadata = fs::metadata(path).ok()?;
+        if metadata.len() > MAX_FILE_SIZE {
+            return None;
+        }
+        let content = fs::read_to_string(path).ok()?;
+        let tags = extract_tags(language, &Rope::from(content))?;
+        Some(FileTags {
+            mod_time: metadata.modified().ok(),
+            tags,
+        })
+    }
+}
+
+struct RawTag {
+    name: std::ops::Range<usize>,
+    node: std::ops::Range<usize>,
+    kind: String,
+    is_definition: bool,
+}
+
+/// Run the language's tags query over the text.
+pub fn extract_tags(language: PhidiLanguage, text: &Rope) -> Option<Vec<Tag>> {
+    let (config, query) = get_tags_query(language)?;
+    extract_query_tags(config, &query, text)
+}
+
+fn extract_query_tags(
+    config: Arc<HighlightConfiguration>,
+    query: &Query,
+    text: &Rope,
+) -> Option<Vec<Tag>> {
+    let name_index = query.capture_index_for_name("name")?;
+    let layers = SyntaxLayers::new(Some(text), config);
+    let tree = layers.try_tree()?;
+
+    let mut raw_tags = Vec::new();
+    let mut cursor = QueryCursor::new();
+    for query_match in cursor.matches(query, tree.root_node(), RopeProvider(text)) {
+        let mut name = None;
+        let mut tag = None;
+        for capture in query_match.captures {
+            if capture.index == name_index {
+                name = Some(capture.node.byte_range());
+                continue;
+            }
+            let capture_name = query.capture_names()[capture.index as usize];
+            if let Some(kind) = capture_name.strip_prefix("definition.") {
+                tag = Some((true, kind, capture.node.byte_range()));
+            } else if let Some(kind) = capture_name.strip_prefix("reference.") {
+                tag = Some((false, kind, capture.node.byte_range()));
+            }
+        }
+        if let (Some(name), Some((is_definition, kind, node))) = (name, tag) {
+            raw_tags.push(RawTag {
+                name,
+                node,
+                kind: kind.to_string(),
+                is_definition,
+            });
+        }
+    }
+
+    Some(build_tags(text, raw_tags))
+}
+
+fn build_tags(text: &Rope, mut raw_tags: Vec<RawTag>) -> Vec<Tag> {
+    // Outer nodes come before the nodes they contain, so a stack of the
+    // enclosing definitions is enough to find each tag's parent.
+    raw_tags.sort_by_key(|tag| (tag.node.start, Reverse(tag.node.end)));
+
+    let rope_text = RopeTextRef::new(text);
+    let mut seen = HashSet::new();
+    let mut tags = Vec::new();
+    let mut parents: Vec<(usize, usize)> = Vec::new();
+    for raw in raw_tags {
+        // Several patterns can match the same name
+        if !seen.insert((raw.name.clone(), raw.is_definition)) {
+            continue;
+        }
+        while parents
+            .last()
+            .map(|(end, _)| *end <= raw.node.start)
+            .unwrap_or(false)
+        {
+            parents.pop();
+        }
+
+        let index = tags.len();
+        tags.push(Tag {
+            name: text.slice_to_cow(raw.name.clone()).into_owned(),
+            kind: raw.kind,
+            is_definition: raw.is_definition,
+            range: Range {
+                start: rope_text.offset_to_position(raw.name.start),
+                end: rope_text.offset_to_position(raw.name.end),
+            },
+            parent: parents.last().map(|(_, parent)| *parent),
+        });
+        if raw.is_definition {
+            parents.push((raw.node.end, index));
+        }
+    }
+    tags
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CodeGraph {
+    version: u32,
+    files: HashMap<PathBuf, FileTags>,
+}
+
+impl Default for CodeGraph {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CodeGraph {
+    pub fn new() -> Self {
+        Self {
+            version: CODE_GRAPH_VERSION,
+            files: HashMap::new(),
+        }
+    }
+
+    /// Load a persisted graph. A graph written by an older version is
+    /// discarded.
+    pub fn load(path: &Path) -> io::Result<CodeGraph> {
+        let content = fs::read(path)?;
+        let graph: CodeGraph =
+            serde_json::from_slice(&content).map_err(io::Error::other)?;
+        if graph.version != CODE_GRAPH_VERSION {
+            return Ok(CodeGraph::new());
+        }
+        Ok(graph)
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let content = serde_json::to_vec(self).map_err(io::Error::other)?;
+        fs::write(path, content)
+    }
+
+    /// Whether the file was indexed since its last modification.
+    pub fn is_up_to_date(&self, path: &Path) -> bool {
+        let Some(file) = self.files.get(path) else {
+            return false;
+        };
+        let mod_time = fs::metadata(path).and_then(|m| m.modified()).ok();
+        mod_time.is_some() && file.mod_time == mod_time
+    }
+
+    pub fn insert(&mut self, path: PathBuf, tags: FileTags) {
+        self.files.insert(path, tags);
+    }
+
+    /// Remove a file, or every file of a directory.
+    pub fn remove(&mut self, path: &Path) {
+        self.files.retain(|p, _| !p.starts_with(path));
+    }
+
+    pub fn retain(&mut self, mut f: impl FnMut(&Path) -> bool) {
+        self.files.retain(|path, _| f(path));
+    }
+
+    pub fn query(&self, query: &CodeGraphQuery) -> Vec<CodeGraphItem> {
+        let mut items = self.find(query);
+        items
+            .sort_by(|a, b| (&a.path, a.range.start).cmp(&(&b.path, b.range.start)));
+        if let CodeGraphQuery::Symbols { name } = query {
+            // Exact matches first, then the shortest names
+            items.sort_by_key(|item| (&item.name != name, item.name.len()));
+        }
+        items
+    }
+
+    fn find(&self, query: &CodeGraphQuery) -> Vec<CodeGraphItem> {
+        match query {
+            CodeGraphQuery::Symbols { name } => {
+                let lowercase = name.to_lowercase();
+                self.collect(|_, tag| {
+                    tag.is_definition && tag.name.to_lowercase().contains(&lowercase)
+                })
+            }
+            CodeGraphQuery::References { name } => {
+                self.collect(|_, tag| !tag.is_definition && &tag.name == name)
+            }
+            CodeGraphQuery::Callers { name } => {
+                let mut items = Vec::new();
+                for (path, file) in &self.files {
+                    let callers: HashSet<usize> = file
+                        .tags
+                        .iter()
+                        .filter(|tag| is_call(tag) && &tag.name == name)
+                        .filter_map(|tag| tag.parent)
+                        .collect();
+                    items.extend(
+                        callers
+                            .into_iter()
+                            .map(|index| item(path, &file.tags[index])),
+                    );
+                }
+                items
+            }
+            CodeGraphQuery::Callees { name } => self.collect(|tags, tag| {
+                is_call(tag)
+                    && ancestors(tags, tag).any(|parent| &parent.name == name)
+            }),
+            CodeGraphQuery::Imports { path } => {
+                let mut items = Vec::new();
+                for (file_path, file) in &self.files {
+                    if file_path.starts_with(path) {
+                        items.extend(
+                            file.tags
+                                .iter()
+                                .filter(|tag| is_import(tag))
+                                .map(|tag| item(file_path, tag)),
+                        );
+                    }
+                }
+                items
+            }
+            CodeGraphQuery::Importers { module } => self.collect(|_, tag| {
+                is_import(tag) && tag.name.contains(module.as_str())
+            }),
+        }
+    }
+
+    fn collect(&self, f: impl Fn(&[Tag], &Tag) -> bool) -> Vec<CodeGraphItem> {
+        let mut items = Vec::new();
+        for (path, file) in &self.files {
+            items.extend(
+                file.tags
+                    .iter()
+                    .filter(|tag| f(&file.tags, tag))
+                    .map(|tag| item(path, tag)),
+            );
+        }
+        items
+    }
+}
+
+fn is_call(tag: &Tag) -> bool {
+    !tag.is_definition && tag.kind == "call"
+}
+
+fn is_import(tag: &Tag) -> bool {
+    !tag.is_definition && tag.kind == "import"
+}
+
+fn ancestors<'a>(tags: &'a [Tag], tag: &Tag) -> impl Iterator<Item = &'a Tag> {
+    std::iter::successors(tag.parent.and_then(|i| tags.get(i)), |tag| {
+        tag.parent.and_then(|i| tags.get(i))
+    })
+}
+
+fn item(path: &Path, tag: &Tag) -> CodeGraphItem {
+    CodeGraphItem {
+        name: tag.name.clone(),
+        kind: tag.kind.clone(),
+        path: path.to_path_buf(),
+        range: tag.range,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use lsp_types::{Position, Range};
+    use phidi_rpc::code_graph::CodeGraphQuery;
+    use phidi_xi_rope::Rope;
+    use tree_sitter::Query;
+
+    use super::{CodeGraph, FileTags, Tag, extract_query_tags, import_query};
+    use crate::{language::PhidiLanguage, syntax::highlight::test_config};
+
+    fn tag(
+        name: &str,
+        kind: &str,
+        is_definition: bool,
+        line: u32,
+        parent: Option<usize>,
+    ) -> Tag {
+        Tag {
+            name: name.to_string(),
+            kind: kind.to_string(),
+            is_definition,
+            range: Range {
+                start: Position::new(line, 0),
+                end: Position::new(line, name.len() as u32),
+            },
+            parent,
+        }
+    }
+
+    fn graph() -> CodeGraph {
+        let mut graph = CodeGraph::new();
+        graph.insert(
+            PathBuf::from("/ws/src/main.rs"),
+            FileTags {
+                mod_time: None,
+                tags: vec![
+                    tag("config", "import", false, 0, None),
+                    tag("main", "function", true, 2, None),
+                    tag("load_config", "call", false, 3, Some(1)),
+                    tag("run", "call", false, 4, Some(1)),
+                ],
+            },
+        );
+        graph.insert(
+            PathBuf::from("/ws/src/config.rs"),
+            FileTags {
+                mod_time: None,
+                tags: vec![
+                    tag("Config", "class", true, 0, None),
+                    tag("load_config", "function", true, 2, None),
+                    tag("parse", "call", false, 3, Some(1)),
+                ],
+            },
+        );
+        graph
+    }
+
+    fn names(items: Vec<phidi_rpc::code_graph::CodeGraphItem>) -> Vec<String> {
+        items.into_iter().map(|item| item.name).collect()
+    }
+
+    #[test]
+    fn test_symbols() {
+        let graph = graph();
+        let items = graph.query(&CodeGraphQuery::Symbols {
+            name: "config".to_string(),
+        });
+        assert_eq!(names(items), vec!["Config", "load_config"]);
+    }
+
+    #[test]
+    fn test_calls() {
+        let graph = graph();
+        let callers = graph.query(&CodeGraphQuery::Callers {
+            name: "load_config".to_string(),
+        });
+        assert_eq!(callers.len(), 1);
+        assert_eq!(callers[0].name, "main");
+        assert_eq!(callers[0].path, Path::new("/ws/src/main.rs"));
+
+        let callees = graph.query(&CodeGraphQuery::Callees {
+            name: "main".to_string(),
+        });
+        assert_eq!(names(callees), vec!["load_config", "run"]);
+    }
+
+    #[test]
+    fn test_imports() {
+        let mut graph = graph();
+        let imports = graph.query(&CodeGraphQuery::Imports {
+            path: PathBuf::from("/ws/src"),
+        });
+        assert_eq!(names(imports), vec!["config"]);
+
+        graph.remove(Path::new("/ws/src/main.rs"));
+        let importers = graph.query(&CodeGraphQuery::Importers {
+            module: "config".to_string(),
+        });
+        assert!(importers.is_empty());
+    }
+
+    #[test]
+    fn test_extract_imports() {
+        let config = test_config(tree_sitter_rust::language());
+        let source = format!(
+            "(function_item name: (identifier) @name) @definition.function
+             (call_expression function: (identifier) @name) @reference.call
+             {}",
+            import_query(PhidiLanguage::Rust)
+        );
+        let query = Query::new(&config.language, &source).unwrap();
+        let text = Rope::from(
+            "use std::collections::HashMap;\n\
+             use crate::config::{load_config, Config};\n\
+             \n\
+             fn main() {\n    load_config();\n}\n",
+        );
+
+        let tags = extract_query_tags(config, &query, &text).unwrap();
+        let imports: Vec<_> =
+            tags.iter().filter(|tag| tag.kind == "import").collect();
+        assert_eq!(imports.len(), 2);
+        assert_eq!(imports[0].name, "std::collections::HashMap");
+        assert_eq!(imports[0].range.start, Position::new(0, 4));
+        assert_eq!(imports[1].name, "crate::config::{load_config, Config}");
+        assert!(
+            imports
+                .iter()
+                .all(|tag| !tag.is_definition && tag.parent.is_none())
+        );
+
+        let mut graph = CodeGraph::new();
+        graph.insert(
+            PathBuf::from("/ws/src/main.rs"),
+            FileTags {
+                mod_time: None,
+                tags,
+            },
+        );
+        let importers = graph.query(&CodeGraphQuery::Importers {
+            module: "crate::config".to_string(),
+        });
+        assert_eq!(
+            names(importers),
+            vec!["crate::config::{load_config, Config}"]
+        );
+    }
+}
@@ -146,6 +146,57 @@ pub fn local_socket() -> Option<PathBuf> {
         Self::data_local_directory().map(|dir| dir.join("local.sock"))
     }
 
+    /// Default socket used by the proxy when running headless
+    pub fn headless_socket() -> Option<PathBuf> {
+        Self::data_local_directory().map(|dir| dir.join("headless.sock"))
+    }
+
+    /// Where the proxy of each editor session serves the MCP server of its
+    /// workspace
+    pub fn mcp_sockets_directory() -> Option<PathBuf> {
+        if let Some(dir) = Self::data_local_directory() {
+            let dir = dir.join("mcp");
+            if !dir.exists() {
+                if let Err(err) = std::fs::create_dir(&dir) {
+                    tracing::error!("{:?}", err);
+                }
+            }
+            Some(dir)
+        } else {
+            None
+        }
+    }
+
+    /// Where the checkpoints of each workspace are stored
+    pub fn checkpoints_directory() -> Option<PathBuf> {
+        if let Some(dir) = Self::data_local_directory() {
+            let dir = dir.join("checkpoints");
+            if !dir.exists() {
+                if let Err(err) = std::fs::create_dir(&dir) {
+                    tracing::error!("{:?}", err);
+                }
+            }
+            Some(dir)
+        } else {
+            None
+        }
+    }
+
+    /// Where the code graph of each workspace is persisted
+    pub fn code_graph_directory() -> Option<PathBuf> {
+        if let Some(dir) = Self::data_local_directory() {
+            let dir = dir.join("code_graph");
+            if !dir.exists() {
+                if let Err(err) = std::fs::create_dir(&dir) {
+                    tracing::error!("{:?}", err);
+                }
+            }
+            Some(dir)
+        } else {
+            None
+        }
+    }
+
     pub fn updates_directory() -> Option<PathBuf> {
         if let Some(dir) = Self::data_local_directory() {
             let dir = dir.join("updates");
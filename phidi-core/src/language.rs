@@ -1694,6 +1694,7 @@ pub enum PhidiLanguage {
 impl PhidiLanguage {
     const HIGHLIGHTS_INJECTIONS_FILE_NAME: &'static str = "injections.scm";
     const HIGHLIGHTS_QUERIES_FILE_NAME: &'static str = "highlights.scm";
+    const TAGS_QUERIES_FILE_NAME: &'static str = "tags.scm";
 
     pub fn from_path(path: &Path) -> PhidiLanguage {
         Self::from_path_raw(path).unwrap_or(PhidiLanguage::PlainText)
@@ -1844,6 +1845,24 @@ fn get_grammar_query(&self) -> (String, String) {
         ("".to_string(), "".to_string())
     }
 
+    /// The tags query of the language, if the queries directory has one.
+    pub(crate) fn get_tags_query(&self) -> Option<String> {
+        let query_name = self.query_name();
+        let queries_dir = Directory::queries_directory()?;
+        if !queries_dir
+            .join(&query_name)
+            .join(Self::TAGS_QUERIES_FILE_NAME)
+            .exists()
+        {
+            return None;
+        }
+        Some(read_grammar_query(
+            &queries_dir,
+            &query_name,
+            Self::TAGS_QUERIES_FILE_NAME,
+        ))
+    }
+
     pub(crate) fn new_highlight_config(
         &self,
     ) -> Result<HighlightConfiguration, HighlightIssue> {
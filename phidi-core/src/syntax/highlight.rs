@@ -50,6 +50,13 @@ pub(crate) fn get_highlight_config(
     })
 }
 
+/// A configuration without highlight queries for a grammar the tests link
+/// against, which are built for the same version of tree-sitter.
+#[cfg(test)]
+pub(crate) fn test_config(language: Language) -> Arc<HighlightConfiguration> {
+    Arc::new(HighlightConfiguration::new(language, "", "", "").unwrap())
+}
+
 /// Indicates which highlight should be applied to a region of source code.
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub struct Highlight(pub usize);
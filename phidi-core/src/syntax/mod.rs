@@ -1123,19 +1123,36 @@ fn find_tag_in_siblings(
         previous: bool,
         tag: &str,
     ) -> Option<usize> {
-        let mut node = node;
-        while let Some(sibling) = if previous {
-            node.prev_sibling()
-        } else {
-            node.next_sibling()
-        } {
-            if sibling.kind() == tag {
-                let offset = sibling.start_byte();
-                return Some(offset);
+        find_sibling(node, previous, tag).map(|sibling| sibling.start_byte())
+    }
+
+    /// The names of the start and end tags of the element whose tag name is
+    /// at `offset`, which are edited together. This is what's used when no
+    /// language server gives linked editing ranges.
+    pub fn linked_tag_names(
+        &self,
+        offset: usize,
+    ) -> Option<Vec<std::ops::Range<usize>>> {
+        let tree = self.layers.as_ref()?.try_tree()?;
+        let mut node = tree.root_node().descendant_for_byte_range(offset, offset)?;
+        let (tag, other) = loop {
+            if let Some((start_kind, end_kind, name_kind)) = TAG_KINDS
+                .iter()
+                .find(|(start, end, _)| node.kind() == *start || node.kind() == *end)
+            {
+                let other = if node.kind() == *start_kind {
+                    find_sibling(node, false, end_kind)?
+                } else {
+                    find_sibling(node, true, start_kind)?
+                };
+                break (tag_name(node, name_kind)?, tag_name(other, name_kind)?);
             }
-            node = sibling;
+            node = node.parent()?;
+        };
+        if offset < tag.start_byte() || offset > tag.end_byte() {
+            return None;
         }
-        None
+        Some(vec![tag.byte_range(), other.byte_range()])
     }
 
     fn find_tag_in_children(&self, node: Node, tag: &str) -> Option<usize> {
@@ -1241,6 +1258,39 @@ pub fn find_enclosing_pair(&self, offset: usize) -> Option<(usize, usize)> {
     }
 }
 
+/// The kinds of the start and end tag nodes, and of their name, in the
+/// grammars of markup languages: html (and the languages built on it), xml and
+/// jsx.
+const TAG_KINDS: &[(&str, &str, &str)] = &[
+    ("start_tag", "end_tag", "tag_name"),
+    ("STag", "ETag", "Name"),
+    ("jsx_opening_element", "jsx_closing_element", "identifier"),
+];
+
+fn find_sibling<'a>(node: Node<'a>, previous: bool, kind: &str) -> Option<Node<'a>> {
+    let mut node = node;
+    while let Some(sibling) = if previous {
+        node.prev_sibling()
+    } else {
+        node.next_sibling()
+    } {
+        if sibling.kind() == kind {
+            return Some(sibling);
+        }
+        node = sibling;
+    }
+    None
+}
+
+fn tag_name<'a>(tag: Node<'a>, name_kind: &str) -> Option<Node<'a>> {
+    if let Some(name) = tag.child_by_field_name("name") {
+        return Some(name);
+    }
+    (0..tag.named_child_count())
+        .filter_map(|i| tag.named_child(i))
+        .find(|child| child.kind() == name_kind)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1277,4 +1327,29 @@ fn test_lens_iter() {
         assert_eq!(Some((90, 25)), iter.next());
         assert_eq!(None, iter.next());
     }
+
+    fn html_syntax(text: &str) -> Syntax {
+        let config = highlight::test_config(tree_sitter_html::language());
+        let text = Rope::from(text);
+        Syntax {
+            language: PhidiLanguage::Html,
+            layers: Some(SyntaxLayers::new(Some(&text), config)),
+            text,
+            ..Syntax::plaintext()
+        }
+    }
+
+    #[test]
+    fn test_linked_tag_names() {
+        let syntax = html_syntax("<div><p>hi</p></div>");
+        assert_eq!(syntax.linked_tag_names(2), Some(vec![1..4, 16..19]));
+        assert_eq!(syntax.linked_tag_names(12), Some(vec![12..13, 6..7]));
+        // Not in a tag name.
+        assert_eq!(syntax.linked_tag_names(9), None);
+
+        // The paragraph is closed by the end of the div, without a tag.
+        let syntax = html_syntax("<div><p>hi</div>");
+        assert_eq!(syntax.linked_tag_names(6), None);
+        assert_eq!(syntax.linked_tag_names(2), Some(vec![1..4, 12..15]));
+    }
 }
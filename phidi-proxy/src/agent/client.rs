@@ -0,0 +1,142 @@
+//! A minimal client of the OpenAI chat completions api, which most model
+//! servers (llama.cpp, Ollama, vLLM, ...) implement as well.
+
+use std::time::Duration;
+
+use anyhow::{Result, anyhow};
+use phidi_rpc::agent::AgentSettings;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(300);
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ChatMessage {
+    pub role: String,
+    #[serde(default)]
+    pub content: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tool_calls: Vec<ToolCall>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_call_id: Option<String>,
+}
+
+impl ChatMessage {
+    pub fn system(content: String) -> Self {
+        Self {
+            role: "system".to_string(),
+            content: Some(content),
+            ..Default::default()
+        }
+    }
+
+    pub fn user(content: String) -> Self {
+        Self {
+            role: "user".to_string(),
+            content: Some(content),
+            ..Default::default()
+        }
+    }
+
+    pub fn tool(tool_call_id: String, content: String) -> Self {
+        Self {
+            role: "tool".to_string(),
+            content: Some(content),
+            tool_call_id: Some(tool_call_id),
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ToolCall {
+    pub id: String,
+    #[serde(rename = "type", default = "function_type")]
+    pub kind: String,
+    pub function: FunctionCall,
+}
+
+fn function_type() -> String {
+    "function".to_string()
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct FunctionCall {
+    pub name: String,
+    /// The arguments as a JSON encoded object.
+    pub arguments: String,
+}
+
+#[derive(Deserialize)]
+struct ChatResponse {
+    choices: Vec<Choice>,
+}
+
+#[derive(Deserialize)]
+struct Choice {
+    message: ChatMessage,
+}
+
+pub struct ChatClient {
+    client: reqwest::blocking::Client,
+    url: String,
+    api_key: Option<String>,
+    model: String,
+}
+
+impl ChatClient {
+    pub fn new(settings: &AgentSettings) -> Result<Self> {
+        if settings.endpoint.is_empty() {
+            return Err(anyhow!("no agent endpoint is configured"));
+        }
+        let api_key = if settings.api_key.is_empty() {
+            std::env::var("OPENAI_API_KEY").ok()
+        } else {
+            Some(settings.api_key.clone())
+        };
+        let client = reqwest::blocking::Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .build()?;
+        Ok(Self {
+            client,
+            url: format!(
+                "{}/chat/completions",
+                settings.endpoint.trim_end_matches('/')
+            ),
+            api_key,
+            model: settings.model.clone(),
+        })
+    }
+
+    /// Ask the model for the next message of the conversation.
+    pub fn complete(
+        &self,
+        messages: &[ChatMessage],
+        tools: &[Value],
+    ) -> Result<ChatMessage> {
+        let mut body = json!({
+            "model": self.model,
+            "messages": messages,
+        });
+        if !tools.is_empty() {
+            body["tools"] = json!(tools);
+        }
+
+        let mut request = self.client.post(&self.url).json(&body);
+        if let Some(api_key) = self.api_key.as_ref() {
+            request = request.bearer_auth(api_key);
+        }
+        let resp = request.send()?;
+        let status = resp.status();
+        if !status.is_success() {
+            let text = resp.text().unwrap_or_default();
+            return Err(anyhow!("model request failed with {status}: {text}"));
+        }
+        let resp: ChatResponse = resp.json()?;
+        resp.choices
+            .into_iter()
+            .next()
+            .map(|choice| choice.message)
+            .ok_or_else(|| anyhow!("the model returned no message"))
+    }
+}
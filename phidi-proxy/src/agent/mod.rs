@@ -0,0 +1,565 @@
+//! The backend of the agent panel. Conversations run in the proxy, so that the
+//! agent works on remote workspaces as well, and the agent is given the same
+//! workspace tools as the MCP server. Each conversation has its own thread
+//! running the tool call loop: the model is asked for the next message until
+//! it stops calling tools.
+
+mod client;
+mod tools;
+
+use std::{
+    cell::RefCell,
+    collections::{HashMap, VecDeque},
+    path::PathBuf,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    thread,
+    time::Duration,
+};
+
+use anyhow::{Result, anyhow};
+use crossbeam_channel::{Receiver, RecvTimeoutError, Sender};
+use parking_lot::Mutex;
+use phidi_rpc::{
+    agent::{AgentEvent, AgentSessionId, AgentSettings},
+    core::{CoreNotification, CoreRpcHandler},
+    proxy::ProxyRpcHandler,
+};
+
+use self::client::{ChatClient, ChatMessage};
+use crate::{checkpoint::CheckpointStore, mcp::McpServer};
+
+const DEFAULT_MAX_STEPS: usize = 25;
+
+enum SessionInput {
+    Message {
+        settings: AgentSettings,
+        message: String,
+    },
+    Approval {
+        id: String,
+        approved: bool,
+    },
+}
+
+struct Session {
+    sender: Sender<SessionInput>,
+    cancelled: Arc<AtomicBool>,
+}
+
+#[derive(Clone)]
+pub struct Agent {
+    core_rpc: CoreRpcHandler,
+    proxy_rpc: ProxyRpcHandler,
+    /// The tools of the workspace, there are none until a workspace is open.
+    server: Arc<Mutex<Option<McpServer>>>,
+    checkpoints: Arc<Mutex<Option<CheckpointStore>>>,
+    sessions: Arc<Mutex<HashMap<AgentSessionId, Session>>>,
+}
+
+impl Agent {
+    pub fn new(core_rpc: CoreRpcHandler, proxy_rpc: ProxyRpcHandler) -> Self {
+        Self {
+            core_rpc,
+            proxy_rpc,
+            server: Arc::new(Mutex::new(None)),
+            checkpoints: Arc::new(Mutex::new(None)),
+            sessions: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    pub fn set_workspace(
+        &self,
+        workspace: Option<PathBuf>,
+        checkpoints: Option<CheckpointStore>,
+    ) {
+        *self.server.lock() = workspace
+            .map(|workspace| McpServer::new(workspace, self.proxy_rpc.clone()));
+        *self.checkpoints.lock() = checkpoints;
+    }
+
+    /// The tools of the workspace, which are also served to external agents.
+    pub fn server(&self) -> Option<McpServer> {
+        self.server.lock().clone()
+    }
+
+    /// The diagnostics the tools report on only reach the agent through the
+    /// notifications sent to the editor.
+    pub fn handle_core_notification(&self, notification: &CoreNotification) {
+        if let Some(server) = self.server.lock().as_ref() {
+            server.handle_core_notification(notification);
+        }
+    }
+
+    pub fn send(
+        &self,
+        session: AgentSessionId,
+        settings: AgentSettings,
+        message: String,
+    ) {
+        let mut sessions = self.sessions.lock();
+        let session = sessions
+            .entry(session)
+            .or_insert_with(|| self.start_session(session));
+        if let Err(err) = session
+            .sender
+            .send(SessionInput::Message { settings, message })
+        {
+            tracing::error!("{:?}", err);
+        }
+    }
+
+    pub fn approve(&self, session: AgentSessionId, id: String, approved: bool) {
+        if let Some(session) = self.sessions.lock().get(&session) {
+            if let Err(err) =
+                session.sender.send(SessionInput::Approval { id, approved })
+            {
+                tracing::error!("{:?}", err);
+            }
+        }
+    }
+
+    pub fn cancel(&self, session: AgentSessionId) {
+        if let Some(session) = self.sessions.lock().get(&session) {
+            session.cancelled.store(true, Ordering::Relaxed);
+        }
+    }
+
+    pub fn close(&self, session: AgentSessionId) {
+        // Dropping the sender ends the thread once the current turn is over.
+        if let Some(session) = self.sessions.lock().remove(&session) {
+            session.cancelled.store(true, Ordering::Relaxed);
+        }
+    }
+
+    fn start_session(&self, id: AgentSessionId) -> Session {
+        let (sender, receiver) = crossbeam_channel::unbounded();
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let conversation = Conversation {
+            session: id,
+            core_rpc: self.core_rpc.clone(),
+            server: self.server.lock().clone(),
+            checkpoints: self.checkpoints.lock().clone(),
+            cancelled: cancelled.clone(),
+            receiver,
+            queued: RefCell::new(VecDeque::new()),
+            messages: Vec::new(),
+        };
+        thread::spawn(move || conversation.run());
+        Session { sender, cancelled }
+    }
+}
+
+struct Conversation {
+    session: AgentSessionId,
+    core_rpc: CoreRpcHandler,
+    server: Option<McpServer>,
+    checkpoints: Option<CheckpointStore>,
+    cancelled: Arc<AtomicBool>,
+    receiver: Receiver<SessionInput>,
+    /// Messages sent while a tool call waited for approval, which are
+    /// answered once the current turn is over.
+    queued: RefCell<VecDeque<SessionInput>>,
+    messages: Vec<ChatMessage>,
+}
+
+impl Conversation {
+    fn run(mut self) {
+        loop {
+            let queued = self.queued.borrow_mut().pop_front();
+            let input = match queued {
+                Some(input) => input,
+                None => match self.receiver.recv() {
+                    Ok(input) => input,
+                    Err(_) => return,
+                },
+            };
+            // Approvals are only expected while a tool call waits for them.
+            let SessionInput::Message { settings, message } = input else {
+                continue;
+            };
+            self.cancelled.store(false, Ordering::Relaxed);
+            if let Err(err) = self.turn(&settings, message) {
+                self.event(AgentEvent::Error {
+                    message: format!("{err:#}"),
+                });
+            }
+            self.event(AgentEvent::Done);
+        }
+    }
+
+    fn turn(&mut self, settings: &AgentSettings, message: String) -> Result<()> {
+        let client = ChatClient::new(settings)?;
+        let tools = if self.server.is_some() {
+            tools::list()
+        } else {
+            Vec::new()
+        };
+        if self.messages.is_empty() {
+            self.messages
+                .push(ChatMessage::system(self.system_prompt()));
+        }
+        self.checkpoint(&message);
+        self.messages.push(ChatMessage::user(message));
+
+        let max_steps = if settings.max_steps == 0 {
+            DEFAULT_MAX_STEPS
+        } else {
+            settings.max_steps
+        };
+        for _ in 0..max_steps {
+            if self.is_cancelled() {
+                return Ok(());
+            }
+            let reply = client.complete(&self.messages, &tools)?;
+            if self.is_cancelled() {
+                return Ok(());
+            }
+            self.messages.push(reply.clone());
+
+            if let Some(content) = reply.content.filter(|c| !c.trim().is_empty()) {
+                self.event(AgentEvent::Message { content });
+            }
+            if reply.tool_calls.is_empty() {
+                return Ok(());
+            }
+            // Every tool call needs a result, even when the turn is cancelled
+            // halfway through, or the conversation can't be continued.
+            for call in reply.tool_calls {
+                self.event(AgentEvent::ToolCall {
+                    id: call.id.clone(),
+                    name: call.function.name.clone(),
+                    arguments: call.function.arguments.clone(),
+                });
+                let result = if self.is_cancelled() {
+                    Err(anyhow!("cancelled by the user"))
+                } else if let Some(server) = self.server.as_ref() {
+                    tools::call(self, server, &call)
+                } else {
+                    Err(anyhow!("no workspace is open"))
+                };
+                let (content, is_error) = match result {
+                    Ok(content) => (content, false),
+                    Err(err) => (format!("{err:#}"), true),
+                };
+                self.event(AgentEvent::ToolResult {
+                    id: call.id.clone(),
+                    content: content.clone(),
+                    is_error,
+                });
+                self.messages.push(ChatMessage::tool(call.id, content));
+            }
+        }
+        Err(anyhow!("stopped after {max_steps} steps"))
+    }
+
+    /// Snapshot the workspace before the agent gets to work, so that what it
+    /// did can be rolled back.
+    fn checkpoint(&self, message: &str) {
+        let Some(checkpoints) = self.checkpoints.as_ref() else {
+            return;
+        };
+        let summary: String = message
+            .lines()
+            .next()
+            .unwrap_or_default()
+            .chars()
+            .take(60)
+            .collect();
+        match checkpoints.create_if_changed(&format!("Before agent: {summary}")) {
+            Ok(Some(_)) => match checkpoints.list() {
+                Ok(list) => self.core_rpc.checkpoints_changed(list),
+                Err(err) => tracing::error!("{:?}", err),
+            },
+            Ok(None) => {}
+            Err(err) => tracing::error!("{:?}", err),
+        }
+    }
+
+    fn system_prompt(&self) -> String {
+        let mut prompt = "You are a coding agent working inside the Phidi editor. \
+             Use the tools to explore the code before answering. Changes made \
+             with `edit_file` are proposed to the user, who reviews them before \
+             they are applied."
+            .to_string();
+        if let Some(server) = self.server.as_ref() {
+            prompt.push_str(&format!(
+                " The workspace is at {}, relative paths are resolved from it.",
+                server.workspace.display()
+            ));
+        }
+        prompt
+    }
+
+    /// Ask the user to approve a tool call, and wait for the answer.
+    fn approve(&self, id: &str, description: String) -> bool {
+        self.event(AgentEvent::ApprovalRequest {
+            id: id.to_string(),
+            description,
+        });
+        loop {
+            match self.receiver.recv_timeout(Duration::from_millis(200)) {
+                Ok(SessionInput::Approval {
+                    id: answered,
+                    approved,
+                }) if answered == id => return approved,
+                Ok(input @ SessionInput::Message { .. }) => {
+                    self.queued.borrow_mut().push_back(input);
+                }
+                Ok(SessionInput::Approval { .. }) => {}
+                Err(RecvTimeoutError::Timeout) => {
+                    if self.is_cancelled() {
+                        return false;
+                    }
+                }
+                Err(RecvTimeoutError::Disconnected) => return false,
+            }
+        }
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    fn event(&self, event: AgentEvent) {
+        self.core_rpc.agent_event(self.session, event);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{BufRead, BufReader, Read, Write},
+        net::TcpListener,
+    };
+
+    use phidi_rpc::core::CoreRpc;
+    use serde_json::{Value, json};
+
+    use super::*;
+
+    /// Serves one canned chat completion per request, and sends back the
+    /// request bodies.
+    fn mock_model(responses: Vec<Value>) -> (String, Receiver<Value>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let endpoint = format!("http://{}/v1", listener.local_addr().unwrap());
+        let (tx, rx) = crossbeam_channel::unbounded();
+        thread::spawn(move || {
+            for (stream, response) in listener.incoming().zip(responses) {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut content_length = 0;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    let line = line.trim_end();
+                    if line.is_empty() {
+                        break;
+                    }
+                    if let Some((name, value)) = line.split_once(':') {
+                        if name.eq_ignore_ascii_case("content-length") {
+                            content_length = value.trim().parse().unwrap();
+                        }
+                    }
+                }
+                let mut body = vec![0; content_length];
+                reader.read_exact(&mut body).unwrap();
+                tx.send(serde_json::from_slice(&body).unwrap()).unwrap();
+
+                let response = json!({
+                    "choices": [{ "index": 0, "message": response }],
+                })
+                .to_string();
+                write!(
+                    stream,
+                    "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\n\
+                     Content-Length: {}\r\nConnection: close\r\n\r\n{response}",
+                    response.len()
+                )
+                .unwrap();
+            }
+        });
+        (endpoint, rx)
+    }
+
+    fn tool_call(name: &str, arguments: Value) -> Value {
+        json!({
+            "role": "assistant",
+            "content": null,
+            "tool_calls": [{
+                "id": "call_1",
+                "type": "function",
+                "function": { "name": name, "arguments": arguments.to_string() },
+            }],
+        })
+    }
+
+    fn agent(core_rpc: &CoreRpcHandler) -> Agent {
+        let agent = Agent::new(core_rpc.clone(), ProxyRpcHandler::new());
+        agent.set_workspace(Some(PathBuf::from("/workspace")), None);
+        agent
+    }
+
+    fn next_event(core_rpc: &CoreRpcHandler) -> AgentEvent {
+        loop {
+            let msg = core_rpc.rx().recv_timeout(Duration::from_secs(10)).unwrap();
+            if let CoreRpc::Notification(notification) = msg {
+                if let CoreNotification::AgentEvent { event, .. } = *notification {
+                    return event;
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_tool_call_loop() {
+        let (endpoint, requests) = mock_model(vec![
+            tool_call("diagnostics", json!({})),
+            json!({ "role": "assistant", "content": "All good" }),
+        ]);
+        let core_rpc = CoreRpcHandler::new();
+        let agent = agent(&core_rpc);
+        let session = AgentSessionId::next();
+        agent.send(
+            session,
+            AgentSettings {
+                endpoint,
+                model: "mock".to_string(),
+                api_key: "key".to_string(),
+                max_steps: 5,
+            },
+            "Any errors?".to_string(),
+        );
+
+        assert!(matches!(
+            next_event(&core_rpc),
+            AgentEvent::ToolCall { name, .. } if name == "diagnostics"
+        ));
+        assert_eq!(
+            next_event(&core_rpc),
+            AgentEvent::ToolResult {
+                id: "call_1".to_string(),
+                content: "no diagnostics".to_string(),
+                is_error: false,
+            }
+        );
+        assert_eq!(
+            next_event(&core_rpc),
+            AgentEvent::Message {
+                content: "All good".to_string()
+            }
+        );
+        assert_eq!(next_event(&core_rpc), AgentEvent::Done);
+
+        let first = requests.recv().unwrap();
+        assert_eq!(first["model"], "mock");
+        assert_eq!(first["messages"][0]["role"], "system");
+        assert_eq!(first["messages"][1]["content"], "Any errors?");
+        let names: Vec<&str> = first["tools"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|tool| tool["function"]["name"].as_str().unwrap())
+            .collect();
+        assert!(names.contains(&"edit_file"));
+        assert!(!names.contains(&"apply_workspace_edit"));
+
+        let second = requests.recv().unwrap();
+        let tool_result = &second["messages"][3];
+        assert_eq!(tool_result["role"], "tool");
+        assert_eq!(tool_result["tool_call_id"], "call_1");
+        assert_eq!(tool_result["content"], "no diagnostics");
+    }
+
+    #[test]
+    fn test_declined_command() {
+        let (endpoint, requests) = mock_model(vec![
+            tool_call("run_command", json!({ "command": "rm -rf target" })),
+            json!({ "role": "assistant", "content": "Skipped" }),
+        ]);
+        let core_rpc = CoreRpcHandler::new();
+        let agent = agent(&core_rpc);
+        let session = AgentSessionId::next();
+        agent.send(
+            session,
+            AgentSettings {
+                endpoint,
+                model: "mock".to_string(),
+                ..Default::default()
+            },
+            "Clean up".to_string(),
+        );
+
+        assert!(matches!(next_event(&core_rpc), AgentEvent::ToolCall { .. }));
+        let AgentEvent::ApprovalRequest { id, description } = next_event(&core_rpc)
+        else {
+            panic!("expected an approval request");
+        };
+        assert_eq!(description, "Run `rm -rf target`");
+        agent.approve(session, id, false);
+
+        assert!(matches!(
+            next_event(&core_rpc),
+            AgentEvent::ToolResult { is_error: true, .. }
+        ));
+        assert_eq!(
+            next_event(&core_rpc),
+            AgentEvent::Message {
+                content: "Skipped".to_string()
+            }
+        );
+        assert_eq!(next_event(&core_rpc), AgentEvent::Done);
+        assert_eq!(requests.iter().count(), 2);
+    }
+
+    #[test]
+    fn test_message_sent_while_waiting_for_approval() {
+        let (endpoint, requests) = mock_model(vec![
+            tool_call("run_command", json!({ "command": "cargo test" })),
+            json!({ "role": "assistant", "content": "Skipped" }),
+            json!({ "role": "assistant", "content": "Sure" }),
+        ]);
+        let core_rpc = CoreRpcHandler::new();
+        let agent = agent(&core_rpc);
+        let session = AgentSessionId::next();
+        let settings = AgentSettings {
+            endpoint,
+            model: "mock".to_string(),
+            ..Default::default()
+        };
+        agent.send(session, settings.clone(), "Run the tests".to_string());
+
+        assert!(matches!(next_event(&core_rpc), AgentEvent::ToolCall { .. }));
+        let AgentEvent::ApprovalRequest { id, .. } = next_event(&core_rpc) else {
+            panic!("expected an approval request");
+        };
+        agent.send(session, settings, "Then explain them".to_string());
+        agent.approve(session, id, false);
+
+        assert!(matches!(
+            next_event(&core_rpc),
+            AgentEvent::ToolResult { is_error: true, .. }
+        ));
+        assert_eq!(
+            next_event(&core_rpc),
+            AgentEvent::Message {
+                content: "Skipped".to_string()
+            }
+        );
+        assert_eq!(next_event(&core_rpc), AgentEvent::Done);
+
+        // The message is answered in a turn of its own once the first is over.
+        assert_eq!(
+            next_event(&core_rpc),
+            AgentEvent::Message {
+                content: "Sure".to_string()
+            }
+        );
+        assert_eq!(next_event(&core_rpc), AgentEvent::Done);
+        let third = requests.iter().nth(2).unwrap();
+        let messages = third["messages"].as_array().unwrap();
+        assert_eq!(messages.last().unwrap()["content"], "Then explain them");
+    }
+}
@@ -0,0 +1,221 @@
+//! The tools offered to the agent: the tools of the MCP server, except that
+//! the workspace is never modified directly. `edit_file` stages its change as
+//! a proposal to be reviewed in the editor, and `run_command` only runs once
+//! the user approved it.
+
+use std::collections::HashMap;
+
+use anyhow::{Result, anyhow};
+use lsp_types::{Range, TextEdit, Url, WorkspaceEdit};
+use phidi_core::{buffer::rope_text::RopeTextRef, rope_text_pos::RopeTextPosition};
+use phidi_rpc::{
+    core::CoreNotification,
+    file::{LineCol, PathObject},
+    proxy::{ProxyRequest, ProxyResponse},
+};
+use phidi_xi_rope::Rope;
+use serde::{Deserialize, de::DeserializeOwned};
+use serde_json::{Value, json};
+
+use super::{Conversation, client::ToolCall};
+use crate::mcp::{McpServer, tools as mcp_tools};
+
+/// MCP tools that aren't offered to the agent.
+const EXCLUDED_TOOLS: &[&str] = &["apply_workspace_edit"];
+
+/// The tools in the format of the chat completions api.
+pub(super) fn list() -> Vec<Value> {
+    let mut tools: Vec<Value> = mcp_tools::list()
+        .into_iter()
+        .filter(|tool| {
+            !EXCLUDED_TOOLS.contains(&tool["name"].as_str().unwrap_or_default())
+        })
+        .map(|tool| {
+            function(
+                &tool["name"],
+                &tool["description"],
+                tool["inputSchema"].clone(),
+            )
+        })
+        .collect();
+    tools.push(function(
+        &json!("open_file"),
+        &json!("Open a file in the user's editor, at a line if one is given."),
+        json!({
+            "type": "object",
+            "properties": {
+                "path": { "type": "string" },
+                "line": { "type": "integer", "minimum": 1 },
+            },
+            "required": ["path"],
+        }),
+    ));
+    tools.push(function(
+        &json!("edit_file"),
+        &json!(
+            "Replace `old_text`, which has to occur exactly once in the file, \
+             with `new_text`. The change is proposed to the user, who reviews \
+             it before it is applied."
+        ),
+        json!({
+            "type": "object",
+            "properties": {
+                "path": { "type": "string" },
+                "old_text": { "type": "string" },
+                "new_text": { "type": "string" },
+            },
+            "required": ["path", "old_text", "new_text"],
+        }),
+    ));
+    tools
+}
+
+fn function(name: &Value, description: &Value, parameters: Value) -> Value {
+    json!({
+        "type": "function",
+        "function": {
+            "name": name,
+            "description": description,
+            "parameters": parameters,
+        },
+    })
+}
+
+pub(super) fn call(
+    conversation: &Conversation,
+    server: &McpServer,
+    call: &ToolCall,
+) -> Result<String> {
+    let arguments: Value = if call.function.arguments.trim().is_empty() {
+        json!({})
+    } else {
+        serde_json::from_str(&call.function.arguments)
+            .map_err(|e| anyhow!("invalid arguments: {e}"))?
+    };
+    match call.function.name.as_str() {
+        "open_file" => open_file(conversation, server, parse(arguments)?),
+        "edit_file" => edit_file(server, parse(arguments)?),
+        "run_command" => {
+            let command = arguments["command"].as_str().unwrap_or_default();
+            if !conversation.approve(&call.id, format!("Run `{command}`")) {
+                return Err(anyhow!("the user declined to run the command"));
+            }
+            run_mcp_tool(server, &call.function.name, arguments)
+        }
+        name if EXCLUDED_TOOLS.contains(&name) => {
+            Err(anyhow!("unknown tool: {name}"))
+        }
+        name => run_mcp_tool(server, name, arguments),
+    }
+}
+
+fn run_mcp_tool(server: &McpServer, name: &str, arguments: Value) -> Result<String> {
+    mcp_tools::call(server, name, arguments).map_err(|e| anyhow!(e.message))?
+}
+
+fn parse<T: DeserializeOwned>(arguments: Value) -> Result<T> {
+    serde_json::from_value(arguments).map_err(|e| anyhow!("invalid arguments: {e}"))
+}
+
+#[derive(Deserialize)]
+struct OpenFileArgs {
+    path: String,
+    line: Option<usize>,
+}
+
+fn open_file(
+    conversation: &Conversation,
+    server: &McpServer,
+    args: OpenFileArgs,
+) -> Result<String> {
+    let path = server.resolve_path(&args.path)?;
+    if !path.is_file() {
+        return Err(anyhow!("{} is not a file", args.path));
+    }
+    conversation
+        .core_rpc
+        .notification(CoreNotification::OpenPaths {
+            paths: vec![PathObject {
+                path,
+                linecol: args.line.map(|line| LineCol { line, column: 1 }),
+                is_dir: false,
+            }],
+        });
+    Ok("opened".to_string())
+}
+
+#[derive(Deserialize)]
+struct EditFileArgs {
+    path: String,
+    old_text: String,
+    new_text: String,
+}
+
+fn edit_file(server: &McpServer, args: EditFileArgs) -> Result<String> {
+    let path = server.resolve_path(&args.path)?;
+    // The edit is applied to the buffer, which may have unsaved changes.
+    let content = server.buffer_content(&path)?;
+    let edit = replacement(&content, &args.old_text, &args.new_text)?;
+
+    let url = Url::from_file_path(&path)
+        .map_err(|_| anyhow!("{} is not a valid path", path.display()))?;
+    let resp = server.request(ProxyRequest::ProposeEdit {
+        title: format!("Edit {}", server.display_path(&path)),
+        source: "Agent".to_string(),
+        edit: WorkspaceEdit {
+            changes: Some(HashMap::from([(url, vec![edit])])),
+            ..Default::default()
+        },
+    })?;
+    let ProxyResponse::ProposeEditResponse { .. } = resp else {
+        return Err(anyhow!("unexpected response"));
+    };
+    Ok("the edit was proposed to the user for review".to_string())
+}
+
+/// The text edit replacing the only occurrence of `old_text`.
+fn replacement(content: &str, old_text: &str, new_text: &str) -> Result<TextEdit> {
+    if old_text.is_empty() {
+        return Err(anyhow!("old_text can't be empty"));
+    }
+    let mut matches = content.match_indices(old_text);
+    let Some((start, _)) = matches.next() else {
+        return Err(anyhow!("old_text doesn't occur in the file"));
+    };
+    let count = matches.count() + 1;
+    if count > 1 {
+        return Err(anyhow!(
+            "old_text occurs {count} times in the file, include more context"
+        ));
+    }
+
+    let rope = Rope::from(content);
+    let text = RopeTextRef::new(&rope);
+    Ok(TextEdit {
+        range: Range {
+            start: text.offset_to_position(start),
+            end: text.offset_to_position(start + old_text.len()),
+        },
+        new_text: new_text.to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use lsp_types::Position;
+
+    use super::replacement;
+
+    #[test]
+    fn test_replacement() {
+        let content = "fn main() {\n    let a = 1;\n}\n";
+        let edit = replacement(content, "a = 1", "b = 2").unwrap();
+        assert_eq!(edit.range.start, Position::new(1, 8));
+        assert_eq!(edit.range.end, Position::new(1, 13));
+        assert_eq!(edit.new_text, "b = 2");
+
+        assert!(replacement(content, "missing", "").is_err());
+        assert!(replacement("a a", "a", "b").is_err());
+        assert!(replacement(content, "", "b").is_err());
+    }
+}
@@ -11,7 +11,7 @@
 use anyhow::{Result, anyhow};
 use floem_editor_core::buffer::rope_text::CharIndicesJoin;
 use lsp_types::*;
-use phidi_core::encoding::offset_utf8_to_utf16;
+use phidi_core::encoding::{offset_utf8_to_utf16, offset_utf16_to_utf8};
 use phidi_rpc::buffer::BufferId;
 use phidi_xi_rope::{RopeDelta, interval::IntervalBounds, rope::Rope};
 
@@ -133,6 +133,15 @@ pub fn update(
         )
     }
 
+    /// Whether the content differs from the file, which is the case when the
+    /// editor holds unsaved changes.
+    pub fn has_unsaved_changes(&self) -> bool {
+        match load_file(&self.path) {
+            Ok(content) => self.rope.slice_to_cow(..) != content.as_str(),
+            Err(_) => !self.rope.is_empty(),
+        }
+    }
+
     pub fn get_document(&self) -> String {
         self.rope.to_string()
     }
@@ -165,6 +174,21 @@ pub fn offset_to_position(&self, offset: usize) -> Position {
         }
     }
 
+    /// Converts a UTF16 LSP position to a UTF8 offset
+    pub fn offset_of_position(&self, pos: &Position) -> usize {
+        let line = pos.line as usize;
+        if line > self.line_of_offset(self.len()) {
+            return self.len();
+        }
+        let line_offset = self.offset_of_line(line);
+        let line_end = self.offset_of_line(line + 1);
+        let col = offset_utf16_to_utf8(
+            self.char_indices_iter(line_offset..line_end),
+            pos.character as usize,
+        );
+        (line_offset + col).min(line_end)
+    }
+
     pub fn slice_to_cow<T: IntervalBounds>(&self, range: T) -> Cow<'_, str> {
         self.rope.slice_to_cow(range)
     }
@@ -0,0 +1,308 @@
+//! Checkpoints of the workspace, to roll back what an agent or a large
+//! refactoring did to the files.
+//!
+//! Snapshots are commits in a repository kept in the data directory, whose
+//! work tree is the workspace. That way folders which aren't git repositories
+//! can be snapshotted as well, and the history of those that are is left
+//! untouched. Git stores content addressed objects, so files that didn't
+//! change between checkpoints are only stored once, and the index of the
+//! repository makes taking a snapshot of an unchanged workspace cheap.
+//!
+//! Every checkpoint has the one taken before it as its parent, so that they
+//! are ordered even when taken within the same second.
+
+use std::{
+    collections::{HashSet, hash_map::DefaultHasher},
+    fs,
+    hash::{Hash, Hasher},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use anyhow::{Result, anyhow};
+use git2::{
+    Commit, Delta, FileMode, IndexAddOption, IndexMatchedPath, Repository,
+    Signature, Sort, Tree,
+};
+use parking_lot::Mutex;
+use phidi_core::directory::Directory;
+use phidi_rpc::checkpoint::{Checkpoint, RestoreResult};
+
+const CHECKPOINT_REFS: &str = "refs/checkpoints/";
+
+#[derive(Clone)]
+pub struct CheckpointStore {
+    repo: Arc<Mutex<Repository>>,
+    workspace: PathBuf,
+}
+
+impl CheckpointStore {
+    pub fn open(workspace: &Path) -> Result<Self> {
+        let mut hasher = DefaultHasher::new();
+        workspace.hash(&mut hasher);
+        let dir = Directory::checkpoints_directory()
+            .ok_or_else(|| anyhow!("can't find the data directory"))?
+            .join(format!("{:x}", hasher.finish()));
+        Self::open_at(&dir, workspace)
+    }
+
+    fn open_at(dir: &Path, workspace: &Path) -> Result<Self> {
+        let repo = match Repository::open_bare(dir) {
+            Ok(repo) => repo,
+            Err(_) => Repository::init_bare(dir)?,
+        };
+        repo.set_workdir(workspace, false)?;
+        Ok(Self {
+            repo: Arc::new(Mutex::new(repo)),
+            workspace: workspace.to_path_buf(),
+        })
+    }
+
+    /// The checkpoints, most recent first.
+    pub fn list(&self) -> Result<Vec<Checkpoint>> {
+        let repo = self.repo.lock();
+        Ok(commits(&repo)?.iter().map(checkpoint).collect())
+    }
+
+    pub fn create(&self, label: &str) -> Result<Checkpoint> {
+        let repo = self.repo.lock();
+        let tree = snapshot(&repo)?;
+        commit(&repo, &tree, label)
+    }
+
+    /// Like `create`, but nothing is created if the files are the same as in
+    /// the latest checkpoint.
+    pub fn create_if_changed(&self, label: &str) -> Result<Option<Checkpoint>> {
+        let repo = self.repo.lock();
+        let tree = snapshot(&repo)?;
+        let latest = commits(&repo)?.into_iter().next();
+        if latest.is_some_and(|commit| commit.tree_id() == tree.id()) {
+            return Ok(None);
+        }
+        commit(&repo, &tree, label).map(Some)
+    }
+
+    /// Writes the files of the checkpoint back to the workspace, and deletes
+    /// the files it didn't have. The current state is saved as a checkpoint
+    /// first, so that the restore can be undone.
+    pub fn restore(
+        &self,
+        id: &str,
+        unsaved: &HashSet<PathBuf>,
+    ) -> Result<RestoreResult> {
+        let repo = self.repo.lock();
+        let target = find(&repo, id)?;
+        let current = snapshot(&repo)?;
+        let diff =
+            repo.diff_tree_to_tree(Some(&current), Some(&target.tree()?), None)?;
+        if diff.deltas().next().is_some() {
+            let label = target.summary().unwrap_or_default();
+            commit(&repo, &current, &format!("Before restoring \"{label}\""))?;
+        }
+
+        let mut result = RestoreResult::default();
+        for delta in diff.deltas() {
+            let (old, new) = (delta.old_file(), delta.new_file());
+            let Some(relative) = new.path().or_else(|| old.path()) else {
+                continue;
+            };
+            // Only regular files are restored, not symlinks or submodules.
+            let is_file = |mode| {
+                matches!(
+                    mode,
+                    FileMode::Blob | FileMode::BlobExecutable | FileMode::Unreadable
+                )
+            };
+            if !is_file(old.mode()) || !is_file(new.mode()) {
+                continue;
+            }
+
+            let path = self.workspace.join(relative);
+            if unsaved.contains(&path) {
+                result.conflicts.push(path);
+                continue;
+            }
+            match delta.status() {
+                Delta::Deleted => fs::remove_file(&path)?,
+                _ => {
+                    let blob = repo.find_blob(new.id())?;
+                    if let Some(parent) = path.parent() {
+                        fs::create_dir_all(parent)?;
+                    }
+                    fs::write(&path, blob.content())?;
+                    #[cfg(unix)]
+                    {
+                        use std::os::unix::fs::PermissionsExt;
+
+                        let mut permissions = fs::metadata(&path)?.permissions();
+                        let mode = if new.mode() == FileMode::BlobExecutable {
+                            permissions.mode() | 0o111
+                        } else {
+                            permissions.mode() & !0o111
+                        };
+                        permissions.set_mode(mode);
+                        fs::set_permissions(&path, permissions)?;
+                    }
+                }
+            }
+            result.restored.push(path);
+        }
+        Ok(result)
+    }
+
+    pub fn delete(&self, id: &str) -> Result<()> {
+        self.repo
+            .lock()
+            .find_reference(&format!("{CHECKPOINT_REFS}{id}"))?
+            .delete()?;
+        Ok(())
+    }
+}
+
+/// The commits of the checkpoints, most recent first.
+fn commits(repo: &Repository) -> Result<Vec<Commit<'_>>> {
+    let mut revwalk = repo.revwalk()?;
+    revwalk.set_sorting(Sort::TOPOLOGICAL | Sort::TIME)?;
+    let mut ids = HashSet::new();
+    for reference in repo.references_glob(&format!("{CHECKPOINT_REFS}*"))? {
+        let id = reference?.peel_to_commit()?.id();
+        revwalk.push(id)?;
+        ids.insert(id);
+    }
+    if ids.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    // The walk goes through deleted checkpoints too, which are still the
+    // parents of later ones.
+    let mut commits = Vec::new();
+    for id in revwalk {
+        let id = id?;
+        if ids.contains(&id) {
+            commits.push(repo.find_commit(id)?);
+        }
+    }
+    Ok(commits)
+}
+
+fn find<'a>(repo: &'a Repository, id: &str) -> Result<Commit<'a>> {
+    repo.find_reference(&format!("{CHECKPOINT_REFS}{id}"))
+        .and_then(|reference| reference.peel_to_commit())
+        .map_err(|_| anyhow!("checkpoint {id} doesn't exist"))
+}
+
+/// Updates the index from the workspace, which respects its ignore files, and
+/// writes it as a tree.
+fn snapshot(repo: &Repository) -> Result<Tree<'_>> {
+    let mut index = repo.index()?;
+    // Never descend into the workspace's own repository, or nested ones.
+    let skip_git: &mut IndexMatchedPath =
+        &mut |path, _| i32::from(path.components().any(|c| c.as_os_str() == ".git"));
+    index.add_all(["*"], IndexAddOption::DEFAULT, Some(&mut *skip_git))?;
+    index.update_all(["*"], Some(skip_git))?;
+    index.write()?;
+    let oid = index.write_tree()?;
+    Ok(repo.find_tree(oid)?)
+}
+
+fn commit(repo: &Repository, tree: &Tree, label: &str) -> Result<Checkpoint> {
+    let signature = Signature::now("phidi", "phidi@localhost")?;
+    let parent = repo.head().and_then(|head| head.peel_to_commit()).ok();
+    let parents = parent.iter().collect::<Vec<_>>();
+    let oid =
+        repo.commit(Some("HEAD"), &signature, &signature, label, tree, &parents)?;
+    repo.reference(&format!("{CHECKPOINT_REFS}{oid}"), oid, true, label)?;
+    Ok(checkpoint(&repo.find_commit(oid)?))
+}
+
+fn checkpoint(commit: &Commit) -> Checkpoint {
+    Checkpoint {
+        id: commit.id().to_string(),
+        label: commit.summary().unwrap_or_default().to_string(),
+        time: commit.time().seconds(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashSet, fs, path::Path};
+
+    use super::CheckpointStore;
+
+    #[test]
+    fn test_restore() {
+        let store_dir = tempfile::tempdir().unwrap();
+        let workspace = tempfile::tempdir().unwrap();
+        let root = workspace.path();
+        fs::write(root.join(".gitignore"), "target\n").unwrap();
+        fs::write(root.join("a.txt"), "a").unwrap();
+        fs::create_dir(root.join("src")).unwrap();
+        fs::write(root.join("src/b.txt"), "b").unwrap();
+        fs::create_dir(root.join("target")).unwrap();
+        fs::write(root.join("target/out"), "out").unwrap();
+
+        let store = CheckpointStore::open_at(store_dir.path(), root).unwrap();
+        let checkpoint = store.create("before").unwrap();
+        assert!(store.create_if_changed("unchanged").unwrap().is_none());
+
+        fs::write(root.join("a.txt"), "changed").unwrap();
+        fs::remove_file(root.join("src/b.txt")).unwrap();
+        fs::write(root.join("c.txt"), "new").unwrap();
+        fs::write(root.join("target/out"), "rebuilt").unwrap();
+
+        let unsaved = HashSet::from([root.join("c.txt")]);
+        let result = store.restore(&checkpoint.id, &unsaved).unwrap();
+        assert_eq!(fs::read_to_string(root.join("a.txt")).unwrap(), "a");
+        assert_eq!(fs::read_to_string(root.join("src/b.txt")).unwrap(), "b");
+        assert_eq!(result.restored.len(), 2);
+        assert_eq!(result.conflicts, vec![root.join("c.txt")]);
+        assert!(root.join("c.txt").exists());
+        assert_eq!(
+            fs::read_to_string(root.join("target/out")).unwrap(),
+            "rebuilt"
+        );
+
+        let checkpoints = store.list().unwrap();
+        assert_eq!(checkpoints.len(), 2);
+        assert!(
+            checkpoints
+                .iter()
+                .any(|c| c.label == "Before restoring \"before\"")
+        );
+
+        store.delete(&checkpoint.id).unwrap();
+        assert_eq!(store.list().unwrap().len(), 1);
+        assert!(store.restore(&checkpoint.id, &unsaved).is_err());
+    }
+
+    #[test]
+    fn test_order_within_a_second() {
+        let store_dir = tempfile::tempdir().unwrap();
+        let workspace = tempfile::tempdir().unwrap();
+        let root = workspace.path();
+        fs::create_dir_all(root.join("vendor/dep/.git")).unwrap();
+        fs::write(root.join("vendor/dep/.git/HEAD"), "").unwrap();
+
+        let store = CheckpointStore::open_at(store_dir.path(), root).unwrap();
+        for label in ["first", "second", "third"] {
+            fs::write(root.join("a.txt"), label).unwrap();
+            store.create(label).unwrap();
+        }
+        let labels = store
+            .list()
+            .unwrap()
+            .into_iter()
+            .map(|c| c.label)
+            .collect::<Vec<_>>();
+        assert_eq!(labels, ["third", "second", "first"]);
+
+        // Compared with the latest checkpoint, not one from the same second.
+        assert!(store.create_if_changed("unchanged").unwrap().is_none());
+        fs::write(root.join("a.txt"), "first").unwrap();
+        assert!(store.create_if_changed("changed").unwrap().is_some());
+
+        let repo = store.repo.lock();
+        let tree = repo.head().unwrap().peel_to_tree().unwrap();
+        assert!(tree.get_path(Path::new("vendor/dep/.git/HEAD")).is_err());
+    }
+}
@@ -0,0 +1,225 @@
+//! Keeps the code graph of the workspace up to date.
+//!
+//! The graph is loaded from its cache on startup and only the files whose
+//! modification time changed since are indexed again. After that, the paths
+//! reported by the file watcher are re-indexed in batches on a background
+//! thread, and the graph is persisted after every batch.
+
+use std::{
+    collections::{HashSet, hash_map::DefaultHasher},
+    hash::{Hash, Hasher},
+    path::{Path, PathBuf},
+    sync::Arc,
+    thread,
+    time::Duration,
+};
+
+use crossbeam_channel::{Receiver, Sender};
+use parking_lot::RwLock;
+use phidi_core::{
+    code_graph::{CodeGraph, FileTags},
+    directory::Directory,
+};
+use phidi_rpc::code_graph::{CodeGraphItem, CodeGraphQuery};
+
+#[derive(Clone)]
+pub struct CodeGraphIndex {
+    graph: Arc<RwLock<CodeGraph>>,
+    /// Every file of the workspace that isn't ignored, tagged or not.
+    files: Arc<RwLock<HashSet<PathBuf>>>,
+    sender: Sender<Vec<PathBuf>>,
+}
+
+impl CodeGraphIndex {
+    pub fn new(workspace: PathBuf) -> Self {
+        let cache = cache_path(&workspace);
+        let graph = cache
+            .as_deref()
+            .and_then(|cache| CodeGraph::load(cache).ok())
+            .unwrap_or_default();
+        let graph = Arc::new(RwLock::new(graph));
+        let files = Arc::new(RwLock::new(HashSet::new()));
+
+        let (sender, receiver) = crossbeam_channel::unbounded();
+        {
+            let graph = graph.clone();
+            let files = files.clone();
+            thread::spawn(move || {
+                index(&workspace, &graph, &files, cache.as_deref(), receiver);
+            });
+        }
+
+        Self {
+            graph,
+            files,
+            sender,
+        }
+    }
+
+    /// Index the changed paths again. Paths that no longer exist are removed
+    /// from the graph.
+    pub fn update(&self, paths: Vec<PathBuf>) {
+        if let Err(err) = self.sender.send(paths) {
+            tracing::error!("{:?}", err);
+        }
+    }
+
+    pub fn query(&self, query: &CodeGraphQuery) -> Vec<CodeGraphItem> {
+        self.graph.read().query(query)
+    }
+
+    /// The files of the workspace, kept up to date with the file watcher so
+    /// that the workspace doesn't have to be walked again.
+    pub fn files(&self) -> Arc<RwLock<HashSet<PathBuf>>> {
+        self.files.clone()
+    }
+}
+
+fn cache_path(workspace: &Path) -> Option<PathBuf> {
+    let mut hasher = DefaultHasher::new();
+    workspace.hash(&mut hasher);
+    Directory::code_graph_directory()
+        .map(|dir| dir.join(format!("{:x}.json", hasher.finish())))
+}
+
+fn index(
+    workspace: &Path,
+    graph: &RwLock<CodeGraph>,
+    files: &RwLock<HashSet<PathBuf>>,
+    cache: Option<&Path>,
+    receiver: Receiver<Vec<PathBuf>>,
+) {
+    let walked = workspace_files(workspace);
+    *files.write() = walked.clone();
+    graph.write().retain(|path| walked.contains(path));
+    for path in walked {
+        if !graph.read().is_up_to_date(&path) {
+            update_file(graph, path);
+        }
+    }
+    save(graph, cache);
+
+    while let Ok(mut paths) = receiver.recv() {
+        // File events come in bursts, so wait for the burst to finish
+        thread::sleep(Duration::from_millis(500));
+        for more in receiver.try_iter() {
+            paths.extend(more);
+        }
+
+        let paths: HashSet<PathBuf> = paths.into_iter().collect();
+        let kept = workspace_files_among(workspace, &paths);
+        for path in paths {
+            if kept.contains(&path) {
+                files.write().insert(path.clone());
+                if !graph.read().is_up_to_date(&path) {
+                    update_file(graph, path);
+                }
+            } else if !path.is_dir() {
+                files.write().remove(&path);
+                graph.write().remove(&path);
+            }
+        }
+        save(graph, cache);
+    }
+}
+
+fn update_file(graph: &RwLock<CodeGraph>, path: PathBuf) {
+    // The tags are extracted before taking the lock, so that queries aren't
+    // blocked while parsing.
+    match FileTags::read(&path) {
+        Some(tags) => graph.write().insert(path, tags),
+        None => graph.write().remove(&path),
+    }
+}
+
+fn save(graph: &RwLock<CodeGraph>, cache: Option<&Path>) {
+    if let Some(cache) = cache {
+        if let Err(err) = graph.read().save(cache) {
+            tracing::error!("{:?}", err);
+        }
+    }
+}
+
+/// The files of the workspace, walked the same way as the file picker does.
+pub(crate) fn workspace_files(workspace: &Path) -> HashSet<PathBuf> {
+    walked_files(walk_builder(workspace).build())
+}
+
+/// The paths which are files of the workspace, by the same ignore rules as
+/// [`workspace_files`], only walking the directories leading to them.
+fn workspace_files_among(
+    workspace: &Path,
+    paths: &HashSet<PathBuf>,
+) -> HashSet<PathBuf> {
+    let wanted: HashSet<PathBuf> = paths
+        .iter()
+        .flat_map(|path| path.ancestors())
+        .map(Path::to_path_buf)
+        .collect();
+    let walker = walk_builder(workspace)
+        .filter_entry(move |entry| wanted.contains(entry.path()))
+        .build();
+    walked_files(walker)
+        .into_iter()
+        .filter(|path| paths.contains(path))
+        .collect()
+}
+
+fn walk_builder(workspace: &Path) -> ignore::WalkBuilder {
+    let git_folder = ignore::overrides::OverrideBuilder::new(workspace)
+        .add("!.git/")
+        .map(|git_folder| git_folder.build());
+
+    let mut builder = ignore::WalkBuilder::new(workspace);
+    builder.parents(false).require_git(false);
+    if let Ok(Ok(git_folder)) = git_folder {
+        builder.hidden(false).overrides(git_folder);
+    }
+    builder
+}
+
+fn walked_files(walker: ignore::Walk) -> HashSet<PathBuf> {
+    walker
+        .flatten()
+        .filter(|entry| {
+            entry
+                .file_type()
+                .map(|file_type| file_type.is_file())
+                .unwrap_or(false)
+        })
+        .map(|entry| entry.into_path())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashSet, fs};
+
+    use super::workspace_files_among;
+
+    #[test]
+    fn test_workspace_files_among_nested_ignores() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::create_dir_all(root.join("src/gen")).unwrap();
+        fs::create_dir_all(root.join(".git")).unwrap();
+        fs::write(root.join("src/.gitignore"), "gen/\n").unwrap();
+        fs::write(root.join(".ignore"), "*.log\n").unwrap();
+        for file in ["src/lib.rs", "src/gen/out.rs", "run.log", ".git/HEAD"] {
+            fs::write(root.join(file), "").unwrap();
+        }
+
+        let paths: HashSet<_> = [
+            "src/lib.rs",
+            "src/gen/out.rs",
+            "run.log",
+            ".git/HEAD",
+            "gone.rs",
+        ]
+        .iter()
+        .map(|file| root.join(file))
+        .collect();
+        let kept = workspace_files_among(root, &paths);
+        assert_eq!(kept, HashSet::from([root.join("src/lib.rs")]));
+    }
+}
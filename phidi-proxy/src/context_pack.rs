@@ -0,0 +1,537 @@
+//! Context packs bundle the code a question is about into one document to
+//! paste into a prompt: the selected lines, the definition and the references
+//! of the symbol at the start of the selection, the diagnostics and the open
+//! files. Items are added in that order until the token budget is used up.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+use globset::{Glob, GlobMatcher};
+use lsp_types::{
+    DiagnosticSeverity, GotoDefinitionResponse, Location, Position, Range,
+};
+use parking_lot::RwLock;
+use phidi_rpc::context_pack::{ContextPack, ContextPackFormat, ContextPackRequest};
+use serde::Serialize;
+
+use crate::plugin::PluginCatalogRpcHandler;
+
+/// How long to wait for the language server to answer.
+const LSP_TIMEOUT: Duration = Duration::from_secs(5);
+/// The most lines of a definition that are included.
+const MAX_DEFINITION_LINES: usize = 80;
+const MAX_REFERENCES: usize = 20;
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "snake_case")]
+#[serde(tag = "kind")]
+enum Item {
+    Selection {
+        path: String,
+        start_line: usize,
+        end_line: usize,
+        text: String,
+    },
+    Diagnostic {
+        path: String,
+        line: usize,
+        severity: &'static str,
+        message: String,
+    },
+    Definition {
+        path: String,
+        start_line: usize,
+        end_line: usize,
+        text: String,
+    },
+    Reference {
+        path: String,
+        line: usize,
+        text: String,
+    },
+    File {
+        path: String,
+        text: String,
+    },
+}
+
+impl Item {
+    fn heading(&self) -> &'static str {
+        match self {
+            Item::Selection { .. } => "Selection",
+            Item::Diagnostic { .. } => "Diagnostics",
+            Item::Definition { .. } => "Definitions",
+            Item::Reference { .. } => "References",
+            Item::File { .. } => "Open Files",
+        }
+    }
+
+    fn to_markdown(&self) -> String {
+        match self {
+            Item::Selection {
+                path,
+                start_line,
+                end_line,
+                text,
+            }
+            | Item::Definition {
+                path,
+                start_line,
+                end_line,
+                text,
+            } => format!(
+                "`{path}:{start_line}-{end_line}`\n{}\n",
+                code_block(path, text)
+            ),
+            Item::Diagnostic {
+                path,
+                line,
+                severity,
+                message,
+            } => format!("- `{path}:{line}` {severity}: {message}\n"),
+            Item::Reference { path, line, text } => {
+                format!("- `{path}:{line}` `{}`\n", text.trim())
+            }
+            Item::File { path, text } => {
+                format!("`{path}`\n{}\n", code_block(path, text))
+            }
+        }
+    }
+
+    fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap_or_default()
+    }
+}
+
+/// A fenced code block, tagged with the file's extension for highlighting.
+fn code_block(path: &str, text: &str) -> String {
+    let language = Path::new(path)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .unwrap_or_default();
+    let text = text.strip_suffix('\n').unwrap_or(text);
+    // The fence has to be longer than any run of backticks in the text.
+    let mut fence = "```".to_string();
+    while text.contains(&fence) {
+        fence.push('`');
+    }
+    format!("{fence}{language}\n{text}\n{fence}\n")
+}
+
+/// A rough estimate of the tokens of a text, about four characters each.
+fn estimate_tokens(text: &str) -> usize {
+    text.chars().count().div_ceil(4)
+}
+
+/// The files that may go into a pack, read from the open buffers if they are
+/// open, and from disk otherwise.
+pub struct Files {
+    workspace: Option<PathBuf>,
+    workspace_files: Arc<RwLock<HashSet<PathBuf>>>,
+    exclude: Option<GlobMatcher>,
+    buffers: HashMap<PathBuf, String>,
+}
+
+impl Files {
+    /// `workspace_files` are the files of the workspace the code graph index
+    /// keeps track of.
+    pub fn new(
+        workspace: Option<&Path>,
+        workspace_files: Arc<RwLock<HashSet<PathBuf>>>,
+        files_exclude: &str,
+        buffers: HashMap<PathBuf, String>,
+    ) -> Self {
+        let exclude = if files_exclude.is_empty() {
+            None
+        } else {
+            match Glob::new(files_exclude) {
+                Ok(glob) => Some(glob.compile_matcher()),
+                Err(err) => {
+                    tracing::error!("{:?}", err);
+                    None
+                }
+            }
+        };
+        Self {
+            workspace: workspace.map(Path::to_path_buf),
+            workspace_files,
+            exclude,
+            buffers,
+        }
+    }
+
+    /// Files of the workspace are only included if the index has them, which
+    /// leaves out the ignored ones. Files outside of it, like the
+    /// sources of dependencies, only have to pass `files-exclude`.
+    fn is_included(&self, path: &Path) -> bool {
+        if self
+            .exclude
+            .as_ref()
+            .is_some_and(|exclude| exclude.is_match(path))
+        {
+            return false;
+        }
+        match &self.workspace {
+            Some(workspace) if path.starts_with(workspace) => {
+                self.workspace_files.read().contains(path)
+            }
+            _ => true,
+        }
+    }
+
+    fn read(&self, path: &Path) -> Option<String> {
+        if let Some(text) = self.buffers.get(path) {
+            return Some(text.clone());
+        }
+        fs::read_to_string(path).ok()
+    }
+
+    fn display(&self, path: &Path) -> String {
+        self.workspace
+            .as_deref()
+            .and_then(|workspace| path.strip_prefix(workspace).ok())
+            .unwrap_or(path)
+            .display()
+            .to_string()
+    }
+}
+
+/// Ask the language server for the definition and the references of the
+/// symbol at `position`. Blocks until they answer, so it must not be called
+/// on the dispatcher's thread.
+pub fn query_symbol(
+    catalog_rpc: &PluginCatalogRpcHandler,
+    path: &Path,
+    position: Position,
+) -> (Vec<Location>, Vec<Location>) {
+    let (tx, rx) = crossbeam_channel::unbounded();
+    catalog_rpc.get_definition(path, position, move |_, result| {
+        let _ = tx.send(result.ok().map(|definition| {
+            match definition {
+                GotoDefinitionResponse::Scalar(location) => vec![location],
+                GotoDefinitionResponse::Array(locations) => locations,
+                GotoDefinitionResponse::Link(links) => links
+                    .into_iter()
+                    .map(|link| Location {
+                        uri: link.target_uri,
+                        range: link.target_selection_range,
+                    })
+                    .collect(),
+            }
+        }));
+    });
+    let definitions = rx.recv_timeout(LSP_TIMEOUT).ok().flatten();
+
+    let (tx, rx) = crossbeam_channel::unbounded();
+    catalog_rpc.get_references(path, position, move |_, result| {
+        let _ = tx.send(result.ok());
+    });
+    let references = rx.recv_timeout(LSP_TIMEOUT).ok().flatten();
+
+    (
+        definitions.unwrap_or_default(),
+        references.unwrap_or_default(),
+    )
+}
+
+pub fn build(
+    request: &ContextPackRequest,
+    files: &Files,
+    definitions: &[Location],
+    references: &[Location],
+) -> ContextPack {
+    let mut items = Vec::new();
+
+    if let Some((path, range)) = &request.selection {
+        if let Some(text) = files.read(path) {
+            let (start, end) = selected_lines(range);
+            items.push(Item::Selection {
+                path: files.display(path),
+                start_line: start + 1,
+                end_line: end + 1,
+                text: lines(&text, start, end),
+            });
+        }
+    }
+
+    for (path, diagnostics) in &request.diagnostics {
+        if !files.is_included(path) {
+            continue;
+        }
+        for diagnostic in diagnostics {
+            items.push(Item::Diagnostic {
+                path: files.display(path),
+                line: diagnostic.range.start.line as usize + 1,
+                severity: severity(diagnostic.severity),
+                message: diagnostic.message.clone(),
+            });
+        }
+    }
+
+    let mut definition_lines = HashSet::new();
+    for location in definitions {
+        let Ok(path) = location.uri.to_file_path() else {
+            continue;
+        };
+        if !files.is_included(&path) {
+            continue;
+        }
+        let Some(text) = files.read(&path) else {
+            continue;
+        };
+        let start = location.range.start.line as usize;
+        definition_lines.insert((path.clone(), start));
+        let end = definition_end(&text, start);
+        items.push(Item::Definition {
+            path: files.display(&path),
+            start_line: start + 1,
+            end_line: end + 1,
+            text: lines(&text, start, end),
+        });
+    }
+
+    let mut contents = HashMap::new();
+    for location in references.iter().take(MAX_REFERENCES) {
+        let Ok(path) = location.uri.to_file_path() else {
+            continue;
+        };
+        let line = location.range.start.line as usize;
+        // The definition is usually one of the references too.
+        if definition_lines.contains(&(path.clone(), line))
+            || !files.is_included(&path)
+        {
+            continue;
+        }
+        let Some(text) = contents
+            .entry(path.clone())
+            .or_insert_with(|| files.read(&path))
+        else {
+            continue;
+        };
+        items.push(Item::Reference {
+            path: files.display(&path),
+            line: line + 1,
+            text: lines(text, line, line),
+        });
+    }
+
+    for path in &request.open_files {
+        if !files.is_included(path) {
+            continue;
+        }
+        if let Some(text) = files.read(path) {
+            items.push(Item::File {
+                path: files.display(path),
+                text,
+            });
+        }
+    }
+
+    let mut omitted = 0;
+    let mut tokens = 0;
+    items.retain(|item| {
+        let item_tokens = estimate_tokens(&match request.format {
+            ContextPackFormat::Markdown => item.to_markdown(),
+            ContextPackFormat::Json => item.to_json(),
+        });
+        if request.token_budget > 0 && tokens + item_tokens > request.token_budget {
+            omitted += 1;
+            return false;
+        }
+        tokens += item_tokens;
+        true
+    });
+
+    let content = match request.format {
+        ContextPackFormat::Markdown => markdown(&items, omitted),
+        ContextPackFormat::Json => {
+            serde_json::to_string_pretty(&serde_json::json!({
+                "items": items,
+                "omitted": omitted,
+            }))
+            .unwrap_or_default()
+        }
+    };
+    ContextPack {
+        tokens: estimate_tokens(&content),
+        content,
+        omitted,
+    }
+}
+
+fn markdown(items: &[Item], omitted: usize) -> String {
+    let mut content = "# Context\n".to_string();
+    let mut heading = "";
+    for item in items {
+        if item.heading() != heading {
+            heading = item.heading();
+            content.push_str(&format!("\n## {heading}\n\n"));
+        }
+        content.push_str(&item.to_markdown());
+    }
+    if omitted > 0 {
+        content.push_str(&format!(
+            "\n{omitted} more items were left out to stay within the token budget.\n"
+        ));
+    }
+    content
+}
+
+/// The first and the last line of a selection. A selection that ends at the
+/// start of a line doesn't include that line.
+fn selected_lines(range: &Range) -> (usize, usize) {
+    let start = range.start.line as usize;
+    let mut end = range.end.line as usize;
+    if end > start && range.end.character == 0 {
+        end -= 1;
+    }
+    (start, end)
+}
+
+fn lines(text: &str, start: usize, end: usize) -> String {
+    text.lines()
+        .skip(start)
+        .take(end + 1 - start)
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Guess where the definition starting at `start` ends: at the first line
+/// after its body that is indented no further than its first line, or at the
+/// last line of the body if the language doesn't close blocks.
+fn definition_end(text: &str, start: usize) -> usize {
+    let indent = |line: &str| line.len() - line.trim_start().len();
+    let mut lines = text.lines().enumerate().skip(start);
+    let Some((_, first)) = lines.next() else {
+        return start;
+    };
+    let first_indent = indent(first);
+    let mut end = start;
+    for (i, line) in lines.take(MAX_DEFINITION_LINES - 1) {
+        if line.trim().is_empty() {
+            continue;
+        }
+        if indent(line) <= first_indent {
+            let closes = line
+                .trim_start()
+                .starts_with(|c| matches!(c, '}' | ')' | ']'))
+                || line.trim() == "end";
+            if closes {
+                end = i;
+            }
+            break;
+        }
+        end = i;
+    }
+    end
+}
+
+fn severity(severity: Option<DiagnosticSeverity>) -> &'static str {
+    match severity {
+        Some(DiagnosticSeverity::ERROR) => "error",
+        Some(DiagnosticSeverity::WARNING) => "warning",
+        Some(DiagnosticSeverity::INFORMATION) => "info",
+        Some(DiagnosticSeverity::HINT) => "hint",
+        _ => "diagnostic",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, fs, sync::Arc};
+
+    use lsp_types::{Diagnostic, Location, Position, Range, Url};
+    use parking_lot::RwLock;
+    use phidi_rpc::context_pack::{ContextPackFormat, ContextPackRequest};
+
+    use super::{Files, build, definition_end};
+    use crate::code_graph::workspace_files;
+
+    #[test]
+    fn test_definition_end() {
+        let text = "fn a() {\n    b();\n\n    c();\n}\n\nfn d() {}\n";
+        assert_eq!(definition_end(text, 0), 4);
+        assert_eq!(definition_end(text, 6), 6);
+        let text = "def a():\n    return 1\n\ndef b():\n    pass\n";
+        assert_eq!(definition_end(text, 0), 1);
+    }
+
+    #[test]
+    fn test_build() {
+        let workspace = tempfile::tempdir().unwrap();
+        let root = workspace.path();
+        fs::write(root.join(".gitignore"), "target\n").unwrap();
+        fs::write(root.join("main.rs"), "fn main() {\n    helper();\n}\n").unwrap();
+        fs::write(root.join("lib.rs"), "fn helper() {\n    todo!()\n}\n").unwrap();
+        fs::write(root.join("secret.env"), "KEY=1\n").unwrap();
+        fs::create_dir(root.join("target")).unwrap();
+        fs::write(root.join("target/gen.rs"), "fn gen() {}\n").unwrap();
+
+        let location = |path: &str, line| Location {
+            uri: Url::from_file_path(root.join(path)).unwrap(),
+            range: Range::new(Position::new(line, 3), Position::new(line, 9)),
+        };
+        let files = Files::new(
+            Some(root),
+            Arc::new(RwLock::new(workspace_files(root))),
+            "**/*.env",
+            HashMap::from([(
+                root.join("main.rs"),
+                "fn main() {\n    helper();\n    unsaved();\n}\n".to_string(),
+            )]),
+        );
+        let mut request = ContextPackRequest {
+            selection: Some((
+                root.join("main.rs"),
+                Range::new(Position::new(1, 0), Position::new(3, 0)),
+            )),
+            open_files: vec![
+                root.join("lib.rs"),
+                root.join("secret.env"),
+                root.join("target/gen.rs"),
+            ],
+            diagnostics: vec![(
+                root.join("lib.rs"),
+                vec![Diagnostic {
+                    range: Range::new(Position::new(1, 4), Position::new(1, 11)),
+                    message: "not yet implemented".to_string(),
+                    ..Default::default()
+                }],
+            )],
+            files_exclude: "**/*.env".to_string(),
+            token_budget: 0,
+            format: ContextPackFormat::Markdown,
+        };
+        let definitions = [location("lib.rs", 0)];
+        let references = [location("lib.rs", 0), location("main.rs", 1)];
+
+        let pack = build(&request, &files, &definitions, &references);
+        assert_eq!(pack.omitted, 0);
+        assert!(
+            pack.content.contains(
+                "`main.rs:2-3`\n```rs\n    helper();\n    unsaved();\n```"
+            )
+        );
+        assert!(pack.content.contains("- `lib.rs:2` diagnostic: not yet"));
+        assert!(pack.content.contains("`lib.rs:1-3`\n```rs\nfn helper()"));
+        assert!(pack.content.contains("- `main.rs:2` `helper();`"));
+        assert!(!pack.content.contains("- `lib.rs:1`"));
+        assert!(!pack.content.contains("KEY=1"));
+        assert!(!pack.content.contains("gen()"));
+
+        request.format = ContextPackFormat::Json;
+        request.token_budget = 30;
+        let pack = build(&request, &files, &definitions, &references);
+        let json: serde_json::Value = serde_json::from_str(&pack.content).unwrap();
+        let items = json["items"].as_array().unwrap();
+        assert_eq!(items[0]["kind"], "selection");
+        assert_eq!(json["omitted"].as_u64().unwrap() as usize, pack.omitted);
+        assert!(pack.omitted > 0);
+        assert_eq!(items.len() + pack.omitted, 5);
+    }
+}
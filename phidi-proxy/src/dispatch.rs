@@ -12,54 +12,78 @@
 
 use alacritty_terminal::{event::WindowSize, event_loop::Msg};
 use anyhow::{Context, Result, anyhow};
-use crossbeam_channel::Sender;
+use crossbeam_channel::{Receiver, Sender};
 use git2::{
     DiffOptions, ErrorCode::NotFound, Oid, Repository, build::CheckoutBuilder,
 };
-use grep_matcher::Matcher;
-use grep_regex::RegexMatcherBuilder;
-use grep_searcher::{SearcherBuilder, sinks::UTF8};
 use indexmap::IndexMap;
 use lsp_types::{
-    CancelParams, MessageType, NumberOrString, Position, Range, ShowMessageParams,
-    TextDocumentItem, Url,
+    CancelParams, DocumentChangeOperation, DocumentChanges, MessageType,
+    NumberOrString, OneOf, Position, ProgressParams, ProgressParamsValue, Range,
+    ResourceOp, ShowMessageParams, TextDocumentItem, TextEdit, Url,
+    WorkDoneProgress, WorkDoneProgressBegin, WorkDoneProgressEnd,
+    WorkDoneProgressReport, WorkspaceEdit,
     notification::{Cancel, Notification},
 };
 use parking_lot::Mutex;
 use phidi_rpc::{
     RequestId, RpcError,
     buffer::BufferId,
-    core::{CoreNotification, CoreRpcHandler, FileChanged},
+    core::{CoreNotification, CoreResponse, CoreRpcHandler, FileChanged},
     file::FileNodeItem,
     file_line::FileLine,
+    proposal::{EditProposal, ProposalId},
     proxy::{
         ProxyHandler, ProxyNotification, ProxyRequest, ProxyResponse,
-        ProxyRpcHandler, SearchMatch,
+        ProxyRpcHandler, SourcedCodeAction,
     },
-    source_control::{DiffInfo, FileDiff},
-    style::{LineStyle, SemanticStyles},
-    terminal::TermId,
+    source_control::{DiffInfo, FileDiff, GitCredentials, RemoteOperation},
+    style::SemanticStyles,
+    terminal::{TermId, TerminalProfile},
 };
 use phidi_xi_rope::Rope;
 
 use crate::{
+    agent::Agent,
     buffer::{Buffer, get_mod_time, load_file},
-    plugin::{PluginCatalogRpcHandler, catalog::PluginCatalog},
-    terminal::{Terminal, TerminalSender},
+    checkpoint::CheckpointStore,
+    code_graph::CodeGraphIndex,
+    context_pack, git, mcp,
+    plugin::{
+        PluginCatalogRpcHandler, catalog::PluginCatalog, psp::SemanticTokenStyles,
+    },
+    search::{
+        build_matcher, replace_in_file, replacements_in_text, search_files,
+        search_in_path,
+    },
+    terminal::{
+        DEFAULT_COMMAND_TIMEOUT, Terminal, TerminalSender, run_command,
+        run_in_terminal,
+    },
     watcher::{FileWatcher, Notify, WatchToken},
 };
 
 const OPEN_FILE_EVENT_TOKEN: WatchToken = WatchToken(1);
 const WORKSPACE_EVENT_TOKEN: WatchToken = WatchToken(2);
+/// How long creating, renaming or deleting files waits for the edits language
+/// servers make along with it.
+const FILE_OPERATION_EDIT_TIMEOUT: Duration = Duration::from_secs(3);
 
 pub struct Dispatcher {
     workspace: Option<PathBuf>,
     pub proxy_rpc: ProxyRpcHandler,
+    pub agent: Agent,
     core_rpc: CoreRpcHandler,
     catalog_rpc: PluginCatalogRpcHandler,
     buffers: HashMap<PathBuf, Buffer>,
+    /// Where the buffers of renamed files went, for the updates the client
+    /// sent before it knew about the rename.
+    renamed_buffers: HashMap<PathBuf, PathBuf>,
     terminals: HashMap<TermId, TerminalSender>,
     file_watcher: FileWatcher,
+    code_graph: Option<CodeGraphIndex>,
+    checkpoints: Option<CheckpointStore>,
+    mcp_socket: Option<PathBuf>,
     window_id: usize,
     tab_id: usize,
 }
@@ -73,16 +97,33 @@ fn handle_notification(&mut self, rpc: ProxyNotification) {
                 disabled_volts,
                 extra_plugin_paths,
                 plugin_configurations,
+                lsp_servers,
+                mcp_server,
                 window_id,
                 tab_id,
             } => {
                 self.window_id = window_id;
                 self.tab_id = tab_id;
                 self.workspace = workspace;
+                self.code_graph = self.workspace.clone().map(CodeGraphIndex::new);
+                self.checkpoints = self.workspace.as_deref().and_then(|workspace| {
+                    CheckpointStore::open(workspace)
+                        .map_err(|err| tracing::error!("{:?}", err))
+                        .ok()
+                });
+                self.agent
+                    .set_workspace(self.workspace.clone(), self.checkpoints.clone());
+                if let Some(server) = self.agent.server().filter(|_| mcp_server) {
+                    match mcp::listen(server) {
+                        Ok(socket) => self.mcp_socket = Some(socket),
+                        Err(err) => tracing::error!("{:?}", err),
+                    }
+                }
                 self.file_watcher.notify(FileWatchNotifier::new(
                     self.workspace.clone(),
                     self.core_rpc.clone(),
                     self.proxy_rpc.clone(),
+                    self.code_graph.clone(),
                 ));
                 if let Some(workspace) = self.workspace.as_ref() {
                     self.file_watcher
@@ -97,6 +138,7 @@ fn handle_notification(&mut self, rpc: ProxyNotification) {
                         disabled_volts,
                         extra_plugin_paths,
                         plugin_configurations,
+                        lsp_servers,
                         plugin_rpc.clone(),
                     );
                     plugin_rpc.mainloop(&mut plugin);
@@ -163,9 +205,15 @@ fn handle_notification(&mut self, rpc: ProxyNotification) {
                 for (_, sender) in self.terminals.iter() {
                     sender.send(Msg::Shutdown);
                 }
+                if let Some(socket) = self.mcp_socket.take() {
+                    if let Err(err) = std::fs::remove_file(&socket) {
+                        tracing::error!("{:?}", err);
+                    }
+                }
                 self.proxy_rpc.shutdown();
             }
             Update { path, delta, rev } => {
+                let path = self.renamed_buffers.get(&path).cloned().unwrap_or(path);
                 let buffer = self.buffers.get_mut(&path).unwrap();
                 let old_text = buffer.rope.clone();
                 buffer.update(&delta, rev);
@@ -182,32 +230,15 @@ fn handle_notification(&mut self, rpc: ProxyNotification) {
                     tracing::error!("{:?}", err);
                 }
             }
+            UpdateLspServers { servers } => {
+                if let Err(err) = self.catalog_rpc.update_lsp_servers(servers) {
+                    tracing::error!("{:?}", err);
+                }
+            }
             NewTerminal { term_id, profile } => {
-                let mut terminal = match Terminal::new(term_id, profile, 50, 10) {
-                    Ok(terminal) => terminal,
-                    Err(e) => {
-                        self.core_rpc.terminal_launch_failed(term_id, e.to_string());
-                        return;
-                    }
+                let Some(mut terminal) = self.new_terminal(term_id, profile) else {
+                    return;
                 };
-
-                #[allow(unused)]
-                let mut child_id = None;
-
-                #[cfg(target_os = "windows")]
-                {
-                    child_id = terminal.pty.child_watcher().pid().map(|x| x.get());
-                }
-                #[cfg(not(target_os = "windows"))]
-                {
-                    child_id = Some(terminal.pty.child().id());
-                }
-
-                self.core_rpc.terminal_process_id(term_id, child_id);
-                let tx = terminal.tx.clone();
-                let poller = terminal.poller.clone();
-                let sender = TerminalSender::new(tx, poller);
-                self.terminals.insert(term_id, sender);
                 let rpc = self.core_rpc.clone();
                 thread::spawn(move || {
                     terminal.run(rpc);
@@ -386,6 +417,121 @@ fn handle_notification(&mut self, rpc: ProxyNotification) {
                     }
                 }
             }
+            GitStageFiles { files } => {
+                if let Some(workspace) = self.workspace.clone() {
+                    let result = git::stage_files(&workspace, &files);
+                    self.git_index_changed(&workspace, "Git Stage failure", result);
+                }
+            }
+            GitUnstageFiles { files } => {
+                if let Some(workspace) = self.workspace.clone() {
+                    let result = git::unstage_files(&workspace, &files);
+                    self.git_index_changed(
+                        &workspace,
+                        "Git Unstage failure",
+                        result,
+                    );
+                }
+            }
+            GitStageLines { path, lines } => {
+                if let Some(workspace) = self.workspace.clone() {
+                    let result = self.file_content(&path).and_then(|content| {
+                        git::stage_lines(&workspace, &path, &content, &lines)
+                    });
+                    self.git_index_changed(&workspace, "Git Stage failure", result);
+                }
+            }
+            GitUnstageLines { path, lines } => {
+                if let Some(workspace) = self.workspace.clone() {
+                    let result = self.file_content(&path).and_then(|content| {
+                        git::unstage_lines(&workspace, &path, &content, &lines)
+                    });
+                    self.git_index_changed(
+                        &workspace,
+                        "Git Unstage failure",
+                        result,
+                    );
+                }
+            }
+            GitCreateBranch { name, checkout } => {
+                if let Some(workspace) = self.workspace.clone() {
+                    let result = git::create_branch(&workspace, &name, checkout);
+                    self.git_index_changed(&workspace, "Git Branch failure", result);
+                }
+            }
+            GitRenameBranch { from, to } => {
+                if let Some(workspace) = self.workspace.clone() {
+                    let result = git::rename_branch(&workspace, &from, &to);
+                    self.git_index_changed(&workspace, "Git Branch failure", result);
+                }
+            }
+            GitDeleteBranch { name, force } => {
+                if let Some(workspace) = self.workspace.clone() {
+                    let result = git::delete_branch(&workspace, &name, force);
+                    self.git_index_changed(&workspace, "Git Branch failure", result);
+                }
+            }
+            GitMerge { reference } => {
+                if let Some(workspace) = self.workspace.clone() {
+                    let result = git::merge(&workspace, &reference);
+                    self.git_index_changed(&workspace, "Git Merge failure", result);
+                }
+            }
+            GitCreateTag { name, message } => {
+                if let Some(workspace) = self.workspace.clone() {
+                    let result =
+                        git::create_tag(&workspace, &name, message.as_deref());
+                    self.git_index_changed(&workspace, "Git Tag failure", result);
+                }
+            }
+            GitStashPush {
+                message,
+                include_untracked,
+            } => {
+                if let Some(workspace) = self.workspace.clone() {
+                    let result = git::stash_push(
+                        &workspace,
+                        message.as_deref(),
+                        include_untracked,
+                    );
+                    self.git_index_changed(&workspace, "Git Stash failure", result);
+                }
+            }
+            GitStashApply { index } => {
+                if let Some(workspace) = self.workspace.clone() {
+                    let result = git::stash_apply(&workspace, index, false);
+                    self.git_index_changed(&workspace, "Git Stash failure", result);
+                }
+            }
+            GitStashPop { index } => {
+                if let Some(workspace) = self.workspace.clone() {
+                    let result = git::stash_apply(&workspace, index, true);
+                    self.git_index_changed(&workspace, "Git Stash failure", result);
+                }
+            }
+            GitStashDrop { index } => {
+                if let Some(workspace) = self.workspace.clone() {
+                    let result = git::stash_drop(&workspace, index);
+                    self.git_index_changed(&workspace, "Git Stash failure", result);
+                }
+            }
+            GitRemoteOperation {
+                operation,
+                credentials,
+            } => {
+                if let Some(workspace) = self.workspace.clone() {
+                    let core_rpc = self.core_rpc.clone();
+                    // Talking to the remote can take a long while.
+                    thread::spawn(move || {
+                        git_remote_operation(
+                            &core_rpc,
+                            &workspace,
+                            operation,
+                            credentials,
+                        );
+                    });
+                }
+            }
             LspCancel { id } => {
                 self.catalog_rpc.send_notification(
                     None,
@@ -398,24 +544,54 @@ fn handle_notification(&mut self, rpc: ProxyNotification) {
                     false,
                 );
             }
+            AgentSend {
+                session,
+                settings,
+                message,
+            } => {
+                self.agent.send(session, settings, message);
+            }
+            AgentApprove {
+                session,
+                id,
+                approved,
+            } => {
+                self.agent.approve(session, id, approved);
+            }
+            AgentCancel { session } => {
+                self.agent.cancel(session);
+            }
+            AgentClose { session } => {
+                self.agent.close(session);
+            }
         }
     }
 
     fn handle_request(&mut self, id: RequestId, rpc: ProxyRequest) {
         use ProxyRequest::*;
         match rpc {
+            BufferContent { path } => {
+                let result = if let Some(buffer) = self.buffers.get(&path) {
+                    Ok(buffer.rope.to_string())
+                } else if path.is_file() {
+                    Ok(self.open_buffer(BufferId::next(), path).rope.to_string())
+                } else {
+                    Err(RpcError {
+                        code: 0,
+                        message: format!("{} is not a file", path.display()),
+                    })
+                };
+                self.respond_rpc(
+                    id,
+                    result.map(|content| ProxyResponse::BufferContentResponse {
+                        content,
+                    }),
+                );
+            }
             NewBuffer { buffer_id, path } => {
-                let buffer = Buffer::new(buffer_id, path.clone());
+                let buffer = self.open_buffer(buffer_id, path);
                 let content = buffer.rope.to_string();
                 let read_only = buffer.read_only;
-                self.catalog_rpc.did_open_document(
-                    &path,
-                    buffer.language_id.to_string(),
-                    buffer.rev as i32,
-                    content.clone(),
-                );
-                self.file_watcher.watch(&path, false, OPEN_FILE_EVENT_TOKEN);
-                self.buffers.insert(path, buffer);
                 self.respond_rpc(
                     id,
                     Ok(ProxyResponse::NewBufferResponse { content, read_only }),
@@ -448,17 +624,36 @@ fn handle_request(&mut self, id: RequestId, rpc: ProxyRequest) {
                 case_sensitive,
                 whole_word,
                 is_regex,
+                replacement,
+                options,
             } => {
                 static WORKER_ID: AtomicU64 = AtomicU64::new(0);
                 let our_id = WORKER_ID.fetch_add(1, Ordering::SeqCst) + 1;
 
-                let workspace = self.workspace.clone();
                 let buffers = self
                     .buffers
                     .iter()
                     .map(|p| p.0)
                     .cloned()
                     .collect::<Vec<PathBuf>>();
+                let open = self
+                    .buffers
+                    .iter()
+                    .map(|(path, buffer)| {
+                        (path.clone(), (buffer.rev, buffer.rope.clone()))
+                    })
+                    .collect::<HashMap<_, _>>();
+                let paths = match search_files(
+                    self.workspace.as_deref(),
+                    &buffers,
+                    &options,
+                ) {
+                    Ok(paths) => paths,
+                    Err(err) => {
+                        self.respond_rpc(id, Err(err));
+                        return;
+                    }
+                };
                 let proxy_rpc = self.proxy_rpc.clone();
 
                 // Perform the search on another thread to avoid blocking the proxy thread
@@ -468,23 +663,109 @@ fn handle_request(&mut self, id: RequestId, rpc: ProxyRequest) {
                         search_in_path(
                             our_id,
                             &WORKER_ID,
-                            workspace
-                                .iter()
-                                .flat_map(|w| ignore::Walk::new(w).flatten())
-                                .chain(
-                                    buffers.iter().flat_map(|p| {
-                                        ignore::Walk::new(p).flatten()
-                                    }),
-                                )
-                                .map(|p| p.into_path()),
+                            paths,
+                            &open,
                             &pattern,
                             case_sensitive,
                             whole_word,
                             is_regex,
+                            replacement.as_deref(),
+                            &options,
                         ),
                     );
                 });
             }
+            GlobalReplace {
+                pattern,
+                case_sensitive,
+                whole_word,
+                is_regex,
+                replacement,
+                files,
+                revisions,
+            } => {
+                let matcher = match build_matcher(
+                    &pattern,
+                    case_sensitive,
+                    whole_word,
+                    is_regex,
+                ) {
+                    Ok(matcher) => matcher,
+                    Err(err) => {
+                        self.respond_rpc(id, Err(err));
+                        return;
+                    }
+                };
+
+                // Open files are edited by the client, so that the changes
+                // can be undone. The matches are only where they were found if
+                // the file hasn't changed since it was searched.
+                let mut edits = IndexMap::new();
+                let mut closed = Vec::new();
+                let mut stale = Vec::new();
+                for (path, included) in files {
+                    let included = included.into_iter().collect::<HashSet<_>>();
+                    let Some(buffer) = self.buffers.get(&path) else {
+                        closed.push((path, included));
+                        continue;
+                    };
+                    if revisions.get(&path) != Some(&buffer.rev) {
+                        stale.push(path);
+                        continue;
+                    }
+                    let text = buffer.rope.slice_to_cow(..);
+                    match replacements_in_text(
+                        &matcher,
+                        &text,
+                        &replacement,
+                        is_regex,
+                        &included,
+                    ) {
+                        Ok(replacements) if !replacements.is_empty() => {
+                            let file_edits = replacements
+                                .into_iter()
+                                .map(|(range, new_text)| TextEdit {
+                                    range: Range {
+                                        start: buffer
+                                            .offset_to_position(range.start),
+                                        end: buffer.offset_to_position(range.end),
+                                    },
+                                    new_text,
+                                })
+                                .collect();
+                            edits.insert(path, file_edits);
+                        }
+                        Ok(_) => {}
+                        Err(err) => tracing::error!("{:?}", err),
+                    }
+                }
+
+                let proxy_rpc = self.proxy_rpc.clone();
+                thread::spawn(move || {
+                    let mut written = Vec::new();
+                    for (path, included) in closed {
+                        match replace_in_file(
+                            &matcher,
+                            &path,
+                            &replacement,
+                            is_regex,
+                            &included,
+                        ) {
+                            Ok(true) => written.push(path),
+                            Ok(false) => {}
+                            Err(err) => tracing::error!("{:?}", err),
+                        }
+                    }
+                    proxy_rpc.handle_response(
+                        id,
+                        Ok(ProxyResponse::GlobalReplaceResponse {
+                            edits,
+                            written,
+                            stale,
+                        }),
+                    );
+                });
+            }
             CompletionResolve {
                 plugin_id,
                 completion_item,
@@ -542,6 +823,119 @@ fn handle_request(&mut self, id: RequestId, rpc: ProxyRequest) {
                     }
                 }
             }
+            GitBlame { path } => {
+                let Some(workspace) = self.workspace.clone() else {
+                    return;
+                };
+                let content = self.file_content(&path);
+                let proxy_rpc = self.proxy_rpc.clone();
+                thread::spawn(move || {
+                    let result = content
+                        .and_then(|content| git::blame(&workspace, &path, &content))
+                        .map(|hunks| ProxyResponse::GitBlame { hunks })
+                        .map_err(|e| RpcError {
+                            code: 0,
+                            message: e.to_string(),
+                        });
+                    proxy_rpc.handle_response(id, result);
+                });
+            }
+            GitFileAtRevision { path, revision } => {
+                let result = match self.workspace.as_ref() {
+                    Some(workspace) => {
+                        git::file_at_revision(workspace, &path, &revision)
+                            .map(|content| ProxyResponse::GitFileAtRevision {
+                                content,
+                            })
+                            .map_err(|e| RpcError {
+                                code: 0,
+                                message: e.to_string(),
+                            })
+                    }
+                    None => Err(RpcError {
+                        code: 0,
+                        message: "no workspace set".to_string(),
+                    }),
+                };
+                self.respond_rpc(id, result);
+            }
+            GitLog {
+                filter,
+                after,
+                limit,
+            } => {
+                let Some(workspace) = self.workspace.clone() else {
+                    self.respond_rpc(
+                        id,
+                        Err(RpcError {
+                            code: 0,
+                            message: "no workspace set".to_string(),
+                        }),
+                    );
+                    return;
+                };
+                let proxy_rpc = self.proxy_rpc.clone();
+                // Filtering by path diffs every commit, which takes a while in
+                // a long history.
+                thread::spawn(move || {
+                    let result =
+                        git::log(&workspace, &filter, after.as_deref(), limit)
+                            .map(|(commits, has_more)| ProxyResponse::GitLog {
+                                commits,
+                                has_more,
+                            })
+                            .map_err(|e| RpcError {
+                                code: 0,
+                                message: e.to_string(),
+                            });
+                    proxy_rpc.handle_response(id, result);
+                });
+            }
+            GitCommitChanges { commit } => {
+                let result = match self.workspace.as_ref() {
+                    Some(workspace) => git::commit_changes(workspace, &commit)
+                        .map(|diffs| ProxyResponse::GitCommitChanges { diffs })
+                        .map_err(|e| RpcError {
+                            code: 0,
+                            message: e.to_string(),
+                        }),
+                    None => Err(RpcError {
+                        code: 0,
+                        message: "no workspace set".to_string(),
+                    }),
+                };
+                self.respond_rpc(id, result);
+            }
+            GitBranches {} => {
+                let result = match self.workspace.as_ref() {
+                    Some(workspace) => git::branches(workspace)
+                        .map(|branches| ProxyResponse::GitBranches { branches })
+                        .map_err(|e| RpcError {
+                            code: 0,
+                            message: e.to_string(),
+                        }),
+                    None => Err(RpcError {
+                        code: 0,
+                        message: "no workspace set".to_string(),
+                    }),
+                };
+                self.respond_rpc(id, result);
+            }
+            GitStashes {} => {
+                let result = match self.workspace.as_ref() {
+                    Some(workspace) => git::stashes(workspace)
+                        .map(|stashes| ProxyResponse::GitStashes { stashes })
+                        .map_err(|e| RpcError {
+                            code: 0,
+                            message: e.to_string(),
+                        }),
+                    None => Err(RpcError {
+                        code: 0,
+                        message: "no workspace set".to_string(),
+                    }),
+                };
+                self.respond_rpc(id, result);
+            }
             GetDefinition {
                 request_id,
                 path,
@@ -611,6 +1005,67 @@ fn handle_request(&mut self, id: RequestId, rpc: ProxyRequest) {
                     },
                 );
             }
+            CallHierarchyOutgoing {
+                path,
+                call_hierarchy_item,
+            } => {
+                let proxy_rpc = self.proxy_rpc.clone();
+                self.catalog_rpc.call_hierarchy_outgoing(
+                    &path,
+                    call_hierarchy_item,
+                    move |_, result| {
+                        let result = result.map(|items| {
+                            ProxyResponse::CallHierarchyOutgoingResponse { items }
+                        });
+                        proxy_rpc.handle_response(id, result);
+                    },
+                );
+            }
+            ShowTypeHierarchy { path, position } => {
+                let proxy_rpc = self.proxy_rpc.clone();
+                self.catalog_rpc.show_type_hierarchy(
+                    &path,
+                    position,
+                    move |_, result| {
+                        let result = result.map(|items| {
+                            ProxyResponse::TypeHierarchyResponse { items }
+                        });
+                        proxy_rpc.handle_response(id, result);
+                    },
+                );
+            }
+            TypeHierarchySupertypes {
+                path,
+                type_hierarchy_item,
+            } => {
+                let proxy_rpc = self.proxy_rpc.clone();
+                self.catalog_rpc.type_hierarchy_supertypes(
+                    &path,
+                    type_hierarchy_item,
+                    move |_, result| {
+                        let result = result.map(|items| {
+                            ProxyResponse::TypeHierarchyResponse { items }
+                        });
+                        proxy_rpc.handle_response(id, result);
+                    },
+                );
+            }
+            TypeHierarchySubtypes {
+                path,
+                type_hierarchy_item,
+            } => {
+                let proxy_rpc = self.proxy_rpc.clone();
+                self.catalog_rpc.type_hierarchy_subtypes(
+                    &path,
+                    type_hierarchy_item,
+                    move |_, result| {
+                        let result = result.map(|items| {
+                            ProxyResponse::TypeHierarchyResponse { items }
+                        });
+                        proxy_rpc.handle_response(id, result);
+                    },
+                );
+            }
             GetInlayHints { path } => {
                 let proxy_rpc = self.proxy_rpc.clone();
                 let buffer = self.buffers.get(&path).unwrap();
@@ -625,6 +1080,81 @@ fn handle_request(&mut self, id: RequestId, rpc: ProxyRequest) {
                         proxy_rpc.handle_response(id, result);
                     });
             }
+            GetDocumentHighlights { path, position } => {
+                let proxy_rpc = self.proxy_rpc.clone();
+                self.catalog_rpc.get_document_highlights(
+                    &path,
+                    position,
+                    move |_, result| {
+                        let result = result.map(|highlights| {
+                            ProxyResponse::GetDocumentHighlights {
+                                highlights: highlights.unwrap_or_default(),
+                            }
+                        });
+                        proxy_rpc.handle_response(id, result);
+                    },
+                );
+            }
+            GetLinkedEditingRanges { path, position } => {
+                let proxy_rpc = self.proxy_rpc.clone();
+                self.catalog_rpc.get_linked_editing_ranges(
+                    &path,
+                    position,
+                    move |_, result| {
+                        let result = result.map(|ranges| {
+                            ProxyResponse::GetLinkedEditingRanges { ranges }
+                        });
+                        proxy_rpc.handle_response(id, result);
+                    },
+                );
+            }
+            GetDocumentLinks { path } => {
+                let proxy_rpc = self.proxy_rpc.clone();
+                self.catalog_rpc
+                    .get_document_links(&path, move |_, result| {
+                        let result =
+                            result.map(|links| ProxyResponse::GetDocumentLinks {
+                                links: links.unwrap_or_default(),
+                            });
+                        proxy_rpc.handle_response(id, result);
+                    });
+            }
+            ResolveDocumentLink { path, link } => {
+                let proxy_rpc = self.proxy_rpc.clone();
+                self.catalog_rpc.resolve_document_link(
+                    &path,
+                    link,
+                    move |_, result| {
+                        let result = result
+                            .map(|link| ProxyResponse::ResolveDocumentLink { link });
+                        proxy_rpc.handle_response(id, result);
+                    },
+                );
+            }
+            GetDocumentColors { path } => {
+                let proxy_rpc = self.proxy_rpc.clone();
+                self.catalog_rpc
+                    .get_document_colors(&path, move |_, result| {
+                        let result = result.map(|colors| {
+                            ProxyResponse::GetDocumentColors { colors }
+                        });
+                        proxy_rpc.handle_response(id, result);
+                    });
+            }
+            GetColorPresentations { path, color, range } => {
+                let proxy_rpc = self.proxy_rpc.clone();
+                self.catalog_rpc.get_color_presentations(
+                    &path,
+                    color,
+                    range,
+                    move |_, result| {
+                        let result = result.map(|presentations| {
+                            ProxyResponse::GetColorPresentations { presentations }
+                        });
+                        proxy_rpc.handle_response(id, result);
+                    },
+                );
+            }
             GetInlineCompletions {
                 path,
                 position,
@@ -643,7 +1173,7 @@ fn handle_request(&mut self, id: RequestId, rpc: ProxyRequest) {
                     },
                 );
             }
-            GetSemanticTokens { path } => {
+            GetSemanticTokens { path, result_id } => {
                 let buffer = self.buffers.get(&path).unwrap();
                 let text = buffer.rope.clone();
                 let rev = buffer.rev;
@@ -652,34 +1182,82 @@ fn handle_request(&mut self, id: RequestId, rpc: ProxyRequest) {
                 let proxy_rpc = self.proxy_rpc.clone();
                 let catalog_rpc = self.catalog_rpc.clone();
 
-                let handle_tokens =
-                    move |result: Result<Vec<LineStyle>, RpcError>| match result {
-                        Ok(styles) => {
-                            proxy_rpc.handle_response(
-                                id,
-                                Ok(ProxyResponse::GetSemanticTokens {
-                                    styles: SemanticStyles {
-                                        rev,
-                                        path: local_path,
-                                        styles,
-                                        len,
-                                    },
-                                }),
+                self.catalog_rpc.get_semantic_tokens(
+                    &path,
+                    result_id,
+                    move |plugin_id, result| match result {
+                        Ok((tokens, changed)) => {
+                            let result_id = tokens.result_id.clone();
+                            let handle_tokens = move |result: Result<
+                                SemanticTokenStyles,
+                                RpcError,
+                            >| {
+                                let result = result.map(|(styles, range)| {
+                                    ProxyResponse::GetSemanticTokens {
+                                        styles: SemanticStyles {
+                                            rev,
+                                            path: local_path,
+                                            styles,
+                                            len,
+                                            range,
+                                            result_id,
+                                        },
+                                    }
+                                });
+                                proxy_rpc.handle_response(id, result);
+                            };
+                            catalog_rpc.format_semantic_tokens(
+                                plugin_id,
+                                tokens,
+                                changed,
+                                text,
+                                Box::new(handle_tokens),
                             );
                         }
                         Err(e) => {
                             proxy_rpc.handle_response(id, Err(e));
                         }
-                    };
-
+                    },
+                );
+            }
+            GetSemanticTokensRange { path, range } => {
+                let buffer = self.buffers.get(&path).unwrap();
+                let text = buffer.rope.clone();
+                let rev = buffer.rev;
+                let len = buffer.len();
+                let styles_range = buffer.offset_of_position(&range.start)
+                    ..buffer.offset_of_position(&range.end);
+                let local_path = path.clone();
                 let proxy_rpc = self.proxy_rpc.clone();
-                self.catalog_rpc.get_semantic_tokens(
+                let catalog_rpc = self.catalog_rpc.clone();
+
+                self.catalog_rpc.get_semantic_tokens_range(
                     &path,
+                    range,
                     move |plugin_id, result| match result {
-                        Ok(result) => {
+                        Ok(tokens) => {
+                            let handle_tokens = move |result: Result<
+                                SemanticTokenStyles,
+                                RpcError,
+                            >| {
+                                let result = result.map(|(styles, _)| {
+                                    ProxyResponse::GetSemanticTokens {
+                                        styles: SemanticStyles {
+                                            rev,
+                                            path: local_path,
+                                            styles,
+                                            len,
+                                            range: Some(styles_range),
+                                            result_id: None,
+                                        },
+                                    }
+                                });
+                                proxy_rpc.handle_response(id, result);
+                            };
                             catalog_rpc.format_semantic_tokens(
                                 plugin_id,
-                                result,
+                                tokens,
+                                None,
                                 text,
                                 Box::new(handle_tokens),
                             );
@@ -696,15 +1274,38 @@ fn handle_request(&mut self, id: RequestId, rpc: ProxyRequest) {
                 diagnostics,
             } => {
                 let proxy_rpc = self.proxy_rpc.clone();
+                let catalog_rpc = self.catalog_rpc.clone();
                 self.catalog_rpc.get_code_actions(
                     &path,
                     position,
                     diagnostics,
-                    move |plugin_id, result| {
-                        let result = result.map(|resp| {
-                            ProxyResponse::GetCodeActionsResponse { plugin_id, resp }
-                        });
-                        proxy_rpc.handle_response(id, result);
+                    move |responses| {
+                        // Label the actions with their server when there are
+                        // actions from more than one.
+                        let labelled = responses
+                            .iter()
+                            .filter(|(_, resp)| !resp.is_empty())
+                            .count()
+                            > 1;
+                        let actions = responses
+                            .into_iter()
+                            .flat_map(|(plugin_id, resp)| {
+                                let source = labelled
+                                    .then(|| catalog_rpc.server_name(plugin_id))
+                                    .flatten();
+                                resp.into_iter().map(move |action| {
+                                    SourcedCodeAction {
+                                        plugin_id,
+                                        source: source.clone(),
+                                        action,
+                                    }
+                                })
+                            })
+                            .collect();
+                        proxy_rpc.handle_response(
+                            id,
+                            Ok(ProxyResponse::GetCodeActionsResponse { actions }),
+                        );
                     },
                 );
             }
@@ -737,6 +1338,35 @@ fn handle_request(&mut self, id: RequestId, rpc: ProxyRequest) {
                         proxy_rpc.handle_response(id, result);
                     });
             }
+            GetDocumentRangeFormatting { path, ranges } => {
+                let proxy_rpc = self.proxy_rpc.clone();
+                self.catalog_rpc.get_document_ranges_formatting(
+                    &path,
+                    ranges,
+                    move |_, result| {
+                        let result = result.map(|edits| {
+                            ProxyResponse::GetDocumentFormatting { edits }
+                        });
+                        proxy_rpc.handle_response(id, result);
+                    },
+                );
+            }
+            OnTypeFormatting { path, position, ch } => {
+                let proxy_rpc = self.proxy_rpc.clone();
+                self.catalog_rpc.on_type_formatting(
+                    &path,
+                    position,
+                    ch,
+                    move |_, result| {
+                        let result = result.map(|edits| {
+                            ProxyResponse::GetDocumentFormatting {
+                                edits: edits.unwrap_or_default(),
+                            }
+                        });
+                        proxy_rpc.handle_response(id, result);
+                    },
+                );
+            }
             PrepareRename { path, position } => {
                 let proxy_rpc = self.proxy_rpc.clone();
                 self.catalog_rpc.prepare_rename(
@@ -892,6 +1522,9 @@ fn handle_request(&mut self, id: RequestId, rpc: ProxyRequest) {
                 self.respond_rpc(id, result);
             }
             CreateFile { path } => {
+                let paths = [path.clone()];
+                let edit = self.catalog_rpc.will_create_files(&paths);
+                self.apply_file_operation_edit(edit, |edit| edit);
                 let result = path
                     .parent()
                     .map_or(Ok(()), std::fs::create_dir_all)
@@ -906,24 +1539,39 @@ fn handle_request(&mut self, id: RequestId, rpc: ProxyRequest) {
                         code: 0,
                         message: e.to_string(),
                     });
+                if result.is_ok() {
+                    self.catalog_rpc.did_create_files(&paths);
+                }
                 self.respond_rpc(id, result);
             }
             CreateDirectory { path } => {
+                let paths = [path.clone()];
+                let edit = self.catalog_rpc.will_create_files(&paths);
+                self.apply_file_operation_edit(edit, |edit| edit);
                 let result = std::fs::create_dir_all(path)
                     .map(|_| ProxyResponse::Success {})
                     .map_err(|e| RpcError {
                         code: 0,
                         message: e.to_string(),
                     });
+                if result.is_ok() {
+                    self.catalog_rpc.did_create_files(&paths);
+                }
                 self.respond_rpc(id, result);
             }
             TrashPath { path } => {
+                let paths = [path.clone()];
+                let edit = self.catalog_rpc.will_delete_files(&paths);
+                self.apply_file_operation_edit(edit, |edit| edit);
                 let result = trash::delete(path)
                     .map(|_| ProxyResponse::Success {})
                     .map_err(|e| RpcError {
                         code: 0,
                         message: e.to_string(),
                     });
+                if result.is_ok() {
+                    self.catalog_rpc.did_delete_files(&paths);
+                }
                 self.respond_rpc(id, result);
             }
             DuplicatePath {
@@ -948,12 +1596,19 @@ fn handle_request(&mut self, id: RequestId, rpc: ProxyRequest) {
                             return;
                         }
                     }
-                    std::fs::copy(existing_path, new_path)
+                    let paths = [new_path.clone()];
+                    let edit = self.catalog_rpc.will_create_files(&paths);
+                    self.apply_file_operation_edit(edit, |edit| edit);
+                    let result = std::fs::copy(existing_path, new_path)
                         .map(|_| ProxyResponse::Success {})
                         .map_err(|e| RpcError {
                             code: 0,
                             message: e.to_string(),
-                        })
+                        });
+                    if result.is_ok() {
+                        self.catalog_rpc.did_create_files(&paths);
+                    }
+                    result
                 };
                 self.respond_rpc(id, result);
             }
@@ -983,9 +1638,23 @@ fn handle_request(&mut self, id: RequestId, rpc: ProxyRequest) {
                     }
                 });
 
+                let renames = [(from.clone(), to.clone())];
+                if result.is_ok() {
+                    let edit = self.catalog_rpc.will_rename_files(&renames);
+                    self.apply_file_operation_edit(edit, |edit| {
+                        rename_edit_uris(edit, &from, &to, |path| {
+                            self.buffers.contains_key(path)
+                        })
+                    });
+                }
+
                 let result = result
                     .and_then(|_| fs::rename(&from, &to).map_err(|e| e.to_string()));
 
+                if result.is_ok() {
+                    self.catalog_rpc.did_rename_files(&renames);
+                }
+
                 let result = result
                     .map(|_| {
                         let to = to.canonicalize().unwrap_or(to);
@@ -1016,6 +1685,8 @@ fn handle_request(&mut self, id: RequestId, rpc: ProxyRequest) {
                                     let new_path = to.join(suffix);
                                     buffer.path = new_path;
 
+                                    self.renamed_buffers
+                                        .insert(path, buffer.path.clone());
                                     self.buffers.insert(buffer.path.clone(), buffer);
                                 }
                             }
@@ -1026,6 +1697,8 @@ fn handle_request(&mut self, id: RequestId, rpc: ProxyRequest) {
 
                             if let Some(mut buffer) = buffer {
                                 buffer.path.clone_from(&to);
+                                self.renamed_buffers
+                                    .insert(from.clone(), to.clone());
                                 self.buffers.insert(to.clone(), buffer);
                             }
                         }
@@ -1204,6 +1877,171 @@ fn handle_request(&mut self, id: RequestId, rpc: ProxyRequest) {
                 let resp = ProxyResponse::ReferencesResolveResponse { items };
                 self.proxy_rpc.handle_response(id, Ok(resp));
             }
+            ApplyWorkspaceEdit { edit } => {
+                let result = self
+                    .apply_workspace_edit(edit)
+                    .map(|_| ProxyResponse::Success {})
+                    .map_err(|e| RpcError {
+                        code: 0,
+                        message: e.to_string(),
+                    });
+                self.respond_rpc(id, result);
+            }
+            ProposeEdit {
+                title,
+                source,
+                edit,
+            } => {
+                let proposal = EditProposal {
+                    id: ProposalId::next(),
+                    title,
+                    source,
+                    edit,
+                };
+                let resp = ProxyResponse::ProposeEditResponse { id: proposal.id };
+                self.core_rpc.edit_proposed(proposal);
+                self.respond_rpc(id, Ok(resp));
+            }
+            CodeGraph { query } => {
+                let result = self
+                    .code_graph
+                    .as_ref()
+                    .map(|code_graph| ProxyResponse::CodeGraphResponse {
+                        items: code_graph.query(&query),
+                    })
+                    .ok_or_else(|| RpcError {
+                        code: 0,
+                        message: "no workspace".to_string(),
+                    });
+                self.respond_rpc(id, result);
+            }
+            RunCommand {
+                term_id,
+                profile,
+                timeout_secs,
+            } => {
+                let timeout = timeout_secs
+                    .map(Duration::from_secs)
+                    .unwrap_or(DEFAULT_COMMAND_TIMEOUT);
+                let proxy_rpc = self.proxy_rpc.clone();
+                if let Some(term_id) = term_id {
+                    let Some(terminal) = self.new_terminal(term_id, profile) else {
+                        self.respond_rpc(
+                            id,
+                            Err(RpcError {
+                                code: 0,
+                                message: "failed to launch the terminal".to_string(),
+                            }),
+                        );
+                        return;
+                    };
+                    let core_rpc = self.core_rpc.clone();
+                    thread::spawn(move || {
+                        let result = run_in_terminal(terminal, core_rpc, timeout);
+                        proxy_rpc.handle_response(
+                            id,
+                            Ok(ProxyResponse::RunCommandResponse { result }),
+                        );
+                    });
+                } else {
+                    thread::spawn(move || {
+                        let result = run_command(&profile, timeout)
+                            .map(|result| ProxyResponse::RunCommandResponse {
+                                result,
+                            })
+                            .map_err(|e| RpcError {
+                                code: 0,
+                                message: e.to_string(),
+                            });
+                        proxy_rpc.handle_response(id, result);
+                    });
+                }
+            }
+            CreateCheckpoint { label } => {
+                self.with_checkpoints(id, true, move |checkpoints| {
+                    checkpoints.create(&label).map(|checkpoint| {
+                        ProxyResponse::CreateCheckpointResponse { checkpoint }
+                    })
+                });
+            }
+            ListCheckpoints {} => {
+                self.with_checkpoints(id, false, |checkpoints| {
+                    checkpoints.list().map(|checkpoints| {
+                        ProxyResponse::ListCheckpointsResponse { checkpoints }
+                    })
+                });
+            }
+            RestoreCheckpoint { id: checkpoint } => {
+                let unsaved = self
+                    .buffers
+                    .values()
+                    .filter(|buffer| buffer.has_unsaved_changes())
+                    .map(|buffer| buffer.path.clone())
+                    .collect::<HashSet<_>>();
+                let open = self.buffers.keys().cloned().collect::<HashSet<_>>();
+                let core_rpc = self.core_rpc.clone();
+                self.with_checkpoints(id, true, move |checkpoints| {
+                    let result = checkpoints.restore(&checkpoint, &unsaved)?;
+                    // Reload the open documents right away rather than when
+                    // the file watcher gets to them.
+                    for path in result.restored.iter().filter(|p| open.contains(*p))
+                    {
+                        let change = match load_file(path) {
+                            Ok(content) => FileChanged::Change(content),
+                            Err(_) => FileChanged::Delete,
+                        };
+                        core_rpc.open_file_changed(path.clone(), change);
+                    }
+                    Ok(ProxyResponse::RestoreCheckpointResponse { result })
+                });
+            }
+            DeleteCheckpoint { id: checkpoint } => {
+                self.with_checkpoints(id, true, move |checkpoints| {
+                    checkpoints.delete(&checkpoint)?;
+                    Ok(ProxyResponse::Success {})
+                });
+            }
+            ContextPack { request } => {
+                let workspace = self.workspace.clone();
+                let workspace_files = self
+                    .code_graph
+                    .as_ref()
+                    .map(|code_graph| code_graph.files())
+                    .unwrap_or_default();
+                let buffers = self
+                    .buffers
+                    .iter()
+                    .map(|(path, buffer)| (path.clone(), buffer.rope.to_string()))
+                    .collect();
+                let catalog_rpc = self.catalog_rpc.clone();
+                let proxy_rpc = self.proxy_rpc.clone();
+                thread::spawn(move || {
+                    let (definitions, references) = match &request.selection {
+                        Some((path, range)) => context_pack::query_symbol(
+                            &catalog_rpc,
+                            path,
+                            range.start,
+                        ),
+                        None => (Vec::new(), Vec::new()),
+                    };
+                    let files = context_pack::Files::new(
+                        workspace.as_deref(),
+                        workspace_files,
+                        &request.files_exclude,
+                        buffers,
+                    );
+                    let pack = context_pack::build(
+                        &request,
+                        &files,
+                        &definitions,
+                        &references,
+                    );
+                    proxy_rpc.handle_response(
+                        id,
+                        Ok(ProxyResponse::ContextPackResponse { pack }),
+                    );
+                });
+            }
         }
     }
 }
@@ -1214,35 +2052,288 @@ pub fn new(core_rpc: CoreRpcHandler, proxy_rpc: ProxyRpcHandler) -> Self {
             PluginCatalogRpcHandler::new(core_rpc.clone(), proxy_rpc.clone());
 
         let file_watcher = FileWatcher::new();
+        let agent = Agent::new(core_rpc.clone(), proxy_rpc.clone());
 
         Self {
             workspace: None,
             proxy_rpc,
+            agent,
             core_rpc,
             catalog_rpc: plugin_rpc,
             buffers: HashMap::new(),
+            renamed_buffers: HashMap::new(),
             terminals: HashMap::new(),
             file_watcher,
+            code_graph: None,
+            checkpoints: None,
+            mcp_socket: None,
             window_id: 1,
             tab_id: 1,
         }
     }
 
+    /// Runs `f` on the checkpoints of the workspace on another thread, as
+    /// snapshotting a large workspace takes a while. If `changed` the client
+    /// is sent the updated list of checkpoints.
+    fn with_checkpoints(
+        &self,
+        id: RequestId,
+        changed: bool,
+        f: impl FnOnce(&CheckpointStore) -> Result<ProxyResponse> + Send + 'static,
+    ) {
+        let Some(checkpoints) = self.checkpoints.clone() else {
+            self.respond_rpc(
+                id,
+                Err(RpcError {
+                    code: 0,
+                    message: "no workspace".to_string(),
+                }),
+            );
+            return;
+        };
+        let proxy_rpc = self.proxy_rpc.clone();
+        let core_rpc = self.core_rpc.clone();
+        thread::spawn(move || {
+            let result = f(&checkpoints).map_err(|e| RpcError {
+                code: 0,
+                message: e.to_string(),
+            });
+            if changed {
+                match checkpoints.list() {
+                    Ok(list) => core_rpc.checkpoints_changed(list),
+                    Err(err) => tracing::error!("{:?}", err),
+                }
+            }
+            proxy_rpc.handle_response(id, result);
+        });
+    }
+
+    /// Starts the process of a terminal, and registers the terminal so that
+    /// the client can write to it, resize or close it.
+    fn new_terminal(
+        &mut self,
+        term_id: TermId,
+        profile: TerminalProfile,
+    ) -> Option<Terminal> {
+        let terminal = match Terminal::new(term_id, profile, 50, 10) {
+            Ok(terminal) => terminal,
+            Err(e) => {
+                self.core_rpc.terminal_launch_failed(term_id, e.to_string());
+                return None;
+            }
+        };
+
+        #[allow(unused)]
+        let mut child_id = None;
+
+        #[cfg(target_os = "windows")]
+        {
+            child_id = terminal.pty.child_watcher().pid().map(|x| x.get());
+        }
+        #[cfg(not(target_os = "windows"))]
+        {
+            child_id = Some(terminal.pty.child().id());
+        }
+
+        self.core_rpc.terminal_process_id(term_id, child_id);
+        let tx = terminal.tx.clone();
+        let poller = terminal.poller.clone();
+        let sender = TerminalSender::new(tx, poller);
+        self.terminals.insert(term_id, sender);
+        Some(terminal)
+    }
+
     fn respond_rpc(&self, id: RequestId, result: Result<ProxyResponse, RpcError>) {
         self.proxy_rpc.handle_response(id, result);
     }
 
+    /// The text of the file as it is in the editor, or on disk if it isn't open.
+    fn file_content(&self, path: &Path) -> Result<String> {
+        match self.buffers.get(path) {
+            Some(buffer) => Ok(buffer.rope.to_string()),
+            None => Ok(fs::read_to_string(path)?),
+        }
+    }
+
+    /// Shows the error of a change to the git index, or else sends the client
+    /// the changes as they are now, without waiting for the file watcher.
+    fn git_index_changed(&self, workspace: &Path, title: &str, result: Result<()>) {
+        match result {
+            Ok(()) => {
+                if let Some(diff) = git_diff_new(workspace) {
+                    self.core_rpc.diff_info(diff);
+                }
+            }
+            Err(e) => {
+                self.core_rpc.show_message(
+                    title.to_owned(),
+                    ShowMessageParams {
+                        typ: MessageType::ERROR,
+                        message: e.to_string(),
+                    },
+                );
+            }
+        }
+    }
+
+    /// Waits for the edits language servers asked for along with creating,
+    /// renaming or deleting files, and has the client apply them before the
+    /// files change, so that they end up in the editor's undo history like
+    /// other server edits. Servers too slow to answer are left out.
+    fn apply_file_operation_edit(
+        &self,
+        edit: Receiver<Option<WorkspaceEdit>>,
+        map: impl FnOnce(WorkspaceEdit) -> WorkspaceEdit,
+    ) {
+        let edit = match edit.recv_timeout(FILE_OPERATION_EDIT_TIMEOUT) {
+            Ok(Some(edit)) => map(edit),
+            Ok(None) => return,
+            Err(err) => {
+                tracing::error!("{:?}", err);
+                return;
+            }
+        };
+        match self.core_rpc.request_workspace_edit(edit) {
+            Ok(CoreResponse::ApplyWorkspaceEditResponse {
+                failure_reason: Some(reason),
+                ..
+            }) => tracing::error!("{reason}"),
+            Ok(_) => {}
+            Err(err) => tracing::error!("{:?}", err),
+        }
+    }
+
+    /// Reads the file into a new buffer, replacing any the file had, and lets
+    /// the language servers know about it.
+    fn open_buffer(&mut self, buffer_id: BufferId, path: PathBuf) -> &Buffer {
+        self.renamed_buffers.remove(&path);
+        let buffer = Buffer::new(buffer_id, path.clone());
+        self.catalog_rpc.did_open_document(
+            &path,
+            buffer.language_id.to_string(),
+            buffer.rev as i32,
+            buffer.rope.to_string(),
+        );
+        self.file_watcher.watch(&path, false, OPEN_FILE_EVENT_TOKEN);
+        self.buffers.insert(path.clone(), buffer);
+        &self.buffers[&path]
+    }
+
     fn get_buffer_or_insert(&mut self, path: PathBuf) -> &mut Buffer {
         self.buffers
             .entry(path.clone())
             .or_insert(Buffer::new(BufferId::next(), path))
     }
+
+    /// Creates, renames and deletes the files of the edit, then hands its text
+    /// edits to the client, which applies them to its documents the way it
+    /// does with the edits of language servers. Editing the buffers here would
+    /// leave the client with stale revisions.
+    fn apply_workspace_edit(&mut self, edit: WorkspaceEdit) -> Result<()> {
+        let mut changes = edit.changes.unwrap_or_default();
+        let operations = match edit.document_changes {
+            Some(DocumentChanges::Edits(edits)) => edits
+                .into_iter()
+                .map(DocumentChangeOperation::Edit)
+                .collect(),
+            Some(DocumentChanges::Operations(operations)) => operations,
+            None => Vec::new(),
+        };
+        for operation in operations {
+            match operation {
+                DocumentChangeOperation::Edit(edit) => {
+                    changes.entry(edit.text_document.uri).or_default().extend(
+                        edit.edits.into_iter().map(|edit| match edit {
+                            OneOf::Left(edit) => edit,
+                            OneOf::Right(edit) => edit.text_edit,
+                        }),
+                    );
+                }
+                DocumentChangeOperation::Op(op) => {
+                    self.apply_resource_op(op)?;
+                }
+            }
+        }
+        if !changes.is_empty() {
+            self.core_rpc.apply_workspace_edit(WorkspaceEdit {
+                changes: Some(changes),
+                ..Default::default()
+            });
+        }
+        Ok(())
+    }
+
+    fn apply_resource_op(&mut self, op: ResourceOp) -> Result<()> {
+        match op {
+            ResourceOp::Create(create) => {
+                let path = create
+                    .uri
+                    .to_file_path()
+                    .map_err(|_| anyhow!("{} is not a file path", create.uri))?;
+                let options = create.options.unwrap_or_default();
+                if path.exists() && !options.overwrite.unwrap_or(false) {
+                    if options.ignore_if_exists.unwrap_or(false) {
+                        return Ok(());
+                    }
+                    return Err(anyhow!("{path:?} already exists"));
+                }
+                if let Some(parent) = path.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                fs::File::create(&path)?;
+            }
+            ResourceOp::Rename(rename) => {
+                let from = rename
+                    .old_uri
+                    .to_file_path()
+                    .map_err(|_| anyhow!("{} is not a file path", rename.old_uri))?;
+                let to = rename
+                    .new_uri
+                    .to_file_path()
+                    .map_err(|_| anyhow!("{} is not a file path", rename.new_uri))?;
+                let options = rename.options.unwrap_or_default();
+                if to.exists() && !options.overwrite.unwrap_or(false) {
+                    if options.ignore_if_exists.unwrap_or(false) {
+                        return Ok(());
+                    }
+                    return Err(anyhow!("{to:?} already exists"));
+                }
+                fs::rename(&from, &to)?;
+                self.buffers.remove(&from);
+            }
+            ResourceOp::Delete(delete) => {
+                let path = delete
+                    .uri
+                    .to_file_path()
+                    .map_err(|_| anyhow!("{} is not a file path", delete.uri))?;
+                let options = delete.options.unwrap_or_default();
+                if !path.exists() {
+                    if options.ignore_if_not_exists.unwrap_or(false) {
+                        return Ok(());
+                    }
+                    return Err(anyhow!("{path:?} doesn't exist"));
+                }
+                if path.is_dir() {
+                    if options.recursive.unwrap_or(false) {
+                        fs::remove_dir_all(&path)?;
+                    } else {
+                        fs::remove_dir(&path)?;
+                    }
+                } else {
+                    fs::remove_file(&path)?;
+                }
+                self.buffers.remove(&path);
+            }
+        }
+        Ok(())
+    }
 }
 
 struct FileWatchNotifier {
     core_rpc: CoreRpcHandler,
     proxy_rpc: ProxyRpcHandler,
     workspace: Option<PathBuf>,
+    code_graph: Option<CodeGraphIndex>,
     workspace_fs_change_handler: Arc<Mutex<Option<Sender<bool>>>>,
     last_diff: Arc<Mutex<DiffInfo>>,
 }
@@ -1258,11 +2349,13 @@ fn new(
         workspace: Option<PathBuf>,
         core_rpc: CoreRpcHandler,
         proxy_rpc: ProxyRpcHandler,
+        code_graph: Option<CodeGraphIndex>,
     ) -> Self {
         let notifier = Self {
             workspace,
             core_rpc,
             proxy_rpc,
+            code_graph,
             workspace_fs_change_handler: Arc::new(Mutex::new(None)),
             last_diff: Arc::new(Mutex::new(DiffInfo::default())),
         };
@@ -1320,6 +2413,10 @@ fn handle_workspace_fs_event(&self, event: notify::Event) {
             _ => return,
         };
 
+        if let Some(code_graph) = self.code_graph.as_ref() {
+            code_graph.update(event.paths.clone());
+        }
+
         let mut handler = self.workspace_fs_change_handler.lock();
         if let Some(sender) = handler.as_mut() {
             if explorer_change {
@@ -1380,6 +2477,133 @@ pub struct DiffHunk {
     pub header: String,
 }
 
+/// Points the edits for files under `from` which aren't open at where they
+/// will be under `to`, as the client opens them after the rename. The open
+/// ones are edited where they are, and move along with their files.
+fn rename_edit_uris(
+    mut edit: WorkspaceEdit,
+    from: &Path,
+    to: &Path,
+    is_open: impl Fn(&Path) -> bool,
+) -> WorkspaceEdit {
+    let rename = |uri: &mut Url| {
+        let Some(suffix) = uri.to_file_path().ok().and_then(|path| {
+            if is_open(&path) {
+                return None;
+            }
+            path.strip_prefix(from).ok().map(Path::to_path_buf)
+        }) else {
+            return;
+        };
+        let path = if suffix.as_os_str().is_empty() {
+            to.to_path_buf()
+        } else {
+            to.join(suffix)
+        };
+        if let Ok(new_uri) = Url::from_file_path(path) {
+            *uri = new_uri;
+        }
+    };
+    if let Some(changes) = edit.changes.take() {
+        edit.changes = Some(
+            changes
+                .into_iter()
+                .map(|(mut uri, edits)| {
+                    rename(&mut uri);
+                    (uri, edits)
+                })
+                .collect(),
+        );
+    }
+    match edit.document_changes.as_mut() {
+        Some(DocumentChanges::Edits(edits)) => {
+            for edit in edits {
+                rename(&mut edit.text_document.uri);
+            }
+        }
+        Some(DocumentChanges::Operations(operations)) => {
+            for operation in operations {
+                if let DocumentChangeOperation::Edit(edit) = operation {
+                    rename(&mut edit.text_document.uri);
+                }
+            }
+        }
+        None => {}
+    }
+    edit
+}
+
+/// Run the operation, reporting its progress, and then either send the new
+/// diff info, ask the user for credentials, or show what went wrong.
+fn git_remote_operation(
+    core_rpc: &CoreRpcHandler,
+    workspace_path: &Path,
+    operation: RemoteOperation,
+    credentials: Option<GitCredentials>,
+) {
+    let title = operation.title();
+    let token = NumberOrString::String(format!("git-{}", title.to_lowercase()));
+    let send_progress = |progress: WorkDoneProgress| {
+        core_rpc.work_done_progress(ProgressParams {
+            token: token.clone(),
+            value: ProgressParamsValue::WorkDone(progress),
+        });
+    };
+    send_progress(WorkDoneProgress::Begin(WorkDoneProgressBegin {
+        title: format!("Git {title}"),
+        cancellable: Some(false),
+        message: None,
+        percentage: Some(0),
+    }));
+    // The callbacks of libgit2 are called for every object, so only a change
+    // of the percentage is reported.
+    let mut last_percentage = 0;
+    let mut progress = |message: String, percentage: u32| {
+        if percentage != last_percentage {
+            last_percentage = percentage;
+            send_progress(WorkDoneProgress::Report(WorkDoneProgressReport {
+                cancellable: Some(false),
+                message: Some(message),
+                percentage: Some(percentage),
+            }));
+        }
+    };
+    let result = match operation {
+        RemoteOperation::Fetch => {
+            git::fetch(workspace_path, credentials, &mut progress)
+        }
+        RemoteOperation::Pull { mode } => {
+            git::pull(workspace_path, mode, credentials, &mut progress)
+        }
+        RemoteOperation::Push => {
+            git::push(workspace_path, credentials, &mut progress)
+        }
+    };
+    send_progress(WorkDoneProgress::End(WorkDoneProgressEnd { message: None }));
+
+    match result {
+        Ok(()) => {
+            if let Some(diff) = git_diff_new(workspace_path) {
+                core_rpc.diff_info(diff);
+            }
+        }
+        Err(e) => match e.downcast::<git::CredentialsRequired>() {
+            Ok(required) => {
+                core_rpc.git_credentials_required(operation, required.url);
+            }
+            Err(e) => {
+                core_rpc.show_message(
+                    format!("Git {title} failure"),
+                    ShowMessageParams {
+                        typ: MessageType::ERROR,
+                        message: e.to_string(),
+                    },
+                );
+            }
+        },
+    }
+}
+
 fn git_init(workspace_path: &Path) -> Result<()> {
     if Repository::discover(workspace_path).is_err() {
         Repository::init(workspace_path)?;
@@ -1533,7 +2757,7 @@ fn git_diff_new(workspace_path: &Path) -> Option<DiffInfo> {
         }
     }
 
-    let mut deltas = Vec::new();
+    let mut unstaged = Vec::new();
     let mut diff_options = DiffOptions::new();
     let diff = repo
         .diff_index_to_workdir(
@@ -1547,7 +2771,7 @@ fn git_diff_new(workspace_path: &Path) -> Option<DiffInfo> {
         .ok()?;
     for delta in diff.deltas() {
         if let Some(delta) = git_delta_format(workspace_path, &delta) {
-            deltas.push(delta);
+            unstaged.push(delta);
         }
     }
 
@@ -1556,6 +2780,7 @@ fn git_diff_new(workspace_path: &Path) -> Option<DiffInfo> {
         _ => Oid::zero(),
     };
 
+    let mut staged = Vec::new();
     let cached_diff = repo
         .diff_tree_to_index(repo.find_tree(oid).ok().as_ref(), None, None)
         .ok();
@@ -1563,10 +2788,22 @@ fn git_diff_new(workspace_path: &Path) -> Option<DiffInfo> {
     if let Some(cached_diff) = cached_diff {
         for delta in cached_diff.deltas() {
             if let Some(delta) = git_delta_format(workspace_path, &delta) {
-                deltas.push(delta);
+                staged.push(delta);
             }
         }
     }
+
+    Some(DiffInfo {
+        head: name,
+        branches,
+        tags,
+        staged: git_file_diffs(staged),
+        unstaged: git_file_diffs(unstaged),
+    })
+}
+
+/// Pair the added and deleted files with the same content as renames.
+fn git_file_diffs(deltas: Vec<(git2::Delta, git2::Oid, PathBuf)>) -> Vec<FileDiff> {
     let mut renames = Vec::new();
     let mut renamed_deltas = HashSet::new();
 
@@ -1608,12 +2845,7 @@ fn git_diff_new(workspace_path: &Path) -> Option<DiffInfo> {
         | FileDiff::Renamed(p, _)
         | FileDiff::Deleted(p) => p.clone(),
     });
-    Some(DiffInfo {
-        head: name,
-        branches,
-        tags,
-        diffs: file_diffs,
-    })
+    file_diffs
 }
 
 fn file_get_head(workspace_path: &Path, path: &Path) -> Result<(String, String)> {
@@ -1674,88 +2906,91 @@ fn git_get_remote_file_url(workspace_path: &Path, file: &Path) -> Result<String>
     Ok(url)
 }
 
-fn search_in_path(
-    id: u64,
-    current_id: &AtomicU64,
-    paths: impl Iterator<Item = PathBuf>,
-    pattern: &str,
-    case_sensitive: bool,
-    whole_word: bool,
-    is_regex: bool,
-) -> Result<ProxyResponse, RpcError> {
-    let mut matches = IndexMap::new();
-    let mut matcher = RegexMatcherBuilder::new();
-    let matcher = matcher.case_insensitive(!case_sensitive).word(whole_word);
-    let matcher = if is_regex {
-        matcher.build(pattern)
-    } else {
-        matcher.build_literals(&[&regex::escape(pattern)])
+// The paths are unix ones.
+#[cfg(all(test, unix))]
+mod tests {
+    use std::{collections::HashMap, path::Path};
+
+    use lsp_types::{
+        DocumentChangeOperation, DocumentChanges, OneOf,
+        OptionalVersionedTextDocumentIdentifier, Position, Range, TextDocumentEdit,
+        TextEdit, Url, WorkspaceEdit,
     };
-    let matcher = matcher.map_err(|_| RpcError {
-        code: 0,
-        message: "can't build matcher".to_string(),
-    })?;
-    let mut searcher = SearcherBuilder::new().build();
-
-    for path in paths {
-        if current_id.load(Ordering::SeqCst) != id {
-            return Err(RpcError {
-                code: 0,
-                message: "expired search job".to_string(),
-            });
-        }
 
-        if path.is_file() {
-            let mut line_matches = Vec::new();
-            if let Err(err) = searcher.search_path(
-                &matcher,
-                path.clone(),
-                UTF8(|lnum, line| {
-                    if current_id.load(Ordering::SeqCst) != id {
-                        return Ok(false);
-                    }
+    use super::rename_edit_uris;
 
-                    let mymatch = matcher.find(line.as_bytes())?.unwrap();
-                    let line = if line.len() > 200 {
-                        // Shorten the line to avoid sending over absurdly long-lines
-                        // (such as in minified javascript)
-                        // Note that the start/end are column based, not absolute from the
-                        // start of the file.
-                        let left_keep = line[..mymatch.start()]
-                            .chars()
-                            .rev()
-                            .take(100)
-                            .map(|c| c.len_utf8())
-                            .sum::<usize>();
-                        let right_keep = line[mymatch.end()..]
-                            .chars()
-                            .take(100)
-                            .map(|c| c.len_utf8())
-                            .sum::<usize>();
-                        let display_range =
-                            mymatch.start() - left_keep..mymatch.end() + right_keep;
-                        line[display_range].to_string()
-                    } else {
-                        line.to_string()
-                    };
-                    line_matches.push(SearchMatch {
-                        line: lnum as usize,
-                        start: mymatch.start(),
-                        end: mymatch.end(),
-                        line_content: line,
-                    });
-                    Ok(true)
-                }),
-            ) {
-                {
-                    tracing::error!("{:?}", err);
-                }
-            }
-            if !line_matches.is_empty() {
-                matches.insert(path.clone(), line_matches);
-            }
+    fn uri(path: &str) -> Url {
+        Url::from_file_path(path).unwrap()
+    }
+
+    fn text_edit() -> TextEdit {
+        TextEdit::new(
+            Range::new(Position::new(0, 0), Position::new(0, 0)),
+            "mod a;\n".to_string(),
+        )
+    }
+
+    fn document_edit(path: &str) -> TextDocumentEdit {
+        TextDocumentEdit {
+            text_document: OptionalVersionedTextDocumentIdentifier {
+                uri: uri(path),
+                version: None,
+            },
+            edits: vec![OneOf::Left(text_edit())],
         }
     }
 
-    Ok(ProxyResponse::GlobalSearchResponse { matches })
+    #[test]
+    fn test_rename_edit_uris() {
+        let (from, to) = (Path::new("/ws/src/old"), Path::new("/ws/src/new"));
+
+        let edit = WorkspaceEdit {
+            changes: Some(HashMap::from([
+                (uri("/ws/src/old"), vec![text_edit()]),
+                (uri("/ws/src/old/mod.rs"), vec![text_edit()]),
+                (uri("/ws/src/old/open.rs"), vec![text_edit()]),
+                (uri("/ws/src/lib.rs"), vec![text_edit()]),
+            ])),
+            ..Default::default()
+        };
+        // Open files are edited before they move.
+        let is_open = |path: &Path| path == Path::new("/ws/src/old/open.rs");
+        let mut uris = rename_edit_uris(edit, from, to, is_open)
+            .changes
+            .unwrap()
+            .into_keys()
+            .collect::<Vec<_>>();
+        uris.sort();
+        assert_eq!(
+            uris,
+            vec![
+                uri("/ws/src/lib.rs"),
+                uri("/ws/src/new"),
+                uri("/ws/src/new/mod.rs"),
+                uri("/ws/src/old/open.rs")
+            ]
+        );
+
+        let edit = WorkspaceEdit {
+            document_changes: Some(DocumentChanges::Operations(vec![
+                DocumentChangeOperation::Edit(document_edit("/ws/src/old/a.rs")),
+                DocumentChangeOperation::Edit(document_edit("/ws/src/older.rs")),
+            ])),
+            ..Default::default()
+        };
+        let Some(DocumentChanges::Operations(operations)) =
+            rename_edit_uris(edit, from, to, |_| false).document_changes
+        else {
+            panic!("the document changes are gone");
+        };
+        let uris = operations
+            .into_iter()
+            .filter_map(|operation| match operation {
+                DocumentChangeOperation::Edit(edit) => Some(edit.text_document.uri),
+                DocumentChangeOperation::Op(_) => None,
+            })
+            .collect::<Vec<_>>();
+        // Only whole path components are renamed.
+        assert_eq!(uris, vec![uri("/ws/src/new/a.rs"), uri("/ws/src/older.rs")]);
+    }
 }
@@ -0,0 +1,1522 @@
+//! Changes to the git repository of the workspace, other than committing and
+//! checking out, which are with the rest of the requests in the dispatcher, and
+//! its history, branches, tags and stashes, and fetching, pulling and pushing.
+//!
+//! Parts of a file are staged by writing a blob for the index which has only
+//! the selected changes of the file applied, the way `git add -p` does, rather
+//! than by building patches for `git apply`.
+
+use std::{
+    collections::{HashMap, hash_map::Entry},
+    fmt,
+    ops::Range,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Result, anyhow};
+use git2::{
+    BranchType, Commit, Config, Cred, CredentialType, Delta, Diff, DiffOptions,
+    ErrorCode, FetchOptions, Index, IndexEntry, IndexTime, Oid, Patch, PushOptions,
+    RebaseOptions, RemoteCallbacks, Repository, Signature, Sort, StashApplyOptions,
+    StashFlags, StatusOptions, build::CheckoutBuilder,
+};
+use phidi_rpc::source_control::{
+    BlameHunk, BranchInfo, CommitInfo, FileDiff, GitCredentials, GitLogFilter,
+    LogEntry, PullMode, StashEntry,
+};
+
+/// Add the files, as they are on disk, to the index.
+pub fn stage_files(workspace_path: &Path, files: &[PathBuf]) -> Result<()> {
+    let repo = Repository::discover(workspace_path)?;
+    let mut index = repo.index()?;
+    for path in files {
+        let relative = relative_path(&repo, path)?;
+        if path.exists() {
+            index.add_path(relative)?;
+        } else {
+            index.remove_path(relative)?;
+        }
+    }
+    index.write()?;
+    Ok(())
+}
+
+/// Put the files in the index back to how they are in `HEAD`.
+pub fn unstage_files(workspace_path: &Path, files: &[PathBuf]) -> Result<()> {
+    let repo = Repository::discover(workspace_path)?;
+    let relative = files
+        .iter()
+        .map(|path| relative_path(&repo, path))
+        .collect::<Result<Vec<_>>>()?;
+    // Without a commit yet, the files are only taken out of the index.
+    let head = repo.head().and_then(|head| head.peel_to_commit()).ok();
+    repo.reset_default(head.as_ref().map(|head| head.as_object()), relative)?;
+    Ok(())
+}
+
+/// Stage the changes of `content`, the text of the file, which are on the
+/// given lines of it. A deletion is on the line before which it was deleted.
+pub fn stage_lines(
+    workspace_path: &Path,
+    path: &Path,
+    content: &str,
+    ranges: &[Range<usize>],
+) -> Result<()> {
+    let repo = Repository::discover(workspace_path)?;
+    let relative = relative_path(&repo, path)?;
+    let mut index = repo.index()?;
+    let staged = index_content(&repo, &index, relative)?;
+    let new_staged = apply_selected_changes(&staged, content, |line, deleted| {
+        in_ranges(ranges, line, deleted)
+    })?;
+    write_index_content(&mut index, relative, &new_staged)
+}
+
+/// Unstage the staged changes which are on the given lines of `content`, the
+/// text of the file.
+pub fn unstage_lines(
+    workspace_path: &Path,
+    path: &Path,
+    content: &str,
+    ranges: &[Range<usize>],
+) -> Result<()> {
+    let repo = Repository::discover(workspace_path)?;
+    let relative = relative_path(&repo, path)?;
+    let mut index = repo.index()?;
+    let head = head_content(&repo, relative)?;
+    let staged = index_content(&repo, &index, relative)?;
+    // The lines are of the file, which can have unstaged changes on top.
+    let staged_ranges = map_ranges_to_old(&staged, content, ranges)?;
+    let new_staged = apply_selected_changes(&head, &staged, |line, deleted| {
+        !in_ranges(&staged_ranges, line, deleted)
+    })?;
+    write_index_content(&mut index, relative, &new_staged)
+}
+
+/// Who last changed each line of `content`, the text of the file, which can
+/// have changes that aren't committed yet.
+pub fn blame(
+    workspace_path: &Path,
+    path: &Path,
+    content: &str,
+) -> Result<Vec<BlameHunk>> {
+    let repo = Repository::discover(workspace_path)?;
+    let relative = relative_path(&repo, path)?;
+    let committed = repo
+        .head()
+        .and_then(|head| head.peel_to_tree())
+        .is_ok_and(|tree| tree.get_path(relative).is_ok());
+    if !committed {
+        return Ok(vec![BlameHunk {
+            lines: 0..content.lines().count().max(1),
+            commit: None,
+        }]);
+    }
+
+    let blame = repo.blame_file(relative, None)?;
+    let blame = blame.blame_buffer(content.as_bytes())?;
+    let mut commits: HashMap<git2::Oid, CommitInfo> = HashMap::new();
+    let mut hunks = Vec::new();
+    for hunk in blame.iter() {
+        let start = hunk.final_start_line().saturating_sub(1);
+        let id = hunk.final_commit_id();
+        // Lines which are only in the buffer are blamed on no commit.
+        let commit = if id.is_zero() {
+            None
+        } else {
+            match commits.entry(id) {
+                Entry::Occupied(entry) => Some(entry.get().clone()),
+                Entry::Vacant(entry) => {
+                    let commit = commit_info(&repo.find_commit(id)?);
+                    Some(entry.insert(commit).clone())
+                }
+            }
+        };
+        hunks.push(BlameHunk {
+            lines: start..start + hunk.lines_in_hunk(),
+            commit,
+        });
+    }
+    Ok(hunks)
+}
+
+/// The content of the file in `revision`, which is empty if the file or the
+/// revision doesn't exist, like the parent of the first commit.
+pub fn file_at_revision(
+    workspace_path: &Path,
+    path: &Path,
+    revision: &str,
+) -> Result<String> {
+    let repo = Repository::discover(workspace_path)?;
+    let relative = relative_path(&repo, path)?;
+    let Ok(object) = repo.revparse_single(revision) else {
+        return Ok(String::new());
+    };
+    let tree = object.peel_to_tree()?;
+    match tree.get_path(relative) {
+        Ok(entry) => blob_content(&repo, entry.id()),
+        Err(_) => Ok(String::new()),
+    }
+}
+
+/// A page of the commits which match the filter, newest first, and whether
+/// there are more of them after it. The page starts after the commit `after`,
+/// the last one of the previous page, without matching the commits before it
+/// again.
+pub fn log(
+    workspace_path: &Path,
+    filter: &GitLogFilter,
+    after: Option<&str>,
+    limit: usize,
+) -> Result<(Vec<LogEntry>, bool)> {
+    let repo = Repository::discover(workspace_path)?;
+    let start =
+        match repo.revparse_single(filter.branch.as_deref().unwrap_or("HEAD")) {
+            Ok(object) => object.peel_to_commit()?,
+            // A repository without commits has no history to show yet.
+            Err(_) if filter.branch.is_none() => return Ok((Vec::new(), false)),
+            Err(err) => return Err(err.into()),
+        };
+    let path = filter
+        .path
+        .as_deref()
+        .map(|path| relative_path(&repo, path))
+        .transpose()?
+        .filter(|path| !path.as_os_str().is_empty());
+    let author = filter.author.as_deref().map(str::to_lowercase);
+    let message = filter.message.as_deref().map(str::to_lowercase);
+    let after = after.map(Oid::from_str).transpose()?;
+
+    let mut revwalk = repo.revwalk()?;
+    revwalk.set_sorting(Sort::TOPOLOGICAL | Sort::TIME)?;
+    revwalk.push(start.id())?;
+    let mut commits = Vec::new();
+    let mut resumed = after.is_none();
+    for id in revwalk {
+        let id = id?;
+        if !resumed {
+            resumed = Some(id) == after;
+            continue;
+        }
+        let commit = repo.find_commit(id)?;
+        if !commit_matches(
+            &repo,
+            &commit,
+            author.as_deref(),
+            message.as_deref(),
+            path,
+        )? {
+            continue;
+        }
+        if commits.len() == limit {
+            return Ok((commits, true));
+        }
+        commits.push(LogEntry {
+            commit: commit_info(&commit),
+            parents: commit.parent_ids().map(|id| id.to_string()).collect(),
+        });
+    }
+    Ok((commits, false))
+}
+
+/// The files which the commit changed compared to its first parent, with
+/// their paths in the work tree.
+pub fn commit_changes(workspace_path: &Path, commit: &str) -> Result<Vec<FileDiff>> {
+    let repo = Repository::discover(workspace_path)?;
+    let workdir = repo
+        .workdir()
+        .ok_or_else(|| anyhow!("the repository has no work tree"))?;
+    let commit = repo.revparse_single(commit)?.peel_to_commit()?;
+    let mut diff = commit_diff(&repo, &commit, None)?;
+    diff.find_similar(None)?;
+
+    let mut diffs = Vec::new();
+    for delta in diff.deltas() {
+        let old = delta.old_file().path().map(|path| workdir.join(path));
+        let new = delta.new_file().path().map(|path| workdir.join(path));
+        let diff = match (delta.status(), old, new) {
+            (Delta::Added | Delta::Copied, _, Some(new)) => FileDiff::Added(new),
+            (Delta::Deleted, Some(old), _) => FileDiff::Deleted(old),
+            (Delta::Modified, _, Some(new)) => FileDiff::Modified(new),
+            (Delta::Renamed, Some(old), Some(new)) => FileDiff::Renamed(new, old),
+            _ => continue,
+        };
+        diffs.push(diff);
+    }
+    Ok(diffs)
+}
+
+/// The local branches, with how they compare to the branches they track.
+pub fn branches(workspace_path: &Path) -> Result<Vec<BranchInfo>> {
+    let repo = Repository::discover(workspace_path)?;
+    let head = repo.head().ok().and_then(|head| head.target());
+    let mut branches = Vec::new();
+    for branch in repo.branches(Some(BranchType::Local))? {
+        let (branch, _) = branch?;
+        let Some(name) = branch.name()? else {
+            continue;
+        };
+        let target = branch.get().target();
+        let upstream = branch.upstream().ok();
+        let upstream_target = upstream.as_ref().and_then(|u| u.get().target());
+        let (ahead, behind) = match (target, upstream_target) {
+            (Some(local), Some(remote)) => repo.graph_ahead_behind(local, remote)?,
+            _ => (0, 0),
+        };
+        let merged = match (target, head) {
+            (Some(target), Some(head)) => is_ancestor(&repo, target, head)?,
+            _ => false,
+        };
+        branches.push(BranchInfo {
+            name: name.to_string(),
+            is_head: branch.is_head(),
+            upstream: match &upstream {
+                Some(upstream) => upstream.name()?.map(str::to_string),
+                None => None,
+            },
+            ahead,
+            behind,
+            merged,
+        });
+    }
+    Ok(branches)
+}
+
+/// Create a branch at HEAD, and check it out if asked to, which leaves the
+/// work tree as it is.
+pub fn create_branch(
+    workspace_path: &Path,
+    name: &str,
+    checkout: bool,
+) -> Result<()> {
+    let repo = Repository::discover(workspace_path)?;
+    let head = repo.head()?.peel_to_commit()?;
+    let branch = repo.branch(name, &head, false)?;
+    if checkout {
+        let reference = branch
+            .get()
+            .name()
+            .ok_or_else(|| anyhow!("the branch name isn't valid UTF-8"))?;
+        repo.set_head(reference)?;
+    }
+    Ok(())
+}
+
+pub fn rename_branch(workspace_path: &Path, from: &str, to: &str) -> Result<()> {
+    let repo = Repository::discover(workspace_path)?;
+    repo.find_branch(from, BranchType::Local)?
+        .rename(to, false)?;
+    Ok(())
+}
+
+/// Delete the local branch. Unless forced, only a branch whose commits are all
+/// in HEAD is deleted, like `git branch -d` does.
+pub fn delete_branch(workspace_path: &Path, name: &str, force: bool) -> Result<()> {
+    let repo = Repository::discover(workspace_path)?;
+    let mut branch = repo.find_branch(name, BranchType::Local)?;
+    if branch.is_head() {
+        return Err(anyhow!("the branch '{name}' is checked out"));
+    }
+    if !force {
+        let merged = match (branch.get().target(), repo.head()?.target()) {
+            (Some(target), Some(head)) => is_ancestor(&repo, target, head)?,
+            _ => false,
+        };
+        if !merged {
+            return Err(anyhow!("the branch '{name}' isn't merged into HEAD"));
+        }
+    }
+    branch.delete()?;
+    Ok(())
+}
+
+/// Merge the branch, or any other revision, into HEAD, fast-forwarding when
+/// HEAD has no commits of its own. Merges with conflicts are refused before
+/// anything is changed, as there is nowhere to resolve them yet.
+pub fn merge(workspace_path: &Path, reference: &str) -> Result<()> {
+    let repo = Repository::discover(workspace_path)?;
+    let ours = repo.head()?.peel_to_commit()?;
+    let theirs = repo.revparse_single(reference)?.peel_to_commit()?;
+    if is_ancestor(&repo, theirs.id(), ours.id())? {
+        return Ok(());
+    }
+
+    if is_ancestor(&repo, ours.id(), theirs.id())? {
+        return fast_forward(
+            &repo,
+            &theirs,
+            &format!("merge {reference}: Fast-forward"),
+        );
+    }
+
+    let mut index = repo.merge_commits(&ours, &theirs, None)?;
+    if index.has_conflicts() {
+        return Err(anyhow!(
+            "merging '{reference}' has conflicts, which have to be resolved \
+             outside of the editor"
+        ));
+    }
+    let tree = repo.find_tree(index.write_tree_to(&repo)?)?;
+    let mut checkout = CheckoutBuilder::new();
+    checkout.safe();
+    repo.checkout_tree(tree.as_object(), Some(&mut checkout))?;
+    let signature = signature(&repo)?;
+    repo.commit(
+        Some("HEAD"),
+        &signature,
+        &signature,
+        &format!("Merge branch '{reference}'"),
+        &tree,
+        &[&ours, &theirs],
+    )?;
+    Ok(())
+}
+
+/// Tag HEAD, with an annotated tag when there is a message.
+pub fn create_tag(
+    workspace_path: &Path,
+    name: &str,
+    message: Option<&str>,
+) -> Result<()> {
+    let repo = Repository::discover(workspace_path)?;
+    let target = repo.head()?.peel_to_commit()?;
+    match message {
+        Some(message) => {
+            let signature = signature(&repo)?;
+            repo.tag(name, target.as_object(), &signature, message, false)?;
+        }
+        None => {
+            repo.tag_lightweight(name, target.as_object(), false)?;
+        }
+    }
+    Ok(())
+}
+
+pub fn stash_push(
+    workspace_path: &Path,
+    message: Option<&str>,
+    include_untracked: bool,
+) -> Result<()> {
+    let mut repo = Repository::discover(workspace_path)?;
+    let signature = signature(&repo)?;
+    let flags = if include_untracked {
+        StashFlags::INCLUDE_UNTRACKED
+    } else {
+        StashFlags::DEFAULT
+    };
+    repo.stash_save2(&signature, message, Some(flags))?;
+    Ok(())
+}
+
+pub fn stashes(workspace_path: &Path) -> Result<Vec<StashEntry>> {
+    let mut repo = Repository::discover(workspace_path)?;
+    let mut stashes = Vec::new();
+    repo.stash_foreach(|index, message, id| {
+        stashes.push(StashEntry {
+            index,
+            message: message.to_string(),
+            id: id.to_string(),
+        });
+        true
+    })?;
+    Ok(stashes)
+}
+
+/// Apply the stash, keeping what was staged staged, and drop it afterwards
+/// when popping it.
+pub fn stash_apply(workspace_path: &Path, index: usize, pop: bool) -> Result<()> {
+    let mut repo = Repository::discover(workspace_path)?;
+    let mut options = StashApplyOptions::new();
+    options.reinstantiate_index();
+    if pop {
+        repo.stash_pop(index, Some(&mut options))?;
+    } else {
+        repo.stash_apply(index, Some(&mut options))?;
+    }
+    Ok(())
+}
+
+pub fn stash_drop(workspace_path: &Path, index: usize) -> Result<()> {
+    let mut repo = Repository::discover(workspace_path)?;
+    repo.stash_drop(index)?;
+    Ok(())
+}
+
+/// The remote asked for a username and password, and there were none left to
+/// try: the credential helper had none, or the ones the user typed in were
+/// refused.
+#[derive(Debug)]
+pub struct CredentialsRequired {
+    pub url: String,
+}
+
+impl fmt::Display for CredentialsRequired {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "authentication to {} failed", self.url)
+    }
+}
+
+impl std::error::Error for CredentialsRequired {}
+
+/// Fetch from the remote of the current branch, or from `origin` when it
+/// doesn't track one. `progress` is called with a message and a percentage.
+pub fn fetch(
+    workspace_path: &Path,
+    credentials: Option<GitCredentials>,
+    progress: &mut dyn FnMut(String, u32),
+) -> Result<()> {
+    let repo = Repository::discover(workspace_path)?;
+    let (_, remote) = current_branch_remote(&repo)?;
+    fetch_remote(&repo, &remote, credentials, progress)
+}
+
+/// Fetch, then bring the current branch up to date with its upstream, by
+/// fast-forwarding it or by rebasing its own commits onto the upstream ones.
+pub fn pull(
+    workspace_path: &Path,
+    mode: PullMode,
+    credentials: Option<GitCredentials>,
+    progress: &mut dyn FnMut(String, u32),
+) -> Result<()> {
+    let repo = Repository::discover(workspace_path)?;
+    let (branch, remote) = current_branch_remote(&repo)?;
+    fetch_remote(&repo, &remote, credentials, progress)?;
+
+    let upstream = repo
+        .find_branch(&branch, BranchType::Local)?
+        .upstream()
+        .map_err(|_| anyhow!("the branch '{branch}' doesn't track a branch"))?;
+    let ours = repo.head()?.peel_to_commit()?;
+    let theirs = upstream.get().peel_to_commit()?;
+    if is_ancestor(&repo, theirs.id(), ours.id())? {
+        return Ok(());
+    }
+    if is_ancestor(&repo, ours.id(), theirs.id())? {
+        return fast_forward(&repo, &theirs, "pull: Fast-forward");
+    }
+    match mode {
+        PullMode::FastForward => Err(anyhow!(
+            "the branch '{branch}' and its upstream have diverged, pull with \
+             rebase instead"
+        )),
+        PullMode::Rebase => rebase_onto(&repo, upstream.get()),
+    }
+}
+
+/// Push the current branch to the branch it tracks, or to a branch of the same
+/// name on `origin`, which it tracks afterwards, like `git push -u` does.
+pub fn push(
+    workspace_path: &Path,
+    credentials: Option<GitCredentials>,
+    progress: &mut dyn FnMut(String, u32),
+) -> Result<()> {
+    let repo = Repository::discover(workspace_path)?;
+    let (branch, remote_name) = current_branch_remote(&repo)?;
+    let local = format!("refs/heads/{branch}");
+    let merge = repo.branch_upstream_merge(&local).ok();
+    let target = match merge.as_ref().and_then(|merge| merge.as_str()) {
+        Some(merge) => merge.to_string(),
+        None => local.clone(),
+    };
+
+    let mut remote = repo.find_remote(&remote_name)?;
+    let mut authenticator = Authenticator::new(repo.config()?, credentials);
+    let mut rejection = None;
+    let result = {
+        let mut callbacks = RemoteCallbacks::new();
+        callbacks.credentials(|url, username, allowed| {
+            authenticator.credentials(url, username, allowed)
+        });
+        callbacks.push_transfer_progress(|current, total, _| {
+            if total > 0 {
+                progress(
+                    format!("{current}/{total} objects"),
+                    percentage(current, total),
+                );
+            }
+        });
+        callbacks.push_update_reference(|reference, status| {
+            if let Some(status) = status {
+                rejection = Some(format!("{reference}: {status}"));
+            }
+            Ok(())
+        });
+        let mut options = PushOptions::new();
+        options.remote_callbacks(callbacks);
+        remote.push(&[format!("{local}:{target}")], Some(&mut options))
+    };
+    authenticator.check(result)?;
+    if let Some(rejection) = rejection {
+        return Err(anyhow!("the remote refused the push of {rejection}"));
+    }
+
+    if merge.is_none() {
+        repo.find_branch(&branch, BranchType::Local)?
+            .set_upstream(Some(&format!("{remote_name}/{branch}")))?;
+    }
+    Ok(())
+}
+
+pub fn commit_info(commit: &Commit) -> CommitInfo {
+    let author = commit.author();
+    CommitInfo {
+        id: commit.id().to_string(),
+        author: author.name().unwrap_or_default().to_string(),
+        email: author.email().unwrap_or_default().to_string(),
+        time: author.when().seconds(),
+        summary: commit.summary().unwrap_or_default().to_string(),
+    }
+}
+
+/// Whether `ancestor` is `commit` or one of its ancestors.
+fn is_ancestor(
+    repo: &Repository,
+    ancestor: git2::Oid,
+    commit: git2::Oid,
+) -> Result<bool> {
+    Ok(ancestor == commit || repo.graph_descendant_of(commit, ancestor)?)
+}
+
+/// Check the commit out and point the current branch at it. A safe checkout
+/// fails without touching anything when it would overwrite changes in the work
+/// tree.
+fn fast_forward(repo: &Repository, commit: &Commit, message: &str) -> Result<()> {
+    let mut checkout = CheckoutBuilder::new();
+    checkout.safe();
+    repo.checkout_tree(commit.as_object(), Some(&mut checkout))?;
+    repo.head()?.set_target(commit.id(), message)?;
+    Ok(())
+}
+
+/// Rebase the commits of HEAD which aren't in `upstream` onto it. A rebase
+/// with conflicts is aborted, as there is nowhere to resolve them yet.
+fn rebase_onto(repo: &Repository, upstream: &git2::Reference) -> Result<()> {
+    let mut status_options = StatusOptions::new();
+    status_options.include_untracked(false);
+    if !repo.statuses(Some(&mut status_options))?.is_empty() {
+        return Err(anyhow!(
+            "there are uncommitted changes, commit or stash them before pulling \
+             with rebase"
+        ));
+    }
+
+    let signature = signature(repo)?;
+    let upstream = repo.reference_to_annotated_commit(upstream)?;
+    let mut rebase =
+        repo.rebase(None, Some(&upstream), None, Some(&mut RebaseOptions::new()))?;
+    while let Some(operation) = rebase.next() {
+        operation?;
+        if repo.index()?.has_conflicts() {
+            rebase.abort()?;
+            return Err(anyhow!(
+                "rebasing onto the upstream has conflicts, which have to be \
+                 resolved outside of the editor"
+            ));
+        }
+        match rebase.commit(None, &signature, None) {
+            // The upstream already has the changes of the commit.
+            Err(e) if e.code() == ErrorCode::Applied => {}
+            result => {
+                result?;
+            }
+        }
+    }
+    rebase.finish(Some(&signature))?;
+    Ok(())
+}
+
+/// The name of the current branch, and the remote it tracks, which is `origin`
+/// when it doesn't track one yet.
+fn current_branch_remote(repo: &Repository) -> Result<(String, String)> {
+    let head = repo.head()?;
+    if !head.is_branch() {
+        return Err(anyhow!("HEAD is detached, check out a branch first"));
+    }
+    let branch = head
+        .shorthand()
+        .ok_or_else(|| anyhow!("the branch name isn't valid UTF-8"))?
+        .to_string();
+    let reference = head
+        .name()
+        .ok_or_else(|| anyhow!("the branch name isn't valid UTF-8"))?;
+    let remote = match repo.branch_upstream_remote(reference) {
+        Ok(remote) => remote
+            .as_str()
+            .ok_or_else(|| anyhow!("the remote name isn't valid UTF-8"))?
+            .to_string(),
+        Err(_) if repo.find_remote("origin").is_ok() => "origin".to_string(),
+        Err(_) => {
+            return Err(anyhow!(
+                "the branch '{branch}' doesn't track a branch and there is no \
+                 'origin' remote"
+            ));
+        }
+    };
+    Ok((branch, remote))
+}
+
+fn fetch_remote(
+    repo: &Repository,
+    remote: &str,
+    credentials: Option<GitCredentials>,
+    progress: &mut dyn FnMut(String, u32),
+) -> Result<()> {
+    let mut remote = repo.find_remote(remote)?;
+    let mut authenticator = Authenticator::new(repo.config()?, credentials);
+    let result = {
+        let mut callbacks = RemoteCallbacks::new();
+        callbacks.credentials(|url, username, allowed| {
+            authenticator.credentials(url, username, allowed)
+        });
+        callbacks.transfer_progress(|stats| {
+            let total = stats.total_objects();
+            if total > 0 {
+                let received = stats.received_objects();
+                progress(
+                    format!("{received}/{total} objects"),
+                    percentage(received, total),
+                );
+            }
+            true
+        });
+        let mut options = FetchOptions::new();
+        options.remote_callbacks(callbacks);
+        remote.fetch::<&str>(&[], Some(&mut options), None)
+    };
+    authenticator.check(result)
+}
+
+fn percentage(current: usize, total: usize) -> u32 {
+    (current * 100 / total.max(1)) as u32
+}
+
+/// Answers the credential requests of a remote: with the SSH agent for SSH
+/// remotes, and with what the user typed in or else with the configured
+/// credential helper for the rest. Each is tried once, as libgit2 keeps asking
+/// for as long as authentication fails.
+struct Authenticator {
+    config: Config,
+    credentials: Option<GitCredentials>,
+    tried_agent: bool,
+    tried_helper: bool,
+    /// The url which wanted a username and password when none were left.
+    required: Option<String>,
+}
+
+impl Authenticator {
+    fn new(config: Config, credentials: Option<GitCredentials>) -> Self {
+        Self {
+            config,
+            credentials,
+            tried_agent: false,
+            tried_helper: false,
+            required: None,
+        }
+    }
+
+    fn credentials(
+        &mut self,
+        url: &str,
+        username: Option<&str>,
+        allowed: CredentialType,
+    ) -> Result<Cred, git2::Error> {
+        if allowed.contains(CredentialType::USERNAME) {
+            return Cred::username(username.unwrap_or("git"));
+        }
+        if allowed.contains(CredentialType::SSH_KEY) && !self.tried_agent {
+            self.tried_agent = true;
+            return Cred::ssh_key_from_agent(username.unwrap_or("git"));
+        }
+        if allowed.contains(CredentialType::USER_PASS_PLAINTEXT) {
+            if let Some(credentials) = self.credentials.take() {
+                return Cred::userpass_plaintext(
+                    &credentials.username,
+                    &credentials.password,
+                );
+            }
+            if !self.tried_helper {
+                self.tried_helper = true;
+                if let Ok(cred) =
+                    Cred::credential_helper(&self.config, url, username)
+                {
+                    return Ok(cred);
+                }
+            }
+            self.required = Some(url.to_string());
+        }
+        Err(git2::Error::from_str("no credentials left to try"))
+    }
+
+    /// Turn a failure for a lack of credentials into [`CredentialsRequired`].
+    fn check(&mut self, result: Result<(), git2::Error>) -> Result<()> {
+        match (result, self.required.take()) {
+            (Ok(()), _) => Ok(()),
+            (Err(_), Some(url)) => Err(CredentialsRequired { url }.into()),
+            (Err(e), None) => Err(e.into()),
+        }
+    }
+}
+
+fn signature(repo: &Repository) -> Result<Signature<'static>> {
+    repo.signature().map_err(|e| match e.code() {
+        ErrorCode::NotFound => anyhow!(
+            "No user.name and/or user.email configured for this git repository."
+        ),
+        _ => anyhow!("Error while creating commit's signature: {}", e.message()),
+    })
+}
+
+fn commit_matches(
+    repo: &Repository,
+    commit: &Commit,
+    author: Option<&str>,
+    message: Option<&str>,
+    path: Option<&Path>,
+) -> Result<bool> {
+    if let Some(author) = author {
+        let signature = commit.author();
+        let name = signature.name().unwrap_or_default().to_lowercase();
+        let email = signature.email().unwrap_or_default().to_lowercase();
+        if !name.contains(author) && !email.contains(author) {
+            return Ok(false);
+        }
+    }
+    if let Some(message) = message {
+        let text = commit.message().unwrap_or_default().to_lowercase();
+        if !text.contains(message) {
+            return Ok(false);
+        }
+    }
+    if let Some(path) = path {
+        let diff = commit_diff(repo, commit, Some(path))?;
+        return Ok(diff.deltas().next().is_some());
+    }
+    Ok(true)
+}
+
+/// The diff of the commit against its first parent, or against nothing for
+/// the first commit.
+fn commit_diff<'a>(
+    repo: &'a Repository,
+    commit: &Commit,
+    path: Option<&Path>,
+) -> Result<Diff<'a>> {
+    let parent_tree = match commit.parents().next() {
+        Some(parent) => Some(parent.tree()?),
+        None => None,
+    };
+    let mut options = DiffOptions::new();
+    if let Some(path) = path {
+        options.pathspec(path);
+    }
+    Ok(repo.diff_tree_to_tree(
+        parent_tree.as_ref(),
+        Some(&commit.tree()?),
+        Some(&mut options),
+    )?)
+}
+
+fn relative_path<'a>(repo: &Repository, path: &'a Path) -> Result<&'a Path> {
+    let workdir = repo
+        .workdir()
+        .ok_or_else(|| anyhow!("the repository has no work tree"))?;
+    Ok(path.strip_prefix(workdir)?)
+}
+
+fn head_content(repo: &Repository, relative: &Path) -> Result<String> {
+    let Ok(tree) = repo.head().and_then(|head| head.peel_to_tree()) else {
+        return Ok(String::new());
+    };
+    let Ok(entry) = tree.get_path(relative) else {
+        return Ok(String::new());
+    };
+    blob_content(repo, entry.id())
+}
+
+fn index_content(
+    repo: &Repository,
+    index: &Index,
+    relative: &Path,
+) -> Result<String> {
+    match index.get_path(relative, 0) {
+        Some(entry) => blob_content(repo, entry.id),
+        None => Ok(String::new()),
+    }
+}
+
+fn blob_content(repo: &Repository, id: git2::Oid) -> Result<String> {
+    let blob = repo.find_blob(id)?;
+    if blob.is_binary() {
+        return Err(anyhow!("the file is binary"));
+    }
+    Ok(std::str::from_utf8(blob.content())?.to_string())
+}
+
+fn write_index_content(
+    index: &mut Index,
+    relative: &Path,
+    content: &str,
+) -> Result<()> {
+    let entry = index.get_path(relative, 0).unwrap_or_else(|| IndexEntry {
+        ctime: IndexTime::new(0, 0),
+        mtime: IndexTime::new(0, 0),
+        dev: 0,
+        ino: 0,
+        mode: 0o100644,
+        uid: 0,
+        gid: 0,
+        file_size: 0,
+        id: git2::Oid::zero(),
+        flags: 0,
+        flags_extended: 0,
+        path: relative.to_string_lossy().replace('\\', "/").into_bytes(),
+    });
+    index.add_frombuffer(&entry, content.as_bytes())?;
+    index.write()?;
+    Ok(())
+}
+
+/// Whether a change on `line` is in the ranges. An empty range selects the
+/// deletions before its line, and only those.
+fn in_ranges(ranges: &[Range<usize>], line: usize, deleted: bool) -> bool {
+    ranges.iter().any(|range| {
+        if deleted && range.is_empty() {
+            line == range.start
+        } else {
+            range.contains(&line)
+        }
+    })
+}
+
+/// The hunks of the line diff of `old` and `new`, without context.
+fn line_patch<'a>(old: &'a str, new: &'a str) -> Result<Patch<'a>> {
+    let mut options = DiffOptions::new();
+    options.context_lines(0);
+    Patch::from_buffers(
+        old.as_bytes(),
+        None,
+        new.as_bytes(),
+        None,
+        Some(&mut options),
+    )
+    .map_err(Into::into)
+}
+
+/// The line in `old` of the first line of a hunk, and that in `new`. For no
+/// lines, the hunk's start is the line before, which makes it the line after.
+fn hunk_starts(hunk: &git2::DiffHunk) -> (usize, usize) {
+    let start = |start: u32, lines: u32| {
+        if lines == 0 {
+            start as usize
+        } else {
+            start as usize - 1
+        }
+    };
+    (
+        start(hunk.old_start(), hunk.old_lines()),
+        start(hunk.new_start(), hunk.new_lines()),
+    )
+}
+
+/// Apply the changes from `old` to `new` which are `selected`, by their line
+/// in `new` and whether they are deletions, to `old`.
+fn apply_selected_changes(
+    old: &str,
+    new: &str,
+    selected: impl Fn(usize, bool) -> bool,
+) -> Result<String> {
+    let old_lines = old.split_inclusive('\n').collect::<Vec<_>>();
+    let new_lines = new.split_inclusive('\n').collect::<Vec<_>>();
+    let patch = line_patch(old, new)?;
+
+    let mut result = String::with_capacity(old.len());
+    let mut push_line = |line: &str| {
+        if !result.is_empty() && !result.ends_with('\n') {
+            result.push('\n');
+        }
+        result.push_str(line);
+    };
+    let mut old_line = 0;
+    for hunk_index in 0..patch.num_hunks() {
+        let (hunk, lines) = patch.hunk(hunk_index)?;
+        let (old_start, new_start) = hunk_starts(&hunk);
+        for line in &old_lines[old_line..old_start.min(old_lines.len())] {
+            push_line(line);
+        }
+        old_line = old_start;
+        for i in 0..lines {
+            let line = patch.line_in_hunk(hunk_index, i)?;
+            match line.origin() {
+                '-' => {
+                    let Some(lineno) = line.old_lineno() else {
+                        continue;
+                    };
+                    let lineno = lineno as usize - 1;
+                    if !selected(new_start, true) {
+                        push_line(old_lines[lineno]);
+                    }
+                    old_line = lineno + 1;
+                }
+                '+' => {
+                    let Some(lineno) = line.new_lineno() else {
+                        continue;
+                    };
+                    let lineno = lineno as usize - 1;
+                    if selected(lineno, false) {
+                        push_line(new_lines[lineno]);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+    for line in &old_lines[old_line.min(old_lines.len())..] {
+        push_line(line);
+    }
+    Ok(result)
+}
+
+/// The ranges of lines of `new` as lines of `old`. A range which starts or
+/// ends in a changed part of `new` covers that whole part in `old`.
+fn map_ranges_to_old(
+    old: &str,
+    new: &str,
+    ranges: &[Range<usize>],
+) -> Result<Vec<Range<usize>>> {
+    let patch = line_patch(old, new)?;
+    let mut hunks = Vec::new();
+    for hunk_index in 0..patch.num_hunks() {
+        let (hunk, _) = patch.hunk(hunk_index)?;
+        let (old_start, new_start) = hunk_starts(&hunk);
+        hunks.push((
+            old_start..old_start + hunk.old_lines() as usize,
+            new_start..new_start + hunk.new_lines() as usize,
+        ));
+    }
+    let map_line = |line: usize, end: bool| {
+        let mut old_line = line;
+        for (old, new) in &hunks {
+            if line < new.start {
+                break;
+            }
+            if new.contains(&line) {
+                return if end { old.end } else { old.start };
+            }
+            old_line = line - new.end + old.end;
+        }
+        if end { old_line + 1 } else { old_line }
+    };
+    Ok(ranges
+        .iter()
+        .map(|range| {
+            let start = map_line(range.start, false);
+            if range.is_empty() {
+                start..start
+            } else {
+                start..map_line(range.end - 1, true).max(start)
+            }
+        })
+        .collect())
+}
+
+#[cfg(test)]
+#[allow(clippy::single_range_in_vec_init)]
+mod tests {
+    use std::{fs, path::Path};
+
+    use git2::{BranchType, CredentialType, Oid, Repository};
+
+    use phidi_rpc::source_control::{
+        FileDiff, GitCredentials, GitLogFilter, PullMode,
+    };
+
+    use super::{
+        Authenticator, CredentialsRequired, apply_selected_changes, blame, branches,
+        commit_changes, create_branch, create_tag, delete_branch, fetch,
+        file_at_revision, in_ranges, log, merge, pull, push, rename_branch,
+        stage_lines, stash_apply, stash_drop, stash_push, stashes, unstage_lines,
+    };
+
+    /// A new repository in `path`, whose commits are made by Someone.
+    fn init(path: &Path) -> Repository {
+        configure(Repository::init(path).unwrap())
+    }
+
+    fn configure(repo: Repository) -> Repository {
+        let mut config = repo.config().unwrap();
+        config.set_str("user.name", "Someone").unwrap();
+        config.set_str("user.email", "someone@example.com").unwrap();
+        repo
+    }
+
+    /// Write the files, or remove those without content, and commit them on
+    /// top of the head.
+    fn commit(
+        repo: &Repository,
+        files: &[(&str, Option<&str>)],
+        message: &str,
+    ) -> Oid {
+        let workdir = repo.workdir().unwrap();
+        let mut index = repo.index().unwrap();
+        index.read(true).unwrap();
+        for (file, content) in files {
+            let path = workdir.join(file);
+            match content {
+                Some(content) => {
+                    fs::create_dir_all(path.parent().unwrap()).unwrap();
+                    fs::write(&path, content).unwrap();
+                    index.add_path(file.as_ref()).unwrap();
+                }
+                None => {
+                    fs::remove_file(&path).unwrap();
+                    index.remove_path(file.as_ref()).unwrap();
+                }
+            }
+        }
+        index.write().unwrap();
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+        let signature = repo.signature().unwrap();
+        let parent = repo.head().ok().map(|h| h.peel_to_commit().unwrap());
+        let parents = parent.iter().collect::<Vec<_>>();
+        repo.commit(
+            Some("HEAD"),
+            &signature,
+            &signature,
+            message,
+            &tree,
+            &parents,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_apply_selected_changes() {
+        let old = "a\nb\nc\nd\n";
+        let new = "a\nB\nc\nd\ne\n";
+        let only_first = apply_selected_changes(old, new, |line, deleted| {
+            in_ranges(&[1..2], line, deleted)
+        })
+        .unwrap();
+        assert_eq!(only_first, "a\nB\nc\nd\n");
+        let only_last = apply_selected_changes(old, new, |line, deleted| {
+            in_ranges(&[4..5], line, deleted)
+        })
+        .unwrap();
+        assert_eq!(only_last, "a\nb\nc\nd\ne\n");
+        let deletion = apply_selected_changes(old, "a\nc\nd\n", |line, deleted| {
+            in_ranges(&[1..1], line, deleted)
+        })
+        .unwrap();
+        assert_eq!(deletion, "a\nc\nd\n");
+    }
+
+    #[test]
+    fn test_stage_and_unstage_lines() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        let path = dir.path().join("file.txt");
+        fs::write(&path, "a\nb\nc\n").unwrap();
+        let mut index = repo.index().unwrap();
+        index.add_path("file.txt".as_ref()).unwrap();
+        index.write().unwrap();
+
+        let staged = || {
+            let mut index = repo.index().unwrap();
+            index.read(true).unwrap();
+            let entry = index.get_path("file.txt".as_ref(), 0).unwrap();
+            let blob = repo.find_blob(entry.id).unwrap();
+            String::from_utf8(blob.content().to_vec()).unwrap()
+        };
+
+        let content = "A\nb\nc\nd\n";
+        stage_lines(dir.path(), &path, content, &[3..4]).unwrap();
+        assert_eq!(staged(), "a\nb\nc\nd\n");
+        stage_lines(dir.path(), &path, content, &[0..1]).unwrap();
+        assert_eq!(staged(), content);
+        unstage_lines(dir.path(), &path, content, &[3..4]).unwrap();
+        assert_eq!(staged(), "A\nb\nc\n");
+    }
+
+    #[test]
+    fn test_stage_one_of_the_deletions() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = init(dir.path());
+        commit(&repo, &[("file.txt", Some("a\nb\nc\nd\ne\n"))], "Add file");
+
+        // `b` and `e` are deleted, and only the deletion of `b` is staged.
+        let path = dir.path().join("file.txt");
+        let content = "a\nc\nd\n";
+        fs::write(&path, content).unwrap();
+        stage_lines(dir.path(), &path, content, &[1..1]).unwrap();
+
+        let mut index = repo.index().unwrap();
+        index.read(true).unwrap();
+        let entry = index.get_path("file.txt".as_ref(), 0).unwrap();
+        let blob = repo.find_blob(entry.id).unwrap();
+        assert_eq!(blob.content(), b"a\nc\nd\ne\n");
+    }
+
+    #[test]
+    fn test_blame() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = init(dir.path());
+        let id = commit(&repo, &[("file.txt", Some("a\nb\nc\n"))], "Add file");
+
+        let path = dir.path().join("file.txt");
+        let hunks = blame(dir.path(), &path, "a\nB\nc\n").unwrap();
+        let lines = hunks
+            .iter()
+            .map(|hunk| (hunk.lines.clone(), hunk.commit.is_some()))
+            .collect::<Vec<_>>();
+        assert_eq!(lines, vec![(0..1, true), (1..2, false), (2..3, true)]);
+        let commit = hunks[0].commit.as_ref().unwrap();
+        assert_eq!(commit.id, id.to_string());
+        assert_eq!(commit.author, "Someone");
+        assert_eq!(commit.summary, "Add file");
+    }
+
+    #[test]
+    fn test_file_at_revision() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = init(dir.path());
+        let id = commit(&repo, &[("file.txt", Some("a\nb\nc\n"))], "Add file");
+
+        let path = dir.path().join("file.txt");
+        let revision = id.to_string();
+        assert_eq!(
+            file_at_revision(dir.path(), &path, &revision).unwrap(),
+            "a\nb\nc\n"
+        );
+        assert_eq!(
+            file_at_revision(dir.path(), &path, &format!("{revision}^")).unwrap(),
+            ""
+        );
+    }
+
+    /// A repository with three commits, returned newest first: Alice adds
+    /// `a.txt`, Bob adds `src/b.txt` and Alice renames `a.txt` to `c.txt`.
+    fn history(path: &Path) -> [String; 3] {
+        let repo = init(path);
+        let commit_as = |name: &str, files, message| {
+            let mut config = repo.config().unwrap();
+            config.set_str("user.name", name).unwrap();
+            config
+                .set_str("user.email", &format!("{name}@example.com"))
+                .unwrap();
+            commit(&repo, files, message).to_string()
+        };
+        let first = commit_as("Alice", &[("a.txt", Some("a\n"))], "Add a");
+        let second = commit_as("Bob", &[("src/b.txt", Some("b\n"))], "Add b");
+        let third = commit_as(
+            "Alice",
+            &[("a.txt", None), ("c.txt", Some("a\n"))],
+            "Rename a",
+        );
+        [third, second, first]
+    }
+
+    /// The ids of a page of the log, and whether there are more after it.
+    fn log_ids(
+        path: &Path,
+        filter: &GitLogFilter,
+        after: Option<&str>,
+        limit: usize,
+    ) -> (Vec<String>, bool) {
+        let (commits, has_more) = log(path, filter, after, limit).unwrap();
+        let ids = commits.into_iter().map(|entry| entry.commit.id).collect();
+        (ids, has_more)
+    }
+
+    #[test]
+    fn test_log() {
+        let dir = tempfile::tempdir().unwrap();
+        init(dir.path());
+        let all = GitLogFilter::default();
+        assert_eq!(log_ids(dir.path(), &all, None, 10), (Vec::new(), false));
+
+        let [third, second, first] = history(dir.path());
+        assert_eq!(
+            log_ids(dir.path(), &all, None, 10),
+            (vec![third, second.clone(), first], false)
+        );
+        let entries = log(dir.path(), &all, None, 1).unwrap().0;
+        assert_eq!(entries[0].parents, vec![second]);
+    }
+
+    #[test]
+    fn test_log_pages() {
+        let dir = tempfile::tempdir().unwrap();
+        let [third, second, first] = history(dir.path());
+        let all = GitLogFilter::default();
+        assert_eq!(
+            log_ids(dir.path(), &all, None, 2),
+            (vec![third, second.clone()], true)
+        );
+        assert_eq!(
+            log_ids(dir.path(), &all, Some(&second), 2),
+            (vec![first.clone()], false)
+        );
+        assert_eq!(
+            log_ids(dir.path(), &all, Some(&first), 2),
+            (Vec::new(), false)
+        );
+    }
+
+    #[test]
+    fn test_log_filters() {
+        let dir = tempfile::tempdir().unwrap();
+        let [third, second, first] = history(dir.path());
+        let by_author = GitLogFilter {
+            author: Some("alice".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            log_ids(dir.path(), &by_author, None, 10),
+            (vec![third.clone(), first.clone()], false)
+        );
+        assert_eq!(
+            log_ids(dir.path(), &by_author, Some(&third), 10),
+            (vec![first.clone()], false)
+        );
+        let by_message = GitLogFilter {
+            message: Some("ADD".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            log_ids(dir.path(), &by_message, None, 10),
+            (vec![second.clone(), first], false)
+        );
+        let by_path = GitLogFilter {
+            path: Some(dir.path().join("src")),
+            ..Default::default()
+        };
+        assert_eq!(
+            log_ids(dir.path(), &by_path, None, 10),
+            (vec![second], false)
+        );
+    }
+
+    #[test]
+    fn test_commit_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        let [third, _, first] = history(dir.path());
+        assert_eq!(
+            commit_changes(dir.path(), &third).unwrap(),
+            vec![FileDiff::Renamed(
+                dir.path().join("c.txt"),
+                dir.path().join("a.txt")
+            )]
+        );
+        assert_eq!(
+            commit_changes(dir.path(), &first).unwrap(),
+            vec![FileDiff::Added(dir.path().join("a.txt"))]
+        );
+    }
+
+    /// Check out the branch, dropping the changes of the work tree.
+    fn checkout(repo: &Repository, branch: &str) {
+        repo.set_head(&format!("refs/heads/{branch}")).unwrap();
+        repo.checkout_head(Some(git2::build::CheckoutBuilder::new().force()))
+            .unwrap();
+    }
+
+    #[test]
+    fn test_create_and_delete_branch() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = init(dir.path());
+        commit(&repo, &[("a.txt", Some("a\n"))], "a");
+        let main = repo.head().unwrap().shorthand().unwrap().to_string();
+
+        create_branch(dir.path(), "feature", true).unwrap();
+        assert_eq!(repo.head().unwrap().shorthand(), Some("feature"));
+        commit(&repo, &[("b.txt", Some("b\n"))], "b");
+        let info = branches(dir.path()).unwrap();
+        let feature = info.iter().find(|b| b.name == "feature").unwrap();
+        assert!(feature.is_head && feature.merged);
+        let main_info = info.iter().find(|b| b.name == main).unwrap();
+        assert!(!main_info.is_head && main_info.merged);
+
+        // The branch has commits which aren't merged into the head.
+        checkout(&repo, &main);
+        assert!(delete_branch(dir.path(), "feature", false).is_err());
+        create_branch(dir.path(), "other", false).unwrap();
+        rename_branch(dir.path(), "other", "topic").unwrap();
+        assert!(repo.find_branch("other", BranchType::Local).is_err());
+        delete_branch(dir.path(), "topic", false).unwrap();
+        assert!(repo.find_branch("topic", BranchType::Local).is_err());
+    }
+
+    #[test]
+    fn test_merge() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = init(dir.path());
+        commit(&repo, &[("a.txt", Some("a\n"))], "a");
+        let main = repo.head().unwrap().shorthand().unwrap().to_string();
+        create_branch(dir.path(), "topic", true).unwrap();
+        commit(&repo, &[("b.txt", Some("b\n"))], "b");
+
+        // Main is fast-forwarded to the topic.
+        checkout(&repo, &main);
+        merge(dir.path(), "topic").unwrap();
+        assert!(dir.path().join("b.txt").exists());
+
+        // Diverged branches are merged with a merge commit.
+        commit(&repo, &[("c.txt", Some("c\n"))], "c");
+        checkout(&repo, "topic");
+        commit(&repo, &[("d.txt", Some("d\n"))], "d");
+        merge(dir.path(), &main).unwrap();
+        let head = repo.head().unwrap().peel_to_commit().unwrap();
+        assert_eq!(head.parent_count(), 2);
+        assert!(dir.path().join("c.txt").exists());
+
+        repo.find_branch("topic", BranchType::Local)
+            .unwrap()
+            .set_upstream(Some(&main))
+            .unwrap();
+        let info = branches(dir.path()).unwrap();
+        let topic = info.iter().find(|b| b.name == "topic").unwrap();
+        assert_eq!(topic.upstream.as_deref(), Some(main.as_str()));
+        assert_eq!((topic.ahead, topic.behind), (2, 0));
+    }
+
+    #[test]
+    fn test_create_tag() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = init(dir.path());
+        let head = commit(&repo, &[("a.txt", Some("a\n"))], "a");
+
+        create_tag(dir.path(), "light", None).unwrap();
+        create_tag(dir.path(), "annotated", Some("A release")).unwrap();
+        let tag = repo.revparse_single("annotated").unwrap();
+        assert_eq!(tag.as_tag().unwrap().message(), Some("A release"));
+        assert_eq!(repo.revparse_single("light").unwrap().id(), head);
+    }
+
+    #[test]
+    fn test_stashes() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = init(dir.path());
+        commit(&repo, &[("a.txt", Some("a\n"))], "a");
+        let path = dir.path().join("a.txt");
+
+        fs::write(&path, "changed\n").unwrap();
+        stash_push(dir.path(), Some("work in progress"), false).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "a\n");
+        let list = stashes(dir.path()).unwrap();
+        assert_eq!(list.len(), 1);
+        assert!(list[0].message.contains("work in progress"));
+        stash_apply(dir.path(), 0, false).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "changed\n");
+        stash_drop(dir.path(), 0).unwrap();
+        assert!(stashes(dir.path()).unwrap().is_empty());
+    }
+
+    /// A repository in `path` with a commit pushed to a new bare repository
+    /// in `remote`, and the name of its branch.
+    fn pushed(remote: &Path, path: &Path) -> (Repository, String) {
+        Repository::init_bare(remote).unwrap();
+        let repo = init(path);
+        repo.remote("origin", remote.to_str().unwrap()).unwrap();
+        commit(&repo, &[("a.txt", Some("a\n"))], "a");
+        push(path, None, &mut |_, _| {}).unwrap();
+        let branch = repo.head().unwrap().shorthand().unwrap().to_string();
+        (repo, branch)
+    }
+
+    #[test]
+    fn test_push() {
+        let remote = tempfile::tempdir().unwrap();
+        let one = tempfile::tempdir().unwrap();
+        let (repo, branch) = pushed(remote.path(), one.path());
+
+        let upstream = repo
+            .find_branch(&branch, BranchType::Local)
+            .unwrap()
+            .upstream()
+            .unwrap();
+        assert_eq!(
+            upstream.name().unwrap(),
+            Some(format!("origin/{branch}").as_str())
+        );
+        let remote = Repository::open_bare(remote.path()).unwrap();
+        assert_eq!(
+            remote
+                .find_branch(&branch, BranchType::Local)
+                .unwrap()
+                .get()
+                .target(),
+            repo.head().unwrap().target()
+        );
+    }
+
+    #[test]
+    fn test_pull_fast_forward() {
+        let mut progress = |_: String, _: u32| {};
+        let remote = tempfile::tempdir().unwrap();
+        let one = tempfile::tempdir().unwrap();
+        let (repo_one, _) = pushed(remote.path(), one.path());
+        let two = tempfile::tempdir().unwrap();
+        let url = remote.path().to_str().unwrap();
+        let repo_two = configure(Repository::clone(url, two.path()).unwrap());
+
+        let pushed = commit(&repo_two, &[("b.txt", Some("b\n"))], "b");
+        push(two.path(), None, &mut progress).unwrap();
+        pull(one.path(), PullMode::FastForward, None, &mut progress).unwrap();
+        assert_eq!(repo_one.head().unwrap().target(), Some(pushed));
+        assert!(one.path().join("b.txt").exists());
+    }
+
+    #[test]
+    fn test_fetch_and_pull_rebase() {
+        let mut progress = |_: String, _: u32| {};
+        let remote = tempfile::tempdir().unwrap();
+        let one = tempfile::tempdir().unwrap();
+        let (repo_one, branch) = pushed(remote.path(), one.path());
+        let two = tempfile::tempdir().unwrap();
+        let url = remote.path().to_str().unwrap();
+        let repo_two = configure(Repository::clone(url, two.path()).unwrap());
+
+        let local = commit(&repo_one, &[("c.txt", Some("c\n"))], "c");
+        let pushed = commit(&repo_two, &[("d.txt", Some("d\n"))], "d");
+        push(two.path(), None, &mut progress).unwrap();
+        fetch(one.path(), None, &mut progress).unwrap();
+        let tracking = format!("refs/remotes/origin/{branch}");
+        assert_eq!(
+            repo_one.find_reference(&tracking).unwrap().target(),
+            Some(pushed)
+        );
+
+        // The branches diverged, so they can't be fast-forwarded.
+        assert!(
+            pull(one.path(), PullMode::FastForward, None, &mut progress).is_err()
+        );
+        pull(one.path(), PullMode::Rebase, None, &mut progress).unwrap();
+        let head = repo_one.head().unwrap().peel_to_commit().unwrap();
+        assert_ne!(head.id(), local);
+        assert_eq!(head.summary(), Some("c"));
+        assert_eq!(head.parent_id(0).unwrap(), pushed);
+        assert!(one.path().join("d.txt").exists());
+        assert_eq!(repo_one.head().unwrap().shorthand(), Some(branch.as_str()));
+    }
+
+    #[test]
+    fn test_authenticator() {
+        let credentials = GitCredentials {
+            username: "someone".to_string(),
+            password: "secret".to_string(),
+        };
+        let mut authenticator =
+            Authenticator::new(git2::Config::new().unwrap(), Some(credentials));
+        let url = "https://example.com/repo.git";
+        let allowed = CredentialType::USER_PASS_PLAINTEXT;
+        assert!(authenticator.credentials(url, None, allowed).is_ok());
+        // The typed in credentials were refused and there is no credential
+        // helper configured.
+        let Err(refused) = authenticator.credentials(url, None, allowed) else {
+            panic!("the credentials were given twice");
+        };
+        let error = authenticator.check(Err(refused)).unwrap_err();
+        let required = error.downcast_ref::<CredentialsRequired>().unwrap();
+        assert_eq!(required.url, url);
+    }
+}
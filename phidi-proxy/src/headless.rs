@@ -0,0 +1,321 @@
+//! Headless mode: runs the proxy for a workspace without a window and serves
+//! the proxy rpc over a Unix socket, so that scripts and agents can drive the
+//! same buffers, plugins and language servers as the editor.
+//!
+//! Every connected client speaks the same newline delimited json protocol as
+//! the editor does over stdio. Request ids are scoped to the client that sent
+//! them, and core notifications are broadcast to all connected clients.
+//!
+//! Core requests, such as a language server asking for an edit to be applied,
+//! can't be traced back to the call that caused them, so only one client is
+//! supported in answering them: they are all sent to the client that has been
+//! connected the longest.
+
+use std::{
+    collections::HashMap,
+    io::BufReader,
+    os::unix::net::{UnixListener, UnixStream},
+    path::PathBuf,
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+    thread,
+};
+
+use anyhow::{Result, anyhow};
+use crossbeam_channel::Sender;
+use parking_lot::Mutex;
+use phidi_core::directory::Directory;
+use phidi_rpc::{
+    RequestId, RpcError, RpcMessage,
+    core::{CoreNotification, CoreRequest, CoreResponse, CoreRpc, CoreRpcHandler},
+    proxy::{ProxyNotification, ProxyRequest, ProxyResponse, ProxyRpcHandler},
+    stdio::stdio_transport,
+};
+
+use crate::dispatch::Dispatcher;
+
+type ClientId = u64;
+
+/// Messages written back to a client: core requests and notifications, plus
+/// the responses to the proxy requests it sent.
+type ClientMessage = RpcMessage<CoreRequest, CoreNotification, ProxyResponse>;
+
+/// Messages read from a client: proxy requests and notifications, plus the
+/// responses to core requests.
+type ClientRequest = RpcMessage<ProxyRequest, ProxyNotification, CoreResponse>;
+
+/// The writer channels of the currently connected clients.
+#[derive(Clone, Default)]
+struct Clients {
+    next_id: Arc<AtomicU64>,
+    writers: Arc<Mutex<HashMap<ClientId, Sender<ClientMessage>>>>,
+}
+
+impl Clients {
+    fn insert(&self, writer: Sender<ClientMessage>) -> ClientId {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        self.writers.lock().insert(id, writer);
+        id
+    }
+
+    fn remove(&self, id: ClientId) {
+        self.writers.lock().remove(&id);
+    }
+
+    /// Sends the request to the client connected the longest, returning
+    /// whether there was one to send it to.
+    fn request(&self, id: RequestId, request: CoreRequest) -> bool {
+        let mut writers = self.writers.lock();
+        while let Some(&client) = writers.keys().min() {
+            if writers[&client]
+                .send(RpcMessage::Request(id, request.clone()))
+                .is_ok()
+            {
+                return true;
+            }
+            writers.remove(&client);
+        }
+        false
+    }
+
+    fn broadcast(&self, notification: CoreNotification) {
+        let mut writers = self.writers.lock();
+        // Clients whose writer has gone away are dropped here as well, since
+        // their reader thread may still be blocked on the socket.
+        writers.retain(|_, writer| {
+            writer
+                .send(RpcMessage::Notification(notification.clone()))
+                .is_ok()
+        });
+    }
+}
+
+pub fn mainloop(workspace: PathBuf, socket: Option<PathBuf>) -> Result<()> {
+    let workspace = workspace.canonicalize()?;
+    if !workspace.is_dir() {
+        return Err(anyhow!("{} is not a directory", workspace.display()));
+    }
+    let socket = match socket {
+        Some(socket) => socket,
+        None => Directory::headless_socket()
+            .ok_or_else(|| anyhow!("can't get headless socket path"))?,
+    };
+    if socket.exists() {
+        std::fs::remove_file(&socket)?;
+    }
+    let listener = UnixListener::bind(&socket)?;
+
+    let core_rpc = CoreRpcHandler::new();
+    let proxy_rpc = ProxyRpcHandler::new();
+    let mut dispatcher = Dispatcher::new(core_rpc.clone(), proxy_rpc.clone());
+    let clients = Clients::default();
+
+    {
+        let core_rpc = core_rpc.clone();
+        let clients = clients.clone();
+        let agent = dispatcher.agent.clone();
+        thread::spawn(move || {
+            for msg in core_rpc.rx() {
+                match msg {
+                    CoreRpc::Request(id, rpc) => {
+                        // Without a client to answer, the requester would wait
+                        // forever.
+                        if !clients.request(id, rpc) {
+                            core_rpc.handle_response(
+                                id,
+                                Err(RpcError {
+                                    code: 0,
+                                    message: "no client is connected".to_string(),
+                                }),
+                            );
+                        }
+                    }
+                    CoreRpc::Notification(rpc) => {
+                        agent.handle_core_notification(&rpc);
+                        clients.broadcast(*rpc);
+                    }
+                    CoreRpc::Shutdown => {
+                        return;
+                    }
+                }
+            }
+        });
+    }
+
+    {
+        let proxy_rpc = proxy_rpc.clone();
+        let core_rpc = core_rpc.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                if let Err(err) =
+                    serve_client(stream, &clients, &proxy_rpc, &core_rpc)
+                {
+                    tracing::error!("{:?}", err);
+                }
+            }
+        });
+    }
+
+    proxy_rpc.initialize(
+        Some(workspace),
+        Vec::new(),
+        Vec::new(),
+        HashMap::new(),
+        HashMap::new(),
+        false,
+        1,
+        1,
+    );
+    tracing::info!("phidi-proxy listening on {}", socket.display());
+
+    proxy_rpc.mainloop(&mut dispatcher);
+    core_rpc.shutdown();
+    if let Err(err) = std::fs::remove_file(&socket) {
+        tracing::error!("{:?}", err);
+    }
+    Ok(())
+}
+
+fn serve_client(
+    stream: UnixStream,
+    clients: &Clients,
+    proxy_rpc: &ProxyRpcHandler,
+    core_rpc: &CoreRpcHandler,
+) -> Result<()> {
+    let reader = BufReader::new(stream.try_clone()?);
+    let (writer_tx, writer_rx) = crossbeam_channel::unbounded::<ClientMessage>();
+    let (reader_tx, reader_rx) = crossbeam_channel::unbounded::<ClientRequest>();
+    stdio_transport(stream, writer_rx, reader, reader_tx);
+
+    let client_id = clients.insert(writer_tx.clone());
+    let clients = clients.clone();
+    let proxy_rpc = proxy_rpc.clone();
+    let core_rpc = core_rpc.clone();
+    thread::spawn(move || {
+        for msg in reader_rx {
+            match msg {
+                RpcMessage::Request(id, req) => {
+                    // The proxy assigns its own id to the request, the
+                    // callback maps the response back to the client's id.
+                    let writer_tx = writer_tx.clone();
+                    proxy_rpc.request_async(req, move |result| {
+                        let msg = match result {
+                            Ok(resp) => RpcMessage::Response(id, resp),
+                            Err(e) => RpcMessage::Error(id, e),
+                        };
+                        if let Err(err) = writer_tx.send(msg) {
+                            tracing::error!("{:?}", err);
+                        }
+                    });
+                }
+                RpcMessage::Notification(n) => {
+                    proxy_rpc.notification(n);
+                }
+                RpcMessage::Response(id, resp) => {
+                    core_rpc.handle_response(id, Ok(resp));
+                }
+                RpcMessage::Error(id, err) => {
+                    core_rpc.handle_response(id, Err(err));
+                }
+            }
+        }
+        clients.remove(client_id);
+    });
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, io::BufReader, os::unix::net::UnixStream, thread};
+
+    use lsp_types::WorkspaceEdit;
+    use phidi_rpc::{
+        RpcMessage,
+        core::{CoreRequest, CoreRpcHandler},
+        proxy::{ProxyNotification, ProxyRequest, ProxyResponse, ProxyRpcHandler},
+        stdio::{read_msg, write_msg},
+    };
+
+    use super::{Clients, serve_client};
+    use crate::dispatch::Dispatcher;
+
+    type Message = RpcMessage<ProxyRequest, ProxyNotification, ProxyResponse>;
+
+    #[test]
+    fn test_socket_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("file.txt"), "").unwrap();
+
+        let core_rpc = CoreRpcHandler::new();
+        let proxy_rpc = ProxyRpcHandler::new();
+        let mut dispatcher = Dispatcher::new(core_rpc.clone(), proxy_rpc.clone());
+        let clients = Clients::default();
+
+        // Both clients use the same request id, each gets its own response.
+        let mut streams = Vec::new();
+        for _ in 0..2 {
+            let (client, server) = UnixStream::pair().unwrap();
+            serve_client(server, &clients, &proxy_rpc, &core_rpc).unwrap();
+            streams.push(client);
+        }
+
+        let path = dir.path().to_path_buf();
+        let client = {
+            let proxy_rpc = proxy_rpc.clone();
+            thread::spawn(move || {
+                let mut responses = Vec::new();
+                for mut stream in streams {
+                    let request = ProxyRequest::ReadDir { path: path.clone() };
+                    write_msg(&mut stream, Message::Request(7, request)).unwrap();
+                    let mut reader = BufReader::new(stream);
+                    let response: Message = read_msg(&mut reader).unwrap().unwrap();
+                    responses.push(response);
+                }
+                proxy_rpc.shutdown();
+                responses
+            })
+        };
+        proxy_rpc.mainloop(&mut dispatcher);
+
+        let responses = client.join().unwrap();
+        assert_eq!(responses.len(), 2);
+        for response in responses {
+            let RpcMessage::Response(7, ProxyResponse::ReadDirResponse { items }) =
+                response
+            else {
+                panic!("unexpected response");
+            };
+            assert_eq!(items.len(), 1);
+            assert_eq!(items[0].path, dir.path().join("file.txt"));
+        }
+    }
+
+    #[test]
+    fn test_request_goes_to_oldest_client() {
+        let clients = Clients::default();
+        let (first, first_rx) = crossbeam_channel::unbounded();
+        let (second, second_rx) = crossbeam_channel::unbounded();
+        clients.insert(first);
+        clients.insert(second);
+
+        let request = CoreRequest::ApplyWorkspaceEdit {
+            edit: WorkspaceEdit::default(),
+        };
+        assert!(clients.request(1, request.clone()));
+        assert!(matches!(first_rx.try_recv(), Ok(RpcMessage::Request(1, _))));
+        assert!(second_rx.try_recv().is_err());
+
+        // Once the first client is gone, the next one takes over.
+        drop(first_rx);
+        assert!(clients.request(2, request.clone()));
+        assert!(matches!(
+            second_rx.try_recv(),
+            Ok(RpcMessage::Request(2, _))
+        ));
+
+        drop(second_rx);
+        assert!(!clients.request(3, request));
+    }
+}
@@ -1,14 +1,24 @@
 #![allow(clippy::manual_clamp)]
 
+pub mod agent;
 pub mod buffer;
+pub mod checkpoint;
 pub mod cli;
+pub mod code_graph;
+pub mod context_pack;
 pub mod dispatch;
+pub mod git;
+#[cfg(unix)]
+pub mod headless;
+pub mod mcp;
 pub mod plugin;
+pub mod search;
 pub mod terminal;
 pub mod watcher;
 
 use std::{
     io::{BufReader, stdin, stdout},
+    path::PathBuf,
     process::exit,
     sync::Arc,
     thread,
@@ -30,10 +40,30 @@
 #[derive(Parser)]
 #[clap(name = "Phidi-proxy")]
 #[clap(version = meta::VERSION)]
+#[clap(group(clap::ArgGroup::new("mode").args(["headless", "mcp"])))]
 struct Cli {
     #[clap(short, long, action, hide = true)]
     proxy: bool,
 
+    /// Run without a window for the given workspace, serving the proxy rpc
+    /// to any number of clients over a Unix socket
+    #[clap(long, value_name = "WORKSPACE")]
+    #[clap(value_hint = clap::ValueHint::DirPath)]
+    headless: Option<PathBuf>,
+
+    /// Connect stdio to the Model Context Protocol server of the editor
+    /// session that has the given workspace open, which is served when the
+    /// `agent.mcp-server` setting is on
+    #[clap(long, value_name = "WORKSPACE")]
+    #[clap(value_hint = clap::ValueHint::DirPath)]
+    mcp: Option<PathBuf>,
+
+    /// Socket path to serve on with `--headless`, which defaults to
+    /// `headless.sock` in the local data directory, or to connect to with
+    /// `--mcp`
+    #[clap(long, requires = "mode")]
+    socket: Option<PathBuf>,
+
     /// Paths to file(s) and/or folder(s) to open.
     /// When path is a file (that exists or not),
     /// it accepts `path:line:column` syntax
@@ -45,6 +75,27 @@ struct Cli {
 
 pub fn mainloop() {
     let cli = Cli::parse();
+    if let Some(workspace) = cli.headless {
+        #[cfg(unix)]
+        let result = headless::mainloop(workspace, cli.socket);
+        #[cfg(not(unix))]
+        let result: Result<()> = {
+            let _ = (workspace, cli.socket);
+            Err(anyhow!("headless mode is only supported on unix platforms"))
+        };
+        if let Err(e) = result {
+            error!("failed to run headless proxy: {e}");
+            exit(1);
+        }
+        return;
+    }
+    if let Some(workspace) = cli.mcp {
+        if let Err(e) = mcp::mainloop(workspace, cli.socket) {
+            error!("failed to run mcp server: {e}");
+            exit(1);
+        }
+        return;
+    }
     if !cli.proxy {
         if let Err(e) = cli::try_open_in_existing_process(&cli.paths) {
             error!("failed to open path(s): {e}");
@@ -61,6 +112,7 @@ pub fn mainloop() {
 
     let local_core_rpc = core_rpc.clone();
     let local_writer_tx = writer_tx.clone();
+    let agent = dispatcher.agent.clone();
     thread::spawn(move || {
         for msg in local_core_rpc.rx() {
             match msg {
@@ -72,6 +124,7 @@ pub fn mainloop() {
                     }
                 }
                 CoreRpc::Notification(rpc) => {
+                    agent.handle_core_notification(&rpc);
                     if let Err(err) =
                         local_writer_tx.send(RpcMessage::Notification(rpc))
                     {
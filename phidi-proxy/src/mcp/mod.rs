@@ -0,0 +1,534 @@
+//! A Model Context Protocol server exposing the workspace to external agents.
+//!
+//! The server is attached to the proxy of an editor session and drives it the
+//! same way the editor does, by sending requests through its
+//! [`ProxyRpcHandler`], so every tool is backed by the regular
+//! [`Dispatcher`](crate::dispatch::Dispatcher) handlers, the buffers the user
+//! has open and the language servers they started. Messages are newline
+//! delimited JSON-RPC 2.0, served on a Unix socket per workspace that
+//! `phidi-proxy --mcp` connects stdio to. The socket is only served when the
+//! `agent.mcp-server` setting is on, as anyone who can connect to it can run
+//! commands in the workspace.
+
+pub(crate) mod tools;
+
+use std::{
+    collections::{HashMap, hash_map::DefaultHasher},
+    hash::{Hash, Hasher},
+    io::{BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+    sync::Arc,
+    thread,
+    time::Duration,
+};
+
+use anyhow::{Result, anyhow};
+use lsp_types::Diagnostic;
+use parking_lot::Mutex;
+use phidi_core::{directory::Directory, meta};
+use phidi_rpc::{
+    RpcError,
+    core::CoreNotification,
+    proxy::{ProxyRequest, ProxyResponse, ProxyRpcHandler},
+};
+use serde_json::{Value, json};
+
+const PROTOCOL_VERSIONS: &[&str] = &["2025-06-18", "2025-03-26", "2024-11-05"];
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+
+#[derive(Default)]
+struct McpState {
+    /// Latest diagnostics published for each file.
+    diagnostics: HashMap<PathBuf, Vec<Diagnostic>>,
+}
+
+#[derive(Clone)]
+pub struct McpServer {
+    pub(crate) workspace: PathBuf,
+    proxy_rpc: ProxyRpcHandler,
+    state: Arc<Mutex<McpState>>,
+}
+
+impl McpServer {
+    pub fn new(workspace: PathBuf, proxy_rpc: ProxyRpcHandler) -> Self {
+        Self {
+            workspace: workspace.canonicalize().unwrap_or(workspace),
+            proxy_rpc,
+            state: Arc::new(Mutex::new(McpState::default())),
+        }
+    }
+
+    /// Keeps track of what the proxy reports back to its client, which for the
+    /// MCP server is the only source of diagnostics.
+    pub fn handle_core_notification(&self, notification: &CoreNotification) {
+        if let CoreNotification::PublishDiagnostics { diagnostics } = notification {
+            let Ok(path) = diagnostics.uri.to_file_path() else {
+                return;
+            };
+            let mut state = self.state.lock();
+            if diagnostics.diagnostics.is_empty() {
+                state.diagnostics.remove(&path);
+            } else {
+                state
+                    .diagnostics
+                    .insert(path, diagnostics.diagnostics.clone());
+            }
+        }
+    }
+
+    /// Handles one JSON-RPC message, returning the response to send back if
+    /// the message was a request.
+    pub fn handle_message(&self, msg: Value) -> Option<Value> {
+        let id = msg.get("id").cloned();
+        let Some(method) = msg.get("method").and_then(|m| m.as_str()) else {
+            // Responses to requests we never send are ignored.
+            if msg.get("result").is_some() || msg.get("error").is_some() {
+                return None;
+            }
+            return Some(error_response(
+                id.unwrap_or(Value::Null),
+                INVALID_REQUEST,
+                "missing method",
+            ));
+        };
+        let params = msg.get("params").cloned().unwrap_or(Value::Null);
+        // Notifications such as `notifications/initialized` need no answer.
+        let id = id?;
+        let response = match self.handle_request(method, params) {
+            Ok(result) => json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "result": result,
+            }),
+            Err(err) => error_response(id, err.code, &err.message),
+        };
+        Some(response)
+    }
+
+    fn handle_request(
+        &self,
+        method: &str,
+        params: Value,
+    ) -> Result<Value, RpcError> {
+        match method {
+            "initialize" => {
+                let requested = params
+                    .get("protocolVersion")
+                    .and_then(|v| v.as_str())
+                    .unwrap_or_default();
+                let version = PROTOCOL_VERSIONS
+                    .iter()
+                    .find(|v| **v == requested)
+                    .unwrap_or(&PROTOCOL_VERSIONS[0]);
+                Ok(json!({
+                    "protocolVersion": version,
+                    "capabilities": {
+                        "tools": {},
+                    },
+                    "serverInfo": {
+                        "name": meta::NAME,
+                        "version": meta::VERSION,
+                    },
+                }))
+            }
+            "ping" => Ok(json!({})),
+            "tools/list" => Ok(json!({ "tools": tools::list() })),
+            "tools/call" => {
+                let name = params.get("name").and_then(|n| n.as_str()).ok_or_else(
+                    || RpcError {
+                        code: INVALID_PARAMS,
+                        message: "missing tool name".to_string(),
+                    },
+                )?;
+                let arguments =
+                    params.get("arguments").cloned().unwrap_or(json!({}));
+                let result = tools::call(self, name, arguments)?;
+                Ok(match result {
+                    Ok(text) => json!({
+                        "content": [{ "type": "text", "text": text }],
+                        "isError": false,
+                    }),
+                    Err(e) => json!({
+                        "content": [{ "type": "text", "text": format!("{e:#}") }],
+                        "isError": true,
+                    }),
+                })
+            }
+            _ => Err(RpcError {
+                code: METHOD_NOT_FOUND,
+                message: format!("method not found: {method}"),
+            }),
+        }
+    }
+
+    /// Serves one client until its input is closed.
+    pub fn serve<R: BufRead, W: Write>(
+        &self,
+        mut reader: R,
+        mut writer: W,
+    ) -> Result<()> {
+        let mut buf = String::new();
+        loop {
+            buf.clear();
+            if reader.read_line(&mut buf)? == 0 {
+                return Ok(());
+            }
+            if buf.trim().is_empty() {
+                continue;
+            }
+            let response = match serde_json::from_str::<Value>(&buf) {
+                Ok(msg) => self.handle_message(msg),
+                Err(e) => {
+                    Some(error_response(Value::Null, PARSE_ERROR, &e.to_string()))
+                }
+            };
+            if let Some(response) = response {
+                writeln!(writer, "{}", serde_json::to_string(&response)?)?;
+                writer.flush()?;
+            }
+        }
+    }
+
+    /// Sends a request to the proxy and waits for its response.
+    pub(crate) fn request(&self, request: ProxyRequest) -> Result<ProxyResponse> {
+        self.request_with_timeout(request, REQUEST_TIMEOUT)
+    }
+
+    pub(crate) fn request_with_timeout(
+        &self,
+        request: ProxyRequest,
+        timeout: Duration,
+    ) -> Result<ProxyResponse> {
+        let (tx, rx) = crossbeam_channel::bounded(1);
+        self.proxy_rpc.request_async(request, move |result| {
+            if let Err(err) = tx.send(result) {
+                tracing::error!("{:?}", err);
+            }
+        });
+        match rx.recv_timeout(timeout) {
+            Ok(Ok(resp)) => Ok(resp),
+            Ok(Err(e)) => Err(anyhow!(e.message)),
+            Err(_) => Err(anyhow!("request timed out")),
+        }
+    }
+
+    /// The text of the file as the editor has it, unsaved edits included. The
+    /// proxy opens the file if no buffer has it yet, which lets the language
+    /// servers see it.
+    pub(crate) fn buffer_content(&self, path: &Path) -> Result<String> {
+        let resp = self.request(ProxyRequest::BufferContent {
+            path: path.to_path_buf(),
+        })?;
+        let ProxyResponse::BufferContentResponse { content } = resp else {
+            return Err(anyhow!("unexpected response"));
+        };
+        Ok(content)
+    }
+
+    /// Resolves a path given by the client against the workspace, refusing
+    /// any that ends up outside of it, through `..` or a symlink alike.
+    pub(crate) fn resolve_path(&self, path: &str) -> Result<PathBuf> {
+        let joined = self.workspace.join(path);
+        let resolved = match joined.canonicalize() {
+            Ok(resolved) => resolved,
+            // A file that doesn't exist yet is resolved through its parent.
+            Err(_) => {
+                let name = joined
+                    .file_name()
+                    .ok_or_else(|| anyhow!("{path} is not a file path"))?;
+                let parent = joined
+                    .parent()
+                    .and_then(|parent| parent.canonicalize().ok())
+                    .ok_or_else(|| anyhow!("{path} doesn't exist"))?;
+                parent.join(name)
+            }
+        };
+        if !resolved.starts_with(&self.workspace) {
+            return Err(anyhow!("{path} is outside of the workspace"));
+        }
+        Ok(resolved)
+    }
+
+    pub(crate) fn display_path(&self, path: &Path) -> String {
+        path.strip_prefix(&self.workspace)
+            .unwrap_or(path)
+            .display()
+            .to_string()
+    }
+}
+
+fn error_response(id: Value, code: i64, message: &str) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "error": {
+            "code": code,
+            "message": message,
+        },
+    })
+}
+
+/// The socket the editor session that has `workspace` open serves the MCP
+/// server on.
+pub fn session_socket(workspace: &Path) -> Option<PathBuf> {
+    let mut hasher = DefaultHasher::new();
+    workspace.hash(&mut hasher);
+    Directory::mcp_sockets_directory()
+        .map(|dir| dir.join(format!("{:x}.sock", hasher.finish())))
+}
+
+/// Serves the server on the socket of its workspace, for `phidi-proxy --mcp`
+/// to connect to, returning the socket to remove once the session ends.
+pub(crate) fn listen(server: McpServer) -> Result<PathBuf> {
+    let socket = session_socket(&server.workspace)
+        .ok_or_else(|| anyhow!("can't get mcp socket path"))?;
+    serve_socket(server, &socket)?;
+    Ok(socket)
+}
+
+/// Connects stdio to the MCP server of the editor session that has the
+/// workspace open, or to the one on `socket` when it's given.
+#[cfg(unix)]
+pub fn mainloop(workspace: PathBuf, socket: Option<PathBuf>) -> Result<()> {
+    use std::{
+        io::{copy, stdin, stdout},
+        net::Shutdown,
+        os::unix::net::UnixStream,
+    };
+
+    let workspace = workspace.canonicalize()?;
+    let socket = match socket {
+        Some(socket) => socket,
+        None => session_socket(&workspace)
+            .ok_or_else(|| anyhow!("can't get mcp socket path"))?,
+    };
+    let mut stream = UnixStream::connect(&socket).map_err(|e| {
+        anyhow!("no editor session has {} open: {e}", workspace.display())
+    })?;
+
+    let mut reader = stream.try_clone()?;
+    let output = thread::spawn(move || copy(&mut reader, &mut stdout()));
+    copy(&mut stdin(), &mut stream)?;
+    // The server stops once our side is closed, which ends the output.
+    stream.shutdown(Shutdown::Write)?;
+    output
+        .join()
+        .map_err(|_| anyhow!("mcp output thread panicked"))??;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn mainloop(_workspace: PathBuf, _socket: Option<PathBuf>) -> Result<()> {
+    Err(anyhow!("Unix sockets are not supported on this platform"))
+}
+
+#[cfg(unix)]
+fn serve_socket(server: McpServer, socket: &Path) -> Result<()> {
+    use std::os::unix::net::{UnixListener, UnixStream};
+
+    if socket.exists() {
+        // Only a socket left behind by a session that is gone may be taken
+        // over, not the one of another session on the same workspace.
+        if UnixStream::connect(socket).is_ok() {
+            return Err(anyhow!(
+                "another session is serving the mcp server on {}",
+                socket.display()
+            ));
+        }
+        std::fs::remove_file(socket)?;
+    }
+    let listener = UnixListener::bind(socket)?;
+    tracing::info!("phidi-proxy mcp server listening on {}", socket.display());
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let server = server.clone();
+            thread::spawn(move || -> Result<()> {
+                let reader = BufReader::new(stream.try_clone()?);
+                server.serve(reader, stream)
+            });
+        }
+    });
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn serve_socket(_server: McpServer, _socket: &Path) -> Result<()> {
+    Err(anyhow!("Unix sockets are not supported on this platform"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn server() -> McpServer {
+        McpServer::new(PathBuf::from("/workspace"), ProxyRpcHandler::new())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_socket_of_live_session_is_kept() {
+        let dir = tempfile::tempdir().unwrap();
+        let socket = dir.path().join("mcp.sock");
+
+        // A socket left behind by a session that is gone is taken over.
+        drop(std::os::unix::net::UnixListener::bind(&socket).unwrap());
+        serve_socket(server(), &socket).unwrap();
+
+        assert!(serve_socket(server(), &socket).is_err());
+        assert!(socket.exists());
+    }
+
+    #[test]
+    fn test_initialize() {
+        let resp = server()
+            .handle_message(json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "method": "initialize",
+                "params": { "protocolVersion": "2024-11-05" },
+            }))
+            .unwrap();
+        assert_eq!(resp["id"], 1);
+        assert_eq!(resp["result"]["protocolVersion"], "2024-11-05");
+        assert!(resp["result"]["capabilities"]["tools"].is_object());
+
+        let resp = server()
+            .handle_message(json!({
+                "jsonrpc": "2.0",
+                "id": 2,
+                "method": "initialize",
+                "params": { "protocolVersion": "1999-01-01" },
+            }))
+            .unwrap();
+        assert_eq!(resp["result"]["protocolVersion"], PROTOCOL_VERSIONS[0]);
+    }
+
+    #[test]
+    fn test_notification_has_no_response() {
+        let resp = server().handle_message(json!({
+            "jsonrpc": "2.0",
+            "method": "notifications/initialized",
+        }));
+        assert!(resp.is_none());
+    }
+
+    #[test]
+    fn test_tools_list() {
+        let resp = server()
+            .handle_message(json!({
+                "jsonrpc": "2.0",
+                "id": "a",
+                "method": "tools/list",
+            }))
+            .unwrap();
+        let tools = resp["result"]["tools"].as_array().unwrap();
+        let names: Vec<&str> =
+            tools.iter().map(|t| t["name"].as_str().unwrap()).collect();
+        for name in [
+            "read_file",
+            "global_search",
+            "go_to_definition",
+            "find_references",
+            "document_symbols",
+            "workspace_symbols",
+            "diagnostics",
+            "apply_workspace_edit",
+            "run_command",
+        ] {
+            assert!(names.contains(&name), "missing tool {name}");
+        }
+        for tool in tools {
+            assert_eq!(tool["inputSchema"]["type"], "object");
+        }
+    }
+
+    #[test]
+    fn test_unknown_method_and_tool() {
+        let resp = server()
+            .handle_message(json!({
+                "jsonrpc": "2.0",
+                "id": 3,
+                "method": "resources/list",
+            }))
+            .unwrap();
+        assert_eq!(resp["error"]["code"], METHOD_NOT_FOUND);
+
+        let resp = server()
+            .handle_message(json!({
+                "jsonrpc": "2.0",
+                "id": 4,
+                "method": "tools/call",
+                "params": { "name": "no_such_tool", "arguments": {} },
+            }))
+            .unwrap();
+        assert_eq!(resp["error"]["code"], INVALID_PARAMS);
+    }
+
+    #[test]
+    fn test_diagnostics_snapshot() {
+        let server = server();
+        let uri = lsp_types::Url::from_file_path("/workspace/src/main.rs").unwrap();
+        let diagnostic = Diagnostic {
+            message: "unused variable".to_string(),
+            ..Default::default()
+        };
+        server.handle_core_notification(&CoreNotification::PublishDiagnostics {
+            diagnostics: lsp_types::PublishDiagnosticsParams {
+                uri: uri.clone(),
+                diagnostics: vec![diagnostic],
+                version: None,
+            },
+        });
+        let resp = server
+            .handle_message(json!({
+                "jsonrpc": "2.0",
+                "id": 5,
+                "method": "tools/call",
+                "params": { "name": "diagnostics", "arguments": {} },
+            }))
+            .unwrap();
+        assert_eq!(resp["result"]["isError"], false);
+        let text = resp["result"]["content"][0]["text"].as_str().unwrap();
+        assert!(text.contains("src/main.rs:1:1"));
+        assert!(text.contains("unused variable"));
+
+        server.handle_core_notification(&CoreNotification::PublishDiagnostics {
+            diagnostics: lsp_types::PublishDiagnosticsParams {
+                uri,
+                diagnostics: Vec::new(),
+                version: None,
+            },
+        });
+        assert!(server.state.lock().diagnostics.is_empty());
+    }
+
+    #[test]
+    fn test_resolve_path_stays_in_workspace() {
+        let dir = tempfile::tempdir().unwrap();
+        let workspace = dir.path().join("workspace");
+        std::fs::create_dir_all(workspace.join("src")).unwrap();
+        std::fs::write(workspace.join("src/main.rs"), "").unwrap();
+        std::fs::write(dir.path().join("secret"), "").unwrap();
+        let server = McpServer::new(workspace, ProxyRpcHandler::new());
+
+        let main = server.workspace.join("src/main.rs");
+        assert_eq!(server.resolve_path("src/main.rs").unwrap(), main);
+        assert_eq!(server.resolve_path("src/../src/main.rs").unwrap(), main);
+        assert_eq!(server.resolve_path(main.to_str().unwrap()).unwrap(), main);
+        assert_eq!(
+            server.resolve_path("src/new.rs").unwrap(),
+            server.workspace.join("src/new.rs")
+        );
+
+        assert!(server.resolve_path("../secret").is_err());
+        assert!(server.resolve_path("src/../../secret").is_err());
+        let secret = dir.path().join("secret");
+        assert!(server.resolve_path(secret.to_str().unwrap()).is_err());
+        assert!(server.resolve_path("/etc/passwd").is_err());
+    }
+}
@@ -0,0 +1,622 @@
+//! The tools offered by the MCP server. Positions taken and returned by the
+//! tools use 1-based lines and columns, which is what agents read off the
+//! numbered output of `read_file` and `global_search`.
+
+use std::{fmt::Write, time::Duration};
+
+use anyhow::{Result, anyhow};
+use lsp_types::{
+    DiagnosticSeverity, DocumentChangeOperation, DocumentChanges, DocumentSymbol,
+    DocumentSymbolResponse, GotoDefinitionResponse, Location, Position, Range,
+    ResourceOp, Url, WorkspaceEdit,
+};
+use phidi_rpc::{
+    RpcError,
+    proxy::{ProxyRequest, ProxyResponse, SearchOptions},
+    terminal::{CommandResult, TerminalProfile},
+};
+use serde::{Deserialize, de::DeserializeOwned};
+use serde_json::{Value, json};
+
+use super::{INVALID_PARAMS, McpServer};
+use crate::terminal::DEFAULT_COMMAND_TIMEOUT;
+
+const DEFAULT_MAX_RESULTS: usize = 200;
+
+pub(crate) fn list() -> Vec<Value> {
+    vec![
+        tool(
+            "read_file",
+            "Read a file of the workspace. Lines are numbered from 1, and can \
+             be limited to an inclusive range.",
+            json!({
+                "path": path_schema(),
+                "start_line": { "type": "integer", "minimum": 1 },
+                "end_line": { "type": "integer", "minimum": 1 },
+            }),
+            &["path"],
+        ),
+        tool(
+            "global_search",
+            "Search all files of the workspace that aren't ignored for a \
+             pattern, returning `path:line:column: content` lines, and \
+             `path-line- content` lines around them when asked for context. \
+             Files can be narrowed down by globs and by ripgrep file types \
+             like `rust` or `py`.",
+            json!({
+                "pattern": { "type": "string" },
+                "case_sensitive": { "type": "boolean", "default": false },
+                "whole_word": { "type": "boolean", "default": false },
+                "is_regex": { "type": "boolean", "default": false },
+                "include": { "type": "array", "items": { "type": "string" } },
+                "exclude": { "type": "array", "items": { "type": "string" } },
+                "file_types": { "type": "array", "items": { "type": "string" } },
+                "context_lines": { "type": "integer", "minimum": 0, "default": 0 },
+                "max_results": {
+                    "type": "integer",
+                    "minimum": 1,
+                    "default": DEFAULT_MAX_RESULTS,
+                },
+            }),
+            &["pattern"],
+        ),
+        tool(
+            "go_to_definition",
+            "Find where the symbol at a position is defined, using the \
+             language server of the file.",
+            position_schema(),
+            &["path", "line", "column"],
+        ),
+        tool(
+            "find_references",
+            "Find all references to the symbol at a position, using the \
+             language server of the file.",
+            position_schema(),
+            &["path", "line", "column"],
+        ),
+        tool(
+            "document_symbols",
+            "List the symbols defined in a file as an indented outline.",
+            json!({ "path": path_schema() }),
+            &["path"],
+        ),
+        tool(
+            "workspace_symbols",
+            "Search the symbols of the whole workspace by name.",
+            json!({ "query": { "type": "string" } }),
+            &["query"],
+        ),
+        tool(
+            "diagnostics",
+            "Snapshot of the errors and warnings currently reported by the \
+             language servers, for one file or the whole workspace.",
+            json!({ "path": path_schema() }),
+            &[],
+        ),
+        tool(
+            "apply_workspace_edit",
+            "Apply an LSP WorkspaceEdit in the editor, where the user can \
+             review, undo and save it. Positions in the edit follow the LSP \
+             convention of 0-based lines and UTF-16 characters.",
+            json!({ "edit": { "type": "object" } }),
+            &["edit"],
+        ),
+        tool(
+            "run_command",
+            "Run a shell command in the workspace root and return its output \
+             and exit code.",
+            json!({
+                "command": { "type": "string" },
+                "timeout_secs": {
+                    "type": "integer",
+                    "minimum": 1,
+                    "default": DEFAULT_COMMAND_TIMEOUT.as_secs(),
+                },
+            }),
+            &["command"],
+        ),
+    ]
+}
+
+fn tool(
+    name: &str,
+    description: &str,
+    properties: Value,
+    required: &[&str],
+) -> Value {
+    json!({
+        "name": name,
+        "description": description,
+        "inputSchema": {
+            "type": "object",
+            "properties": properties,
+            "required": required,
+        },
+    })
+}
+
+fn path_schema() -> Value {
+    json!({
+        "type": "string",
+        "description": "Absolute path, or relative to the workspace root",
+    })
+}
+
+fn position_schema() -> Value {
+    json!({
+        "path": path_schema(),
+        "line": { "type": "integer", "minimum": 1 },
+        "column": { "type": "integer", "minimum": 1 },
+    })
+}
+
+/// Runs a tool. The outer error is a protocol error, the inner one is
+/// reported to the agent as a failed tool call.
+pub(crate) fn call(
+    server: &McpServer,
+    name: &str,
+    arguments: Value,
+) -> Result<Result<String>, RpcError> {
+    let result = match name {
+        "read_file" => parse(arguments).and_then(|args| read_file(server, args)),
+        "global_search" => {
+            parse(arguments).and_then(|args| global_search(server, args))
+        }
+        "go_to_definition" => {
+            parse(arguments).and_then(|args| go_to_definition(server, args))
+        }
+        "find_references" => {
+            parse(arguments).and_then(|args| find_references(server, args))
+        }
+        "document_symbols" => {
+            parse(arguments).and_then(|args| document_symbols(server, args))
+        }
+        "workspace_symbols" => {
+            parse(arguments).and_then(|args| workspace_symbols(server, args))
+        }
+        "diagnostics" => parse(arguments).and_then(|args| diagnostics(server, args)),
+        "apply_workspace_edit" => {
+            parse(arguments).and_then(|args| apply_workspace_edit(server, args))
+        }
+        "run_command" => parse(arguments).and_then(|args| run_command(server, args)),
+        _ => {
+            return Err(RpcError {
+                code: INVALID_PARAMS,
+                message: format!("unknown tool: {name}"),
+            });
+        }
+    };
+    Ok(result)
+}
+
+fn parse<T: DeserializeOwned>(arguments: Value) -> Result<T> {
+    serde_json::from_value(arguments).map_err(|e| anyhow!("invalid arguments: {e}"))
+}
+
+#[derive(Deserialize)]
+struct ReadFileArgs {
+    path: String,
+    start_line: Option<usize>,
+    end_line: Option<usize>,
+}
+
+fn read_file(server: &McpServer, args: ReadFileArgs) -> Result<String> {
+    let path = server.resolve_path(&args.path)?;
+    let content = server.buffer_content(&path)?;
+    let start = args.start_line.unwrap_or(1).max(1);
+    let end = args.end_line.unwrap_or(usize::MAX);
+    let mut output = String::new();
+    for (i, line) in content.lines().enumerate() {
+        let line_number = i + 1;
+        if line_number < start {
+            continue;
+        }
+        if line_number > end {
+            break;
+        }
+        writeln!(output, "{line_number:>6}\t{line}")?;
+    }
+    Ok(output)
+}
+
+#[derive(Deserialize)]
+struct GlobalSearchArgs {
+    pattern: String,
+    #[serde(default)]
+    case_sensitive: bool,
+    #[serde(default)]
+    whole_word: bool,
+    #[serde(default)]
+    is_regex: bool,
+    #[serde(default)]
+    include: Vec<String>,
+    #[serde(default)]
+    exclude: Vec<String>,
+    #[serde(default)]
+    file_types: Vec<String>,
+    #[serde(default)]
+    context_lines: usize,
+    max_results: Option<usize>,
+}
+
+fn global_search(server: &McpServer, args: GlobalSearchArgs) -> Result<String> {
+    let max_results = args.max_results.unwrap_or(DEFAULT_MAX_RESULTS);
+    let resp = server.request(ProxyRequest::GlobalSearch {
+        pattern: args.pattern,
+        case_sensitive: args.case_sensitive,
+        whole_word: args.whole_word,
+        is_regex: args.is_regex,
+        replacement: None,
+        options: SearchOptions {
+            include: args.include,
+            exclude: args.exclude,
+            file_types: args.file_types,
+            max_results: Some(max_results),
+            context_lines: args.context_lines,
+        },
+    })?;
+    let ProxyResponse::GlobalSearchResponse {
+        matches, limit_hit, ..
+    } = resp
+    else {
+        return Err(anyhow!("unexpected response"));
+    };
+
+    let mut output = String::new();
+    for (path, matches) in &matches {
+        let path = server.display_path(path);
+        // The context of matches close to each other overlaps, each line is
+        // only written once.
+        let mut last_line = 0;
+        for (i, search_match) in matches.iter().enumerate() {
+            let first_before = search_match.line - search_match.context_before.len();
+            for (j, line) in search_match.context_before.iter().enumerate() {
+                let line_number = first_before + j;
+                if line_number > last_line {
+                    writeln!(output, "{path}-{line_number}- {}", line.trim_end())?;
+                    last_line = line_number;
+                }
+            }
+            writeln!(
+                output,
+                "{path}:{}:{}: {}",
+                search_match.line,
+                search_match.start + 1,
+                search_match.line_content.trim_end()
+            )?;
+            last_line = search_match.line;
+            let next_line = matches.get(i + 1).map(|m| m.line);
+            for (j, line) in search_match.context_after.iter().enumerate() {
+                let line_number = search_match.line + 1 + j;
+                if next_line.is_some_and(|next| line_number >= next) {
+                    break;
+                }
+                writeln!(output, "{path}-{line_number}- {}", line.trim_end())?;
+                last_line = line_number;
+            }
+        }
+    }
+    if limit_hit {
+        writeln!(output, "... stopped at {max_results} matches")?;
+    } else if matches.is_empty() {
+        output.push_str("no matches");
+    }
+    Ok(output)
+}
+
+#[derive(Deserialize)]
+struct PositionArgs {
+    path: String,
+    line: u32,
+    column: u32,
+}
+
+impl PositionArgs {
+    fn position(&self) -> Position {
+        Position {
+            line: self.line.saturating_sub(1),
+            character: self.column.saturating_sub(1),
+        }
+    }
+}
+
+fn go_to_definition(server: &McpServer, args: PositionArgs) -> Result<String> {
+    let path = server.resolve_path(&args.path)?;
+    server.buffer_content(&path)?;
+    let resp = server.request(ProxyRequest::GetDefinition {
+        request_id: 0,
+        path,
+        position: args.position(),
+    })?;
+    let ProxyResponse::GetDefinitionResponse { definition, .. } = resp else {
+        return Err(anyhow!("unexpected response"));
+    };
+    let locations = match definition {
+        GotoDefinitionResponse::Scalar(location) => vec![location],
+        GotoDefinitionResponse::Array(locations) => locations,
+        GotoDefinitionResponse::Link(links) => links
+            .into_iter()
+            .map(|link| Location {
+                uri: link.target_uri,
+                range: link.target_selection_range,
+            })
+            .collect(),
+    };
+    Ok(format_locations(server, &locations))
+}
+
+fn find_references(server: &McpServer, args: PositionArgs) -> Result<String> {
+    let path = server.resolve_path(&args.path)?;
+    server.buffer_content(&path)?;
+    let resp = server.request(ProxyRequest::GetReferences {
+        path,
+        position: args.position(),
+    })?;
+    let ProxyResponse::GetReferencesResponse { references } = resp else {
+        return Err(anyhow!("unexpected response"));
+    };
+    Ok(format_locations(server, &references))
+}
+
+#[derive(Deserialize)]
+struct PathArgs {
+    path: String,
+}
+
+fn document_symbols(server: &McpServer, args: PathArgs) -> Result<String> {
+    let path = server.resolve_path(&args.path)?;
+    server.buffer_content(&path)?;
+    let resp = server.request(ProxyRequest::GetDocumentSymbols { path })?;
+    let ProxyResponse::GetDocumentSymbols { resp } = resp else {
+        return Err(anyhow!("unexpected response"));
+    };
+    let mut output = String::new();
+    match resp {
+        DocumentSymbolResponse::Flat(symbols) => {
+            for symbol in symbols {
+                writeln!(
+                    output,
+                    "{:?} {} {}",
+                    symbol.kind,
+                    symbol.name,
+                    format_position(&symbol.location.range.start)
+                )?;
+            }
+        }
+        DocumentSymbolResponse::Nested(symbols) => {
+            write_document_symbols(&mut output, &symbols, 0)?;
+        }
+    }
+    Ok(output)
+}
+
+fn write_document_symbols(
+    output: &mut String,
+    symbols: &[DocumentSymbol],
+    depth: usize,
+) -> std::fmt::Result {
+    for symbol in symbols {
+        writeln!(
+            output,
+            "{}{:?} {} {}",
+            "  ".repeat(depth),
+            symbol.kind,
+            symbol.name,
+            format_position(&symbol.selection_range.start)
+        )?;
+        if let Some(children) = symbol.children.as_ref() {
+            write_document_symbols(output, children, depth + 1)?;
+        }
+    }
+    Ok(())
+}
+
+#[derive(Deserialize)]
+struct QueryArgs {
+    query: String,
+}
+
+fn workspace_symbols(server: &McpServer, args: QueryArgs) -> Result<String> {
+    let resp =
+        server.request(ProxyRequest::GetWorkspaceSymbols { query: args.query })?;
+    let ProxyResponse::GetWorkspaceSymbols { symbols } = resp else {
+        return Err(anyhow!("unexpected response"));
+    };
+    let mut output = String::new();
+    for symbol in symbols {
+        writeln!(
+            output,
+            "{:?} {} {}",
+            symbol.kind,
+            symbol.name,
+            format_location(server, &symbol.location.uri, &symbol.location.range)
+        )?;
+    }
+    Ok(output)
+}
+
+#[derive(Deserialize)]
+struct DiagnosticsArgs {
+    path: Option<String>,
+}
+
+fn diagnostics(server: &McpServer, args: DiagnosticsArgs) -> Result<String> {
+    let path = args
+        .path
+        .map(|path| server.resolve_path(&path))
+        .transpose()?;
+    let state = server.state.lock();
+    let mut files: Vec<_> = state
+        .diagnostics
+        .iter()
+        .filter(|(p, _)| path.as_ref().is_none_or(|path| path == *p))
+        .collect();
+    files.sort_by(|a, b| a.0.cmp(b.0));
+
+    let mut output = String::new();
+    for (path, diagnostics) in files {
+        for diagnostic in diagnostics {
+            let severity = match diagnostic.severity {
+                Some(DiagnosticSeverity::ERROR) => "error",
+                Some(DiagnosticSeverity::WARNING) => "warning",
+                Some(DiagnosticSeverity::INFORMATION) => "info",
+                Some(DiagnosticSeverity::HINT) => "hint",
+                _ => "diagnostic",
+            };
+            write!(
+                output,
+                "{}:{}: {severity}: {}",
+                server.display_path(path),
+                format_position(&diagnostic.range.start),
+                diagnostic.message
+            )?;
+            if let Some(source) = diagnostic.source.as_ref() {
+                write!(output, " ({source})")?;
+            }
+            output.push('\n');
+        }
+    }
+    if output.is_empty() {
+        output.push_str("no diagnostics");
+    }
+    Ok(output)
+}
+
+#[derive(Deserialize)]
+struct ApplyWorkspaceEditArgs {
+    edit: WorkspaceEdit,
+}
+
+fn apply_workspace_edit(
+    server: &McpServer,
+    args: ApplyWorkspaceEditArgs,
+) -> Result<String> {
+    for uri in edit_uris(&args.edit) {
+        let path = uri
+            .to_file_path()
+            .map_err(|_| anyhow!("{uri} is not a file path"))?;
+        server.resolve_path(&path.to_string_lossy())?;
+    }
+    server.request(ProxyRequest::ApplyWorkspaceEdit { edit: args.edit })?;
+    Ok("the edit was applied in the editor".to_string())
+}
+
+/// Every file the edit changes, creates, renames or deletes.
+fn edit_uris(edit: &WorkspaceEdit) -> Vec<&Url> {
+    let mut uris: Vec<&Url> = edit.changes.iter().flat_map(|c| c.keys()).collect();
+    let operations = match edit.document_changes.as_ref() {
+        Some(DocumentChanges::Edits(edits)) => {
+            uris.extend(edits.iter().map(|edit| &edit.text_document.uri));
+            return uris;
+        }
+        Some(DocumentChanges::Operations(operations)) => operations,
+        None => return uris,
+    };
+    for operation in operations {
+        match operation {
+            DocumentChangeOperation::Edit(edit) => {
+                uris.push(&edit.text_document.uri);
+            }
+            DocumentChangeOperation::Op(ResourceOp::Create(create)) => {
+                uris.push(&create.uri);
+            }
+            DocumentChangeOperation::Op(ResourceOp::Rename(rename)) => {
+                uris.push(&rename.old_uri);
+                uris.push(&rename.new_uri);
+            }
+            DocumentChangeOperation::Op(ResourceOp::Delete(delete)) => {
+                uris.push(&delete.uri);
+            }
+        }
+    }
+    uris
+}
+
+#[derive(Deserialize)]
+struct RunCommandArgs {
+    command: String,
+    timeout_secs: Option<u64>,
+}
+
+fn run_command(server: &McpServer, args: RunCommandArgs) -> Result<String> {
+    let timeout = args
+        .timeout_secs
+        .map(Duration::from_secs)
+        .unwrap_or(DEFAULT_COMMAND_TIMEOUT);
+    let (program, flag) = if cfg!(target_os = "windows") {
+        ("cmd", "/C")
+    } else {
+        ("/bin/sh", "-c")
+    };
+    let resp = server.request_with_timeout(
+        ProxyRequest::RunCommand {
+            term_id: None,
+            profile: TerminalProfile {
+                name: "mcp".to_string(),
+                command: Some(program.to_string()),
+                arguments: Some(vec![flag.to_string(), args.command]),
+                workdir: Url::from_directory_path(&server.workspace).ok(),
+                environment: None,
+            },
+            timeout_secs: Some(timeout.as_secs()),
+        },
+        // Leave the proxy time to kill the command and collect its output.
+        timeout + Duration::from_secs(5),
+    )?;
+    let ProxyResponse::RunCommandResponse { result } = resp else {
+        return Err(anyhow!("unexpected response"));
+    };
+    format_command_result(&result)
+}
+
+fn format_command_result(result: &CommandResult) -> Result<String> {
+    let mut text = String::new();
+    for (name, output, truncated) in [
+        ("stdout", &result.stdout, result.stdout_truncated),
+        ("stderr", &result.stderr, result.stderr_truncated),
+    ] {
+        if output.is_empty() {
+            continue;
+        }
+        writeln!(text, "[{name}]")?;
+        if truncated {
+            text.push_str("[... start of the output truncated]\n");
+        }
+        text.push_str(output);
+        if !output.ends_with('\n') {
+            text.push('\n');
+        }
+    }
+    let seconds = result.duration_ms as f64 / 1000.0;
+    match result.exit_code {
+        _ if result.timed_out => write!(text, "[timed out after {seconds:.1}s]")?,
+        Some(exit_code) => {
+            write!(text, "[exit code: {exit_code}, took {seconds:.1}s]")?
+        }
+        None => write!(text, "[process killed after {seconds:.1}s]")?,
+    }
+    Ok(text)
+}
+
+fn format_locations(server: &McpServer, locations: &[Location]) -> String {
+    if locations.is_empty() {
+        return "no locations found".to_string();
+    }
+    locations
+        .iter()
+        .map(|location| format_location(server, &location.uri, &location.range))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn format_location(server: &McpServer, uri: &Url, range: &Range) -> String {
+    let path = match uri.to_file_path() {
+        Ok(path) => server.display_path(&path),
+        Err(_) => uri.to_string(),
+    };
+    format!("{path}:{}", format_position(&range.start))
+}
+
+fn format_position(position: &Position) -> String {
+    format!("{}:{}", position.line + 1, position.character + 1)
+}
@@ -1,41 +1,56 @@
 use std::{
     borrow::Cow,
     collections::HashMap,
-    path::PathBuf,
+    fs, ops,
+    path::{Path, PathBuf},
     sync::{
         Arc,
         atomic::{AtomicUsize, Ordering},
     },
     thread,
+    time::Duration,
 };
 
 use lsp_types::{
-    DidOpenTextDocumentParams, MessageType, SemanticTokens, ShowMessageParams,
-    TextDocumentIdentifier, TextDocumentItem, VersionedTextDocumentIdentifier,
-    notification::DidOpenTextDocument, request::Request,
+    DidOpenTextDocumentParams, DocumentFilter, DocumentSelector, MessageType,
+    SemanticTokens, ShowMessageParams, TextDocumentIdentifier, TextDocumentItem,
+    VersionedTextDocumentIdentifier,
+    notification::{DidChangeConfiguration, DidOpenTextDocument},
+    request::{
+        Formatting, GotoDeclaration, GotoDefinition, GotoImplementation,
+        GotoTypeDefinition, HoverRequest, OnTypeFormatting, PrepareRenameRequest,
+        RangeFormatting, References, Rename, Request,
+    },
 };
 use parking_lot::Mutex;
 use phidi_rpc::{
     RpcError,
     dap_types::{self, DapId, DapServer, SetBreakpointsResponse},
-    plugin::{PluginId, VoltID, VoltInfo, VoltMetadata},
+    plugin::{LspServerConfig, PluginId, VoltID, VoltInfo, VoltMetadata},
     proxy::ProxyResponse,
-    style::LineStyle,
 };
 use phidi_xi_rope::{Rope, RopeDelta};
 use psp_types::Notification;
 use serde_json::Value;
 
 use super::{
-    PluginCatalogNotification, PluginCatalogRpcHandler,
+    PluginCatalogNotification, PluginCatalogRpcHandler, RANGES_FORMATTING_METHOD,
     dap::{DapClient, DapRpcHandler, DebuggerData},
-    psp::{ClonableCallback, PluginServerRpc, PluginServerRpcHandler, RpcCallback},
-    wasi::{load_all_volts, start_volt},
+    lsp::LspClient,
+    psp::{
+        ClonableCallback, PluginServerRpc, PluginServerRpcHandler, RpcCallback,
+        SemanticTokenStyles,
+    },
+    wasi::{load_all_volts, start_volt, unflatten_map},
 };
 use crate::plugin::{
     install_volt, psp::PluginHandlerNotification, wasi::enable_volt,
 };
 
+/// How long the answers of lower priority servers wait for those of higher
+/// priority, before they are passed on without them.
+const PRIORITY_TIMEOUT: Duration = Duration::from_secs(5);
+
 pub struct PluginCatalog {
     workspace: Option<PathBuf>,
     plugin_rpc: PluginCatalogRpcHandler,
@@ -45,6 +60,16 @@ pub struct PluginCatalog {
     plugin_configurations: HashMap<String, HashMap<String, serde_json::Value>>,
     unactivated_volts: HashMap<VoltID, VoltMetadata>,
     open_files: HashMap<PathBuf, String>,
+    /// The language servers of the user settings, which the `[lsp]` tables
+    /// of the workspace settings add to.
+    lsp_servers: HashMap<String, LspServerConfig>,
+    /// The language servers of the user and the workspace settings, as of
+    /// the last time servers were started.
+    lsp_server_configs: HashMap<String, LspServerConfig>,
+    started_lsp_servers: HashMap<String, (LspServerConfig, PluginId)>,
+    /// The `.phidi/settings.toml` of the workspace, read again when the
+    /// settings change.
+    workspace_settings: Option<toml::Value>,
 }
 
 impl PluginCatalog {
@@ -53,9 +78,11 @@ pub fn new(
         disabled_volts: Vec<VoltID>,
         extra_plugin_paths: Vec<PathBuf>,
         plugin_configurations: HashMap<String, HashMap<String, serde_json::Value>>,
+        lsp_servers: HashMap<String, LspServerConfig>,
         plugin_rpc: PluginCatalogRpcHandler,
     ) -> Self {
-        let plugin = Self {
+        let workspace_settings = read_workspace_settings(workspace.as_deref());
+        let mut plugin = Self {
             workspace,
             plugin_rpc: plugin_rpc.clone(),
             plugin_configurations,
@@ -64,7 +91,13 @@ pub fn new(
             debuggers: HashMap::new(),
             unactivated_volts: HashMap::new(),
             open_files: HashMap::new(),
+            lsp_servers,
+            lsp_server_configs: HashMap::new(),
+            started_lsp_servers: HashMap::new(),
+            workspace_settings,
         };
+        // For the priorities of the servers of plugins.
+        plugin.lsp_server_configs = plugin.merged_lsp_server_configs();
 
         thread::spawn(move || {
             load_all_volts(plugin_rpc, &extra_plugin_paths, disabled_volts);
@@ -129,9 +162,48 @@ pub fn handle_server_request(
                 request_sent.fetch_add(self.plugins.len(), Ordering::Relaxed);
             }
         }
-        for (plugin_id, plugin) in self.plugins.iter() {
+        let mut plugins: Vec<(PluginId, i32)> = self
+            .plugins
+            .iter()
+            .map(|(plugin_id, plugin)| {
+                (
+                    *plugin_id,
+                    self.lsp_server_priority(&plugin.volt_id, &method),
+                )
+            })
+            .collect();
+        // Without priorities the first answer wins, otherwise the answers are
+        // passed on from the highest priority down, so that the highest one
+        // which answers wins.
+        let prioritized = plugins
+            .iter()
+            .any(|(_, priority)| *priority != plugins[0].1);
+        plugins.sort_by_key(|(plugin_id, priority)| (-priority, plugin_id.0));
+        let ordered = Arc::new(Mutex::new(PriorityOrder {
+            results: plugins.iter().map(|_| None).collect(),
+            next: 0,
+            expired: false,
+        }));
+        let (done_tx, done_rx) = crossbeam_channel::bounded(1);
+        if prioritized {
+            // Servers which are too slow to answer, or won't, are passed over.
+            let ordered = ordered.clone();
+            thread::spawn(move || {
+                if done_rx.recv_timeout(PRIORITY_TIMEOUT).is_err() {
+                    let ready = ordered.lock().expire();
+                    for (plugin_id, result, f) in ready {
+                        f(plugin_id, result);
+                    }
+                }
+            });
+        }
+        for (rank, (plugin_id, _)) in plugins.into_iter().enumerate() {
+            let Some(plugin) = self.plugins.get(&plugin_id) else {
+                continue;
+            };
             let f = dyn_clone::clone_box(&*f);
-            let plugin_id = *plugin_id;
+            let ordered = ordered.clone();
+            let done_tx = done_tx.clone();
             plugin.server_request_async(
                 method.clone(),
                 params.clone(),
@@ -139,12 +211,60 @@ pub fn handle_server_request(
                 path.clone(),
                 check,
                 move |result| {
-                    f(plugin_id, result);
+                    if !prioritized {
+                        f(plugin_id, result);
+                        return;
+                    }
+                    let ready = {
+                        let mut ordered = ordered.lock();
+                        if rank < ordered.next {
+                            // Passed over already, so it comes after the others.
+                            vec![(plugin_id, result, f)]
+                        } else {
+                            ordered.results[rank] = Some((plugin_id, result, f));
+                            let ready = ordered.ready();
+                            if ordered.next == ordered.results.len() {
+                                let _ = done_tx.try_send(());
+                            }
+                            ready
+                        }
+                    };
+                    for (plugin_id, result, f) in ready {
+                        f(plugin_id, result);
+                    }
                 },
             );
         }
     }
 
+    /// The priority of the servers of a plugin or of the settings for the
+    /// feature of a request, from the `[lsp.<name>]` table of the settings.
+    fn lsp_server_priority(&self, volt_id: &VoltID, method: &str) -> i32 {
+        let Some(config) = self.lsp_server_configs.get(&volt_id.name) else {
+            return 0;
+        };
+        let feature = match method {
+            HoverRequest::METHOD => "hover",
+            GotoDefinition::METHOD
+            | GotoTypeDefinition::METHOD
+            | GotoImplementation::METHOD
+            | GotoDeclaration::METHOD => "definition",
+            Formatting::METHOD
+            | RangeFormatting::METHOD
+            | OnTypeFormatting::METHOD
+            | RANGES_FORMATTING_METHOD => "formatting",
+            References::METHOD => "references",
+            Rename::METHOD | PrepareRenameRequest::METHOD => "rename",
+            _ => "default",
+        };
+        config
+            .priority
+            .get(feature)
+            .or_else(|| config.priority.get("default"))
+            .copied()
+            .unwrap_or(0)
+    }
+
     #[allow(clippy::too_many_arguments)]
     pub fn handle_server_notification(
         &mut self,
@@ -282,6 +402,88 @@ fn check_unactivated_volts(&mut self) {
         self.start_unactivated_volts(to_be_activated);
     }
 
+    /// Starts the language servers of the settings which an open document is
+    /// for, and stops the ones which were removed or changed since they were
+    /// started, to be started again with their new settings.
+    fn start_lsp_servers(&mut self) {
+        self.lsp_server_configs = self.merged_lsp_server_configs();
+        let servers = self.lsp_server_configs.clone();
+        let stale: Vec<String> = self
+            .started_lsp_servers
+            .iter()
+            .filter(|(name, (config, _))| servers.get(*name) != Some(config))
+            .map(|(name, _)| name.clone())
+            .collect();
+        for name in stale {
+            if let Some((_, plugin_id)) = self.started_lsp_servers.remove(&name) {
+                if let Some(plugin) = self.plugins.remove(&plugin_id) {
+                    plugin.shutdown();
+                }
+            }
+        }
+
+        for (name, config) in servers {
+            if self.started_lsp_servers.contains_key(&name) {
+                continue;
+            }
+            let Some(command) = config.command.clone() else {
+                continue;
+            };
+            let Some(root) =
+                self.open_files.iter().find_map(|(path, language_id)| {
+                    lsp_server_root(
+                        &config,
+                        self.workspace.as_deref(),
+                        path,
+                        language_id,
+                    )
+                })
+            else {
+                continue;
+            };
+            let plugin_id = PluginId::next();
+            self.started_lsp_servers
+                .insert(name.clone(), (config.clone(), plugin_id));
+            let plugin_rpc = self.plugin_rpc.clone();
+            thread::spawn(move || {
+                if let Err(err) = LspClient::start(
+                    plugin_rpc,
+                    lsp_server_document_selector(&config),
+                    Some(root),
+                    VoltID {
+                        author: "settings".to_string(),
+                        name: name.clone(),
+                    },
+                    name,
+                    None,
+                    Some(plugin_id),
+                    None,
+                    command,
+                    config.args,
+                    config.env,
+                    config.initialization_options,
+                ) {
+                    tracing::error!("{:?}", err);
+                }
+            });
+        }
+    }
+
+    /// The language servers of the user settings, with the ones of the
+    /// workspace settings replacing them by name, see [`merge_lsp_servers`].
+    fn merged_lsp_server_configs(&self) -> HashMap<String, LspServerConfig> {
+        let workspace_servers = self
+            .workspace_settings
+            .as_ref()
+            .and_then(|settings| settings.get("lsp").cloned())
+            .and_then(|lsp| {
+                lsp.try_into::<HashMap<String, LspServerConfig>>()
+                    .inspect_err(|err| tracing::error!("{:?}", err))
+                    .ok()
+            });
+        merge_lsp_servers(&self.lsp_servers, workspace_servers.unwrap_or_default())
+    }
+
     pub fn handle_did_open_text_document(&mut self, document: TextDocumentItem) {
         match document.uri.to_file_path() {
             Ok(path) => {
@@ -291,6 +493,7 @@ pub fn handle_did_open_text_document(&mut self, document: TextDocumentItem) {
                 tracing::error!("{:?}", err);
             }
         }
+        self.start_lsp_servers();
 
         let to_be_activated: Vec<VoltID> = self
             .unactivated_volts
@@ -362,12 +565,14 @@ pub fn format_semantic_tokens(
         &self,
         plugin_id: PluginId,
         tokens: SemanticTokens,
+        changed: Option<ops::Range<usize>>,
         text: Rope,
-        f: Box<dyn RpcCallback<Vec<LineStyle>, RpcError>>,
+        f: Box<dyn RpcCallback<SemanticTokenStyles, RpcError>>,
     ) {
         if let Some(plugin) = self.plugins.get(&plugin_id) {
             plugin.handle_rpc(PluginServerRpc::FormatSemanticTokens {
                 tokens,
+                changed,
                 text,
                 f,
             });
@@ -473,6 +678,27 @@ pub fn dap_get_scopes(
         }
     }
 
+    /// The settings served to `workspace/configuration` requests: the plugin
+    /// configurations, overridden by the `.phidi/settings.toml` of the
+    /// workspace, which a remote workspace doesn't pass on to the client.
+    fn settings(&self) -> Value {
+        let mut settings = Value::Object(
+            self.plugin_configurations
+                .iter()
+                .map(|(name, config)| (name.clone(), unflatten_map(config)))
+                .collect(),
+        );
+        if let Some(workspace_settings) = self.workspace_settings.as_ref() {
+            match serde_json::to_value(workspace_settings) {
+                Ok(workspace_settings) => {
+                    merge_settings(&mut settings, workspace_settings)
+                }
+                Err(err) => tracing::error!("{:?}", err),
+            }
+        }
+        settings
+    }
+
     pub fn handle_notification(&mut self, notification: PluginCatalogNotification) {
         use PluginCatalogNotification::*;
         match notification {
@@ -486,7 +712,45 @@ pub fn handle_notification(&mut self, notification: PluginCatalogNotification) {
             }
             UpdatePluginConfigs(configs) => {
                 tracing::debug!("UpdatePluginConfigs {:?}", configs);
-                self.plugin_configurations = configs;
+                if self.plugin_configurations != configs {
+                    self.plugin_configurations = configs;
+                    // Servers that pull their settings ask for them again.
+                    self.handle_server_notification(
+                        None,
+                        DidChangeConfiguration::METHOD,
+                        serde_json::json!({ "settings": null }),
+                        None,
+                        None,
+                        false,
+                    );
+                }
+            }
+            UpdateLspServers(servers) => {
+                tracing::debug!("UpdateLspServers {:?}", servers);
+                // Sent whenever the settings are reloaded, which includes the
+                // workspace ones changing.
+                self.workspace_settings =
+                    read_workspace_settings(self.workspace.as_deref());
+                self.lsp_servers = servers;
+                self.start_lsp_servers();
+            }
+            Configuration {
+                volt_name,
+                items,
+                resp,
+            } => {
+                let settings = self.settings();
+                let values = items
+                    .iter()
+                    .map(|item| {
+                        configuration_section(
+                            &settings,
+                            &volt_name,
+                            item.section.as_deref(),
+                        )
+                    })
+                    .collect::<Vec<_>>();
+                resp.send(values);
             }
             PluginServerLoaded(plugin) => {
                 // TODO: check if the server has did open registered
@@ -514,6 +778,10 @@ pub fn handle_notification(&mut self, notification: PluginCatalogNotification) {
 
                 let plugin_id = plugin.plugin_id;
                 let spawned_by = plugin.spawned_by;
+                self.plugin_rpc
+                    .server_names
+                    .lock()
+                    .insert(plugin_id, plugin.volt_id.name.clone());
 
                 self.plugins.insert(plugin.plugin_id, plugin);
 
@@ -754,3 +1022,343 @@ pub fn handle_notification(&mut self, notification: PluginCatalogNotification) {
         }
     }
 }
+
+/// The answers of the servers to a request, in the order of their priority.
+struct PriorityOrder {
+    #[allow(clippy::type_complexity)]
+    results: Vec<
+        Option<(
+            PluginId,
+            Result<Value, RpcError>,
+            Box<dyn ClonableCallback<Value, RpcError>>,
+        )>,
+    >,
+    next: usize,
+    /// Whether the servers which haven't answered yet are passed over.
+    expired: bool,
+}
+
+impl PriorityOrder {
+    /// The answers which can be passed on, those of the highest priorities
+    /// which all answered, or are passed over.
+    #[allow(clippy::type_complexity)]
+    fn ready(
+        &mut self,
+    ) -> Vec<(
+        PluginId,
+        Result<Value, RpcError>,
+        Box<dyn ClonableCallback<Value, RpcError>>,
+    )> {
+        let mut ready = Vec::new();
+        while let Some(result) = self.results.get_mut(self.next) {
+            match result.take() {
+                Some(result) => ready.push(result),
+                None if self.expired => {}
+                None => break,
+            }
+            self.next += 1;
+        }
+        ready
+    }
+
+    /// Stops waiting on the servers which haven't answered, and returns the
+    /// answers which were waiting on them.
+    #[allow(clippy::type_complexity)]
+    fn expire(
+        &mut self,
+    ) -> Vec<(
+        PluginId,
+        Result<Value, RpcError>,
+        Box<dyn ClonableCallback<Value, RpcError>>,
+    )> {
+        self.expired = true;
+        self.ready()
+    }
+}
+
+fn lsp_server_document_selector(config: &LspServerConfig) -> DocumentSelector {
+    let languages = config.languages.iter().map(|language| DocumentFilter {
+        language: Some(language.clone()),
+        scheme: None,
+        pattern: None,
+    });
+    let globs = config.globs.iter().map(|glob| DocumentFilter {
+        language: None,
+        scheme: None,
+        pattern: Some(glob.clone()),
+    });
+    languages.chain(globs).collect()
+}
+
+/// Where to start the language server for the document, if it is for it.
+/// Without root markers that is the workspace.
+fn lsp_server_root(
+    config: &LspServerConfig,
+    workspace: Option<&Path>,
+    path: &Path,
+    language_id: &str,
+) -> Option<PathBuf> {
+    let matches = config
+        .languages
+        .iter()
+        .any(|language| language == language_id)
+        || config.globs.iter().any(|glob| {
+            globset::Glob::new(glob)
+                .map(|glob| glob.compile_matcher().is_match(path))
+                .unwrap_or(false)
+        });
+    if !matches {
+        return None;
+    }
+    if config.root_markers.is_empty() {
+        return workspace.or_else(|| path.parent()).map(Path::to_path_buf);
+    }
+    path.ancestors()
+        .skip(1)
+        .take_while(|dir| {
+            workspace.is_none_or(|workspace| dir.starts_with(workspace))
+        })
+        .find(|dir| {
+            config
+                .root_markers
+                .iter()
+                .any(|marker| dir.join(marker).exists())
+        })
+        .map(Path::to_path_buf)
+}
+
+/// The language servers of the user settings, with the ones of the workspace
+/// settings replacing them by name. What's run, the command with its arguments
+/// and environment, only comes from the user settings, or opening a workspace
+/// would be enough to run anything it asks for.
+fn merge_lsp_servers(
+    user: &HashMap<String, LspServerConfig>,
+    workspace: HashMap<String, LspServerConfig>,
+) -> HashMap<String, LspServerConfig> {
+    let mut servers = user.clone();
+    for (name, mut config) in workspace {
+        if config.command.is_some() {
+            tracing::warn!(
+                "the command of the {name} language server of the workspace \
+                 settings is ignored, it can only be set in the user settings"
+            );
+        }
+        let user = user.get(&name);
+        config.command = user.and_then(|user| user.command.clone());
+        config.args = user.map(|user| user.args.clone()).unwrap_or_default();
+        config.env = user.map(|user| user.env.clone()).unwrap_or_default();
+        servers.insert(name, config);
+    }
+    servers
+}
+
+fn read_workspace_settings(workspace: Option<&Path>) -> Option<toml::Value> {
+    let content =
+        fs::read_to_string(workspace?.join(".phidi").join("settings.toml")).ok()?;
+    toml::from_str::<toml::Value>(&content)
+        .inspect_err(|err| tracing::error!("{:?}", err))
+        .ok()
+}
+
+fn merge_settings(settings: &mut Value, other: Value) {
+    match (settings, other) {
+        (Value::Object(settings), Value::Object(other)) => {
+            for (key, value) in other {
+                match settings.get_mut(&key) {
+                    Some(setting) => merge_settings(setting, value),
+                    None => {
+                        settings.insert(key, value);
+                    }
+                }
+            }
+        }
+        (settings, other) => *settings = other,
+    }
+}
+
+/// The value of a `workspace/configuration` section, given as a dotted path
+/// into the settings, e.g. `rust-analyzer.cargo` for the `cargo` table of
+/// the `[rust-analyzer]` settings. Without a section it is the configuration
+/// of the plugin.
+fn configuration_section(
+    settings: &Value,
+    volt_name: &str,
+    section: Option<&str>,
+) -> Value {
+    let section = section.filter(|section| !section.is_empty());
+    section
+        .unwrap_or(volt_name)
+        .split('.')
+        .try_fold(settings, |value, key| value.get(key))
+        .cloned()
+        .unwrap_or(Value::Null)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{Value, json};
+
+    use std::{collections::HashMap, fs};
+
+    use phidi_rpc::{
+        RpcError,
+        plugin::{LspServerConfig, PluginId},
+    };
+
+    use super::{
+        ClonableCallback, PriorityOrder, configuration_section, lsp_server_root,
+        merge_lsp_servers, merge_settings,
+    };
+
+    #[test]
+    fn test_configuration_section() {
+        let mut settings = json!({
+            "rust-analyzer": {
+                "cargo": { "features": "all" },
+                "check": { "command": "check" },
+            },
+        });
+        merge_settings(
+            &mut settings,
+            json!({ "rust-analyzer": { "check": { "command": "clippy" } } }),
+        );
+
+        assert_eq!(
+            configuration_section(&settings, "rust-analyzer", None),
+            json!({
+                "cargo": { "features": "all" },
+                "check": { "command": "clippy" },
+            })
+        );
+        assert_eq!(
+            configuration_section(&settings, "other", Some("rust-analyzer.cargo")),
+            json!({ "features": "all" })
+        );
+        assert_eq!(
+            configuration_section(&settings, "other", Some("typescript")),
+            Value::Null
+        );
+    }
+
+    #[test]
+    fn test_lsp_server_root() {
+        let workspace = tempfile::tempdir().unwrap();
+        let workspace = workspace.path();
+        fs::create_dir_all(workspace.join("crates/a/src")).unwrap();
+        fs::write(workspace.join("crates/a/Cargo.toml"), "").unwrap();
+        let file = workspace.join("crates/a/src/lib.rs");
+
+        let mut config = LspServerConfig {
+            command: Some("rust-analyzer".to_string()),
+            args: Vec::new(),
+            env: HashMap::new(),
+            languages: vec!["rust".to_string()],
+            globs: Vec::new(),
+            initialization_options: None,
+            root_markers: Vec::new(),
+            priority: HashMap::new(),
+        };
+        assert_eq!(
+            lsp_server_root(&config, Some(workspace), &file, "rust"),
+            Some(workspace.to_path_buf())
+        );
+        assert_eq!(
+            lsp_server_root(&config, Some(workspace), &file, "toml"),
+            None
+        );
+
+        config.root_markers = vec!["Cargo.toml".to_string()];
+        assert_eq!(
+            lsp_server_root(&config, Some(workspace), &file, "rust"),
+            Some(workspace.join("crates/a"))
+        );
+        config.root_markers = vec!["go.mod".to_string()];
+        assert_eq!(
+            lsp_server_root(&config, Some(workspace), &file, "rust"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_merge_lsp_servers() {
+        let server = |command: Option<&str>, languages: &[&str]| LspServerConfig {
+            command: command.map(str::to_string),
+            args: command
+                .map(|_| vec!["--stdio".to_string()])
+                .unwrap_or_default(),
+            env: HashMap::new(),
+            languages: languages.iter().map(|l| l.to_string()).collect(),
+            globs: Vec::new(),
+            initialization_options: None,
+            root_markers: Vec::new(),
+            priority: HashMap::new(),
+        };
+        let user = HashMap::from([(
+            "rust-analyzer".to_string(),
+            server(Some("rust-analyzer"), &["rust"]),
+        )]);
+        let workspace = HashMap::from([
+            (
+                "rust-analyzer".to_string(),
+                server(Some("./evil"), &["rust", "toml"]),
+            ),
+            ("evil".to_string(), server(Some("./evil"), &["python"])),
+        ]);
+
+        let servers = merge_lsp_servers(&user, workspace);
+        // The workspace settings tell what the server is for, not what's run.
+        assert_eq!(
+            servers["rust-analyzer"],
+            server(Some("rust-analyzer"), &["rust", "toml"])
+        );
+        assert_eq!(servers["evil"], server(None, &["python"]));
+    }
+
+    #[test]
+    fn test_priority_order_ready() {
+        let mut ordered = PriorityOrder {
+            results: vec![None, None, None, None],
+            next: 0,
+            expired: false,
+        };
+        #[allow(clippy::type_complexity)]
+        fn answer(
+            id: u64,
+        ) -> Option<(
+            PluginId,
+            Result<Value, RpcError>,
+            Box<dyn ClonableCallback<Value, RpcError>>,
+        )> {
+            Some((
+                PluginId(id),
+                Ok(json!(id)),
+                Box::new(|_: PluginId, _: Result<Value, RpcError>| {}),
+            ))
+        }
+        fn ids(ordered: &mut PriorityOrder) -> Vec<u64> {
+            ordered.ready().into_iter().map(|(id, _, _)| id.0).collect()
+        }
+
+        // A server answering before those of higher priority waits for them.
+        ordered.results[1] = answer(1);
+        assert!(ids(&mut ordered).is_empty());
+
+        ordered.results[0] = answer(0);
+        assert_eq!(ids(&mut ordered), vec![0, 1]);
+        assert!(ids(&mut ordered).is_empty());
+
+        ordered.results[3] = answer(3);
+        assert!(ids(&mut ordered).is_empty());
+
+        // Once the wait is over, the server which didn't answer is skipped.
+        let expired = ordered.expire();
+        assert_eq!(
+            expired
+                .into_iter()
+                .map(|(id, _, _)| id.0)
+                .collect::<Vec<_>>(),
+            vec![3]
+        );
+        assert_eq!(ordered.next, 4);
+    }
+}
@@ -1,7 +1,9 @@
 #[cfg(target_os = "windows")]
 use std::os::windows::process::CommandExt;
 use std::{
+    collections::HashMap,
     io::{BufRead, BufReader, BufWriter, Write},
+    ops,
     path::{Path, PathBuf},
     process::{self, Child, Command, Stdio},
     sync::Arc,
@@ -20,7 +22,6 @@
 use phidi_rpc::{
     RpcError,
     plugin::{PluginId, VoltID},
-    style::LineStyle,
 };
 use phidi_xi_rope::Rope;
 use serde_json::Value;
@@ -29,7 +30,7 @@
     client_capabilities,
     psp::{
         PluginHandlerNotification, PluginHostHandler, PluginServerHandler,
-        PluginServerRpcHandler, ResponseSender, RpcCallback,
+        PluginServerRpcHandler, ResponseSender, RpcCallback, SemanticTokenStyles,
         handle_plugin_server_message,
     },
 };
@@ -72,6 +73,10 @@ fn method_registered(&mut self, method: &str) -> bool {
         self.host.method_registered(method)
     }
 
+    fn params_supported(&mut self, method: &str, params: &Params) -> bool {
+        self.host.params_supported(method, params)
+    }
+
     fn document_supported(
         &mut self,
         lanaguage_id: Option<&str>,
@@ -95,6 +100,9 @@ fn handle_handler_notification(
             Shutdown => {
                 self.shutdown();
             }
+            PullWorkspaceDiagnostics => {
+                self.host.pull_workspace_diagnostics();
+            }
             SpawnedPluginLoaded { .. } => {}
         }
     }
@@ -120,6 +128,10 @@ fn handle_host_notification(
         }
     }
 
+    fn handle_did_open_text_document(&mut self, language_id: String, path: PathBuf) {
+        self.host.handle_did_open_text_document(language_id, path);
+    }
+
     fn handle_did_save_text_document(
         &self,
         language_id: String,
@@ -162,10 +174,11 @@ fn handle_did_change_text_document(
     fn format_semantic_tokens(
         &self,
         tokens: SemanticTokens,
+        changed: Option<ops::Range<usize>>,
         text: Rope,
-        f: Box<dyn RpcCallback<Vec<LineStyle>, RpcError>>,
+        f: Box<dyn RpcCallback<SemanticTokenStyles, RpcError>>,
     ) {
-        self.host.format_semantic_tokens(tokens, text, f);
+        self.host.format_semantic_tokens(tokens, changed, text, f);
     }
 }
 
@@ -180,28 +193,12 @@ fn new(
         spawned_by: Option<PluginId>,
         plugin_id: Option<PluginId>,
         pwd: Option<PathBuf>,
-        server_uri: Url,
+        server: String,
         args: Vec<String>,
+        env: HashMap<String, String>,
         options: Option<Value>,
     ) -> Result<Self> {
-        let server = match server_uri.scheme() {
-            "file" => {
-                let path = server_uri.to_file_path().map_err(|_| anyhow!(""))?;
-                #[cfg(unix)]
-                if let Err(err) = std::process::Command::new("chmod")
-                    .arg("+x")
-                    .arg(&path)
-                    .output()
-                {
-                    tracing::error!("{:?}", err);
-                }
-                path.to_str().ok_or_else(|| anyhow!(""))?.to_string()
-            }
-            "urn" => server_uri.path().to_string(),
-            _ => return Err(anyhow!("uri not supported")),
-        };
-
-        let mut process = Self::process(workspace.as_ref(), &server, &args)?;
+        let mut process = Self::process(workspace.as_ref(), &server, &args, &env)?;
         let stdin = process.stdin.take().unwrap();
         let stdout = process.stdout.take().unwrap();
         let stderr = process.stderr.take().unwrap();
@@ -332,8 +329,9 @@ pub fn start(
         spawned_by: Option<PluginId>,
         plugin_id: Option<PluginId>,
         pwd: Option<PathBuf>,
-        server_uri: Url,
+        server: String,
         args: Vec<String>,
+        env: HashMap<String, String>,
         options: Option<Value>,
     ) -> Result<PluginId> {
         let mut lsp = Self::new(
@@ -345,8 +343,9 @@ pub fn start(
             spawned_by,
             plugin_id,
             pwd,
-            server_uri,
+            server,
             args,
+            env,
             options,
         )?;
         let plugin_id = lsp.server_rpc.plugin_id;
@@ -358,6 +357,27 @@ pub fn start(
         Ok(plugin_id)
     }
 
+    /// The command of a server a plugin asked to start, either a file the
+    /// plugin shipped or the name of a program on the path.
+    pub fn server_from_uri(server_uri: &Url) -> Result<String> {
+        match server_uri.scheme() {
+            "file" => {
+                let path = server_uri.to_file_path().map_err(|_| anyhow!(""))?;
+                #[cfg(unix)]
+                if let Err(err) = std::process::Command::new("chmod")
+                    .arg("+x")
+                    .arg(&path)
+                    .output()
+                {
+                    tracing::error!("{:?}", err);
+                }
+                Ok(path.to_str().ok_or_else(|| anyhow!(""))?.to_string())
+            }
+            "urn" => Ok(server_uri.path().to_string()),
+            _ => Err(anyhow!("uri not supported")),
+        }
+    }
+
     fn initialize(&mut self) {
         let root_uri = self
             .workspace
@@ -385,6 +405,14 @@ fn initialize(&mut self) {
             root_path: None,
             work_done_progress_params: WorkDoneProgressParams::default(),
         };
+        let mut params = serde_json::to_value(params).unwrap();
+        // `rangesSupport` is new in LSP 3.18 and not in lsp-types yet.
+        if let Some(range_formatting) = params
+            .pointer_mut("/capabilities/textDocument/rangeFormatting")
+            .and_then(Value::as_object_mut)
+        {
+            range_formatting.insert("rangesSupport".to_string(), Value::Bool(true));
+        }
         match self.server_rpc.server_request(
             Initialize::METHOD,
             params,
@@ -393,6 +421,15 @@ fn initialize(&mut self) {
             false,
         ) {
             Ok(value) => {
+                self.host.type_hierarchy_provider = value
+                    .pointer("/capabilities/typeHierarchyProvider")
+                    .is_some_and(|provider| {
+                        !provider.is_null() && provider != &Value::Bool(false)
+                    });
+                self.host.ranges_formatting_provider = value
+                    .pointer("/capabilities/documentRangeFormattingProvider/rangesSupport")
+                    .and_then(Value::as_bool)
+                    .unwrap_or(false);
                 let result: InitializeResult =
                     serde_json::from_value(value).unwrap();
                 self.host.server_capabilities = result.capabilities;
@@ -403,6 +440,7 @@ fn initialize(&mut self) {
                     None,
                     false,
                 );
+                self.host.pull_workspace_diagnostics();
                 if self
                     .plugin_rpc
                     .plugin_server_loaded(self.server_rpc.clone())
@@ -441,6 +479,7 @@ fn process(
         workspace: Option<&PathBuf>,
         server: &str,
         args: &[String],
+        env: &HashMap<String, String>,
     ) -> Result<Child> {
         let mut process = Command::new(server);
         if let Some(workspace) = workspace {
@@ -448,6 +487,7 @@ fn process(
         }
 
         process.args(args);
+        process.envs(env);
 
         #[cfg(target_os = "windows")]
         let process = process.creation_flags(0x08000000);
@@ -7,7 +7,7 @@
 use std::{
     borrow::Cow,
     collections::HashMap,
-    fs,
+    fs, ops,
     path::{Path, PathBuf},
     sync::{
         Arc,
@@ -22,42 +22,67 @@
 use flate2::read::GzDecoder;
 use lsp_types::{
     CallHierarchyClientCapabilities, CallHierarchyIncomingCall,
-    CallHierarchyIncomingCallsParams, CallHierarchyItem, CallHierarchyPrepareParams,
+    CallHierarchyIncomingCallsParams, CallHierarchyItem, CallHierarchyOutgoingCall,
+    CallHierarchyOutgoingCallsParams, CallHierarchyPrepareParams,
     ClientCapabilities, CodeAction, CodeActionCapabilityResolveSupport,
     CodeActionClientCapabilities, CodeActionContext, CodeActionKind,
     CodeActionKindLiteralSupport, CodeActionLiteralSupport, CodeActionParams,
-    CodeActionResponse, CodeLens, CodeLensParams, CompletionClientCapabilities,
+    CodeActionResponse, CodeLens, CodeLensParams, Color, ColorInformation,
+    ColorPresentation, ColorPresentationParams, CompletionClientCapabilities,
     CompletionItem, CompletionItemCapability,
     CompletionItemCapabilityResolveSupport, CompletionParams, CompletionResponse,
-    Diagnostic, DocumentFormattingParams, DocumentSymbolClientCapabilities,
-    DocumentSymbolParams, DocumentSymbolResponse, FoldingRange,
-    FoldingRangeClientCapabilities, FoldingRangeParams, FormattingOptions,
-    GotoCapability, GotoDefinitionParams, GotoDefinitionResponse, Hover,
-    HoverClientCapabilities, HoverParams, InlayHint, InlayHintClientCapabilities,
-    InlayHintParams, InlineCompletionClientCapabilities, InlineCompletionParams,
-    InlineCompletionResponse, InlineCompletionTriggerKind, Location, MarkupKind,
-    MessageActionItemCapabilities, ParameterInformationSettings,
+    ConfigurationItem, CreateFilesParams, DeleteFilesParams, Diagnostic,
+    DiagnosticClientCapabilities, DiagnosticWorkspaceClientCapabilities,
+    DocumentChangeOperation, DocumentChanges, DocumentColorClientCapabilities,
+    DocumentColorParams, DocumentFormattingParams, DocumentHighlight,
+    DocumentHighlightClientCapabilities, DocumentHighlightParams, DocumentLink,
+    DocumentLinkClientCapabilities, DocumentLinkParams,
+    DocumentOnTypeFormattingParams, DocumentRangeFormattingParams,
+    DocumentSymbolClientCapabilities, DocumentSymbolParams, DocumentSymbolResponse,
+    DynamicRegistrationClientCapabilities, FileCreate, FileDelete, FileRename,
+    FoldingRange, FoldingRangeClientCapabilities, FoldingRangeParams,
+    FormattingOptions, GotoCapability, GotoDefinitionParams, GotoDefinitionResponse,
+    Hover, HoverClientCapabilities, HoverParams, InlayHint,
+    InlayHintClientCapabilities, InlayHintParams,
+    InlineCompletionClientCapabilities, InlineCompletionParams,
+    InlineCompletionResponse, InlineCompletionTriggerKind,
+    LinkedEditingRangeClientCapabilities, LinkedEditingRangeParams,
+    LinkedEditingRanges, Location, MarkupKind, MessageActionItemCapabilities, OneOf,
+    OptionalVersionedTextDocumentIdentifier, ParameterInformationSettings,
     PartialResultParams, Position, PrepareRenameResponse,
-    PublishDiagnosticsClientCapabilities, Range, ReferenceContext, ReferenceParams,
-    RenameParams, SelectionRange, SelectionRangeParams, SemanticTokens,
-    SemanticTokensClientCapabilities, SemanticTokensParams,
+    PublishDiagnosticsClientCapabilities, PublishDiagnosticsParams, Range,
+    ReferenceContext, ReferenceParams, RenameFilesParams, RenameParams,
+    SelectionRange, SelectionRangeParams, SemanticToken, SemanticTokens,
+    SemanticTokensClientCapabilities, SemanticTokensClientCapabilitiesRequests,
+    SemanticTokensDeltaParams, SemanticTokensEdit, SemanticTokensFullDeltaResult,
+    SemanticTokensFullOptions, SemanticTokensParams, SemanticTokensRangeParams,
     ShowMessageRequestClientCapabilities, SignatureHelp,
     SignatureHelpClientCapabilities, SignatureHelpParams,
     SignatureInformationSettings, SymbolInformation, TextDocumentClientCapabilities,
-    TextDocumentIdentifier, TextDocumentItem, TextDocumentPositionParams,
-    TextDocumentSyncClientCapabilities, TextEdit, Url,
+    TextDocumentEdit, TextDocumentIdentifier, TextDocumentItem,
+    TextDocumentPositionParams, TextDocumentSyncClientCapabilities, TextEdit,
+    TypeHierarchyClientCapabilities, TypeHierarchyItem, TypeHierarchyPrepareParams,
+    TypeHierarchySubtypesParams, TypeHierarchySupertypesParams, Url,
     VersionedTextDocumentIdentifier, WindowClientCapabilities,
     WorkDoneProgressParams, WorkspaceClientCapabilities, WorkspaceEdit,
+    WorkspaceEditClientCapabilities, WorkspaceFileOperationsClientCapabilities,
     WorkspaceSymbolClientCapabilities, WorkspaceSymbolParams,
+    notification::{DidCreateFiles, DidDeleteFiles, DidRenameFiles, Notification},
     request::{
-        CallHierarchyIncomingCalls, CallHierarchyPrepare, CodeActionRequest,
-        CodeActionResolveRequest, CodeLensRequest, CodeLensResolve, Completion,
-        DocumentSymbolRequest, FoldingRangeRequest, Formatting, GotoDefinition,
-        GotoImplementation, GotoImplementationResponse, GotoTypeDefinition,
-        GotoTypeDefinitionParams, GotoTypeDefinitionResponse, HoverRequest,
-        InlayHintRequest, InlineCompletionRequest, PrepareRenameRequest, References,
-        Rename, Request, ResolveCompletionItem, SelectionRangeRequest,
-        SemanticTokensFullRequest, SignatureHelpRequest, WorkspaceSymbolRequest,
+        CallHierarchyIncomingCalls, CallHierarchyOutgoingCalls,
+        CallHierarchyPrepare, CodeActionRequest, CodeActionResolveRequest,
+        CodeLensRequest, CodeLensResolve, ColorPresentationRequest, Completion,
+        DocumentColor, DocumentHighlightRequest, DocumentLinkRequest,
+        DocumentLinkResolve, DocumentSymbolRequest, FoldingRangeRequest, Formatting,
+        GotoDefinition, GotoImplementation, GotoImplementationResponse,
+        GotoTypeDefinition, GotoTypeDefinitionParams, GotoTypeDefinitionResponse,
+        HoverRequest, InlayHintRequest, InlineCompletionRequest, LinkedEditingRange,
+        OnTypeFormatting, PrepareRenameRequest, RangeFormatting, References, Rename,
+        Request, ResolveCompletionItem, SelectionRangeRequest,
+        SemanticTokensFullDeltaRequest, SemanticTokensFullRequest,
+        SemanticTokensRangeRequest, SignatureHelpRequest, TypeHierarchyPrepare,
+        TypeHierarchySubtypes, TypeHierarchySupertypes, WillCreateFiles,
+        WillDeleteFiles, WillRenameFiles, WorkspaceSymbolRequest,
     },
 };
 use parking_lot::Mutex;
@@ -66,9 +91,8 @@
     RequestId, RpcError,
     core::CoreRpcHandler,
     dap_types::{self, DapId, RunDebugConfig, SourceBreakpoint, ThreadId},
-    plugin::{PluginId, VoltInfo, VoltMetadata},
+    plugin::{LspServerConfig, PluginId, VoltInfo, VoltMetadata},
     proxy::ProxyRpcHandler,
-    style::LineStyle,
     terminal::TermId,
 };
 use phidi_xi_rope::{Rope, RopeDelta};
@@ -80,7 +104,10 @@
 use self::{
     catalog::PluginCatalog,
     dap::DapRpcHandler,
-    psp::{ClonableCallback, PluginServerRpcHandler, RpcCallback},
+    psp::{
+        ClonableCallback, PluginServerRpcHandler, ResponseSender, RpcCallback,
+        SemanticTokenStyles,
+    },
     wasi::{load_volt, start_volt},
 };
 use crate::buffer::language_id_from_path;
@@ -110,8 +137,9 @@ pub enum PluginCatalogRpc {
     FormatSemanticTokens {
         plugin_id: PluginId,
         tokens: SemanticTokens,
+        changed: Option<ops::Range<usize>>,
         text: Rope,
-        f: Box<dyn RpcCallback<Vec<LineStyle>, RpcError>>,
+        f: Box<dyn RpcCallback<SemanticTokenStyles, RpcError>>,
     },
     DapVariable {
         dap_id: DapId,
@@ -155,6 +183,12 @@ pub enum PluginCatalogRpc {
 #[allow(clippy::large_enum_variant)]
 pub enum PluginCatalogNotification {
     UpdatePluginConfigs(HashMap<String, HashMap<String, serde_json::Value>>),
+    UpdateLspServers(HashMap<String, LspServerConfig>),
+    Configuration {
+        volt_name: String,
+        items: Vec<ConfigurationItem>,
+        resp: ResponseSender,
+    },
     UnactivatedVolts(Vec<VoltMetadata>),
     PluginServerLoaded(PluginServerRpcHandler),
     InstallVolt(VoltInfo),
@@ -225,6 +259,13 @@ pub struct PluginCatalogRpcHandler {
     id: Arc<AtomicU64>,
     #[allow(dead_code, clippy::type_complexity)]
     pending: Arc<Mutex<HashMap<u64, Sender<Result<Value, RpcError>>>>>,
+    server_names: Arc<Mutex<HashMap<PluginId, String>>>,
+    /// The diagnostics of each server for each document.
+    #[allow(clippy::type_complexity)]
+    diagnostics: Arc<Mutex<HashMap<Url, HashMap<PluginId, Vec<Diagnostic>>>>>,
+    /// The last semantic tokens of each document, which the deltas of the
+    /// server that sent them apply to.
+    semantic_tokens: Arc<Mutex<HashMap<PathBuf, (PluginId, SemanticTokens)>>>,
 }
 
 impl PluginCatalogRpcHandler {
@@ -237,6 +278,9 @@ pub fn new(core_rpc: CoreRpcHandler, proxy_rpc: ProxyRpcHandler) -> Self {
             plugin_rx: Arc::new(Mutex::new(Some(plugin_rx))),
             id: Arc::new(AtomicU64::new(0)),
             pending: Arc::new(Mutex::new(HashMap::new())),
+            server_names: Arc::new(Mutex::new(HashMap::new())),
+            diagnostics: Arc::new(Mutex::new(HashMap::new())),
+            semantic_tokens: Arc::new(Mutex::new(HashMap::new())),
         }
     }
 
@@ -297,10 +341,12 @@ pub fn mainloop(&self, plugin: &mut PluginCatalog) {
                 PluginCatalogRpc::FormatSemanticTokens {
                     plugin_id,
                     tokens,
+                    changed,
                     text,
                     f,
                 } => {
-                    plugin.format_semantic_tokens(plugin_id, tokens, text, f);
+                    plugin
+                        .format_semantic_tokens(plugin_id, tokens, changed, text, f);
                 }
                 PluginCatalogRpc::DidOpenTextDocument { document } => {
                     plugin.handle_did_open_text_document(document);
@@ -430,6 +476,91 @@ fn send_request_to_all_plugins<P, Resp>(
         );
     }
 
+    /// Like [`Self::send_request_to_all_plugins`], but waits for all the
+    /// servers and gives the answers of all the ones which succeeded.
+    fn collect_from_all_plugins<P, Resp>(
+        &self,
+        method: &'static str,
+        params: P,
+        language_id: Option<String>,
+        path: Option<PathBuf>,
+        cb: impl FnOnce(Vec<(PluginId, Resp)>) + Send + 'static,
+    ) where
+        P: Serialize,
+        Resp: DeserializeOwned + Send + 'static,
+    {
+        let request_sent = Arc::new(AtomicUsize::new(0));
+        let collected = Arc::new(Mutex::new((0, Vec::new(), Some(cb))));
+        self.send_request(
+            None,
+            Some(request_sent.clone()),
+            method,
+            params,
+            language_id,
+            path,
+            true,
+            move |plugin_id, result| {
+                let mut collected = collected.lock();
+                let (received, items, cb) = &mut *collected;
+                *received += 1;
+                if let Ok(item) = result.and_then(|value| {
+                    serde_json::from_value::<Resp>(value).map_err(|err| RpcError {
+                        code: 0,
+                        message: err.to_string(),
+                    })
+                }) {
+                    items.push((plugin_id, item));
+                }
+                if *received == request_sent.load(Ordering::Acquire) {
+                    if let Some(cb) = cb.take() {
+                        cb(std::mem::take(items));
+                    }
+                }
+            },
+        );
+    }
+
+    /// The name of a server, for telling apart the results of several.
+    pub fn server_name(&self, plugin_id: PluginId) -> Option<String> {
+        self.server_names.lock().get(&plugin_id).cloned()
+    }
+
+    /// Publish the diagnostics of a document from one server, along with the
+    /// ones the other servers have for it. Diagnostics without a source are
+    /// labelled with the server's name.
+    pub(crate) fn publish_diagnostics(
+        &self,
+        plugin_id: PluginId,
+        mut params: PublishDiagnosticsParams,
+    ) {
+        if let Some(name) = self.server_name(plugin_id) {
+            for diagnostic in params.diagnostics.iter_mut() {
+                if diagnostic.source.is_none() {
+                    diagnostic.source = Some(name.clone());
+                }
+            }
+        }
+        let diagnostics = {
+            let mut all = self.diagnostics.lock();
+            let servers = all.entry(params.uri.clone()).or_default();
+            if params.diagnostics.is_empty() {
+                servers.remove(&plugin_id);
+            } else {
+                servers.insert(plugin_id, params.diagnostics);
+            }
+            let diagnostics = servers.values().flatten().cloned().collect();
+            if servers.is_empty() {
+                all.remove(&params.uri);
+            }
+            diagnostics
+        };
+        self.core_rpc.publish_diagnostics(PublishDiagnosticsParams {
+            uri: params.uri,
+            diagnostics,
+            version: params.version,
+        });
+    }
+
     #[allow(clippy::too_many_arguments)]
     pub(crate) fn send_request<P: Serialize>(
         &self,
@@ -482,17 +613,21 @@ pub(crate) fn send_notification<P: Serialize>(
         }
     }
 
+    /// Turn the semantic tokens into styles, only those in `changed`, a range
+    /// of token indices, if it's given.
     pub fn format_semantic_tokens(
         &self,
         plugin_id: PluginId,
         tokens: SemanticTokens,
+        changed: Option<ops::Range<usize>>,
         text: Rope,
-        f: Box<dyn RpcCallback<Vec<LineStyle>, RpcError>>,
+        f: Box<dyn RpcCallback<SemanticTokenStyles, RpcError>>,
     ) {
         if let Err(err) =
             self.plugin_tx.send(PluginCatalogRpc::FormatSemanticTokens {
                 plugin_id,
                 tokens,
+                changed,
                 text,
                 f,
             })
@@ -501,6 +636,91 @@ pub fn format_semantic_tokens(
         }
     }
 
+    /// Ask the language servers for the edits to make along with creating
+    /// files, such as filling in a new module. The edit comes through the
+    /// receiver once the servers answered, without waiting for them here.
+    pub fn will_create_files(
+        &self,
+        paths: &[PathBuf],
+    ) -> Receiver<Option<WorkspaceEdit>> {
+        self.will_change_files(WillCreateFiles::METHOD, create_files_params(paths))
+    }
+
+    pub fn did_create_files(&self, paths: &[PathBuf]) {
+        self.send_notification(
+            None,
+            DidCreateFiles::METHOD,
+            create_files_params(paths),
+            None,
+            None,
+            true,
+        );
+    }
+
+    /// Ask the language servers for the edits to make along with renaming
+    /// files, such as updating the imports of a module, see
+    /// [`Self::will_create_files`].
+    pub fn will_rename_files(
+        &self,
+        renames: &[(PathBuf, PathBuf)],
+    ) -> Receiver<Option<WorkspaceEdit>> {
+        self.will_change_files(WillRenameFiles::METHOD, rename_files_params(renames))
+    }
+
+    pub fn did_rename_files(&self, renames: &[(PathBuf, PathBuf)]) {
+        self.send_notification(
+            None,
+            DidRenameFiles::METHOD,
+            rename_files_params(renames),
+            None,
+            None,
+            true,
+        );
+    }
+
+    /// Ask the language servers for the edits to make along with deleting
+    /// files, see [`Self::will_create_files`].
+    pub fn will_delete_files(
+        &self,
+        paths: &[PathBuf],
+    ) -> Receiver<Option<WorkspaceEdit>> {
+        self.will_change_files(WillDeleteFiles::METHOD, delete_files_params(paths))
+    }
+
+    pub fn did_delete_files(&self, paths: &[PathBuf]) {
+        self.send_notification(
+            None,
+            DidDeleteFiles::METHOD,
+            delete_files_params(paths),
+            None,
+            None,
+            true,
+        );
+    }
+
+    fn will_change_files<P: Serialize>(
+        &self,
+        method: &'static str,
+        params: P,
+    ) -> Receiver<Option<WorkspaceEdit>> {
+        let (tx, rx) = crossbeam_channel::bounded(1);
+        // Every server may have edits to make, e.g. one for the imports of a
+        // renamed module while another has nothing to say about it.
+        self.collect_from_all_plugins(
+            method,
+            params,
+            None,
+            None,
+            move |answers: Vec<(PluginId, Option<WorkspaceEdit>)>| {
+                let edits = answers.into_iter().filter_map(|(_, edit)| edit);
+                if let Err(err) = tx.send(merge_workspace_edits(edits.collect())) {
+                    tracing::error!("{:?}", err);
+                }
+            },
+        );
+        rx
+    }
+
     pub fn did_save_text_document(&self, path: &Path, text: Rope) {
         let text_document =
             TextDocumentIdentifier::new(Url::from_file_path(path).unwrap());
@@ -635,6 +855,35 @@ pub fn call_hierarchy_incoming(
         );
     }
 
+    pub fn call_hierarchy_outgoing(
+        &self,
+        path: &Path,
+        item: CallHierarchyItem,
+        cb: impl FnOnce(
+            PluginId,
+            Result<Option<Vec<CallHierarchyOutgoingCall>>, RpcError>,
+        ) + Clone
+        + Send
+        + 'static,
+    ) {
+        let method = CallHierarchyOutgoingCalls::METHOD;
+        let params = CallHierarchyOutgoingCallsParams {
+            item,
+            work_done_progress_params: WorkDoneProgressParams::default(),
+            partial_result_params: Default::default(),
+        };
+
+        let language_id =
+            Some(language_id_from_path(path).unwrap_or("").to_string());
+        self.send_request_to_all_plugins(
+            method,
+            params,
+            language_id,
+            Some(path.to_path_buf()),
+            cb,
+        );
+    }
+
     pub fn show_call_hierarchy(
         &self,
         path: &Path,
@@ -665,6 +914,90 @@ pub fn show_call_hierarchy(
         );
     }
 
+    pub fn show_type_hierarchy(
+        &self,
+        path: &Path,
+        position: Position,
+        cb: impl FnOnce(PluginId, Result<Option<Vec<TypeHierarchyItem>>, RpcError>)
+        + Clone
+        + Send
+        + 'static,
+    ) {
+        let uri = Url::from_file_path(path).unwrap();
+        let method = TypeHierarchyPrepare::METHOD;
+        let params = TypeHierarchyPrepareParams {
+            text_document_position_params: TextDocumentPositionParams {
+                text_document: TextDocumentIdentifier { uri },
+                position,
+            },
+            work_done_progress_params: WorkDoneProgressParams::default(),
+        };
+
+        let language_id =
+            Some(language_id_from_path(path).unwrap_or("").to_string());
+        self.send_request_to_all_plugins(
+            method,
+            params,
+            language_id,
+            Some(path.to_path_buf()),
+            cb,
+        );
+    }
+
+    pub fn type_hierarchy_supertypes(
+        &self,
+        path: &Path,
+        item: TypeHierarchyItem,
+        cb: impl FnOnce(PluginId, Result<Option<Vec<TypeHierarchyItem>>, RpcError>)
+        + Clone
+        + Send
+        + 'static,
+    ) {
+        let method = TypeHierarchySupertypes::METHOD;
+        let params = TypeHierarchySupertypesParams {
+            item,
+            work_done_progress_params: WorkDoneProgressParams::default(),
+            partial_result_params: PartialResultParams::default(),
+        };
+
+        let language_id =
+            Some(language_id_from_path(path).unwrap_or("").to_string());
+        self.send_request_to_all_plugins(
+            method,
+            params,
+            language_id,
+            Some(path.to_path_buf()),
+            cb,
+        );
+    }
+
+    pub fn type_hierarchy_subtypes(
+        &self,
+        path: &Path,
+        item: TypeHierarchyItem,
+        cb: impl FnOnce(PluginId, Result<Option<Vec<TypeHierarchyItem>>, RpcError>)
+        + Clone
+        + Send
+        + 'static,
+    ) {
+        let method = TypeHierarchySubtypes::METHOD;
+        let params = TypeHierarchySubtypesParams {
+            item,
+            work_done_progress_params: WorkDoneProgressParams::default(),
+            partial_result_params: PartialResultParams::default(),
+        };
+
+        let language_id =
+            Some(language_id_from_path(path).unwrap_or("").to_string());
+        self.send_request_to_all_plugins(
+            method,
+            params,
+            language_id,
+            Some(path.to_path_buf()),
+            cb,
+        );
+    }
+
     pub fn get_references(
         &self,
         path: &Path,
@@ -764,10 +1097,7 @@ pub fn get_code_actions(
         path: &Path,
         position: Position,
         diagnostics: Vec<Diagnostic>,
-        cb: impl FnOnce(PluginId, Result<CodeActionResponse, RpcError>)
-        + Clone
-        + Send
-        + 'static,
+        cb: impl FnOnce(Vec<(PluginId, CodeActionResponse)>) + Send + 'static,
     ) {
         let uri = Url::from_file_path(path).unwrap();
         let method = CodeActionRequest::METHOD;
@@ -787,7 +1117,7 @@ pub fn get_code_actions(
         };
         let language_id =
             Some(language_id_from_path(path).unwrap_or("").to_string());
-        self.send_request_to_all_plugins(
+        self.collect_from_all_plugins(
             method,
             params,
             language_id,
@@ -870,28 +1200,24 @@ pub fn get_inlay_hints(
         );
     }
 
-    pub fn get_inline_completions(
+    pub fn get_document_highlights(
         &self,
         path: &Path,
         position: Position,
-        trigger_kind: InlineCompletionTriggerKind,
-        cb: impl FnOnce(PluginId, Result<InlineCompletionResponse, RpcError>)
+        cb: impl FnOnce(PluginId, Result<Option<Vec<DocumentHighlight>>, RpcError>)
         + Clone
         + Send
         + 'static,
     ) {
         let uri = Url::from_file_path(path).unwrap();
-        let method = InlineCompletionRequest::METHOD;
-        let params = InlineCompletionParams {
-            text_document_position: TextDocumentPositionParams {
+        let method = DocumentHighlightRequest::METHOD;
+        let params = DocumentHighlightParams {
+            text_document_position_params: TextDocumentPositionParams {
                 text_document: TextDocumentIdentifier { uri },
                 position,
             },
-            context: lsp_types::InlineCompletionContext {
-                trigger_kind,
-                selected_completion_info: None,
-            },
             work_done_progress_params: WorkDoneProgressParams::default(),
+            partial_result_params: PartialResultParams::default(),
         };
         let language_id =
             Some(language_id_from_path(path).unwrap_or("").to_string());
@@ -904,20 +1230,23 @@ pub fn get_inline_completions(
         );
     }
 
-    pub fn get_document_symbols(
+    pub fn get_linked_editing_ranges(
         &self,
         path: &Path,
-        cb: impl FnOnce(PluginId, Result<DocumentSymbolResponse, RpcError>)
+        position: Position,
+        cb: impl FnOnce(PluginId, Result<Option<LinkedEditingRanges>, RpcError>)
         + Clone
         + Send
         + 'static,
     ) {
         let uri = Url::from_file_path(path).unwrap();
-        let method = DocumentSymbolRequest::METHOD;
-        let params = DocumentSymbolParams {
-            text_document: TextDocumentIdentifier { uri },
+        let method = LinkedEditingRange::METHOD;
+        let params = LinkedEditingRangeParams {
+            text_document_position_params: TextDocumentPositionParams {
+                text_document: TextDocumentIdentifier { uri },
+                position,
+            },
             work_done_progress_params: WorkDoneProgressParams::default(),
-            partial_result_params: PartialResultParams::default(),
         };
         let language_id =
             Some(language_id_from_path(path).unwrap_or("").to_string());
@@ -930,41 +1259,20 @@ pub fn get_document_symbols(
         );
     }
 
-    pub fn get_workspace_symbols(
-        &self,
-        query: String,
-        cb: impl FnOnce(PluginId, Result<Vec<SymbolInformation>, RpcError>)
-        + Clone
-        + Send
-        + 'static,
-    ) {
-        let method = WorkspaceSymbolRequest::METHOD;
-        let params = WorkspaceSymbolParams {
-            query,
-            work_done_progress_params: WorkDoneProgressParams::default(),
-            partial_result_params: PartialResultParams::default(),
-        };
-        self.send_request_to_all_plugins(method, params, None, None, cb);
-    }
-
-    pub fn get_document_formatting(
+    pub fn get_document_links(
         &self,
         path: &Path,
-        cb: impl FnOnce(PluginId, Result<Vec<TextEdit>, RpcError>)
+        cb: impl FnOnce(PluginId, Result<Option<Vec<DocumentLink>>, RpcError>)
         + Clone
         + Send
         + 'static,
     ) {
         let uri = Url::from_file_path(path).unwrap();
-        let method = Formatting::METHOD;
-        let params = DocumentFormattingParams {
+        let method = DocumentLinkRequest::METHOD;
+        let params = DocumentLinkParams {
             text_document: TextDocumentIdentifier { uri },
-            options: FormattingOptions {
-                tab_size: 4,
-                insert_spaces: true,
-                ..Default::default()
-            },
             work_done_progress_params: WorkDoneProgressParams::default(),
+            partial_result_params: PartialResultParams::default(),
         };
         let language_id =
             Some(language_id_from_path(path).unwrap_or("").to_string());
@@ -977,51 +1285,41 @@ pub fn get_document_formatting(
         );
     }
 
-    pub fn prepare_rename(
+    pub fn resolve_document_link(
         &self,
         path: &Path,
-        position: Position,
-        cb: impl FnOnce(PluginId, Result<PrepareRenameResponse, RpcError>)
+        link: DocumentLink,
+        cb: impl FnOnce(PluginId, Result<DocumentLink, RpcError>)
         + Clone
         + Send
         + 'static,
     ) {
-        let uri = Url::from_file_path(path).unwrap();
-        let method = PrepareRenameRequest::METHOD;
-        let params = TextDocumentPositionParams {
-            text_document: TextDocumentIdentifier { uri },
-            position,
-        };
+        let method = DocumentLinkResolve::METHOD;
         let language_id =
             Some(language_id_from_path(path).unwrap_or("").to_string());
         self.send_request_to_all_plugins(
             method,
-            params,
+            link,
             language_id,
             Some(path.to_path_buf()),
             cb,
         );
     }
 
-    pub fn rename(
+    pub fn get_document_colors(
         &self,
         path: &Path,
-        position: Position,
-        new_name: String,
-        cb: impl FnOnce(PluginId, Result<WorkspaceEdit, RpcError>)
+        cb: impl FnOnce(PluginId, Result<Vec<ColorInformation>, RpcError>)
         + Clone
         + Send
         + 'static,
     ) {
         let uri = Url::from_file_path(path).unwrap();
-        let method = Rename::METHOD;
-        let params = RenameParams {
-            text_document_position: TextDocumentPositionParams {
-                text_document: TextDocumentIdentifier { uri },
-                position,
-            },
-            new_name,
+        let method = DocumentColor::METHOD;
+        let params = DocumentColorParams {
+            text_document: TextDocumentIdentifier { uri },
             work_done_progress_params: WorkDoneProgressParams::default(),
+            partial_result_params: PartialResultParams::default(),
         };
         let language_id =
             Some(language_id_from_path(path).unwrap_or("").to_string());
@@ -1034,13 +1332,438 @@ pub fn rename(
         );
     }
 
-    pub fn get_semantic_tokens(
+    pub fn get_color_presentations(
         &self,
         path: &Path,
-        cb: impl FnOnce(PluginId, Result<SemanticTokens, RpcError>)
+        color: Color,
+        range: Range,
+        cb: impl FnOnce(PluginId, Result<Vec<ColorPresentation>, RpcError>)
+        + Clone
+        + Send
+        + 'static,
+    ) {
+        let uri = Url::from_file_path(path).unwrap();
+        let method = ColorPresentationRequest::METHOD;
+        let params = ColorPresentationParams {
+            text_document: TextDocumentIdentifier { uri },
+            color,
+            range,
+            work_done_progress_params: WorkDoneProgressParams::default(),
+            partial_result_params: PartialResultParams::default(),
+        };
+        let language_id =
+            Some(language_id_from_path(path).unwrap_or("").to_string());
+        self.send_request_to_all_plugins(
+            method,
+            params,
+            language_id,
+            Some(path.to_path_buf()),
+            cb,
+        );
+    }
+
+    pub fn get_inline_completions(
+        &self,
+        path: &Path,
+        position: Position,
+        trigger_kind: InlineCompletionTriggerKind,
+        cb: impl FnOnce(PluginId, Result<InlineCompletionResponse, RpcError>)
+        + Clone
+        + Send
+        + 'static,
+    ) {
+        let uri = Url::from_file_path(path).unwrap();
+        let method = InlineCompletionRequest::METHOD;
+        let params = InlineCompletionParams {
+            text_document_position: TextDocumentPositionParams {
+                text_document: TextDocumentIdentifier { uri },
+                position,
+            },
+            context: lsp_types::InlineCompletionContext {
+                trigger_kind,
+                selected_completion_info: None,
+            },
+            work_done_progress_params: WorkDoneProgressParams::default(),
+        };
+        let language_id =
+            Some(language_id_from_path(path).unwrap_or("").to_string());
+        self.send_request_to_all_plugins(
+            method,
+            params,
+            language_id,
+            Some(path.to_path_buf()),
+            cb,
+        );
+    }
+
+    pub fn get_document_symbols(
+        &self,
+        path: &Path,
+        cb: impl FnOnce(PluginId, Result<DocumentSymbolResponse, RpcError>)
+        + Clone
+        + Send
+        + 'static,
+    ) {
+        let uri = Url::from_file_path(path).unwrap();
+        let method = DocumentSymbolRequest::METHOD;
+        let params = DocumentSymbolParams {
+            text_document: TextDocumentIdentifier { uri },
+            work_done_progress_params: WorkDoneProgressParams::default(),
+            partial_result_params: PartialResultParams::default(),
+        };
+        let language_id =
+            Some(language_id_from_path(path).unwrap_or("").to_string());
+        self.send_request_to_all_plugins(
+            method,
+            params,
+            language_id,
+            Some(path.to_path_buf()),
+            cb,
+        );
+    }
+
+    pub fn get_workspace_symbols(
+        &self,
+        query: String,
+        cb: impl FnOnce(PluginId, Result<Vec<SymbolInformation>, RpcError>)
+        + Clone
+        + Send
+        + 'static,
+    ) {
+        let method = WorkspaceSymbolRequest::METHOD;
+        let params = WorkspaceSymbolParams {
+            query,
+            work_done_progress_params: WorkDoneProgressParams::default(),
+            partial_result_params: PartialResultParams::default(),
+        };
+        self.send_request_to_all_plugins(method, params, None, None, cb);
+    }
+
+    pub fn get_document_formatting(
+        &self,
+        path: &Path,
+        cb: impl FnOnce(PluginId, Result<Vec<TextEdit>, RpcError>)
+        + Clone
+        + Send
+        + 'static,
+    ) {
+        let uri = Url::from_file_path(path).unwrap();
+        let method = Formatting::METHOD;
+        let params = DocumentFormattingParams {
+            text_document: TextDocumentIdentifier { uri },
+            options: formatting_options(),
+            work_done_progress_params: WorkDoneProgressParams::default(),
+        };
+        let language_id =
+            Some(language_id_from_path(path).unwrap_or("").to_string());
+        self.send_request_to_all_plugins(
+            method,
+            params,
+            language_id,
+            Some(path.to_path_buf()),
+            cb,
+        );
+    }
+
+    pub fn get_document_range_formatting(
+        &self,
+        path: &Path,
+        range: Range,
+        cb: impl FnOnce(PluginId, Result<Vec<TextEdit>, RpcError>)
+        + Clone
+        + Send
+        + 'static,
+    ) {
+        let uri = Url::from_file_path(path).unwrap();
+        let method = RangeFormatting::METHOD;
+        let params = DocumentRangeFormattingParams {
+            text_document: TextDocumentIdentifier { uri },
+            range,
+            options: formatting_options(),
+            work_done_progress_params: WorkDoneProgressParams::default(),
+        };
+        let language_id =
+            Some(language_id_from_path(path).unwrap_or("").to_string());
+        self.send_request_to_all_plugins(
+            method,
+            params,
+            language_id,
+            Some(path.to_path_buf()),
+            cb,
+        );
+    }
+
+    /// Format several ranges of a document at once. A single range is sent as
+    /// `textDocument/rangeFormatting`, and so are all of them, one request each,
+    /// when no server supports `textDocument/rangesFormatting`. The edits of all
+    /// the ranges are against the same text, so they're returned together.
+    pub fn get_document_ranges_formatting(
+        &self,
+        path: &Path,
+        ranges: Vec<Range>,
+        cb: impl FnOnce(PluginId, Result<Vec<TextEdit>, RpcError>)
+        + Clone
+        + Send
+        + 'static,
+    ) {
+        let ranges = merge_ranges(ranges);
+        match ranges.as_slice() {
+            [] => {
+                cb(PluginId(0), Ok(Vec::new()));
+                return;
+            }
+            [range] => {
+                self.get_document_range_formatting(path, *range, cb);
+                return;
+            }
+            _ => {}
+        }
+
+        let uri = Url::from_file_path(path).unwrap();
+        let params = DocumentRangesFormattingParams {
+            text_document: TextDocumentIdentifier { uri },
+            ranges: ranges.clone(),
+            options: formatting_options(),
+        };
+        let language_id =
+            Some(language_id_from_path(path).unwrap_or("").to_string());
+        let catalog_rpc = self.clone();
+        let path_buf = path.to_path_buf();
+        self.send_request_to_all_plugins(
+            RANGES_FORMATTING_METHOD,
+            params,
+            language_id,
+            Some(path.to_path_buf()),
+            move |plugin_id, result: Result<Vec<TextEdit>, RpcError>| match result {
+                Ok(edits) => cb(plugin_id, Ok(edits)),
+                Err(_) => {
+                    catalog_rpc.format_ranges_separately(&path_buf, ranges, cb)
+                }
+            },
+        );
+    }
+
+    fn format_ranges_separately(
+        &self,
+        path: &Path,
+        ranges: Vec<Range>,
+        cb: impl FnOnce(PluginId, Result<Vec<TextEdit>, RpcError>)
+        + Clone
+        + Send
+        + 'static,
+    ) {
+        let remaining = Arc::new(AtomicUsize::new(ranges.len()));
+        let edits = Arc::new(Mutex::new(Vec::new()));
+        let cb = Arc::new(Mutex::new(Some(cb)));
+        for range in ranges {
+            let remaining = remaining.clone();
+            let edits = edits.clone();
+            let cb = cb.clone();
+            self.get_document_range_formatting(
+                path,
+                range,
+                move |plugin_id, result| {
+                    if let Ok(mut range_edits) = result {
+                        edits.lock().append(&mut range_edits);
+                    }
+                    if remaining.fetch_sub(1, Ordering::AcqRel) == 1 {
+                        if let Some(cb) = cb.lock().take() {
+                            let edits = std::mem::take(&mut *edits.lock());
+                            cb(plugin_id, Ok(combine_range_edits(edits)));
+                        }
+                    }
+                },
+            );
+        }
+    }
+
+    pub fn on_type_formatting(
+        &self,
+        path: &Path,
+        position: Position,
+        ch: String,
+        cb: impl FnOnce(PluginId, Result<Option<Vec<TextEdit>>, RpcError>)
+        + Clone
+        + Send
+        + 'static,
+    ) {
+        let uri = Url::from_file_path(path).unwrap();
+        let method = OnTypeFormatting::METHOD;
+        let params = DocumentOnTypeFormattingParams {
+            text_document_position: TextDocumentPositionParams {
+                text_document: TextDocumentIdentifier { uri },
+                position,
+            },
+            ch,
+            options: formatting_options(),
+        };
+        let language_id =
+            Some(language_id_from_path(path).unwrap_or("").to_string());
+        self.send_request_to_all_plugins(
+            method,
+            params,
+            language_id,
+            Some(path.to_path_buf()),
+            cb,
+        );
+    }
+
+    pub fn prepare_rename(
+        &self,
+        path: &Path,
+        position: Position,
+        cb: impl FnOnce(PluginId, Result<PrepareRenameResponse, RpcError>)
+        + Clone
+        + Send
+        + 'static,
+    ) {
+        let uri = Url::from_file_path(path).unwrap();
+        let method = PrepareRenameRequest::METHOD;
+        let params = TextDocumentPositionParams {
+            text_document: TextDocumentIdentifier { uri },
+            position,
+        };
+        let language_id =
+            Some(language_id_from_path(path).unwrap_or("").to_string());
+        self.send_request_to_all_plugins(
+            method,
+            params,
+            language_id,
+            Some(path.to_path_buf()),
+            cb,
+        );
+    }
+
+    pub fn rename(
+        &self,
+        path: &Path,
+        position: Position,
+        new_name: String,
+        cb: impl FnOnce(PluginId, Result<WorkspaceEdit, RpcError>)
         + Clone
         + Send
         + 'static,
+    ) {
+        let uri = Url::from_file_path(path).unwrap();
+        let method = Rename::METHOD;
+        let params = RenameParams {
+            text_document_position: TextDocumentPositionParams {
+                text_document: TextDocumentIdentifier { uri },
+                position,
+            },
+            new_name,
+            work_done_progress_params: WorkDoneProgressParams::default(),
+        };
+        let language_id =
+            Some(language_id_from_path(path).unwrap_or("").to_string());
+        self.send_request_to_all_plugins(
+            method,
+            params,
+            language_id,
+            Some(path.to_path_buf()),
+            cb,
+        );
+    }
+
+    /// Get the semantic tokens of the document, as a delta of the last ones
+    /// if the caller has those, which is `result_id`, and their server
+    /// supports it. The callback gets all the tokens, along with the range of
+    /// token indices that changed for a delta.
+    pub fn get_semantic_tokens(
+        &self,
+        path: &Path,
+        result_id: Option<String>,
+        cb: impl FnOnce(
+            PluginId,
+            Result<(SemanticTokens, Option<ops::Range<usize>>), RpcError>,
+        ) + Clone
+        + Send
+        + 'static,
+    ) {
+        let previous = self
+            .semantic_tokens
+            .lock()
+            .get(path)
+            .filter(|(_, tokens)| {
+                result_id.is_some() && tokens.result_id == result_id
+            })
+            .cloned();
+        let Some((plugin_id, previous)) = previous else {
+            self.get_full_semantic_tokens(path, cb);
+            return;
+        };
+
+        let uri = Url::from_file_path(path).unwrap();
+        let method = SemanticTokensFullDeltaRequest::METHOD;
+        let params = SemanticTokensDeltaParams {
+            text_document: TextDocumentIdentifier { uri },
+            previous_result_id: previous.result_id.clone().unwrap_or_default(),
+            work_done_progress_params: WorkDoneProgressParams::default(),
+            partial_result_params: PartialResultParams::default(),
+        };
+        let catalog_rpc = self.clone();
+        let local_path = path.to_path_buf();
+        self.send_request(
+            Some(plugin_id),
+            None,
+            method,
+            params,
+            Some(language_id_from_path(path).unwrap_or("").to_string()),
+            Some(path.to_path_buf()),
+            true,
+            move |plugin_id, result| {
+                let result = result.and_then(|value| {
+                    serde_json::from_value::<SemanticTokensFullDeltaResult>(value)
+                        .map_err(|err| RpcError {
+                            code: 0,
+                            message: err.to_string(),
+                        })
+                });
+                let (tokens, changed) = match result {
+                    Ok(SemanticTokensFullDeltaResult::Tokens(tokens)) => {
+                        (tokens, None)
+                    }
+                    Ok(SemanticTokensFullDeltaResult::TokensDelta(delta)) => {
+                        let mut tokens = previous;
+                        let changed = apply_semantic_tokens_edits(
+                            &mut tokens.data,
+                            delta.edits,
+                        );
+                        tokens.result_id = delta.result_id;
+                        (tokens, Some(changed))
+                    }
+                    Ok(SemanticTokensFullDeltaResult::PartialTokensDelta {
+                        edits,
+                    }) => {
+                        let mut tokens = previous;
+                        let changed =
+                            apply_semantic_tokens_edits(&mut tokens.data, edits);
+                        tokens.result_id = None;
+                        (tokens, Some(changed))
+                    }
+                    Err(_) => {
+                        catalog_rpc.get_full_semantic_tokens(&local_path, cb);
+                        return;
+                    }
+                };
+                catalog_rpc
+                    .semantic_tokens
+                    .lock()
+                    .insert(local_path, (plugin_id, tokens.clone()));
+                cb(plugin_id, Ok((tokens, changed)));
+            },
+        );
+    }
+
+    fn get_full_semantic_tokens(
+        &self,
+        path: &Path,
+        cb: impl FnOnce(
+            PluginId,
+            Result<(SemanticTokens, Option<ops::Range<usize>>), RpcError>,
+        ) + Clone
+        + Send
+        + 'static,
     ) {
         let uri = Url::from_file_path(path).unwrap();
         let method = SemanticTokensFullRequest::METHOD;
@@ -1049,6 +1772,45 @@ pub fn get_semantic_tokens(
             work_done_progress_params: WorkDoneProgressParams::default(),
             partial_result_params: PartialResultParams::default(),
         };
+        let language_id =
+            Some(language_id_from_path(path).unwrap_or("").to_string());
+        let semantic_tokens = self.semantic_tokens.clone();
+        let local_path = path.to_path_buf();
+        self.send_request_to_all_plugins(
+            method,
+            params,
+            language_id,
+            Some(path.to_path_buf()),
+            move |plugin_id, result: Result<SemanticTokens, RpcError>| {
+                if let Ok(tokens) = &result {
+                    semantic_tokens
+                        .lock()
+                        .insert(local_path, (plugin_id, tokens.clone()));
+                }
+                cb(plugin_id, result.map(|tokens| (tokens, None)));
+            },
+        );
+    }
+
+    /// Get the semantic tokens of a range of the document, such as the part
+    /// that's visible, which is quicker than all of them for a large file.
+    pub fn get_semantic_tokens_range(
+        &self,
+        path: &Path,
+        range: Range,
+        cb: impl FnOnce(PluginId, Result<SemanticTokens, RpcError>)
+        + Clone
+        + Send
+        + 'static,
+    ) {
+        let uri = Url::from_file_path(path).unwrap();
+        let method = SemanticTokensRangeRequest::METHOD;
+        let params = SemanticTokensRangeParams {
+            text_document: TextDocumentIdentifier { uri },
+            range,
+            work_done_progress_params: WorkDoneProgressParams::default(),
+            partial_result_params: PartialResultParams::default(),
+        };
         let language_id =
             Some(language_id_from_path(path).unwrap_or("").to_string());
         self.send_request_to_all_plugins(
@@ -1138,22 +1900,25 @@ pub fn completion(
         let language_id =
             Some(language_id_from_path(path).unwrap_or("").to_string());
 
-        self.send_request_to_all_plugins(
+        // Every server answers, the client merges their items.
+        self.send_request(
+            None,
+            None,
             method,
             params,
             language_id,
             Some(path.to_path_buf()),
-            move |plugin_id, result| match result {
-                Ok(value) => {
-                    if let Ok(resp) =
-                        serde_json::from_value::<CompletionResponse>(value)
-                    {
-                        core_rpc
-                            .completion_response(request_id, input, resp, plugin_id);
-                    }
-                }
-                Err(err) => {
-                    tracing::error!("{:?}", err);
+            true,
+            move |plugin_id, result| {
+                if let Ok(resp) = result.and_then(|value| {
+                    serde_json::from_value::<CompletionResponse>(value).map_err(
+                        |err| RpcError {
+                            code: 0,
+                            message: err.to_string(),
+                        },
+                    )
+                }) {
+                    core_rpc.completion_response(request_id, input, resp, plugin_id);
                 }
             },
         );
@@ -1326,6 +2091,34 @@ pub fn update_plugin_configs(
         ))
     }
 
+    pub fn update_lsp_servers(
+        &self,
+        servers: HashMap<String, LspServerConfig>,
+    ) -> Result<()> {
+        self.catalog_notification(PluginCatalogNotification::UpdateLspServers(
+            servers,
+        ))
+    }
+
+    /// Answers a `workspace/configuration` request of a plugin or one of its
+    /// language servers.
+    pub fn configuration(
+        &self,
+        volt_name: String,
+        items: Vec<ConfigurationItem>,
+        resp: ResponseSender,
+    ) {
+        if let Err(err) =
+            self.catalog_notification(PluginCatalogNotification::Configuration {
+                volt_name,
+                items,
+                resp,
+            })
+        {
+            tracing::error!("{:?}", err);
+        }
+    }
+
     pub fn install_volt(&self, volt: VoltInfo) -> Result<()> {
         self.catalog_notification(PluginCatalogNotification::InstallVolt(volt))
     }
@@ -1546,6 +2339,18 @@ pub enum PluginNotification {
     },
 }
 
+/// `textDocument/rangesFormatting` is new in LSP 3.18 and not in `lsp_types`
+/// yet.
+pub(crate) const RANGES_FORMATTING_METHOD: &str = "textDocument/rangesFormatting";
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct DocumentRangesFormattingParams {
+    text_document: TextDocumentIdentifier,
+    ranges: Vec<Range>,
+    options: FormattingOptions,
+}
+
 pub fn volt_icon(volt: &VoltMetadata) -> Option<Vec<u8>> {
     let dir = volt.dir.as_ref()?;
     let icon = dir.join(volt.icon.as_ref()?);
@@ -1663,6 +2468,79 @@ pub fn remove_volt(
     Ok(())
 }
 
+fn file_operation_uri(path: &Path) -> String {
+    Url::from_file_path(path)
+        .map(|uri| uri.to_string())
+        .unwrap_or_default()
+}
+
+fn create_files_params(paths: &[PathBuf]) -> CreateFilesParams {
+    CreateFilesParams {
+        files: paths
+            .iter()
+            .map(|path| FileCreate {
+                uri: file_operation_uri(path),
+            })
+            .collect(),
+    }
+}
+
+fn rename_files_params(renames: &[(PathBuf, PathBuf)]) -> RenameFilesParams {
+    RenameFilesParams {
+        files: renames
+            .iter()
+            .map(|(from, to)| FileRename {
+                old_uri: file_operation_uri(from),
+                new_uri: file_operation_uri(to),
+            })
+            .collect(),
+    }
+}
+
+fn delete_files_params(paths: &[PathBuf]) -> DeleteFilesParams {
+    DeleteFilesParams {
+        files: paths
+            .iter()
+            .map(|path| FileDelete {
+                uri: file_operation_uri(path),
+            })
+            .collect(),
+    }
+}
+
+/// Apply the edits of a semantic tokens delta, returning the range of the
+/// tokens they changed. The edits count in integers, five to a token.
+fn apply_semantic_tokens_edits(
+    data: &mut Vec<SemanticToken>,
+    mut edits: Vec<SemanticTokensEdit>,
+) -> ops::Range<usize> {
+    edits.sort_by_key(|edit| edit.start);
+    let start = edits.first().map_or(0, |edit| edit.start as usize / 5);
+    // The end of the last edit, after the earlier ones changed the number of
+    // tokens before it.
+    let mut end = start;
+    let mut shift = 0isize;
+    for edit in &edits {
+        let inserted = edit.data.as_ref().map_or(0, Vec::len);
+        end = (edit.start as usize / 5).saturating_add_signed(shift) + inserted;
+        shift += inserted as isize - edit.delete_count as isize / 5;
+    }
+    for edit in edits.into_iter().rev() {
+        let edit_start = (edit.start as usize / 5).min(data.len());
+        let edit_end = (edit_start + edit.delete_count as usize / 5).min(data.len());
+        data.splice(edit_start..edit_end, edit.data.unwrap_or_default());
+    }
+    start..end.min(data.len())
+}
+
+fn formatting_options() -> FormattingOptions {
+    FormattingOptions {
+        tab_size: 4,
+        insert_spaces: true,
+        ..Default::default()
+    }
+}
+
 fn client_capabilities() -> ClientCapabilities {
     // https://github.com/rust-lang/rust-analyzer/blob/master/docs/dev/lsp-extensions.md#server-status
     let mut experimental = Map::new();
@@ -1715,6 +2593,25 @@ fn client_capabilities() -> ClientCapabilities {
             inlay_hint: Some(InlayHintClientCapabilities {
                 ..Default::default()
             }),
+            document_highlight: Some(DocumentHighlightClientCapabilities {
+                ..Default::default()
+            }),
+            document_link: Some(DocumentLinkClientCapabilities {
+                tooltip_support: Some(true),
+                ..Default::default()
+            }),
+            color_provider: Some(DocumentColorClientCapabilities {
+                ..Default::default()
+            }),
+            linked_editing_range: Some(LinkedEditingRangeClientCapabilities {
+                ..Default::default()
+            }),
+            range_formatting: Some(DynamicRegistrationClientCapabilities {
+                ..Default::default()
+            }),
+            on_type_formatting: Some(DynamicRegistrationClientCapabilities {
+                ..Default::default()
+            }),
             code_action: Some(CodeActionClientCapabilities {
                 data_support: Some(true),
                 resolve_support: Some(CodeActionCapabilityResolveSupport {
@@ -1741,6 +2638,12 @@ fn client_capabilities() -> ClientCapabilities {
                 ..Default::default()
             }),
             semantic_tokens: Some(SemanticTokensClientCapabilities {
+                requests: SemanticTokensClientCapabilitiesRequests {
+                    range: Some(true),
+                    full: Some(SemanticTokensFullOptions::Delta {
+                        delta: Some(true),
+                    }),
+                },
                 ..Default::default()
             }),
             type_definition: Some(GotoCapability {
@@ -1755,12 +2658,19 @@ fn client_capabilities() -> ClientCapabilities {
             publish_diagnostics: Some(PublishDiagnosticsClientCapabilities {
                 ..Default::default()
             }),
+            diagnostic: Some(DiagnosticClientCapabilities {
+                related_document_support: Some(true),
+                ..Default::default()
+            }),
             inline_completion: Some(InlineCompletionClientCapabilities {
                 ..Default::default()
             }),
             call_hierarchy: Some(CallHierarchyClientCapabilities {
                 dynamic_registration: Some(true),
             }),
+            type_hierarchy: Some(TypeHierarchyClientCapabilities {
+                dynamic_registration: Some(false),
+            }),
             document_symbol: Some(DocumentSymbolClientCapabilities {
                 hierarchical_document_symbol_support: Some(true),
                 ..Default::default()
@@ -1787,7 +2697,27 @@ fn client_capabilities() -> ClientCapabilities {
             symbol: Some(WorkspaceSymbolClientCapabilities {
                 ..Default::default()
             }),
-            configuration: Some(false),
+            apply_edit: Some(true),
+            workspace_edit: Some(WorkspaceEditClientCapabilities {
+                document_changes: Some(true),
+                ..Default::default()
+            }),
+            configuration: Some(true),
+            diagnostic: Some(DiagnosticWorkspaceClientCapabilities {
+                refresh_support: Some(true),
+            }),
+            file_operations: Some(WorkspaceFileOperationsClientCapabilities {
+                did_create: Some(true),
+                will_create: Some(true),
+                did_rename: Some(true),
+                will_rename: Some(true),
+                did_delete: Some(true),
+                will_delete: Some(true),
+                ..Default::default()
+            }),
+            did_change_configuration: Some(DynamicRegistrationClientCapabilities {
+                dynamic_registration: Some(false),
+            }),
             workspace_folders: Some(true),
             ..Default::default()
         }),
@@ -1795,3 +2725,304 @@ fn client_capabilities() -> ClientCapabilities {
         ..Default::default()
     }
 }
+
+/// Sort the ranges and merge those which overlap or touch, so that no part of
+/// the document is formatted twice.
+fn merge_ranges(mut ranges: Vec<Range>) -> Vec<Range> {
+    ranges.sort_by_key(|range| range.start);
+    let mut merged: Vec<Range> = Vec::new();
+    for range in ranges {
+        match merged.last_mut() {
+            Some(last) if range.start <= last.end => {
+                last.end = last.end.max(range.end);
+            }
+            _ => merged.push(range),
+        }
+    }
+    merged
+}
+
+/// Merges the edits of several servers into one. As soon as one of them has
+/// `document_changes`, which may hold resource operations, the merged edit is
+/// made of those, as clients only look at one of the two.
+fn merge_workspace_edits(edits: Vec<WorkspaceEdit>) -> Option<WorkspaceEdit> {
+    if edits.len() <= 1 {
+        return edits.into_iter().next();
+    }
+
+    let mut merged = WorkspaceEdit::default();
+    let with_document_changes =
+        edits.iter().any(|edit| edit.document_changes.is_some());
+    let mut changes: HashMap<Url, Vec<TextEdit>> = HashMap::new();
+    let mut operations = Vec::new();
+    for edit in edits {
+        if let Some(annotations) = edit.change_annotations {
+            merged
+                .change_annotations
+                .get_or_insert_with(HashMap::new)
+                .extend(annotations);
+        }
+        match (edit.document_changes, edit.changes) {
+            (Some(DocumentChanges::Edits(edits)), _) => {
+                operations
+                    .extend(edits.into_iter().map(DocumentChangeOperation::Edit));
+            }
+            (Some(DocumentChanges::Operations(ops)), _) => {
+                operations.extend(ops);
+            }
+            (None, Some(edit_changes)) if with_document_changes => {
+                operations.extend(edit_changes.into_iter().map(|(uri, edits)| {
+                    DocumentChangeOperation::Edit(TextDocumentEdit {
+                        text_document: OptionalVersionedTextDocumentIdentifier {
+                            uri,
+                            version: None,
+                        },
+                        edits: edits.into_iter().map(OneOf::Left).collect(),
+                    })
+                }));
+            }
+            (None, Some(edit_changes)) => {
+                for (uri, edits) in edit_changes {
+                    changes.entry(uri).or_default().extend(edits);
+                }
+            }
+            (None, None) => {}
+        }
+    }
+    if with_document_changes {
+        merged.document_changes = Some(DocumentChanges::Operations(operations));
+    } else {
+        merged.changes = Some(changes);
+    }
+    Some(merged)
+}
+
+/// The edits from formatting ranges separately, in document order. They're
+/// all against the same text, so an edit overlapping one before it can't be
+/// applied and is left out.
+fn combine_range_edits(mut edits: Vec<TextEdit>) -> Vec<TextEdit> {
+    edits.sort_by_key(|edit| edit.range.start);
+    let mut combined: Vec<TextEdit> = Vec::new();
+    for edit in edits {
+        if combined
+            .last()
+            .is_some_and(|last| edit.range.start < last.range.end)
+        {
+            continue;
+        }
+        combined.push(edit);
+    }
+    combined
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use lsp_types::{
+        Diagnostic, DocumentChangeOperation, DocumentChanges, OneOf,
+        OptionalVersionedTextDocumentIdentifier, Position, PublishDiagnosticsParams,
+        Range, SemanticToken, SemanticTokensEdit, TextDocumentEdit, TextEdit, Url,
+        WorkspaceEdit,
+    };
+    use phidi_rpc::{
+        core::{CoreNotification, CoreRpc, CoreRpcHandler},
+        plugin::PluginId,
+        proxy::ProxyRpcHandler,
+    };
+
+    use super::{
+        PluginCatalogRpcHandler, apply_semantic_tokens_edits, combine_range_edits,
+        merge_ranges, merge_workspace_edits,
+    };
+
+    fn range(start_line: u32, end_line: u32) -> Range {
+        Range::new(Position::new(start_line, 0), Position::new(end_line, 0))
+    }
+
+    fn edit(start_line: u32, end_line: u32, new_text: &str) -> TextEdit {
+        TextEdit::new(range(start_line, end_line), new_text.to_string())
+    }
+
+    #[test]
+    fn test_merge_ranges() {
+        let ranges = vec![range(8, 9), range(0, 2), range(1, 4), range(4, 5)];
+        assert_eq!(merge_ranges(ranges), vec![range(0, 5), range(8, 9)]);
+        assert!(merge_ranges(Vec::new()).is_empty());
+    }
+
+    #[test]
+    fn test_merge_workspace_edits() {
+        let uri = |name: &str| Url::parse(&format!("file:///{name}")).unwrap();
+        let changes = |name: &str, edits: Vec<TextEdit>| WorkspaceEdit {
+            changes: Some(HashMap::from([(uri(name), edits)])),
+            ..Default::default()
+        };
+        assert!(merge_workspace_edits(Vec::new()).is_none());
+
+        let merged = merge_workspace_edits(vec![
+            changes("a.rs", vec![edit(0, 1, "a\n")]),
+            changes("a.rs", vec![edit(4, 4, "b\n")]),
+            changes("b.rs", vec![edit(1, 2, "")]),
+        ])
+        .unwrap();
+        assert!(merged.document_changes.is_none());
+        let merged = merged.changes.unwrap();
+        assert_eq!(
+            merged[&uri("a.rs")],
+            vec![edit(0, 1, "a\n"), edit(4, 4, "b\n")]
+        );
+        assert_eq!(merged[&uri("b.rs")], vec![edit(1, 2, "")]);
+
+        // The edits without document changes are turned into those.
+        let document_edit = |name: &str, edits: Vec<TextEdit>| TextDocumentEdit {
+            text_document: OptionalVersionedTextDocumentIdentifier {
+                uri: uri(name),
+                version: None,
+            },
+            edits: edits.into_iter().map(OneOf::Left).collect(),
+        };
+        let merged = merge_workspace_edits(vec![
+            changes("a.rs", vec![edit(0, 1, "a\n")]),
+            WorkspaceEdit {
+                document_changes: Some(DocumentChanges::Edits(vec![document_edit(
+                    "b.rs",
+                    vec![edit(1, 2, "")],
+                )])),
+                ..Default::default()
+            },
+        ])
+        .unwrap();
+        assert!(merged.changes.is_none());
+        assert_eq!(
+            merged.document_changes,
+            Some(DocumentChanges::Operations(vec![
+                DocumentChangeOperation::Edit(document_edit(
+                    "a.rs",
+                    vec![edit(0, 1, "a\n")]
+                )),
+                DocumentChangeOperation::Edit(document_edit(
+                    "b.rs",
+                    vec![edit(1, 2, "")]
+                )),
+            ]))
+        );
+    }
+
+    #[test]
+    fn test_combine_range_edits() {
+        // The responses for the later ranges can come first.
+        let edits = vec![
+            edit(10, 12, "c\n"),
+            edit(11, 13, "overlapping\n"),
+            edit(0, 1, "a\na\na\n"),
+            edit(4, 4, "b\n"),
+        ];
+        // All the edits are against the same text, so those after an edit
+        // which adds lines keep their ranges.
+        assert_eq!(
+            combine_range_edits(edits),
+            vec![
+                edit(0, 1, "a\na\na\n"),
+                edit(4, 4, "b\n"),
+                edit(10, 12, "c\n")
+            ]
+        );
+    }
+
+    fn token(delta_line: u32) -> SemanticToken {
+        SemanticToken {
+            delta_line,
+            delta_start: 0,
+            length: 1,
+            token_type: 0,
+            token_modifiers_bitset: 0,
+        }
+    }
+
+    #[test]
+    fn test_apply_semantic_tokens_edits() {
+        let mut data = (0..5).map(token).collect::<Vec<_>>();
+        let edits = vec![
+            SemanticTokensEdit {
+                start: 20,
+                delete_count: 0,
+                data: Some(vec![token(10), token(11)]),
+            },
+            SemanticTokensEdit {
+                start: 5,
+                delete_count: 10,
+                data: Some(vec![token(12)]),
+            },
+        ];
+        let changed = apply_semantic_tokens_edits(&mut data, edits);
+        let lines = data.iter().map(|t| t.delta_line).collect::<Vec<_>>();
+        assert_eq!(lines, vec![0, 12, 3, 10, 11, 4]);
+        assert_eq!(changed, 1..5);
+    }
+
+    fn publish(
+        catalog_rpc: &PluginCatalogRpcHandler,
+        plugin_id: u64,
+        uri: &Url,
+        messages: &[&str],
+    ) {
+        let diagnostics = messages
+            .iter()
+            .map(|message| Diagnostic::new_simple(range(0, 1), message.to_string()))
+            .collect();
+        catalog_rpc.publish_diagnostics(
+            PluginId(plugin_id),
+            PublishDiagnosticsParams::new(uri.clone(), diagnostics, None),
+        );
+    }
+
+    fn published(core_rpc: &CoreRpcHandler) -> Option<Vec<String>> {
+        match core_rpc.rx().try_recv().ok()? {
+            CoreRpc::Notification(notification) => match *notification {
+                CoreNotification::PublishDiagnostics { diagnostics } => {
+                    let mut messages: Vec<String> = diagnostics
+                        .diagnostics
+                        .into_iter()
+                        .map(|diagnostic| diagnostic.message)
+                        .collect();
+                    messages.sort();
+                    Some(messages)
+                }
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_publish_diagnostics() {
+        let core_rpc = CoreRpcHandler::new();
+        let catalog_rpc =
+            PluginCatalogRpcHandler::new(core_rpc.clone(), ProxyRpcHandler::new());
+        let uri = Url::parse("file:///src/main.rs").unwrap();
+
+        publish(&catalog_rpc, 1, &uri, &["unused"]);
+        assert_eq!(published(&core_rpc), Some(vec!["unused".to_string()]));
+
+        // The diagnostics of another server are added to those of the first.
+        publish(&catalog_rpc, 2, &uri, &["misspelled"]);
+        assert_eq!(
+            published(&core_rpc),
+            Some(vec!["misspelled".to_string(), "unused".to_string()])
+        );
+
+        // A server publishing again replaces only its own diagnostics.
+        publish(&catalog_rpc, 1, &uri, &["unreachable"]);
+        assert_eq!(
+            published(&core_rpc),
+            Some(vec!["misspelled".to_string(), "unreachable".to_string()])
+        );
+
+        publish(&catalog_rpc, 2, &uri, &[]);
+        assert_eq!(published(&core_rpc), Some(vec!["unreachable".to_string()]));
+        publish(&catalog_rpc, 1, &uri, &[]);
+        assert_eq!(published(&core_rpc), Some(Vec::new()));
+        assert!(catalog_rpc.diagnostics.lock().is_empty());
+    }
+}
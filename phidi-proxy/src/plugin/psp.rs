@@ -1,6 +1,7 @@
 use std::{
     borrow::Cow,
     collections::HashMap,
+    fs, ops,
     path::{Path, PathBuf},
     sync::{
         Arc,
@@ -15,38 +16,57 @@
 use floem_editor_core::buffer::rope_text::{RopeText, RopeTextRef};
 use jsonrpc_lite::{Id, JsonRpc, Params};
 use lsp_types::{
-    CancelParams, CodeActionProviderCapability, DidChangeTextDocumentParams,
-    DidSaveTextDocumentParams, DocumentSelector, FoldingRangeProviderCapability,
-    HoverProviderCapability, ImplementationProviderCapability, InitializeResult,
-    LogMessageParams, MessageType, OneOf, ProgressParams, PublishDiagnosticsParams,
-    Range, Registration, RegistrationParams, SemanticTokens, SemanticTokensLegend,
+    ApplyWorkspaceEditParams, ApplyWorkspaceEditResponse, CancelParams,
+    CodeActionProviderCapability, ColorProviderCapability, ConfigurationParams,
+    DiagnosticOptions, DiagnosticServerCapabilities, DidChangeTextDocumentParams,
+    DidSaveTextDocumentParams, DocumentChangeOperation, DocumentChanges,
+    DocumentDiagnosticParams, DocumentDiagnosticReport,
+    DocumentDiagnosticReportKind, DocumentDiagnosticReportResult, DocumentSelector,
+    FileOperationFilter, FileOperationPatternKind, FileOperationRegistrationOptions,
+    FoldingRangeProviderCapability, HoverProviderCapability,
+    ImplementationProviderCapability, InitializeResult,
+    LinkedEditingRangeServerCapabilities, LogMessageParams, MessageType, OneOf,
+    PartialResultParams, PreviousResultId, ProgressParams, PublishDiagnosticsParams,
+    Range, Registration, RegistrationParams, SemanticTokens,
+    SemanticTokensFullOptions, SemanticTokensLegend, SemanticTokensOptions,
     SemanticTokensServerCapabilities, ServerCapabilities, ShowMessageParams,
     TextDocumentContentChangeEvent, TextDocumentIdentifier,
     TextDocumentSaveRegistrationOptions, TextDocumentSyncCapability,
-    TextDocumentSyncKind, TextDocumentSyncSaveOptions,
-    VersionedTextDocumentIdentifier,
+    TextDocumentSyncKind, TextDocumentSyncSaveOptions, Url,
+    VersionedTextDocumentIdentifier, WorkDoneProgressParams,
+    WorkspaceDiagnosticParams, WorkspaceDiagnosticReportResult,
+    WorkspaceDocumentDiagnosticReport, WorkspaceEdit,
     notification::{
-        Cancel, DidChangeTextDocument, DidOpenTextDocument, DidSaveTextDocument,
-        Initialized, LogMessage, Notification, Progress, PublishDiagnostics,
-        ShowMessage,
+        Cancel, DidChangeTextDocument, DidCreateFiles, DidDeleteFiles,
+        DidOpenTextDocument, DidRenameFiles, DidSaveTextDocument, Initialized,
+        LogMessage, Notification, Progress, PublishDiagnostics, ShowMessage,
     },
     request::{
-        CallHierarchyIncomingCalls, CallHierarchyPrepare, CodeActionRequest,
-        CodeActionResolveRequest, CodeLensRequest, CodeLensResolve, Completion,
-        DocumentSymbolRequest, FoldingRangeRequest, Formatting, GotoDefinition,
-        GotoImplementation, GotoTypeDefinition, HoverRequest, Initialize,
-        InlayHintRequest, InlineCompletionRequest, PrepareRenameRequest, References,
-        RegisterCapability, Rename, ResolveCompletionItem, SelectionRangeRequest,
-        SemanticTokensFullRequest, SignatureHelpRequest, WorkDoneProgressCreate,
-        WorkspaceSymbolRequest,
+        ApplyWorkspaceEdit, CallHierarchyIncomingCalls, CallHierarchyOutgoingCalls,
+        CallHierarchyPrepare, CodeActionRequest, CodeActionResolveRequest,
+        CodeLensRequest, CodeLensResolve, ColorPresentationRequest, Completion,
+        DocumentColor, DocumentDiagnosticRequest, DocumentHighlightRequest,
+        DocumentLinkRequest, DocumentLinkResolve, DocumentSymbolRequest,
+        FoldingRangeRequest, Formatting, GotoDefinition, GotoImplementation,
+        GotoTypeDefinition, HoverRequest, Initialize, InlayHintRequest,
+        InlineCompletionRequest, LinkedEditingRange, OnTypeFormatting,
+        PrepareRenameRequest, RangeFormatting, References, RegisterCapability,
+        Rename, ResolveCompletionItem, SelectionRangeRequest,
+        SemanticTokensFullDeltaRequest, SemanticTokensFullRequest,
+        SemanticTokensRangeRequest, SignatureHelpRequest, TypeHierarchyPrepare,
+        TypeHierarchySubtypes, TypeHierarchySupertypes, WillCreateFiles,
+        WillDeleteFiles, WillRenameFiles, WorkDoneProgressCreate,
+        WorkspaceConfiguration, WorkspaceDiagnosticRefresh,
+        WorkspaceDiagnosticRequest, WorkspaceSymbolRequest,
     },
 };
 use parking_lot::Mutex;
 use phidi_core::{encoding::offset_utf16_to_utf8, rope_text_pos::RopeTextPosition};
 use phidi_rpc::{
     RpcError,
-    core::{CoreRpcHandler, ServerStatusParams},
+    core::{CoreResponse, CoreRpcHandler, ServerStatusParams},
     plugin::{PluginId, VoltID},
+    proposal::{EditProposal, ProposalId},
     style::{LineStyle, Style},
 };
 use phidi_xi_rope::{Rope, RopeDelta};
@@ -57,11 +77,11 @@
     SendLspRequestResult, StartLspServer, StartLspServerParams,
     StartLspServerResult,
 };
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
 use super::{
-    PluginCatalogRpcHandler,
+    PluginCatalogRpcHandler, RANGES_FORMATTING_METHOD,
     lsp::{DocumentFilter, LspClient},
 };
 
@@ -93,6 +113,10 @@ impl<Resp, Error, F: Send + FnOnce(PluginId, Result<Resp, Error>) + DynClone>
 {
 }
 
+/// The styles of semantic tokens, with the range of the document they cover
+/// when they are only for a part of it.
+pub type SemanticTokenStyles = (Vec<LineStyle>, Option<ops::Range<usize>>);
+
 pub trait RpcCallback<Resp, Error>: Send {
     fn call(self: Box<Self>, result: Result<Resp, Error>);
 }
@@ -111,7 +135,12 @@ pub enum PluginHandlerNotification {
     InitializeResult(InitializeResult),
     Shutdown,
 
-    SpawnedPluginLoaded { plugin_id: PluginId },
+    SpawnedPluginLoaded {
+        plugin_id: PluginId,
+    },
+    /// Pull the workspace diagnostics which were asked for while an earlier
+    /// pull was on its way.
+    PullWorkspaceDiagnostics,
 }
 
 #[allow(clippy::large_enum_variant)]
@@ -164,8 +193,9 @@ pub enum PluginServerRpc {
     },
     FormatSemanticTokens {
         tokens: SemanticTokens,
+        changed: Option<ops::Range<usize>>,
         text: Rope,
-        f: Box<dyn RpcCallback<Vec<LineStyle>, RpcError>>,
+        f: Box<dyn RpcCallback<SemanticTokenStyles, RpcError>>,
     },
 }
 
@@ -223,6 +253,7 @@ fn document_supported(
         path: Option<&Path>,
     ) -> bool;
     fn method_registered(&mut self, method: &str) -> bool;
+    fn params_supported(&mut self, method: &str, params: &Params) -> bool;
     fn handle_host_notification(
         &mut self,
         method: String,
@@ -240,6 +271,7 @@ fn handle_handler_notification(
         &mut self,
         notification: PluginHandlerNotification,
     );
+    fn handle_did_open_text_document(&mut self, language_id: String, path: PathBuf);
     fn handle_did_save_text_document(
         &self,
         language_id: String,
@@ -264,8 +296,9 @@ fn handle_did_change_text_document(
     fn format_semantic_tokens(
         &self,
         tokens: SemanticTokens,
+        changed: Option<ops::Range<usize>>,
         text: Rope,
-        f: Box<dyn RpcCallback<Vec<LineStyle>, RpcError>>,
+        f: Box<dyn RpcCallback<SemanticTokenStyles, RpcError>>,
     );
 }
 
@@ -467,6 +500,7 @@ pub fn mainloop<H>(&self, handler: &mut H)
                     if handler
                         .document_supported(language_id.as_deref(), path.as_deref())
                         && handler.method_registered(&method)
+                        && handler.params_supported(&method, &params)
                     {
                         self.send_server_request(id, &method, params, rh);
                     } else {
@@ -485,8 +519,19 @@ pub fn mainloop<H>(&self, handler: &mut H)
                     if handler
                         .document_supported(language_id.as_deref(), path.as_deref())
                         && handler.method_registered(&method)
+                        && handler.params_supported(&method, &params)
                     {
                         self.send_server_notification(&method, params);
+                        if method == DidOpenTextDocument::METHOD {
+                            if let (Some(language_id), Some(path)) =
+                                (language_id, path)
+                            {
+                                handler.handle_did_open_text_document(
+                                    language_id,
+                                    path,
+                                );
+                            }
+                        }
                     }
                 }
                 PluginServerRpc::HostRequest {
@@ -534,8 +579,13 @@ pub fn mainloop<H>(&self, handler: &mut H)
                         change,
                     );
                 }
-                PluginServerRpc::FormatSemanticTokens { tokens, text, f } => {
-                    handler.format_semantic_tokens(tokens, text, f);
+                PluginServerRpc::FormatSemanticTokens {
+                    tokens,
+                    changed,
+                    text,
+                    f,
+                } => {
+                    handler.format_semantic_tokens(tokens, changed, text, f);
                 }
                 PluginServerRpc::Handler(notification) => {
                     handler.handle_handler_notification(notification)
@@ -610,6 +660,29 @@ pub fn handle_plugin_server_message(
     }
 }
 
+/// Lets a plugin stage a workspace edit for review in the editor instead of
+/// applying it directly.
+pub enum ProposeEdit {}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProposeEditParams {
+    pub title: String,
+    pub edit: WorkspaceEdit,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProposeEditResult {
+    pub id: u64,
+}
+
+impl Request for ProposeEdit {
+    type Params = ProposeEditParams;
+    type Result = ProposeEditResult;
+    const METHOD: &'static str = "phidi/proposeEdit";
+}
+
 struct SaveRegistration {
     include_text: bool,
     filters: Vec<DocumentFilter>,
@@ -620,6 +693,59 @@ struct ServerRegistrations {
     save: Option<SaveRegistration>,
 }
 
+/// What's known of the diagnostics pulled from a server, shared with the
+/// callbacks of the pull requests.
+#[derive(Default)]
+struct PulledDiagnostics {
+    /// The result id of the last report of each document, which the server
+    /// gets back to answer that nothing changed.
+    result_ids: HashMap<Url, String>,
+    /// The latest pull of each document, the answers to earlier pulls are
+    /// out of date.
+    pulls: HashMap<Url, u64>,
+    next_pull: u64,
+    /// Whether a workspace pull is on its way, servers may take a while to
+    /// answer it.
+    workspace_pull: bool,
+    /// Whether another workspace pull was asked for in the meantime, such as
+    /// by saving several times, which is made once only after the answer.
+    workspace_pull_queued: bool,
+}
+
+impl PulledDiagnostics {
+    fn report(
+        &mut self,
+        catalog_rpc: &PluginCatalogRpcHandler,
+        plugin_id: PluginId,
+        uri: Url,
+        report: DocumentDiagnosticReportKind,
+    ) {
+        match report {
+            DocumentDiagnosticReportKind::Full(report) => {
+                match report.result_id {
+                    Some(result_id) => {
+                        self.result_ids.insert(uri.clone(), result_id);
+                    }
+                    None => {
+                        self.result_ids.remove(&uri);
+                    }
+                }
+                catalog_rpc.publish_diagnostics(
+                    plugin_id,
+                    PublishDiagnosticsParams {
+                        uri,
+                        diagnostics: report.items,
+                        version: None,
+                    },
+                );
+            }
+            DocumentDiagnosticReportKind::Unchanged(report) => {
+                self.result_ids.insert(uri, report.result_id);
+            }
+        }
+    }
+}
+
 pub struct PluginHostHandler {
     volt_id: VoltID,
     volt_display_name: String,
@@ -630,7 +756,17 @@ pub struct PluginHostHandler {
     catalog_rpc: PluginCatalogRpcHandler,
     pub server_rpc: PluginServerRpcHandler,
     pub server_capabilities: ServerCapabilities,
+    /// Whether the server has the `typeHierarchyProvider` capability, which
+    /// lsp-types doesn't deserialize into the server capabilities.
+    pub type_hierarchy_provider: bool,
+    /// Whether the server's `documentRangeFormattingProvider` has
+    /// `rangesSupport`, for the same reason.
+    pub ranges_formatting_provider: bool,
     server_registrations: ServerRegistrations,
+    /// The documents opened in the server, with their language, to pull their
+    /// diagnostics again when the server asks for it.
+    open_documents: HashMap<Url, String>,
+    pulled_diagnostics: Arc<Mutex<PulledDiagnostics>>,
 
     /// Language servers that this plugin has spawned.
     /// Note that these plugin ids could be 'dead' if the LSP died/exited.
@@ -663,7 +799,11 @@ pub fn new(
             catalog_rpc,
             server_rpc,
             server_capabilities: ServerCapabilities::default(),
+            type_hierarchy_provider: false,
+            ranges_formatting_provider: false,
             server_registrations: ServerRegistrations::default(),
+            open_documents: HashMap::new(),
+            pulled_diagnostics: Arc::new(Mutex::new(PulledDiagnostics::default())),
             spawned_lsp: HashMap::new(),
         }
     }
@@ -817,9 +957,40 @@ pub fn method_registered(&mut self, method: &str) -> bool {
                     OneOf::Right(_) => true,
                 })
                 .unwrap_or(false),
+            RangeFormatting::METHOD => self
+                .server_capabilities
+                .document_range_formatting_provider
+                .as_ref()
+                .map(|f| match f {
+                    OneOf::Left(is_capable) => *is_capable,
+                    OneOf::Right(_) => true,
+                })
+                .unwrap_or(false),
+            RANGES_FORMATTING_METHOD => self.ranges_formatting_provider,
+            OnTypeFormatting::METHOD => self
+                .server_capabilities
+                .document_on_type_formatting_provider
+                .is_some(),
             SemanticTokensFullRequest::METHOD => {
                 self.server_capabilities.semantic_tokens_provider.is_some()
             }
+            SemanticTokensFullDeltaRequest::METHOD => self
+                .server_capabilities
+                .semantic_tokens_provider
+                .as_ref()
+                .and_then(|provider| semantic_tokens_options(provider).full.as_ref())
+                .is_some_and(|full| {
+                    matches!(
+                        full,
+                        SemanticTokensFullOptions::Delta { delta: Some(true) }
+                    )
+                }),
+            SemanticTokensRangeRequest::METHOD => self
+                .server_capabilities
+                .semantic_tokens_provider
+                .as_ref()
+                .and_then(|provider| semantic_tokens_options(provider).range)
+                .unwrap_or(false),
             InlayHintRequest::METHOD => {
                 self.server_capabilities.inlay_hint_provider.is_some()
             }
@@ -858,10 +1029,142 @@ pub fn method_registered(&mut self, method: &str) -> bool {
             CallHierarchyIncomingCalls::METHOD => {
                 self.server_capabilities.call_hierarchy_provider.is_some()
             }
+            CallHierarchyOutgoingCalls::METHOD => {
+                self.server_capabilities.call_hierarchy_provider.is_some()
+            }
+            TypeHierarchyPrepare::METHOD
+            | TypeHierarchySupertypes::METHOD
+            | TypeHierarchySubtypes::METHOD => self.type_hierarchy_provider,
+            DocumentHighlightRequest::METHOD => self
+                .server_capabilities
+                .document_highlight_provider
+                .as_ref()
+                .map(|h| match h {
+                    OneOf::Left(is_capable) => *is_capable,
+                    OneOf::Right(_) => true,
+                })
+                .unwrap_or(false),
+            LinkedEditingRange::METHOD => self
+                .server_capabilities
+                .linked_editing_range_provider
+                .as_ref()
+                .map(|l| match l {
+                    LinkedEditingRangeServerCapabilities::Simple(is_capable) => {
+                        *is_capable
+                    }
+                    _ => true,
+                })
+                .unwrap_or(false),
+            DocumentLinkRequest::METHOD => {
+                self.server_capabilities.document_link_provider.is_some()
+            }
+            DocumentLinkResolve::METHOD => self
+                .server_capabilities
+                .document_link_provider
+                .as_ref()
+                .and_then(|x| x.resolve_provider)
+                .unwrap_or(false),
+            DocumentColor::METHOD | ColorPresentationRequest::METHOD => self
+                .server_capabilities
+                .color_provider
+                .as_ref()
+                .map(|c| match c {
+                    ColorProviderCapability::Simple(is_capable) => *is_capable,
+                    ColorProviderCapability::ColorProvider(_)
+                    | ColorProviderCapability::Options(_) => true,
+                })
+                .unwrap_or(false),
+            WillCreateFiles::METHOD
+            | DidCreateFiles::METHOD
+            | WillRenameFiles::METHOD
+            | DidRenameFiles::METHOD
+            | WillDeleteFiles::METHOD
+            | DidDeleteFiles::METHOD => {
+                self.file_operation_filters(method).is_some()
+            }
             _ => false,
         }
     }
 
+    fn file_operation_filters(
+        &self,
+        method: &str,
+    ) -> Option<&FileOperationRegistrationOptions> {
+        let operations = self
+            .server_capabilities
+            .workspace
+            .as_ref()?
+            .file_operations
+            .as_ref()?;
+        match method {
+            WillCreateFiles::METHOD => operations.will_create.as_ref(),
+            DidCreateFiles::METHOD => operations.did_create.as_ref(),
+            WillRenameFiles::METHOD => operations.will_rename.as_ref(),
+            DidRenameFiles::METHOD => operations.did_rename.as_ref(),
+            WillDeleteFiles::METHOD => operations.will_delete.as_ref(),
+            DidDeleteFiles::METHOD => operations.did_delete.as_ref(),
+            _ => None,
+        }
+    }
+
+    /// Servers only format on type after the characters they listed as
+    /// triggers, and only hear about the files matching the filters they
+    /// registered for file operations.
+    pub fn params_supported(&self, method: &str, params: &Params) -> bool {
+        match method {
+            OnTypeFormatting::METHOD => {
+                let Some(options) = self
+                    .server_capabilities
+                    .document_on_type_formatting_provider
+                    .as_ref()
+                else {
+                    return false;
+                };
+                let Some(ch) = (match params {
+                    Params::Map(params) => params.get("ch").and_then(Value::as_str),
+                    _ => None,
+                }) else {
+                    return false;
+                };
+                options.first_trigger_character == ch
+                    || options
+                        .more_trigger_character
+                        .as_ref()
+                        .is_some_and(|more| more.iter().any(|c| c == ch))
+            }
+            WillCreateFiles::METHOD
+            | DidCreateFiles::METHOD
+            | WillRenameFiles::METHOD
+            | DidRenameFiles::METHOD
+            | WillDeleteFiles::METHOD
+            | DidDeleteFiles::METHOD => {
+                let Some(options) = self.file_operation_filters(method) else {
+                    return false;
+                };
+                let Some(files) = (match params {
+                    Params::Map(params) => {
+                        params.get("files").and_then(Value::as_array)
+                    }
+                    _ => None,
+                }) else {
+                    return false;
+                };
+                files.iter().any(|file| {
+                    file.get("oldUri")
+                        .or_else(|| file.get("uri"))
+                        .and_then(Value::as_str)
+                        .and_then(|uri| Url::parse(uri).ok())
+                        .is_some_and(|uri| {
+                            options.filters.iter().any(|filter| {
+                                file_operation_filter_matches(filter, &uri)
+                            })
+                        })
+                })
+            }
+            _ => true,
+        }
+    }
+
     fn check_save_capability(&self, language_id: &str, path: &Path) -> (bool, bool) {
         if self.document_supported(Some(language_id), Some(path)) {
             let (should_send, include_text) = self
@@ -990,6 +1293,72 @@ pub fn process_request(
                 );
                 resp.send_null();
             }
+            ApplyWorkspaceEdit::METHOD => {
+                let params: ApplyWorkspaceEditParams =
+                    serde_json::from_value(serde_json::to_value(params)?)?;
+                // The edit is applied by the client, to the documents, which
+                // doesn't create, rename or delete files.
+                if let Some(DocumentChanges::Operations(operations)) =
+                    params.edit.document_changes.as_ref()
+                {
+                    if operations
+                        .iter()
+                        .any(|op| matches!(op, DocumentChangeOperation::Op(_)))
+                    {
+                        resp.send(ApplyWorkspaceEditResponse {
+                            applied: false,
+                            failure_reason: Some(
+                                "resource operations are not supported".to_string(),
+                            ),
+                            failed_change: None,
+                        });
+                        return Ok(());
+                    }
+                }
+                // The server is answered once the client has applied the edit,
+                // or refused it because a document changed in the meantime.
+                let core_rpc = self.core_rpc.clone();
+                thread::spawn(move || {
+                    let (applied, failure_reason) =
+                        match core_rpc.request_workspace_edit(params.edit) {
+                            Ok(CoreResponse::ApplyWorkspaceEditResponse {
+                                applied,
+                                failure_reason,
+                            }) => (applied, failure_reason),
+                            Err(err) => (false, Some(err.message)),
+                        };
+                    resp.send(ApplyWorkspaceEditResponse {
+                        applied,
+                        failure_reason,
+                        failed_change: None,
+                    });
+                });
+            }
+            WorkspaceDiagnosticRefresh::METHOD => {
+                resp.send_null();
+                self.refresh_diagnostics();
+            }
+            WorkspaceConfiguration::METHOD => {
+                let params: ConfigurationParams =
+                    serde_json::from_value(serde_json::to_value(params)?)?;
+                self.catalog_rpc.configuration(
+                    self.volt_id.name.clone(),
+                    params.items,
+                    resp,
+                );
+            }
+            ProposeEdit::METHOD => {
+                let params: ProposeEditParams =
+                    serde_json::from_value(serde_json::to_value(params)?)?;
+                let proposal = EditProposal {
+                    id: ProposalId::next(),
+                    title: params.title,
+                    source: self.volt_display_name.clone(),
+                    edit: params.edit,
+                };
+                resp.send(ProposeEditResult { id: proposal.id.0 });
+                self.core_rpc.edit_proposed(proposal);
+            }
             StartLspServer::METHOD => {
                 let params: StartLspServerParams =
                     serde_json::from_value(serde_json::to_value(params)?)?;
@@ -1004,19 +1373,24 @@ pub fn process_request(
                 self.spawned_lsp
                     .insert(plugin_id, SpawnedLspInfo { resp: Some(resp) });
                 thread::spawn(move || {
-                    if let Err(err) = LspClient::start(
-                        catalog_rpc,
-                        params.document_selector,
-                        workspace,
-                        volt_id,
-                        volt_display_name,
-                        Some(spawned_by),
-                        Some(plugin_id),
-                        pwd,
-                        params.server_uri,
-                        params.server_args,
-                        params.options,
-                    ) {
+                    if let Err(err) = LspClient::server_from_uri(&params.server_uri)
+                        .and_then(|server| {
+                            LspClient::start(
+                                catalog_rpc,
+                                params.document_selector,
+                                workspace,
+                                volt_id,
+                                volt_display_name,
+                                Some(spawned_by),
+                                Some(plugin_id),
+                                pwd,
+                                server,
+                                params.server_args,
+                                HashMap::new(),
+                                params.options,
+                            )
+                        })
+                    {
                         tracing::error!("{:?}", err);
                     }
                 });
@@ -1113,19 +1487,24 @@ pub fn handle_notification(
                 let volt_id = self.volt_id.clone();
                 let volt_display_name = self.volt_display_name.clone();
                 thread::spawn(move || {
-                    if let Err(err) = LspClient::start(
-                        catalog_rpc,
-                        params.document_selector,
-                        workspace,
-                        volt_id,
-                        volt_display_name,
-                        None,
-                        None,
-                        pwd,
-                        params.server_uri,
-                        params.server_args,
-                        params.options,
-                    ) {
+                    if let Err(err) = LspClient::server_from_uri(&params.server_uri)
+                        .and_then(|server| {
+                            LspClient::start(
+                                catalog_rpc,
+                                params.document_selector,
+                                workspace,
+                                volt_id,
+                                volt_display_name,
+                                None,
+                                None,
+                                pwd,
+                                server,
+                                params.server_args,
+                                HashMap::new(),
+                                params.options,
+                            )
+                        })
+                    {
                         tracing::error!("{:?}", err);
                     }
                 });
@@ -1133,7 +1512,8 @@ pub fn handle_notification(
             PublishDiagnostics::METHOD => {
                 let diagnostics: PublishDiagnosticsParams =
                     serde_json::from_value(serde_json::to_value(params)?)?;
-                self.catalog_rpc.core_rpc.publish_diagnostics(diagnostics);
+                self.catalog_rpc
+                    .publish_diagnostics(self.server_rpc.plugin_id, diagnostics);
             }
             Progress::METHOD => {
                 let progress: ProgressParams =
@@ -1199,6 +1579,15 @@ pub fn handle_did_save_text_document(
         text_document: TextDocumentIdentifier,
         text: Rope,
     ) {
+        if self
+            .diagnostic_options()
+            .is_some_and(|options| options.inter_file_dependencies)
+        {
+            self.refresh_diagnostics();
+        } else {
+            self.pull_workspace_diagnostics();
+        }
+
         let (should_send, include_text) =
             self.check_save_capability(language_id.as_str(), &path);
         if !should_send {
@@ -1277,6 +1666,7 @@ pub fn handle_did_change_text_document(
         };
 
         let path = document.uri.to_file_path().ok();
+        let uri = document.uri.clone();
 
         let params = DidChangeTextDocumentParams {
             text_document: document,
@@ -1286,22 +1676,245 @@ pub fn handle_did_change_text_document(
         self.server_rpc.server_notification(
             DidChangeTextDocument::METHOD,
             params,
-            Some(lanaguage_id),
+            Some(lanaguage_id.clone()),
             path,
             false,
         );
+        self.pull_document_diagnostics(uri, lanaguage_id);
+    }
+
+    pub fn handle_did_open_text_document(
+        &mut self,
+        language_id: String,
+        path: PathBuf,
+    ) {
+        let Ok(uri) = Url::from_file_path(&path) else {
+            return;
+        };
+        // The editor only asks for formatting after the trigger characters.
+        if let Some(options) = self
+            .server_capabilities
+            .document_on_type_formatting_provider
+            .as_ref()
+        {
+            let characters = std::iter::once(&options.first_trigger_character)
+                .chain(options.more_trigger_character.iter().flatten())
+                .cloned()
+                .collect();
+            self.catalog_rpc
+                .core_rpc
+                .on_type_formatting_triggers(path, characters);
+        }
+        self.open_documents.insert(uri.clone(), language_id.clone());
+        self.pull_document_diagnostics(uri, language_id);
+    }
+
+    fn diagnostic_options(&self) -> Option<&DiagnosticOptions> {
+        match self.server_capabilities.diagnostic_provider.as_ref()? {
+            DiagnosticServerCapabilities::Options(options) => Some(options),
+            DiagnosticServerCapabilities::RegistrationOptions(options) => {
+                Some(&options.diagnostic_options)
+            }
+        }
+    }
+
+    /// Pull the diagnostics of the open documents and of the workspace again.
+    fn refresh_diagnostics(&self) {
+        for (uri, language_id) in &self.open_documents {
+            self.pull_document_diagnostics(uri.clone(), language_id.clone());
+        }
+        self.pull_workspace_diagnostics();
+    }
+
+    /// Ask the server for the diagnostics of a document, for servers which
+    /// have them pulled rather than publishing them. They're published like
+    /// the pushed ones.
+    fn pull_document_diagnostics(&self, uri: Url, language_id: String) {
+        let Some(options) = self.diagnostic_options() else {
+            return;
+        };
+        let (pull, previous_result_id) = {
+            let mut pulled = self.pulled_diagnostics.lock();
+            pulled.next_pull += 1;
+            let pull = pulled.next_pull;
+            pulled.pulls.insert(uri.clone(), pull);
+            (pull, pulled.result_ids.get(&uri).cloned())
+        };
+        let params = DocumentDiagnosticParams {
+            text_document: TextDocumentIdentifier { uri: uri.clone() },
+            identifier: options.identifier.clone(),
+            previous_result_id,
+            work_done_progress_params: WorkDoneProgressParams::default(),
+            partial_result_params: PartialResultParams::default(),
+        };
+        let path = uri.to_file_path().ok();
+        let pulled = self.pulled_diagnostics.clone();
+        let catalog_rpc = self.catalog_rpc.clone();
+        let plugin_id = self.server_rpc.plugin_id;
+        self.server_rpc.server_request_async(
+            DocumentDiagnosticRequest::METHOD,
+            params,
+            Some(language_id),
+            path,
+            false,
+            move |result: Result<Value, RpcError>| {
+                let mut pulled = pulled.lock();
+                if pulled.pulls.get(&uri) != Some(&pull) {
+                    return;
+                }
+                pulled.pulls.remove(&uri);
+                // The server may have cancelled the pull, it asks for a
+                // refresh when it has the diagnostics.
+                let Ok(result) = result else {
+                    return;
+                };
+                let result: DocumentDiagnosticReportResult =
+                    match serde_json::from_value(result) {
+                        Ok(result) => result,
+                        Err(err) => {
+                            tracing::error!("{:?}", err);
+                            return;
+                        }
+                    };
+                let related_documents = match result {
+                    DocumentDiagnosticReportResult::Report(
+                        DocumentDiagnosticReport::Full(report),
+                    ) => {
+                        pulled.report(
+                            &catalog_rpc,
+                            plugin_id,
+                            uri,
+                            DocumentDiagnosticReportKind::Full(
+                                report.full_document_diagnostic_report,
+                            ),
+                        );
+                        report.related_documents
+                    }
+                    DocumentDiagnosticReportResult::Report(
+                        DocumentDiagnosticReport::Unchanged(report),
+                    ) => {
+                        pulled.report(
+                            &catalog_rpc,
+                            plugin_id,
+                            uri,
+                            DocumentDiagnosticReportKind::Unchanged(
+                                report.unchanged_document_diagnostic_report,
+                            ),
+                        );
+                        report.related_documents
+                    }
+                    DocumentDiagnosticReportResult::Partial(partial) => {
+                        partial.related_documents
+                    }
+                };
+                for (uri, report) in related_documents.into_iter().flatten() {
+                    pulled.report(&catalog_rpc, plugin_id, uri, report);
+                }
+            },
+        );
+    }
+
+    /// Ask the server for the diagnostics of the whole workspace, including
+    /// the files which aren't open, when it has them.
+    pub fn pull_workspace_diagnostics(&self) {
+        let Some(options) = self
+            .diagnostic_options()
+            .filter(|options| options.workspace_diagnostics)
+        else {
+            return;
+        };
+        let previous_result_ids = {
+            let mut pulled = self.pulled_diagnostics.lock();
+            if pulled.workspace_pull {
+                pulled.workspace_pull_queued = true;
+                return;
+            }
+            pulled.workspace_pull = true;
+            pulled
+                .result_ids
+                .iter()
+                .map(|(uri, value)| PreviousResultId {
+                    uri: uri.clone(),
+                    value: value.clone(),
+                })
+                .collect()
+        };
+        let params = WorkspaceDiagnosticParams {
+            identifier: options.identifier.clone(),
+            previous_result_ids,
+            work_done_progress_params: WorkDoneProgressParams::default(),
+            partial_result_params: PartialResultParams::default(),
+        };
+        let pulled = self.pulled_diagnostics.clone();
+        let catalog_rpc = self.catalog_rpc.clone();
+        let server_rpc = self.server_rpc.clone();
+        let plugin_id = self.server_rpc.plugin_id;
+        self.server_rpc.server_request_async(
+            WorkspaceDiagnosticRequest::METHOD,
+            params,
+            None,
+            None,
+            false,
+            move |result: Result<Value, RpcError>| {
+                let mut pulled = pulled.lock();
+                pulled.workspace_pull = false;
+                if std::mem::take(&mut pulled.workspace_pull_queued) {
+                    server_rpc.handle_rpc(PluginServerRpc::Handler(
+                        PluginHandlerNotification::PullWorkspaceDiagnostics,
+                    ));
+                }
+                let Ok(result) = result else {
+                    return;
+                };
+                let items = match serde_json::from_value(result) {
+                    Ok(WorkspaceDiagnosticReportResult::Report(report)) => {
+                        report.items
+                    }
+                    Ok(WorkspaceDiagnosticReportResult::Partial(partial)) => {
+                        partial.items
+                    }
+                    Err(err) => {
+                        tracing::error!("{:?}", err);
+                        return;
+                    }
+                };
+                for item in items {
+                    let (uri, report) = match item {
+                        WorkspaceDocumentDiagnosticReport::Full(report) => (
+                            report.uri,
+                            DocumentDiagnosticReportKind::Full(
+                                report.full_document_diagnostic_report,
+                            ),
+                        ),
+                        WorkspaceDocumentDiagnosticReport::Unchanged(report) => (
+                            report.uri,
+                            DocumentDiagnosticReportKind::Unchanged(
+                                report.unchanged_document_diagnostic_report,
+                            ),
+                        ),
+                    };
+                    // The document's own pull is more recent.
+                    if pulled.pulls.contains_key(&uri) {
+                        continue;
+                    }
+                    pulled.report(&catalog_rpc, plugin_id, uri, report);
+                }
+            },
+        );
     }
 
     pub fn format_semantic_tokens(
         &self,
         tokens: SemanticTokens,
+        changed: Option<ops::Range<usize>>,
         text: Rope,
-        f: Box<dyn RpcCallback<Vec<LineStyle>, RpcError>>,
+        f: Box<dyn RpcCallback<SemanticTokenStyles, RpcError>>,
     ) {
         let result = format_semantic_styles(
             &text,
             self.server_capabilities.semantic_tokens_provider.as_ref(),
             &tokens,
+            changed,
         )
         .ok_or_else(|| RpcError {
             code: 0,
@@ -1381,32 +1994,71 @@ fn get_document_content_change(
     None
 }
 
+/// Turn the tokens into styles. If only the tokens in `changed` changed, only
+/// those are turned into styles, which then replace the styles between the
+/// tokens around them.
 fn format_semantic_styles(
     text: &Rope,
     semantic_tokens_provider: Option<&SemanticTokensServerCapabilities>,
     tokens: &SemanticTokens,
-) -> Option<Vec<LineStyle>> {
+    changed: Option<ops::Range<usize>>,
+) -> Option<SemanticTokenStyles> {
     let semantic_tokens_provider = semantic_tokens_provider?;
     let semantic_legends = semantic_tokens_legend(semantic_tokens_provider);
 
+    let mut range_end = text.len();
     let text = RopeTextRef::new(text);
+    let formatted = changed.clone().unwrap_or(0..tokens.data.len());
+    // The tokens around the changed ones bound the range they cover.
+    let bounds = formatted.start.saturating_sub(1)..formatted.end + 1;
     let mut highlights = Vec::new();
+    let mut range_start = 0;
+    // The line and the UTF-16 column of the token.
     let mut line = 0;
-    let mut start = 0;
+    let mut column = 0;
+    // The line, column and offset of the last token with an offset.
+    let mut last: Option<(usize, usize, usize)> = None;
     let mut last_start = 0;
-    for semantic_token in &tokens.data {
+    for (i, semantic_token) in tokens.data.iter().enumerate() {
         if semantic_token.delta_line > 0 {
             line += semantic_token.delta_line as usize;
-            start = text.offset_of_line(line);
+            column = semantic_token.delta_start as usize;
+        } else {
+            column += semantic_token.delta_start as usize;
+        }
+        if i < bounds.start {
+            continue;
+        }
+        if i >= bounds.end {
+            break;
         }
 
-        let sub_text = text.char_indices_iter(start..);
-        start += offset_utf16_to_utf8(sub_text, semantic_token.delta_start as usize);
+        let start = match last {
+            Some((last_line, last_column, last_offset)) if last_line == line => {
+                let sub_text = text.char_indices_iter(last_offset..);
+                last_offset + offset_utf16_to_utf8(sub_text, column - last_column)
+            }
+            _ => {
+                let line_start = text.offset_of_line(line);
+                let sub_text = text.char_indices_iter(line_start..);
+                line_start + offset_utf16_to_utf8(sub_text, column)
+            }
+        };
+        last = Some((line, column, start));
 
         let sub_text = text.char_indices_iter(start..);
         let end =
             start + offset_utf16_to_utf8(sub_text, semantic_token.length as usize);
 
+        if i < formatted.start {
+            range_start = end;
+            continue;
+        }
+        if i >= formatted.end {
+            range_end = start;
+            continue;
+        }
+
         let kind = semantic_legends.token_types[semantic_token.token_type as usize]
             .as_str()
             .to_string();
@@ -1423,18 +2075,289 @@ fn format_semantic_styles(
         });
     }
 
-    Some(highlights)
+    let range = changed.map(|_| range_start..range_end.max(range_start));
+    Some((highlights, range))
 }
 
 fn semantic_tokens_legend(
     semantic_tokens_provider: &SemanticTokensServerCapabilities,
 ) -> &SemanticTokensLegend {
+    &semantic_tokens_options(semantic_tokens_provider).legend
+}
+
+fn semantic_tokens_options(
+    semantic_tokens_provider: &SemanticTokensServerCapabilities,
+) -> &SemanticTokensOptions {
     match semantic_tokens_provider {
-        SemanticTokensServerCapabilities::SemanticTokensOptions(options) => {
-            &options.legend
-        }
+        SemanticTokensServerCapabilities::SemanticTokensOptions(options) => options,
         SemanticTokensServerCapabilities::SemanticTokensRegistrationOptions(
             options,
-        ) => &options.semantic_tokens_options.legend,
+        ) => &options.semantic_tokens_options,
+    }
+}
+
+fn file_operation_filter_matches(filter: &FileOperationFilter, uri: &Url) -> bool {
+    if filter
+        .scheme
+        .as_deref()
+        .is_some_and(|scheme| scheme != uri.scheme())
+    {
+        return false;
+    }
+    let Ok(path) = uri.to_file_path() else {
+        return false;
+    };
+    let pattern = &filter.pattern;
+    let matcher = match globset::GlobBuilder::new(&pattern.glob)
+        .case_insensitive(
+            pattern
+                .options
+                .as_ref()
+                .and_then(|options| options.ignore_case)
+                .unwrap_or(false),
+        )
+        .literal_separator(true)
+        .build()
+    {
+        Ok(glob) => glob.compile_matcher(),
+        Err(err) => {
+            tracing::error!("{:?}", err);
+            return false;
+        }
+    };
+    if !matcher.is_match(&path) {
+        return false;
+    }
+    // The path may be gone already, after a deletion or a rename, in which
+    // case there is no telling whether it was a file or a folder.
+    match (&pattern.matches, fs::metadata(&path)) {
+        (Some(FileOperationPatternKind::File), Ok(metadata)) => metadata.is_file(),
+        (Some(FileOperationPatternKind::Folder), Ok(metadata)) => metadata.is_dir(),
+        _ => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use lsp_types::{
+        Diagnostic, DocumentDiagnosticReportKind, FileOperationFilter,
+        FileOperationPattern, FileOperationPatternKind, FileOperationPatternOptions,
+        FullDocumentDiagnosticReport, Position, Range, SemanticToken,
+        SemanticTokenType, SemanticTokens, SemanticTokensLegend,
+        SemanticTokensOptions, SemanticTokensServerCapabilities,
+        UnchangedDocumentDiagnosticReport, Url,
+    };
+    use phidi_rpc::{
+        core::{CoreNotification, CoreRpc, CoreRpcHandler},
+        plugin::PluginId,
+        proxy::ProxyRpcHandler,
+        style::{LineStyle, Style},
+    };
+    use phidi_xi_rope::Rope;
+
+    use super::{
+        PulledDiagnostics, file_operation_filter_matches, format_semantic_styles,
+    };
+    use crate::plugin::PluginCatalogRpcHandler;
+
+    fn full(
+        result_id: Option<&str>,
+        messages: &[&str],
+    ) -> DocumentDiagnosticReportKind {
+        DocumentDiagnosticReportKind::Full(FullDocumentDiagnosticReport {
+            result_id: result_id.map(str::to_string),
+            items: messages
+                .iter()
+                .map(|message| {
+                    Diagnostic::new_simple(
+                        Range::new(Position::new(0, 0), Position::new(0, 1)),
+                        message.to_string(),
+                    )
+                })
+                .collect(),
+        })
+    }
+
+    fn unchanged(result_id: &str) -> DocumentDiagnosticReportKind {
+        DocumentDiagnosticReportKind::Unchanged(UnchangedDocumentDiagnosticReport {
+            result_id: result_id.to_string(),
+        })
+    }
+
+    /// The messages of the diagnostics published to the editor, if any were.
+    fn published(core_rpc: &CoreRpcHandler) -> Option<Vec<String>> {
+        match core_rpc.rx().try_recv().ok()? {
+            CoreRpc::Notification(notification) => match *notification {
+                CoreNotification::PublishDiagnostics { diagnostics } => Some(
+                    diagnostics
+                        .diagnostics
+                        .into_iter()
+                        .map(|diagnostic| diagnostic.message)
+                        .collect(),
+                ),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_report() {
+        let core_rpc = CoreRpcHandler::new();
+        let catalog_rpc =
+            PluginCatalogRpcHandler::new(core_rpc.clone(), ProxyRpcHandler::new());
+        let plugin_id = PluginId(1);
+        let uri = Url::parse("file:///src/main.rs").unwrap();
+        let mut pulled = PulledDiagnostics::default();
+
+        pulled.report(
+            &catalog_rpc,
+            plugin_id,
+            uri.clone(),
+            full(Some("1"), &["unused"]),
+        );
+        assert_eq!(published(&core_rpc), Some(vec!["unused".to_string()]));
+        assert_eq!(pulled.result_ids.get(&uri).map(String::as_str), Some("1"));
+
+        // Nothing changed, so the diagnostics shown are kept, and the new
+        // result id is the one to send back next time.
+        pulled.report(&catalog_rpc, plugin_id, uri.clone(), unchanged("2"));
+        assert_eq!(published(&core_rpc), None);
+        assert_eq!(pulled.result_ids.get(&uri).map(String::as_str), Some("2"));
+
+        // A full report without a result id can't be answered as unchanged.
+        pulled.report(&catalog_rpc, plugin_id, uri.clone(), full(None, &[]));
+        assert_eq!(published(&core_rpc), Some(Vec::new()));
+        assert!(!pulled.result_ids.contains_key(&uri));
+    }
+
+    fn filter(
+        glob: &str,
+        matches: Option<FileOperationPatternKind>,
+        ignore_case: bool,
+    ) -> FileOperationFilter {
+        FileOperationFilter {
+            scheme: Some("file".to_string()),
+            pattern: FileOperationPattern {
+                glob: glob.to_string(),
+                matches,
+                options: Some(FileOperationPatternOptions {
+                    ignore_case: Some(ignore_case),
+                }),
+            },
+        }
+    }
+
+    #[test]
+    fn test_file_operation_filter_matches() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("main.rs");
+        std::fs::write(&file, "").unwrap();
+        let file = Url::from_file_path(&file).unwrap();
+        let folder = Url::from_directory_path(dir.path()).unwrap();
+        let gone = Url::from_file_path(dir.path().join("gone.rs")).unwrap();
+
+        assert!(file_operation_filter_matches(
+            &filter("**/*.rs", None, false),
+            &file
+        ));
+        assert!(!file_operation_filter_matches(
+            &filter("**/*.py", None, false),
+            &file
+        ));
+        assert!(!file_operation_filter_matches(
+            &filter("**/*.RS", None, false),
+            &file
+        ));
+        assert!(file_operation_filter_matches(
+            &filter("**/*.RS", None, true),
+            &file
+        ));
+
+        let untitled = FileOperationFilter {
+            scheme: Some("untitled".to_string()),
+            ..filter("**/*.rs", None, false)
+        };
+        assert!(!file_operation_filter_matches(&untitled, &file));
+
+        let files = filter("**", Some(FileOperationPatternKind::File), false);
+        let folders = filter("**", Some(FileOperationPatternKind::Folder), false);
+        assert!(file_operation_filter_matches(&files, &file));
+        assert!(!file_operation_filter_matches(&folders, &file));
+        assert!(file_operation_filter_matches(&folders, &folder));
+        assert!(!file_operation_filter_matches(&files, &folder));
+        // A path which is gone can't be told apart.
+        assert!(file_operation_filter_matches(&folders, &gone));
+    }
+
+    #[test]
+    fn test_format_semantic_styles() {
+        let text = Rope::from("let a = b;\nfn c() {}\n");
+        let provider = SemanticTokensServerCapabilities::SemanticTokensOptions(
+            SemanticTokensOptions {
+                legend: SemanticTokensLegend {
+                    token_types: vec![
+                        SemanticTokenType::KEYWORD,
+                        SemanticTokenType::VARIABLE,
+                        SemanticTokenType::FUNCTION,
+                    ],
+                    token_modifiers: Vec::new(),
+                },
+                ..Default::default()
+            },
+        );
+        let token = |delta_line, delta_start, length, token_type| SemanticToken {
+            delta_line,
+            delta_start,
+            length,
+            token_type,
+            token_modifiers_bitset: 0,
+        };
+        // `let`, `a`, `b`, `fn` and `c`.
+        let tokens = SemanticTokens {
+            result_id: None,
+            data: vec![
+                token(0, 0, 3, 0),
+                token(0, 4, 1, 1),
+                token(0, 4, 1, 1),
+                token(1, 0, 2, 0),
+                token(0, 3, 1, 2),
+            ],
+        };
+        let style = |start, end, kind: &str| LineStyle {
+            start,
+            end,
+            style: Style {
+                fg_color: Some(kind.to_string()),
+            },
+        };
+        let format = |changed| {
+            format_semantic_styles(&text, Some(&provider), &tokens, changed).unwrap()
+        };
+
+        let (styles, range) = format(None);
+        assert_eq!(styles.len(), 5);
+        assert_eq!(styles[4], style(14, 15, "function"));
+        assert_eq!(range, None);
+
+        // Only the changed tokens are styled, replacing the styles between the
+        // tokens around them.
+        assert_eq!(
+            format(Some(2..4)),
+            (
+                vec![style(8, 9, "variable"), style(11, 13, "keyword")],
+                Some(5..14)
+            )
+        );
+        assert_eq!(
+            format(Some(0..1)),
+            (vec![style(0, 3, "keyword")], Some(0..4))
+        );
+        assert_eq!(
+            format(Some(4..5)),
+            (vec![style(14, 15, "function")], Some(13..21))
+        );
+        // Removed tokens leave no styles between the tokens around them.
+        assert_eq!(format(Some(2..2)), (Vec::new(), Some(5..8)));
     }
 }
@@ -5,6 +5,7 @@
     collections::{HashMap, VecDeque},
     fs,
     io::{Read, Seek, Write},
+    ops,
     path::{Path, PathBuf},
     process,
     sync::{Arc, RwLock},
@@ -24,7 +25,6 @@
 use phidi_rpc::{
     RpcError,
     plugin::{PluginId, VoltID, VoltInfo, VoltMetadata},
-    style::LineStyle,
 };
 use phidi_xi_rope::{Rope, RopeDelta};
 use psp_types::{Notification, Request};
@@ -36,7 +36,8 @@
     PluginCatalogRpcHandler, client_capabilities,
     psp::{
         PluginHandlerNotification, PluginHostHandler, PluginServerHandler,
-        PluginServerRpc, ResponseSender, RpcCallback, handle_plugin_server_message,
+        PluginServerRpc, ResponseSender, RpcCallback, SemanticTokenStyles,
+        handle_plugin_server_message,
     },
     volt_icon,
 };
@@ -91,6 +92,10 @@ fn method_registered(&mut self, method: &str) -> bool {
         self.host.method_registered(method)
     }
 
+    fn params_supported(&mut self, method: &str, params: &Params) -> bool {
+        self.host.params_supported(method, params)
+    }
+
     fn document_supported(
         &mut self,
         language_id: Option<&str>,
@@ -114,6 +119,9 @@ fn handle_handler_notification(
             Shutdown => {
                 self.shutdown();
             }
+            PullWorkspaceDiagnostics => {
+                self.host.pull_workspace_diagnostics();
+            }
             SpawnedPluginLoaded { plugin_id } => {
                 self.host.handle_spawned_plugin_loaded(plugin_id);
             }
@@ -141,6 +149,10 @@ fn handle_host_request(
         self.host.handle_request(id, method, params, resp);
     }
 
+    fn handle_did_open_text_document(&mut self, language_id: String, path: PathBuf) {
+        self.host.handle_did_open_text_document(language_id, path);
+    }
+
     fn handle_did_save_text_document(
         &self,
         language_id: String,
@@ -183,10 +195,11 @@ fn handle_did_change_text_document(
     fn format_semantic_tokens(
         &self,
         tokens: lsp_types::SemanticTokens,
+        changed: Option<ops::Range<usize>>,
         text: Rope,
-        f: Box<dyn RpcCallback<Vec<LineStyle>, RpcError>>,
+        f: Box<dyn RpcCallback<SemanticTokenStyles, RpcError>>,
     ) {
-        self.host.format_semantic_tokens(tokens, text, f);
+        self.host.format_semantic_tokens(tokens, changed, text, f);
     }
 }
 
@@ -608,7 +621,9 @@ fn wasi_read_string(stdout: &Arc<RwLock<WasiPipe>>) -> Result<String> {
     Ok(buf)
 }
 
-fn unflatten_map(map: &HashMap<String, serde_json::Value>) -> serde_json::Value {
+pub(crate) fn unflatten_map(
+    map: &HashMap<String, serde_json::Value>,
+) -> serde_json::Value {
     let mut new = serde_json::json!({});
     for (key, value) in map.iter() {
         let mut current = new.as_object_mut().unwrap();
@@ -0,0 +1,600 @@
+//! Searching and replacing across the workspace.
+
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    fs, io,
+    ops::Range,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use grep_matcher::{Captures, Match, Matcher};
+use grep_regex::{RegexCaptures, RegexMatcher, RegexMatcherBuilder};
+use grep_searcher::{Searcher, SearcherBuilder, Sink, SinkContext, SinkMatch};
+use ignore::{WalkBuilder, overrides::OverrideBuilder, types::TypesBuilder};
+use indexmap::IndexMap;
+use phidi_rpc::{
+    RpcError,
+    proxy::{ProxyResponse, SearchMatch, SearchOptions},
+};
+use phidi_xi_rope::Rope;
+
+pub fn build_matcher(
+    pattern: &str,
+    case_sensitive: bool,
+    whole_word: bool,
+    is_regex: bool,
+) -> Result<RegexMatcher, RpcError> {
+    let mut matcher = RegexMatcherBuilder::new();
+    let matcher = matcher.case_insensitive(!case_sensitive).word(whole_word);
+    let matcher = if is_regex {
+        matcher.build(pattern)
+    } else {
+        matcher.build_literals(&[&regex::escape(pattern)])
+    };
+    matcher.map_err(|_| RpcError {
+        code: 0,
+        message: "can't build matcher".to_string(),
+    })
+}
+
+fn new_captures(matcher: &RegexMatcher) -> Result<RegexCaptures, RpcError> {
+    matcher.new_captures().map_err(|_| RpcError {
+        code: 0,
+        message: "can't build matcher".to_string(),
+    })
+}
+
+/// What a match is replaced with. With a regex the replacement can refer to
+/// the capture groups of the match, as `$1` or `${name}`, otherwise it is
+/// taken literally.
+fn replacement_of(
+    matcher: &RegexMatcher,
+    caps: &RegexCaptures,
+    line: &[u8],
+    replacement: &str,
+    is_regex: bool,
+) -> String {
+    if !is_regex {
+        return replacement.to_string();
+    }
+    let mut dst = Vec::new();
+    caps.interpolate(
+        |name| matcher.capture_index(name),
+        line,
+        replacement.as_bytes(),
+        &mut dst,
+    );
+    String::from_utf8_lossy(&dst).into_owned()
+}
+
+/// The matches of the line, and what they are replaced with if there is a
+/// replacement.
+fn line_matches(
+    matcher: &RegexMatcher,
+    caps: &mut RegexCaptures,
+    line: &[u8],
+    replacement: Option<&str>,
+    is_regex: bool,
+) -> Vec<(Match, Option<String>)> {
+    let mut matches = Vec::new();
+    let result = matcher.captures_iter(line, caps, |caps| {
+        if let Some(found) = caps.get(0) {
+            let replacement = replacement.map(|replacement| {
+                replacement_of(matcher, caps, line, replacement, is_regex)
+            });
+            matches.push((found, replacement));
+        }
+        true
+    });
+    if let Err(err) = result {
+        tracing::error!("{:?}", err);
+    }
+    matches
+}
+
+/// The files to search: those of the workspace that aren't ignored, and the
+/// open files outside of it, narrowed down by the globs and file types of the
+/// options.
+pub fn search_files(
+    workspace: Option<&Path>,
+    buffers: &[PathBuf],
+    options: &SearchOptions,
+) -> Result<impl Iterator<Item = PathBuf> + use<>, RpcError> {
+    let invalid = |err: ignore::Error| RpcError {
+        code: 0,
+        message: err.to_string(),
+    };
+
+    let mut overrides = OverrideBuilder::new(workspace.unwrap_or(Path::new("")));
+    for glob in &options.include {
+        overrides.add(glob).map_err(invalid)?;
+    }
+    for glob in &options.exclude {
+        overrides.add(&format!("!{glob}")).map_err(invalid)?;
+    }
+    let overrides = overrides.build().map_err(invalid)?;
+
+    let mut types = TypesBuilder::new();
+    types.add_defaults();
+    for file_type in &options.file_types {
+        types.select(file_type);
+    }
+    let types = types.build().map_err(invalid)?;
+
+    // The open files of the workspace are walked over unless they are ignored.
+    // The walker doesn't filter the paths it starts from, so the other open
+    // files are filtered here.
+    let buffers = buffers
+        .iter()
+        .filter(|path| {
+            workspace.is_none_or(|workspace| !path.starts_with(workspace))
+                && !overrides.matched(path, false).is_ignore()
+                && !types.matched(path, false).is_ignore()
+        })
+        .cloned()
+        .collect::<Vec<_>>();
+    let walker = workspace.map(|workspace| {
+        WalkBuilder::new(workspace)
+            .overrides(overrides)
+            .types(types)
+            .build()
+    });
+
+    Ok(walker
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|entry| entry.into_path())
+        .chain(buffers))
+}
+
+/// Collects the matches of a file, with the lines around them.
+struct MatchSink<'a> {
+    id: u64,
+    current_id: &'a AtomicU64,
+    matcher: &'a RegexMatcher,
+    caps: RegexCaptures,
+    replacement: Option<&'a str>,
+    is_regex: bool,
+    context_lines: usize,
+    /// How many more matches may be collected.
+    limit: Option<usize>,
+    /// Whether there were more matches than the limit.
+    limit_hit: bool,
+    /// The lines before the current one, for the context of its matches.
+    previous: VecDeque<(usize, String)>,
+    matches: Vec<SearchMatch>,
+}
+
+impl MatchSink<'_> {
+    /// Records a line that has been searched, matched or not, as the context
+    /// of the matches around it.
+    fn add_line(&mut self, lnum: usize, line: &str) {
+        if self.context_lines == 0 {
+            return;
+        }
+        let line = line.trim_end_matches(['\r', '\n']);
+        for m in self.matches.iter_mut().rev() {
+            if m.line >= lnum {
+                continue;
+            }
+            if lnum - m.line > self.context_lines {
+                break;
+            }
+            m.context_after.push(line.to_string());
+        }
+        self.previous.push_back((lnum, line.to_string()));
+        if self.previous.len() > self.context_lines {
+            self.previous.pop_front();
+        }
+    }
+}
+
+impl Sink for MatchSink<'_> {
+    type Error = io::Error;
+
+    fn matched(
+        &mut self,
+        _searcher: &Searcher,
+        mat: &SinkMatch<'_>,
+    ) -> Result<bool, io::Error> {
+        if self.current_id.load(Ordering::SeqCst) != self.id {
+            return Ok(false);
+        }
+        let Ok(line) = std::str::from_utf8(mat.bytes()) else {
+            return Ok(true);
+        };
+        let lnum = mat.line_number().unwrap_or_default() as usize;
+
+        let found = line_matches(
+            self.matcher,
+            &mut self.caps,
+            line.as_bytes(),
+            self.replacement,
+            self.is_regex,
+        );
+        let context_before = self
+            .previous
+            .iter()
+            .filter(|(previous, _)| lnum - previous <= self.context_lines)
+            .map(|(_, line)| line.clone())
+            .collect::<Vec<_>>();
+        for (mymatch, replacement) in found {
+            if self.limit == Some(0) {
+                self.limit_hit = true;
+                return Ok(false);
+            }
+            let line = if line.len() > 200 {
+                // Shorten the line to avoid sending over absurdly long-lines
+                // (such as in minified javascript)
+                // Note that the start/end are column based, not absolute from the
+                // start of the file.
+                let left_keep = line[..mymatch.start()]
+                    .chars()
+                    .rev()
+                    .take(100)
+                    .map(|c| c.len_utf8())
+                    .sum::<usize>();
+                let right_keep = line[mymatch.end()..]
+                    .chars()
+                    .take(100)
+                    .map(|c| c.len_utf8())
+                    .sum::<usize>();
+                let display_range =
+                    mymatch.start() - left_keep..mymatch.end() + right_keep;
+                line[display_range].to_string()
+            } else {
+                line.to_string()
+            };
+            self.matches.push(SearchMatch {
+                line: lnum,
+                start: mymatch.start(),
+                end: mymatch.end(),
+                line_content: line,
+                replacement,
+                context_before: context_before.clone(),
+                context_after: Vec::new(),
+            });
+            if let Some(limit) = self.limit.as_mut() {
+                *limit -= 1;
+            }
+        }
+        self.add_line(lnum, line);
+        Ok(true)
+    }
+
+    fn context(
+        &mut self,
+        _searcher: &Searcher,
+        context: &SinkContext<'_>,
+    ) -> Result<bool, io::Error> {
+        if let (Ok(line), Some(lnum)) =
+            (std::str::from_utf8(context.bytes()), context.line_number())
+        {
+            self.add_line(lnum as usize, line);
+        }
+        Ok(true)
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn search_in_path(
+    id: u64,
+    current_id: &AtomicU64,
+    paths: impl Iterator<Item = PathBuf>,
+    open: &HashMap<PathBuf, (u64, Rope)>,
+    pattern: &str,
+    case_sensitive: bool,
+    whole_word: bool,
+    is_regex: bool,
+    replacement: Option<&str>,
+    options: &SearchOptions,
+) -> Result<ProxyResponse, RpcError> {
+    let mut matches = IndexMap::new();
+    let mut revisions = HashMap::new();
+    let matcher = build_matcher(pattern, case_sensitive, whole_word, is_regex)?;
+    let mut searcher = SearcherBuilder::new()
+        .before_context(options.context_lines)
+        .after_context(options.context_lines)
+        .build();
+    let mut limit = options.max_results;
+
+    for path in paths {
+        if current_id.load(Ordering::SeqCst) != id {
+            return Err(RpcError {
+                code: 0,
+                message: "expired search job".to_string(),
+            });
+        }
+        let buffer = open.get(&path);
+        if buffer.is_none() && !path.is_file() {
+            continue;
+        }
+        let mut sink = MatchSink {
+            id,
+            current_id,
+            matcher: &matcher,
+            caps: new_captures(&matcher)?,
+            replacement,
+            is_regex,
+            context_lines: options.context_lines,
+            limit,
+            limit_hit: false,
+            previous: VecDeque::new(),
+            matches: Vec::new(),
+        };
+        // Open files are searched as they are in the editor, so that the
+        // matches are those of the text a replace edits.
+        let result = match buffer {
+            Some((_, text)) => searcher.search_slice(
+                &matcher,
+                text.slice_to_cow(..).as_bytes(),
+                &mut sink,
+            ),
+            None => searcher.search_path(&matcher, &path, &mut sink),
+        };
+        if let Err(err) = result {
+            tracing::error!("{:?}", err);
+        }
+        limit = sink.limit;
+        if !sink.matches.is_empty() {
+            if let Some((rev, _)) = buffer {
+                revisions.insert(path.clone(), *rev);
+            }
+            matches.insert(path, sink.matches);
+        }
+        if sink.limit_hit {
+            return Ok(ProxyResponse::GlobalSearchResponse {
+                matches,
+                limit_hit: true,
+                revisions,
+            });
+        }
+    }
+
+    Ok(ProxyResponse::GlobalSearchResponse {
+        matches,
+        limit_hit: false,
+        revisions,
+    })
+}
+
+/// The replacements to make in `text`, as byte ranges and their new text.
+/// Matches are found the same way as `search_in_path` does, so that the
+/// `included` ones can be identified by their line and start column.
+pub fn replacements_in_text(
+    matcher: &RegexMatcher,
+    text: &str,
+    replacement: &str,
+    is_regex: bool,
+    included: &HashSet<(usize, usize)>,
+) -> Result<Vec<(Range<usize>, String)>, RpcError> {
+    let mut caps = new_captures(matcher)?;
+    let mut replacements = Vec::new();
+    let mut offset = 0;
+    for (i, line) in text.split_inclusive('\n').enumerate() {
+        let found = line_matches(
+            matcher,
+            &mut caps,
+            line.as_bytes(),
+            Some(replacement),
+            is_regex,
+        );
+        for (found, new_text) in found {
+            if let Some(new_text) = new_text {
+                if included.contains(&(i + 1, found.start())) {
+                    replacements.push((
+                        offset + found.start()..offset + found.end(),
+                        new_text,
+                    ));
+                }
+            }
+        }
+        offset += line.len();
+    }
+    Ok(replacements)
+}
+
+/// Replaces the matches in a file that isn't open, returning whether it
+/// changed.
+pub fn replace_in_file(
+    matcher: &RegexMatcher,
+    path: &Path,
+    replacement: &str,
+    is_regex: bool,
+    included: &HashSet<(usize, usize)>,
+) -> anyhow::Result<bool> {
+    let mut text = fs::read_to_string(path)?;
+    let replacements =
+        replacements_in_text(matcher, &text, replacement, is_regex, included)
+            .map_err(|err| anyhow::anyhow!(err.message))?;
+    if replacements.is_empty() {
+        return Ok(false);
+    }
+    for (range, new_text) in replacements.into_iter().rev() {
+        text.replace_range(range, &new_text);
+    }
+    fs::write(path, text)?;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::{HashMap, HashSet},
+        sync::atomic::AtomicU64,
+    };
+
+    use phidi_rpc::proxy::{ProxyResponse, SearchOptions};
+    use phidi_xi_rope::Rope;
+
+    use super::{build_matcher, replacements_in_text, search_files, search_in_path};
+
+    #[test]
+    fn test_replacements_in_text() {
+        let text = "let foo_1 = 1;\nlet bar = foo_2;\nlet foo_3 = 3;\n";
+
+        let matcher = build_matcher(r"foo_(\d)", true, false, true).unwrap();
+        let all = HashSet::from([(1, 4), (2, 10), (3, 4)]);
+        let replacements =
+            replacements_in_text(&matcher, text, "baz$1", true, &all).unwrap();
+        let mut result = text.to_string();
+        for (range, new_text) in replacements.into_iter().rev() {
+            result.replace_range(range, &new_text);
+        }
+        assert_eq!(result, "let baz1 = 1;\nlet bar = baz2;\nlet baz3 = 3;\n");
+
+        // Matches that weren't previewed, or were excluded, are left alone.
+        let included = HashSet::from([(1, 4), (3, 4)]);
+        let replacements =
+            replacements_in_text(&matcher, text, "baz$1", true, &included).unwrap();
+        assert_eq!(replacements.len(), 2);
+        assert_eq!(replacements[1], (36..41, "baz3".to_string()));
+        let replacements =
+            replacements_in_text(&matcher, text, "baz$1", true, &HashSet::new())
+                .unwrap();
+        assert!(replacements.is_empty());
+
+        let matcher = build_matcher("foo_(", true, false, false).unwrap();
+        let replacements = replacements_in_text(
+            &matcher,
+            "a foo_( b",
+            "$1",
+            false,
+            &HashSet::from([(1, 2)]),
+        )
+        .unwrap();
+        assert_eq!(replacements, vec![(2..7, "$1".to_string())]);
+
+        let matcher = build_matcher("foo", true, false, false).unwrap();
+        let included = HashSet::from([(1, 0), (1, 4)]);
+        let replacements =
+            replacements_in_text(&matcher, "foo(foo)", "bar", false, &included)
+                .unwrap();
+        assert_eq!(
+            replacements,
+            vec![(0..3, "bar".to_string()), (4..7, "bar".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_search_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let workspace = dir.path().join("workspace");
+        std::fs::create_dir_all(workspace.join("target")).unwrap();
+        std::fs::write(workspace.join(".ignore"), "target/\n").unwrap();
+        for file in ["main.rs", "notes.md", "target/out.rs"] {
+            std::fs::write(workspace.join(file), "").unwrap();
+        }
+        std::fs::write(dir.path().join("other.rs"), "").unwrap();
+
+        // Open files are filtered like the others.
+        let buffers = [
+            workspace.join("main.rs"),
+            workspace.join("notes.md"),
+            workspace.join("target/out.rs"),
+            dir.path().join("other.rs"),
+        ];
+        let options = SearchOptions {
+            exclude: vec!["*.md".to_string()],
+            ..Default::default()
+        };
+        let mut files = search_files(Some(&workspace), &buffers, &options)
+            .unwrap()
+            .filter(|path| path.is_file())
+            .collect::<Vec<_>>();
+        files.sort();
+        assert_eq!(
+            files,
+            vec![dir.path().join("other.rs"), workspace.join("main.rs")]
+        );
+    }
+
+    #[test]
+    fn test_search_in_path() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("main.rs");
+        std::fs::write(&path, "a\nfoo foo\nb\nc\nfoo\n").unwrap();
+        let current_id = AtomicU64::new(1);
+        let search = |options: &SearchOptions| {
+            let Ok(ProxyResponse::GlobalSearchResponse {
+                matches, limit_hit, ..
+            }) = search_in_path(
+                1,
+                &current_id,
+                std::iter::once(path.clone()),
+                &HashMap::new(),
+                "foo",
+                true,
+                false,
+                false,
+                None,
+                options,
+            )
+            else {
+                panic!("search failed");
+            };
+            (matches.get(&path).cloned().unwrap_or_default(), limit_hit)
+        };
+
+        let (matches, limit_hit) = search(&SearchOptions {
+            context_lines: 1,
+            ..Default::default()
+        });
+        assert!(!limit_hit);
+        assert_eq!(
+            matches
+                .iter()
+                .map(|m| (m.line, m.start, m.end))
+                .collect::<Vec<_>>(),
+            vec![(2, 0, 3), (2, 4, 7), (5, 0, 3)]
+        );
+        assert_eq!(matches[1].context_before, vec!["a"]);
+        assert_eq!(matches[1].context_after, vec!["b"]);
+        assert_eq!(matches[2].context_before, vec!["c"]);
+        assert!(matches[2].context_after.is_empty());
+
+        let (matches, limit_hit) = search(&SearchOptions {
+            max_results: Some(2),
+            ..Default::default()
+        });
+        assert!(limit_hit);
+        assert_eq!(matches.len(), 2);
+    }
+
+    #[test]
+    fn test_search_open_buffer() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("main.rs");
+        std::fs::write(&path, "foo\n").unwrap();
+        // The buffer has unsaved edits, which are searched instead of the file.
+        let open =
+            HashMap::from([(path.clone(), (3, Rope::from("bar\nfoo foo\n")))]);
+        let Ok(ProxyResponse::GlobalSearchResponse {
+            matches, revisions, ..
+        }) = search_in_path(
+            1,
+            &AtomicU64::new(1),
+            std::iter::once(path.clone()),
+            &open,
+            "foo",
+            true,
+            false,
+            false,
+            None,
+            &SearchOptions::default(),
+        )
+        else {
+            panic!("search failed");
+        };
+        assert_eq!(
+            matches[&path]
+                .iter()
+                .map(|m| (m.line, m.start))
+                .collect::<Vec<_>>(),
+            vec![(2, 0), (2, 4)]
+        );
+        assert_eq!(revisions, HashMap::from([(path, 3)]));
+    }
+}
@@ -4,8 +4,10 @@
     io::{self, ErrorKind, Read, Write},
     num::NonZeroUsize,
     path::PathBuf,
+    process::{Command, Stdio},
     sync::Arc,
-    time::Duration,
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
 };
 
 use alacritty_terminal::{
@@ -13,17 +15,25 @@
     event_loop::Msg,
     tty::{self, EventedPty, EventedReadWrite, Options, Shell, setup_env},
 };
-use anyhow::Result;
+use anyhow::{Result, anyhow};
 use crossbeam_channel::{Receiver, Sender};
 use directories::BaseDirs;
+use parking_lot::Mutex;
 use phidi_rpc::{
     core::CoreRpcHandler,
-    terminal::{TermId, TerminalProfile},
+    terminal::{CommandResult, TermId, TerminalProfile},
 };
 use polling::PollMode;
 
 const READ_BUFFER_SIZE: usize = 0x10_0000;
 
+pub const DEFAULT_COMMAND_TIMEOUT: Duration = Duration::from_secs(60);
+/// How much of the end of each output stream of a command is kept.
+const MAX_COMMAND_OUTPUT: usize = 0x1_0000;
+const COMMAND_POLL_INTERVAL: Duration = Duration::from_millis(20);
+/// How long the output of a command is still read after it exited.
+const COMMAND_OUTPUT_GRACE: Duration = Duration::from_secs(1);
+
 #[cfg(any(target_os = "linux", target_os = "macos"))]
 const PTY_READ_WRITE_TOKEN: usize = 0;
 #[cfg(any(target_os = "linux", target_os = "macos"))]
@@ -60,6 +70,7 @@ pub struct Terminal {
     pub(crate) pty: alacritty_terminal::tty::Pty,
     rx: Receiver<Msg>,
     pub tx: Sender<Msg>,
+    output: Option<Arc<Mutex<CommandOutput>>>,
 }
 
 impl Terminal {
@@ -99,10 +110,18 @@ pub fn new(
             pty,
             tx,
             rx,
+            output: None,
         })
     }
 
-    pub fn run(&mut self, core_rpc: CoreRpcHandler) {
+    /// Keep a copy of everything the terminal outputs from now on.
+    pub fn capture_output(&mut self) -> Arc<Mutex<CommandOutput>> {
+        self.output.get_or_insert_with(Default::default).clone()
+    }
+
+    /// Runs the terminal until its process exits or it is shut down, and
+    /// returns the exit code of the process.
+    pub fn run(&mut self, core_rpc: CoreRpcHandler) -> Option<i32> {
         let mut state = State::default();
         let mut buf = [0u8; READ_BUFFER_SIZE];
 
@@ -204,6 +223,7 @@ pub fn run(&mut self, core_rpc: CoreRpcHandler) {
         if let Err(err) = self.pty.deregister(&self.poller) {
             tracing::error!("{:?}", err);
         }
+        exit_code
     }
 
     /// Drain the channel.
@@ -231,6 +251,9 @@ fn pty_read(
             match self.pty.reader().read(buf) {
                 Ok(0) => break,
                 Ok(n) => {
+                    if let Some(output) = &self.output {
+                        output.lock().push(&buf[..n]);
+                    }
                     core_rpc.update_terminal(self.term_id, buf[..n].to_vec());
                 }
                 Err(err) => match err.kind() {
@@ -308,6 +331,201 @@ fn program(profile: &TerminalProfile) -> Option<Shell> {
     }
 }
 
+/// Runs a command without a pty, so that its stdout and stderr are captured
+/// separately, and kills it once `timeout` elapsed.
+pub fn run_command(
+    profile: &TerminalProfile,
+    timeout: Duration,
+) -> Result<CommandResult> {
+    let Some(program) = &profile.command else {
+        return Err(anyhow!("no command to run"));
+    };
+    let mut command = Command::new(program);
+    command
+        .args(profile.arguments.iter().flatten())
+        .envs(profile.environment.iter().flatten())
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    if let Some(workdir) = Terminal::workdir(profile) {
+        command.current_dir(workdir);
+    }
+
+    let start = Instant::now();
+    let mut child = command.spawn()?;
+    let (stdout, stdout_reader) = read_output(child.stdout.take());
+    let (stderr, stderr_reader) = read_output(child.stderr.take());
+    let (exit_code, timed_out) = loop {
+        if let Some(status) = child.try_wait()? {
+            break (status.code(), false);
+        }
+        if start.elapsed() >= timeout {
+            if let Err(err) = child.kill().and_then(|_| child.wait()) {
+                tracing::error!("{:?}", err);
+            }
+            break (None, true);
+        }
+        thread::sleep(COMMAND_POLL_INTERVAL);
+    };
+    let duration = start.elapsed();
+
+    // Processes left running in the background can keep the pipes open, so
+    // the rest of the output is only waited for a moment.
+    let deadline = Instant::now() + COMMAND_OUTPUT_GRACE;
+    while !(stdout_reader.is_finished() && stderr_reader.is_finished())
+        && Instant::now() < deadline
+    {
+        thread::sleep(COMMAND_POLL_INTERVAL);
+    }
+
+    Ok(command_result(
+        exit_code,
+        &stdout.lock(),
+        &stderr.lock(),
+        duration,
+        timed_out,
+    ))
+}
+
+/// Runs the terminal to completion while capturing its output, and shuts it
+/// down once `timeout` elapsed.
+pub fn run_in_terminal(
+    mut terminal: Terminal,
+    core_rpc: CoreRpcHandler,
+    timeout: Duration,
+) -> CommandResult {
+    let output = terminal.capture_output();
+    let sender = TerminalSender::new(terminal.tx.clone(), terminal.poller.clone());
+    let start = Instant::now();
+    let (tx, rx) = crossbeam_channel::bounded(1);
+    thread::spawn(move || {
+        // Nobody is waiting anymore if the command timed out.
+        let _ = tx.send(terminal.run(core_rpc));
+    });
+    let (exit_code, timed_out) = match rx.recv_timeout(timeout) {
+        Ok(exit_code) => (exit_code, false),
+        Err(_) => {
+            sender.send(Msg::Shutdown);
+            (None, true)
+        }
+    };
+    command_result(
+        exit_code,
+        &output.lock(),
+        &CommandOutput::default(),
+        start.elapsed(),
+        timed_out,
+    )
+}
+
+fn read_output(
+    pipe: Option<impl Read + Send + 'static>,
+) -> (Arc<Mutex<CommandOutput>>, JoinHandle<()>) {
+    let output = Arc::new(Mutex::new(CommandOutput::default()));
+    let reader = {
+        let output = output.clone();
+        thread::spawn(move || {
+            let Some(mut pipe) = pipe else {
+                return;
+            };
+            let mut buf = [0u8; 0x2000];
+            loop {
+                match pipe.read(&mut buf) {
+                    Ok(0) => break,
+                    Ok(n) => output.lock().push(&buf[..n]),
+                    Err(err) if err.kind() == ErrorKind::Interrupted => {}
+                    Err(err) => {
+                        tracing::error!("{:?}", err);
+                        break;
+                    }
+                }
+            }
+        })
+    };
+    (output, reader)
+}
+
+fn command_result(
+    exit_code: Option<i32>,
+    stdout: &CommandOutput,
+    stderr: &CommandOutput,
+    duration: Duration,
+    timed_out: bool,
+) -> CommandResult {
+    let (stdout, stdout_truncated) = stdout.text();
+    let (stderr, stderr_truncated) = stderr.text();
+    CommandResult {
+        exit_code,
+        stdout,
+        stderr,
+        duration_ms: duration.as_millis() as u64,
+        timed_out,
+        stdout_truncated,
+        stderr_truncated,
+    }
+}
+
+/// An output stream of a command, of which only the end is kept.
+#[derive(Default)]
+pub struct CommandOutput {
+    bytes: Vec<u8>,
+    truncated: bool,
+}
+
+impl CommandOutput {
+    fn push(&mut self, bytes: &[u8]) {
+        self.bytes.extend_from_slice(bytes);
+        if self.bytes.len() > 2 * MAX_COMMAND_OUTPUT {
+            self.bytes.drain(..self.bytes.len() - MAX_COMMAND_OUTPUT);
+            self.truncated = true;
+        }
+    }
+
+    /// The output as plain text, and whether its start was dropped.
+    fn text(&self) -> (String, bool) {
+        let start = self.bytes.len().saturating_sub(MAX_COMMAND_OUTPUT);
+        let text = String::from_utf8_lossy(&self.bytes[start..]);
+        (
+            strip_ansi(&text).replace("\r\n", "\n"),
+            self.truncated || start > 0,
+        )
+    }
+}
+
+/// Removes terminal escape sequences (CSI, OSC and two byte escapes).
+pub fn strip_ansi(text: &str) -> String {
+    let mut output = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\u{1b}' {
+            output.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('[') => {
+                for c in chars.by_ref() {
+                    if ('\u{40}'..='\u{7e}').contains(&c) {
+                        break;
+                    }
+                }
+            }
+            Some(']') => {
+                while let Some(c) = chars.next() {
+                    if c == '\u{7}' {
+                        break;
+                    }
+                    if c == '\u{1b}' && chars.peek() == Some(&'\\') {
+                        chars.next();
+                        break;
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    output
+}
+
 struct Writing {
     source: Cow<'static, [u8]>,
     written: usize,
@@ -382,3 +600,51 @@ fn set_locale_environment() {
         std::env::set_var("LC_ALL", locale + ".UTF-8");
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use phidi_rpc::terminal::TerminalProfile;
+
+    use super::{run_command, strip_ansi};
+
+    #[test]
+    fn test_strip_ansi() {
+        assert_eq!(
+            strip_ansi("\u{1b}[1;31merror\u{1b}[0m: failed"),
+            "error: failed"
+        );
+        assert_eq!(
+            strip_ansi("\u{1b}]0;title\u{7}done\u{1b}]2;x\u{1b}\\!"),
+            "done!"
+        );
+        assert_eq!(strip_ansi("plain text"), "plain text");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_run_command() {
+        let profile = |script: &str| TerminalProfile {
+            command: Some("/bin/sh".to_string()),
+            arguments: Some(vec!["-c".to_string(), script.to_string()]),
+            ..Default::default()
+        };
+
+        let result = run_command(
+            &profile("printf '\\033[31mout\\033[0m'; echo err >&2; exit 3"),
+            Duration::from_secs(10),
+        )
+        .unwrap();
+        assert_eq!(result.exit_code, Some(3));
+        assert_eq!(result.stdout, "out");
+        assert_eq!(result.stderr, "err\n");
+        assert!(!result.timed_out);
+        assert!(!result.stdout_truncated);
+
+        let result =
+            run_command(&profile("sleep 5"), Duration::from_millis(100)).unwrap();
+        assert_eq!(result.exit_code, None);
+        assert!(result.timed_out);
+    }
+}
@@ -0,0 +1,59 @@
+use serde::{Deserialize, Serialize};
+
+use crate::counter::Counter;
+
+#[derive(Eq, PartialEq, Hash, Copy, Clone, Debug, Serialize, Deserialize)]
+pub struct AgentSessionId(pub u64);
+
+impl AgentSessionId {
+    pub fn next() -> Self {
+        static AGENT_SESSION_ID_COUNTER: Counter = Counter::new();
+        Self(AGENT_SESSION_ID_COUNTER.next())
+    }
+}
+
+/// The model the agent talks to. They are sent along with every message, so
+/// that changes to the settings apply to the next turn of a conversation.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AgentSettings {
+    /// The base url of an OpenAI compatible api, e.g.
+    /// `https://api.openai.com/v1`.
+    pub endpoint: String,
+    pub model: String,
+    /// Falls back to the `OPENAI_API_KEY` environment variable of the proxy
+    /// when empty.
+    pub api_key: String,
+    /// The maximum number of model requests in a single turn.
+    pub max_steps: usize,
+}
+
+/// What happens in an agent's turn, reported back to the editor as it goes.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[serde(tag = "kind")]
+pub enum AgentEvent {
+    /// Text written by the model.
+    Message {
+        content: String,
+    },
+    ToolCall {
+        id: String,
+        name: String,
+        arguments: String,
+    },
+    ToolResult {
+        id: String,
+        content: String,
+        is_error: bool,
+    },
+    /// The tool call `id` waits for the user to approve it.
+    ApprovalRequest {
+        id: String,
+        description: String,
+    },
+    Error {
+        message: String,
+    },
+    /// The turn is over, and the agent waits for the next message.
+    Done,
+}
@@ -0,0 +1,21 @@
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+/// A snapshot of the files of the workspace that it can be rolled back to.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub id: String,
+    pub label: String,
+    /// When the checkpoint was taken, in seconds since the unix epoch.
+    pub time: i64,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub struct RestoreResult {
+    /// The files that were written or deleted.
+    pub restored: Vec<PathBuf>,
+    /// The files that differ from the checkpoint but were left alone because
+    /// they have unsaved changes in the editor.
+    pub conflicts: Vec<PathBuf>,
+}
@@ -0,0 +1,33 @@
+use std::path::PathBuf;
+
+use lsp_types::Range;
+use serde::{Deserialize, Serialize};
+
+/// A question about the workspace's code graph. Names are matched against
+/// the symbol names extracted by the tree-sitter tags queries.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[serde(tag = "kind")]
+pub enum CodeGraphQuery {
+    /// Definitions whose name contains `name`, exact matches first.
+    Symbols { name: String },
+    /// All references to `name`.
+    References { name: String },
+    /// The definitions that call `name`.
+    Callers { name: String },
+    /// The calls made from within the definitions named `name`.
+    Callees { name: String },
+    /// The imports of a file, or of every file in a directory.
+    Imports { path: PathBuf },
+    /// The imports of modules whose name contains `module`.
+    Importers { module: String },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CodeGraphItem {
+    pub name: String,
+    /// The tag kind, e.g. `function`, `class` or `call`.
+    pub kind: String,
+    pub path: PathBuf,
+    pub range: Range,
+}
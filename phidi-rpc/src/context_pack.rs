@@ -0,0 +1,36 @@
+use std::path::PathBuf;
+
+use lsp_types::{Diagnostic, Range};
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ContextPackFormat {
+    #[default]
+    Markdown,
+    Json,
+}
+
+/// What goes into a context pack, as far as the client knows it. The proxy
+/// adds the definition and the references of the symbol at the start of the
+/// selection, and reads the files.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ContextPackRequest {
+    pub selection: Option<(PathBuf, Range)>,
+    pub open_files: Vec<PathBuf>,
+    pub diagnostics: Vec<(PathBuf, Vec<Diagnostic>)>,
+    /// A glob of the files to leave out, on top of the ignore files.
+    pub files_exclude: String,
+    /// The approximate number of tokens the pack may take, 0 for no limit.
+    pub token_budget: usize,
+    pub format: ContextPackFormat,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ContextPack {
+    pub content: String,
+    /// The approximate number of tokens of the content.
+    pub tokens: usize,
+    /// How many items didn't fit in the token budget.
+    pub omitted: usize,
+}
@@ -10,20 +10,23 @@
 use crossbeam_channel::{Receiver, Sender};
 use lsp_types::{
     CancelParams, CompletionResponse, LogMessageParams, ProgressParams,
-    PublishDiagnosticsParams, ShowMessageParams, SignatureHelp,
+    PublishDiagnosticsParams, ShowMessageParams, SignatureHelp, WorkspaceEdit,
 };
 use parking_lot::Mutex;
 use serde::{Deserialize, Serialize};
 
 use crate::{
     RequestId, RpcError, RpcMessage,
+    agent::{AgentEvent, AgentSessionId},
+    checkpoint::Checkpoint,
     dap_types::{
         self, DapId, RunDebugConfig, Scope, StackFrame, Stopped, ThreadId, Variable,
     },
     file::PathObject,
     plugin::{PluginId, VoltInfo, VoltMetadata},
+    proposal::EditProposal,
     proxy::ProxyStatus,
-    source_control::DiffInfo,
+    source_control::{DiffInfo, RemoteOperation},
     terminal::TermId,
 };
 
@@ -108,6 +111,12 @@ pub enum CoreNotification {
     DiffInfo {
         diff: DiffInfo,
     },
+    /// The remote wants a username and password, so the operation has to be
+    /// started again with what the user types in.
+    GitCredentialsRequired {
+        operation: RemoteOperation,
+        url: String,
+    },
     UpdateTerminal {
         term_id: TermId,
         content: Vec<u8>,
@@ -146,15 +155,46 @@ pub enum CoreNotification {
         path: PathBuf,
         breakpoints: Vec<dap_types::Breakpoint>,
     },
+    EditProposed {
+        proposal: EditProposal,
+    },
+    /// An edit to apply to the documents, which the proxy doesn't wait on.
+    ApplyWorkspaceEdit {
+        edit: WorkspaceEdit,
+    },
+    AgentEvent {
+        session: AgentSessionId,
+        event: AgentEvent,
+    },
+    CheckpointsChanged {
+        checkpoints: Vec<Checkpoint>,
+    },
+    /// The characters after which a language server handling the file
+    /// formats it on type.
+    OnTypeFormattingTriggers {
+        path: PathBuf,
+        characters: Vec<String>,
+    },
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub enum CoreRequest {}
+#[serde(rename_all = "snake_case")]
+#[serde(tag = "method", content = "params")]
+pub enum CoreRequest {
+    /// A language server asked for the edit to be applied, with
+    /// `workspace/applyEdit`, and waits to know whether it was.
+    ApplyWorkspaceEdit { edit: WorkspaceEdit },
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 #[serde(tag = "method", content = "params")]
-pub enum CoreResponse {}
+pub enum CoreResponse {
+    ApplyWorkspaceEditResponse {
+        applied: bool,
+        failure_reason: Option<String>,
+    },
+}
 
 pub type CoreMessage = RpcMessage<CoreRequest, CoreNotification, CoreResponse>;
 
@@ -258,6 +298,13 @@ pub fn diff_info(&self, diff: DiffInfo) {
         self.notification(CoreNotification::DiffInfo { diff });
     }
 
+    pub fn git_credentials_required(&self, operation: RemoteOperation, url: String) {
+        self.notification(CoreNotification::GitCredentialsRequired {
+            operation,
+            url,
+        });
+    }
+
     pub fn open_file_changed(&self, path: PathBuf, content: FileChanged) {
         self.notification(CoreNotification::OpenFileChanged { path, content });
     }
@@ -402,6 +449,42 @@ pub fn dap_breakpoints_resp(
     pub fn home_dir(&self, path: PathBuf) {
         self.notification(CoreNotification::HomeDir { path });
     }
+
+    pub fn edit_proposed(&self, proposal: EditProposal) {
+        self.notification(CoreNotification::EditProposed { proposal });
+    }
+
+    pub fn apply_workspace_edit(&self, edit: WorkspaceEdit) {
+        self.notification(CoreNotification::ApplyWorkspaceEdit { edit });
+    }
+
+    /// Asks the client to apply the edit, and waits for it to be applied or
+    /// refused.
+    pub fn request_workspace_edit(
+        &self,
+        edit: WorkspaceEdit,
+    ) -> Result<CoreResponse, RpcError> {
+        self.request(CoreRequest::ApplyWorkspaceEdit { edit })
+    }
+
+    pub fn agent_event(&self, session: AgentSessionId, event: AgentEvent) {
+        self.notification(CoreNotification::AgentEvent { session, event });
+    }
+
+    pub fn checkpoints_changed(&self, checkpoints: Vec<Checkpoint>) {
+        self.notification(CoreNotification::CheckpointsChanged { checkpoints });
+    }
+
+    pub fn on_type_formatting_triggers(
+        &self,
+        path: PathBuf,
+        characters: Vec<String>,
+    ) {
+        self.notification(CoreNotification::OnTypeFormattingTriggers {
+            path,
+            characters,
+        });
+    }
 }
 
 impl Default for CoreRpcHandler {
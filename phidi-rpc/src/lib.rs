@@ -1,6 +1,10 @@
 #![allow(clippy::manual_clamp)]
 
+pub mod agent;
 pub mod buffer;
+pub mod checkpoint;
+pub mod code_graph;
+pub mod context_pack;
 pub mod core;
 pub mod counter;
 pub mod dap_types;
@@ -8,6 +12,7 @@
 pub mod file_line;
 mod parse;
 pub mod plugin;
+pub mod proposal;
 pub mod proxy;
 pub mod source_control;
 pub mod stdio;
@@ -42,6 +42,39 @@ pub fn id(&self) -> VoltID {
     }
 }
 
+/// A language server declared in a `[lsp.<name>]` table of the settings,
+/// started by the proxy without a plugin wrapping it.
+#[derive(Deserialize, Clone, Debug, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub struct LspServerConfig {
+    /// Without a command the table only sets the priority of a server of a
+    /// plugin with this name.
+    pub command: Option<String>,
+    #[serde(default)]
+    pub args: Vec<String>,
+    #[serde(default)]
+    pub env: HashMap<String, String>,
+    /// The language ids of the documents the server is for.
+    #[serde(default)]
+    pub languages: Vec<String>,
+    /// Globs of the paths of the documents the server is for, on top of the
+    /// languages.
+    #[serde(default)]
+    pub globs: Vec<String>,
+    pub initialization_options: Option<Value>,
+    /// Files such as `Cargo.toml` marking the root of a project. When given,
+    /// the server is started in the closest directory above an opened
+    /// document containing one of them, and not at all without one.
+    #[serde(default)]
+    pub root_markers: Vec<String>,
+    /// Which server answers a request several servers of a document can
+    /// answer: the one with the highest priority for the feature, `hover`,
+    /// `definition`, `formatting`, `references`, `rename`, or `default` for
+    /// the rest. Servers have a priority of 0 otherwise.
+    #[serde(default)]
+    pub priority: HashMap<String, i32>,
+}
+
 #[derive(Deserialize, Clone, Debug, Serialize, PartialEq, Eq)]
 #[serde(rename_all = "kebab-case")]
 pub struct VoltActivation {
@@ -0,0 +1,27 @@
+use lsp_types::WorkspaceEdit;
+use serde::{Deserialize, Serialize};
+
+use crate::counter::Counter;
+
+#[derive(Eq, PartialEq, Hash, Copy, Clone, Debug, Serialize, Deserialize)]
+pub struct ProposalId(pub u64);
+
+impl ProposalId {
+    pub fn next() -> Self {
+        static PROPOSAL_ID_COUNTER: Counter = Counter::new();
+        Self(PROPOSAL_ID_COUNTER.next())
+    }
+}
+
+/// A set of changes proposed by an agent or a plugin. Unlike an applied
+/// `WorkspaceEdit`, the changes are only staged and have to be reviewed
+/// before they land in the documents.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EditProposal {
+    pub id: ProposalId,
+    pub title: String,
+    /// Who proposed the change, e.g. the plugin's display name.
+    pub source: String,
+    pub edit: WorkspaceEdit,
+}
@@ -1,5 +1,6 @@
 use std::{
     collections::HashMap,
+    ops,
     path::PathBuf,
     sync::{
         Arc,
@@ -10,28 +11,38 @@
 use crossbeam_channel::{Receiver, Sender};
 use indexmap::IndexMap;
 use lsp_types::{
-    CallHierarchyIncomingCall, CallHierarchyItem, CodeAction, CodeActionResponse,
-    CodeLens, CompletionItem, Diagnostic, DocumentSymbolResponse, FoldingRange,
-    GotoDefinitionResponse, Hover, InlayHint, InlineCompletionResponse,
-    InlineCompletionTriggerKind, Location, Position, PrepareRenameResponse,
-    SelectionRange, SymbolInformation, TextDocumentItem, TextEdit, WorkspaceEdit,
+    CallHierarchyIncomingCall, CallHierarchyItem, CallHierarchyOutgoingCall,
+    CodeAction, CodeActionOrCommand, CodeLens, Color, ColorInformation,
+    ColorPresentation, CompletionItem, Diagnostic, DocumentHighlight, DocumentLink,
+    DocumentSymbolResponse, FoldingRange, GotoDefinitionResponse, Hover, InlayHint,
+    InlineCompletionResponse, InlineCompletionTriggerKind, LinkedEditingRanges,
+    Location, Position, PrepareRenameResponse, Range, SelectionRange,
+    SymbolInformation, TextDocumentItem, TextEdit, TypeHierarchyItem, WorkspaceEdit,
     request::{GotoImplementationResponse, GotoTypeDefinitionResponse},
 };
 use parking_lot::Mutex;
 use phidi_xi_rope::RopeDelta;
 use serde::{Deserialize, Serialize};
 
-use super::plugin::VoltID;
+use super::plugin::{LspServerConfig, VoltID};
 use crate::{
     RequestId, RpcError, RpcMessage,
+    agent::{AgentSessionId, AgentSettings},
     buffer::BufferId,
+    checkpoint::{Checkpoint, RestoreResult},
+    code_graph::{CodeGraphItem, CodeGraphQuery},
+    context_pack::{ContextPack, ContextPackRequest},
     dap_types::{self, DapId, RunDebugConfig, SourceBreakpoint, ThreadId},
     file::{FileNodeItem, PathObject},
     file_line::FileLine,
     plugin::{PluginId, VoltInfo, VoltMetadata},
-    source_control::FileDiff,
+    proposal::ProposalId,
+    source_control::{
+        BlameHunk, BranchInfo, FileDiff, GitCredentials, GitLogFilter, LogEntry,
+        RemoteOperation, StashEntry,
+    },
     style::SemanticStyles,
-    terminal::{TermId, TerminalProfile},
+    terminal::{CommandResult, TermId, TerminalProfile},
 };
 
 #[allow(clippy::large_enum_variant)]
@@ -54,6 +65,31 @@ pub struct SearchMatch {
     pub start: usize,
     pub end: usize,
     pub line_content: String,
+    /// What the match is replaced with, when searching with a replacement.
+    #[serde(default)]
+    pub replacement: Option<String>,
+    /// The lines before and after the match, as many as asked for.
+    #[serde(default)]
+    pub context_before: Vec<String>,
+    #[serde(default)]
+    pub context_after: Vec<String>,
+}
+
+/// Narrows down a global search.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SearchOptions {
+    /// Globs of the files to search, all of them if empty.
+    pub include: Vec<String>,
+    /// Globs of the files to leave out.
+    pub exclude: Vec<String>,
+    /// Names of the file types to search, like `rust` or `py`, as known to
+    /// ripgrep.
+    pub file_types: Vec<String>,
+    /// The number of matches to stop at.
+    pub max_results: Option<usize>,
+    /// The number of lines to send around each match.
+    pub context_lines: usize,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -67,11 +103,36 @@ pub enum ProxyRequest {
     BufferHead {
         path: PathBuf,
     },
+    /// The text of the open buffer of the file, which is opened first if no
+    /// buffer has it yet, so that unsaved edits aren't lost.
+    BufferContent {
+        path: PathBuf,
+    },
     GlobalSearch {
         pattern: String,
         case_sensitive: bool,
         whole_word: bool,
         is_regex: bool,
+        /// Preview what the matches are replaced with.
+        replacement: Option<String>,
+        #[serde(default)]
+        options: SearchOptions,
+    },
+    /// Replace the matches of a global search listed in `files`, given by
+    /// their line and start column, so that matches past the ones the user
+    /// has seen are left alone. Open files are left to the client to edit,
+    /// closed files are written.
+    GlobalReplace {
+        pattern: String,
+        case_sensitive: bool,
+        whole_word: bool,
+        is_regex: bool,
+        replacement: String,
+        files: IndexMap<PathBuf, Vec<(usize, usize)>>,
+        /// The revisions of the open files when they were searched, as given
+        /// by the search. Open files changed since are left alone.
+        #[serde(default)]
+        revisions: HashMap<PathBuf, u64>,
     },
     CompletionResolve {
         plugin_id: PluginId,
@@ -97,6 +158,28 @@ pub enum ProxyRequest {
     GitGetRemoteFileUrl {
         file: PathBuf,
     },
+    /// Blame the file as it is in the editor, unsaved changes included.
+    GitBlame {
+        path: PathBuf,
+    },
+    /// The content of the file in a revision, empty if it isn't in it.
+    GitFileAtRevision {
+        path: PathBuf,
+        revision: String,
+    },
+    /// A page of the commits matching the filter, newest first, starting
+    /// after the last commit of the previous page.
+    GitLog {
+        filter: GitLogFilter,
+        after: Option<String>,
+        limit: usize,
+    },
+    /// The files a commit changed, compared to its first parent.
+    GitCommitChanges {
+        commit: String,
+    },
+    GitBranches {},
+    GitStashes {},
     GetReferences {
         path: PathBuf,
         position: Position,
@@ -118,6 +201,22 @@ pub enum ProxyRequest {
         path: PathBuf,
         call_hierarchy_item: CallHierarchyItem,
     },
+    CallHierarchyOutgoing {
+        path: PathBuf,
+        call_hierarchy_item: CallHierarchyItem,
+    },
+    ShowTypeHierarchy {
+        path: PathBuf,
+        position: Position,
+    },
+    TypeHierarchySupertypes {
+        path: PathBuf,
+        type_hierarchy_item: TypeHierarchyItem,
+    },
+    TypeHierarchySubtypes {
+        path: PathBuf,
+        type_hierarchy_item: TypeHierarchyItem,
+    },
     GetTypeDefinition {
         request_id: usize,
         path: PathBuf,
@@ -126,6 +225,29 @@ pub enum ProxyRequest {
     GetInlayHints {
         path: PathBuf,
     },
+    GetDocumentHighlights {
+        path: PathBuf,
+        position: Position,
+    },
+    GetLinkedEditingRanges {
+        path: PathBuf,
+        position: Position,
+    },
+    GetDocumentLinks {
+        path: PathBuf,
+    },
+    ResolveDocumentLink {
+        path: PathBuf,
+        link: DocumentLink,
+    },
+    GetDocumentColors {
+        path: PathBuf,
+    },
+    GetColorPresentations {
+        path: PathBuf,
+        color: Color,
+        range: Range,
+    },
     GetInlineCompletions {
         path: PathBuf,
         position: Position,
@@ -133,6 +255,12 @@ pub enum ProxyRequest {
     },
     GetSemanticTokens {
         path: PathBuf,
+        /// The id of the styles the client has, if it can apply a delta.
+        result_id: Option<String>,
+    },
+    GetSemanticTokensRange {
+        path: PathBuf,
+        range: Range,
     },
     LspFoldingRange {
         path: PathBuf,
@@ -168,6 +296,15 @@ pub enum ProxyRequest {
     GetDocumentFormatting {
         path: PathBuf,
     },
+    GetDocumentRangeFormatting {
+        path: PathBuf,
+        ranges: Vec<Range>,
+    },
+    OnTypeFormatting {
+        path: PathBuf,
+        position: Position,
+        ch: String,
+    },
     GetOpenFilesContent {},
     GetFiles {
         path: String,
@@ -220,6 +357,49 @@ pub enum ProxyRequest {
     ReferencesResolve {
         items: Vec<Location>,
     },
+    /// Apply a workspace edit on the proxy side, for clients that don't hold
+    /// documents of their own. Open buffers are updated and saved, other files
+    /// are edited on disk.
+    ApplyWorkspaceEdit {
+        edit: WorkspaceEdit,
+    },
+    /// Stage a workspace edit as a proposal to be reviewed in the editor,
+    /// instead of applying it right away.
+    ProposeEdit {
+        title: String,
+        source: String,
+        edit: WorkspaceEdit,
+    },
+    /// Query the tree-sitter based code graph of the workspace.
+    CodeGraph {
+        query: CodeGraphQuery,
+    },
+    /// Run a command to completion and capture its output. With a `term_id`
+    /// the command runs in a terminal whose output is also streamed to the
+    /// client like any other terminal, otherwise it runs hidden.
+    RunCommand {
+        term_id: Option<TermId>,
+        profile: TerminalProfile,
+        timeout_secs: Option<u64>,
+    },
+    /// Snapshot the files of the workspace.
+    CreateCheckpoint {
+        label: String,
+    },
+    ListCheckpoints {},
+    /// Roll the files of the workspace back to a checkpoint. Files with
+    /// unsaved changes in the editor are left alone and reported.
+    RestoreCheckpoint {
+        id: String,
+    },
+    DeleteCheckpoint {
+        id: String,
+    },
+    /// Bundle code, definitions and diagnostics into a document to paste into
+    /// a prompt.
+    ContextPack {
+        request: ContextPackRequest,
+    },
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -232,6 +412,9 @@ pub enum ProxyNotification {
         /// Paths to extra plugins that should be loaded
         extra_plugin_paths: Vec<PathBuf>,
         plugin_configurations: HashMap<String, HashMap<String, serde_json::Value>>,
+        lsp_servers: HashMap<String, LspServerConfig>,
+        /// Whether to serve the MCP server of the workspace on its socket.
+        mcp_server: bool,
         window_id: usize,
         tab_id: usize,
     },
@@ -261,6 +444,9 @@ pub enum ProxyNotification {
     UpdatePluginConfigs {
         configs: HashMap<String, HashMap<String, serde_json::Value>>,
     },
+    UpdateLspServers {
+        servers: HashMap<String, LspServerConfig>,
+    },
     NewTerminal {
         term_id: TermId,
         profile: TerminalProfile,
@@ -292,6 +478,65 @@ pub enum ProxyNotification {
     },
     GitDiscardWorkspaceChanges {},
     GitInit {},
+    GitStageFiles {
+        files: Vec<PathBuf>,
+    },
+    GitUnstageFiles {
+        files: Vec<PathBuf>,
+    },
+    /// Stage the changes on the lines of the file, as it is in the editor.
+    GitStageLines {
+        path: PathBuf,
+        lines: Vec<ops::Range<usize>>,
+    },
+    /// Unstage the staged changes on the lines of the file, as it is in the
+    /// editor.
+    GitUnstageLines {
+        path: PathBuf,
+        lines: Vec<ops::Range<usize>>,
+    },
+    /// Create a branch at HEAD.
+    GitCreateBranch {
+        name: String,
+        checkout: bool,
+    },
+    GitRenameBranch {
+        from: String,
+        to: String,
+    },
+    /// Delete a local branch, which has to be merged into HEAD unless forced.
+    GitDeleteBranch {
+        name: String,
+        force: bool,
+    },
+    /// Merge a branch, or any other revision, into HEAD.
+    GitMerge {
+        reference: String,
+    },
+    /// Tag HEAD, with an annotated tag when there is a message.
+    GitCreateTag {
+        name: String,
+        message: Option<String>,
+    },
+    GitStashPush {
+        message: Option<String>,
+        include_untracked: bool,
+    },
+    GitStashApply {
+        index: usize,
+    },
+    GitStashPop {
+        index: usize,
+    },
+    GitStashDrop {
+        index: usize,
+    },
+    /// Fetch from, pull from or push to the remote of the current branch.
+    /// Progress is reported with `WorkDoneProgress`.
+    GitRemoteOperation {
+        operation: RemoteOperation,
+        credentials: Option<GitCredentials>,
+    },
     LspCancel {
         id: i32,
     },
@@ -351,6 +596,26 @@ pub enum ProxyNotification {
         path: PathBuf,
         breakpoints: Vec<SourceBreakpoint>,
     },
+    /// Send a message to the agent, starting a new turn of the conversation.
+    AgentSend {
+        session: AgentSessionId,
+        settings: AgentSettings,
+        message: String,
+    },
+    /// Answer an approval request of the agent.
+    AgentApprove {
+        session: AgentSessionId,
+        id: String,
+        approved: bool,
+    },
+    /// Stop the current turn of the agent.
+    AgentCancel {
+        session: AgentSessionId,
+    },
+    /// Forget the conversation.
+    AgentClose {
+        session: AgentSessionId,
+    },
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -360,6 +625,25 @@ pub enum ProxyResponse {
     GitGetRemoteFileUrl {
         file_url: String,
     },
+    GitBlame {
+        hunks: Vec<BlameHunk>,
+    },
+    GitFileAtRevision {
+        content: String,
+    },
+    GitLog {
+        commits: Vec<LogEntry>,
+        has_more: bool,
+    },
+    GitCommitChanges {
+        diffs: Vec<FileDiff>,
+    },
+    GitBranches {
+        branches: Vec<BranchInfo>,
+    },
+    GitStashes {
+        stashes: Vec<StashEntry>,
+    },
     NewBufferResponse {
         content: String,
         read_only: bool,
@@ -368,6 +652,9 @@ pub enum ProxyResponse {
         version: String,
         content: String,
     },
+    BufferContentResponse {
+        content: String,
+    },
     ReadDirResponse {
         items: Vec<FileNodeItem>,
     },
@@ -391,6 +678,14 @@ pub enum ProxyResponse {
     CallHierarchyIncomingResponse {
         items: Option<Vec<CallHierarchyIncomingCall>>,
     },
+    CallHierarchyOutgoingResponse {
+        items: Option<Vec<CallHierarchyOutgoingCall>>,
+    },
+    /// The items of `ShowTypeHierarchy`, or the supertypes or subtypes of an
+    /// item.
+    TypeHierarchyResponse {
+        items: Option<Vec<TypeHierarchyItem>>,
+    },
     GetTypeDefinition {
         request_id: usize,
         definition: GotoTypeDefinitionResponse,
@@ -399,8 +694,7 @@ pub enum ProxyResponse {
         references: Vec<Location>,
     },
     GetCodeActionsResponse {
-        plugin_id: PluginId,
-        resp: CodeActionResponse,
+        actions: Vec<SourcedCodeAction>,
     },
     LspFoldingRangeResponse {
         plugin_id: PluginId,
@@ -436,6 +730,24 @@ pub enum ProxyResponse {
     GetInlayHints {
         hints: Vec<InlayHint>,
     },
+    GetDocumentHighlights {
+        highlights: Vec<DocumentHighlight>,
+    },
+    GetLinkedEditingRanges {
+        ranges: Option<LinkedEditingRanges>,
+    },
+    GetDocumentLinks {
+        links: Vec<DocumentLink>,
+    },
+    ResolveDocumentLink {
+        link: DocumentLink,
+    },
+    GetDocumentColors {
+        colors: Vec<ColorInformation>,
+    },
+    GetColorPresentations {
+        presentations: Vec<ColorPresentation>,
+    },
     GetInlineCompletions {
         completions: InlineCompletionResponse,
     },
@@ -453,6 +765,13 @@ pub enum ProxyResponse {
     },
     GlobalSearchResponse {
         matches: IndexMap<PathBuf, Vec<SearchMatch>>,
+        /// Whether the search stopped at the maximum number of results.
+        #[serde(default)]
+        limit_hit: bool,
+        /// The revisions of the open files with matches, whose unsaved text
+        /// was searched rather than the file on disk.
+        #[serde(default)]
+        revisions: HashMap<PathBuf, u64>,
     },
     DapVariableResponse {
         varialbes: Vec<dap_types::Variable>,
@@ -468,10 +787,50 @@ pub enum ProxyResponse {
     ReferencesResolveResponse {
         items: Vec<FileLine>,
     },
+    ProposeEditResponse {
+        id: ProposalId,
+    },
+    CodeGraphResponse {
+        items: Vec<CodeGraphItem>,
+    },
+    RunCommandResponse {
+        result: CommandResult,
+    },
+    CreateCheckpointResponse {
+        checkpoint: Checkpoint,
+    },
+    ListCheckpointsResponse {
+        checkpoints: Vec<Checkpoint>,
+    },
+    RestoreCheckpointResponse {
+        result: RestoreResult,
+    },
+    ContextPackResponse {
+        pack: ContextPack,
+    },
+    GlobalReplaceResponse {
+        /// The edits of the files that are open.
+        edits: IndexMap<PathBuf, Vec<TextEdit>>,
+        /// The files that aren't open and have been written.
+        written: Vec<PathBuf>,
+        /// The open files that changed since they were searched, and need to
+        /// be searched again.
+        #[serde(default)]
+        stale: Vec<PathBuf>,
+    },
 }
 
 pub type ProxyMessage = RpcMessage<ProxyRequest, ProxyNotification, ProxyResponse>;
 
+/// A code action with the server it is from, which resolves and runs it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SourcedCodeAction {
+    pub plugin_id: PluginId,
+    /// The name of the server, given when the actions are from more than one.
+    pub source: Option<String>,
+    pub action: CodeActionOrCommand,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ReadDirResponse {
     pub items: HashMap<PathBuf, FileNodeItem>,
@@ -597,6 +956,40 @@ pub fn lsp_cancel(&self, id: i32) {
         self.notification(ProxyNotification::LspCancel { id });
     }
 
+    pub fn agent_send(
+        &self,
+        session: AgentSessionId,
+        settings: AgentSettings,
+        message: String,
+    ) {
+        self.notification(ProxyNotification::AgentSend {
+            session,
+            settings,
+            message,
+        });
+    }
+
+    pub fn agent_approve(
+        &self,
+        session: AgentSessionId,
+        id: String,
+        approved: bool,
+    ) {
+        self.notification(ProxyNotification::AgentApprove {
+            session,
+            id,
+            approved,
+        });
+    }
+
+    pub fn agent_cancel(&self, session: AgentSessionId) {
+        self.notification(ProxyNotification::AgentCancel { session });
+    }
+
+    pub fn agent_close(&self, session: AgentSessionId) {
+        self.notification(ProxyNotification::AgentClose { session });
+    }
+
     pub fn git_init(&self) {
         self.notification(ProxyNotification::GitInit {});
     }
@@ -642,6 +1035,8 @@ pub fn initialize(
         disabled_volts: Vec<VoltID>,
         extra_plugin_paths: Vec<PathBuf>,
         plugin_configurations: HashMap<String, HashMap<String, serde_json::Value>>,
+        lsp_servers: HashMap<String, LspServerConfig>,
+        mcp_server: bool,
         window_id: usize,
         tab_id: usize,
     ) {
@@ -650,6 +1045,8 @@ pub fn initialize(
             disabled_volts,
             extra_plugin_paths,
             plugin_configurations,
+            lsp_servers,
+            mcp_server,
             window_id,
             tab_id,
         });
@@ -716,6 +1113,14 @@ pub fn get_buffer_head(&self, path: PathBuf, f: impl ProxyCallback + 'static) {
         self.request_async(ProxyRequest::BufferHead { path }, f);
     }
 
+    pub fn get_buffer_content(
+        &self,
+        path: PathBuf,
+        f: impl ProxyCallback + 'static,
+    ) {
+        self.request_async(ProxyRequest::BufferContent { path }, f);
+    }
+
     pub fn create_file(&self, path: PathBuf, f: impl ProxyCallback + 'static) {
         self.request_async(ProxyRequest::CreateFile { path }, f);
     }
@@ -781,12 +1186,15 @@ pub fn save_buffer_as(
         );
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn global_search(
         &self,
         pattern: String,
         case_sensitive: bool,
         whole_word: bool,
         is_regex: bool,
+        replacement: Option<String>,
+        options: SearchOptions,
         f: impl ProxyCallback + 'static,
     ) {
         self.request_async(
@@ -795,6 +1203,34 @@ pub fn global_search(
                 case_sensitive,
                 whole_word,
                 is_regex,
+                replacement,
+                options,
+            },
+            f,
+        );
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn global_replace(
+        &self,
+        pattern: String,
+        case_sensitive: bool,
+        whole_word: bool,
+        is_regex: bool,
+        replacement: String,
+        files: IndexMap<PathBuf, Vec<(usize, usize)>>,
+        revisions: HashMap<PathBuf, u64>,
+        f: impl ProxyCallback + 'static,
+    ) {
+        self.request_async(
+            ProxyRequest::GlobalReplace {
+                pattern,
+                case_sensitive,
+                whole_word,
+                is_regex,
+                replacement,
+                files,
+                revisions,
             },
             f,
         );
@@ -922,6 +1358,60 @@ pub fn call_hierarchy_incoming(
         );
     }
 
+    pub fn call_hierarchy_outgoing(
+        &self,
+        path: PathBuf,
+        call_hierarchy_item: CallHierarchyItem,
+        f: impl ProxyCallback + 'static,
+    ) {
+        self.request_async(
+            ProxyRequest::CallHierarchyOutgoing {
+                path,
+                call_hierarchy_item,
+            },
+            f,
+        );
+    }
+
+    pub fn show_type_hierarchy(
+        &self,
+        path: PathBuf,
+        position: Position,
+        f: impl ProxyCallback + 'static,
+    ) {
+        self.request_async(ProxyRequest::ShowTypeHierarchy { path, position }, f);
+    }
+
+    pub fn type_hierarchy_supertypes(
+        &self,
+        path: PathBuf,
+        type_hierarchy_item: TypeHierarchyItem,
+        f: impl ProxyCallback + 'static,
+    ) {
+        self.request_async(
+            ProxyRequest::TypeHierarchySupertypes {
+                path,
+                type_hierarchy_item,
+            },
+            f,
+        );
+    }
+
+    pub fn type_hierarchy_subtypes(
+        &self,
+        path: PathBuf,
+        type_hierarchy_item: TypeHierarchyItem,
+        f: impl ProxyCallback + 'static,
+    ) {
+        self.request_async(
+            ProxyRequest::TypeHierarchySubtypes {
+                path,
+                type_hierarchy_item,
+            },
+            f,
+        );
+    }
+
     pub fn get_type_definition(
         &self,
         request_id: usize,
@@ -1011,12 +1501,44 @@ pub fn get_document_formatting(
         self.request_async(ProxyRequest::GetDocumentFormatting { path }, f);
     }
 
+    pub fn get_document_range_formatting(
+        &self,
+        path: PathBuf,
+        ranges: Vec<Range>,
+        f: impl ProxyCallback + 'static,
+    ) {
+        self.request_async(
+            ProxyRequest::GetDocumentRangeFormatting { path, ranges },
+            f,
+        );
+    }
+
+    pub fn on_type_formatting(
+        &self,
+        path: PathBuf,
+        position: Position,
+        ch: String,
+        f: impl ProxyCallback + 'static,
+    ) {
+        self.request_async(ProxyRequest::OnTypeFormatting { path, position, ch }, f);
+    }
+
     pub fn get_semantic_tokens(
         &self,
         path: PathBuf,
+        result_id: Option<String>,
+        f: impl ProxyCallback + 'static,
+    ) {
+        self.request_async(ProxyRequest::GetSemanticTokens { path, result_id }, f);
+    }
+
+    pub fn get_semantic_tokens_range(
+        &self,
+        path: PathBuf,
+        range: Range,
         f: impl ProxyCallback + 'static,
     ) {
-        self.request_async(ProxyRequest::GetSemanticTokens { path }, f);
+        self.request_async(ProxyRequest::GetSemanticTokensRange { path, range }, f);
     }
 
     pub fn get_document_symbols(
@@ -1044,6 +1566,80 @@ pub fn prepare_rename(
         self.request_async(ProxyRequest::PrepareRename { path, position }, f);
     }
 
+    pub fn apply_workspace_edit(
+        &self,
+        edit: WorkspaceEdit,
+        f: impl ProxyCallback + 'static,
+    ) {
+        self.request_async(ProxyRequest::ApplyWorkspaceEdit { edit }, f);
+    }
+
+    pub fn propose_edit(
+        &self,
+        title: String,
+        source: String,
+        edit: WorkspaceEdit,
+        f: impl ProxyCallback + 'static,
+    ) {
+        self.request_async(
+            ProxyRequest::ProposeEdit {
+                title,
+                source,
+                edit,
+            },
+            f,
+        );
+    }
+
+    pub fn code_graph(
+        &self,
+        query: CodeGraphQuery,
+        f: impl ProxyCallback + 'static,
+    ) {
+        self.request_async(ProxyRequest::CodeGraph { query }, f);
+    }
+
+    pub fn run_command(
+        &self,
+        term_id: Option<TermId>,
+        profile: TerminalProfile,
+        timeout_secs: Option<u64>,
+        f: impl ProxyCallback + 'static,
+    ) {
+        self.request_async(
+            ProxyRequest::RunCommand {
+                term_id,
+                profile,
+                timeout_secs,
+            },
+            f,
+        );
+    }
+
+    pub fn create_checkpoint(&self, label: String, f: impl ProxyCallback + 'static) {
+        self.request_async(ProxyRequest::CreateCheckpoint { label }, f);
+    }
+
+    pub fn list_checkpoints(&self, f: impl ProxyCallback + 'static) {
+        self.request_async(ProxyRequest::ListCheckpoints {}, f);
+    }
+
+    pub fn restore_checkpoint(&self, id: String, f: impl ProxyCallback + 'static) {
+        self.request_async(ProxyRequest::RestoreCheckpoint { id }, f);
+    }
+
+    pub fn delete_checkpoint(&self, id: String, f: impl ProxyCallback + 'static) {
+        self.request_async(ProxyRequest::DeleteCheckpoint { id }, f);
+    }
+
+    pub fn context_pack(
+        &self,
+        request: ContextPackRequest,
+        f: impl ProxyCallback + 'static,
+    ) {
+        self.request_async(ProxyRequest::ContextPack { request }, f);
+    }
+
     pub fn git_get_remote_file_url(
         &self,
         file: PathBuf,
@@ -1052,6 +1648,52 @@ pub fn git_get_remote_file_url(
         self.request_async(ProxyRequest::GitGetRemoteFileUrl { file }, f);
     }
 
+    pub fn git_blame(&self, path: PathBuf, f: impl ProxyCallback + 'static) {
+        self.request_async(ProxyRequest::GitBlame { path }, f);
+    }
+
+    pub fn git_file_at_revision(
+        &self,
+        path: PathBuf,
+        revision: String,
+        f: impl ProxyCallback + 'static,
+    ) {
+        self.request_async(ProxyRequest::GitFileAtRevision { path, revision }, f);
+    }
+
+    pub fn git_log(
+        &self,
+        filter: GitLogFilter,
+        after: Option<String>,
+        limit: usize,
+        f: impl ProxyCallback + 'static,
+    ) {
+        self.request_async(
+            ProxyRequest::GitLog {
+                filter,
+                after,
+                limit,
+            },
+            f,
+        );
+    }
+
+    pub fn git_commit_changes(
+        &self,
+        commit: String,
+        f: impl ProxyCallback + 'static,
+    ) {
+        self.request_async(ProxyRequest::GitCommitChanges { commit }, f);
+    }
+
+    pub fn git_branches(&self, f: impl ProxyCallback + 'static) {
+        self.request_async(ProxyRequest::GitBranches {}, f);
+    }
+
+    pub fn git_stashes(&self, f: impl ProxyCallback + 'static) {
+        self.request_async(ProxyRequest::GitStashes {}, f);
+    }
+
     pub fn rename(
         &self,
         path: PathBuf,
@@ -1073,6 +1715,68 @@ pub fn get_inlay_hints(&self, path: PathBuf, f: impl ProxyCallback + 'static) {
         self.request_async(ProxyRequest::GetInlayHints { path }, f);
     }
 
+    pub fn get_document_highlights(
+        &self,
+        path: PathBuf,
+        position: Position,
+        f: impl ProxyCallback + 'static,
+    ) {
+        self.request_async(
+            ProxyRequest::GetDocumentHighlights { path, position },
+            f,
+        );
+    }
+
+    pub fn get_linked_editing_ranges(
+        &self,
+        path: PathBuf,
+        position: Position,
+        f: impl ProxyCallback + 'static,
+    ) {
+        self.request_async(
+            ProxyRequest::GetLinkedEditingRanges { path, position },
+            f,
+        );
+    }
+
+    pub fn get_document_links(
+        &self,
+        path: PathBuf,
+        f: impl ProxyCallback + 'static,
+    ) {
+        self.request_async(ProxyRequest::GetDocumentLinks { path }, f);
+    }
+
+    pub fn resolve_document_link(
+        &self,
+        path: PathBuf,
+        link: DocumentLink,
+        f: impl ProxyCallback + 'static,
+    ) {
+        self.request_async(ProxyRequest::ResolveDocumentLink { path, link }, f);
+    }
+
+    pub fn get_document_colors(
+        &self,
+        path: PathBuf,
+        f: impl ProxyCallback + 'static,
+    ) {
+        self.request_async(ProxyRequest::GetDocumentColors { path }, f);
+    }
+
+    pub fn get_color_presentations(
+        &self,
+        path: PathBuf,
+        color: Color,
+        range: Range,
+        f: impl ProxyCallback + 'static,
+    ) {
+        self.request_async(
+            ProxyRequest::GetColorPresentations { path, color, range },
+            f,
+        );
+    }
+
     pub fn get_inline_completions(
         &self,
         path: PathBuf,
@@ -1101,6 +1805,10 @@ pub fn update_plugin_configs(
         self.notification(ProxyNotification::UpdatePluginConfigs { configs });
     }
 
+    pub fn update_lsp_servers(&self, servers: HashMap<String, LspServerConfig>) {
+        self.notification(ProxyNotification::UpdateLspServers { servers });
+    }
+
     pub fn git_discard_files_changes(&self, files: Vec<PathBuf>) {
         self.notification(ProxyNotification::GitDiscardFilesChanges { files });
     }
@@ -1109,6 +1817,72 @@ pub fn git_discard_workspace_changes(&self) {
         self.notification(ProxyNotification::GitDiscardWorkspaceChanges {});
     }
 
+    pub fn git_stage_files(&self, files: Vec<PathBuf>) {
+        self.notification(ProxyNotification::GitStageFiles { files });
+    }
+
+    pub fn git_unstage_files(&self, files: Vec<PathBuf>) {
+        self.notification(ProxyNotification::GitUnstageFiles { files });
+    }
+
+    pub fn git_stage_lines(&self, path: PathBuf, lines: Vec<ops::Range<usize>>) {
+        self.notification(ProxyNotification::GitStageLines { path, lines });
+    }
+
+    pub fn git_unstage_lines(&self, path: PathBuf, lines: Vec<ops::Range<usize>>) {
+        self.notification(ProxyNotification::GitUnstageLines { path, lines });
+    }
+
+    pub fn git_create_branch(&self, name: String, checkout: bool) {
+        self.notification(ProxyNotification::GitCreateBranch { name, checkout });
+    }
+
+    pub fn git_rename_branch(&self, from: String, to: String) {
+        self.notification(ProxyNotification::GitRenameBranch { from, to });
+    }
+
+    pub fn git_delete_branch(&self, name: String, force: bool) {
+        self.notification(ProxyNotification::GitDeleteBranch { name, force });
+    }
+
+    pub fn git_merge(&self, reference: String) {
+        self.notification(ProxyNotification::GitMerge { reference });
+    }
+
+    pub fn git_create_tag(&self, name: String, message: Option<String>) {
+        self.notification(ProxyNotification::GitCreateTag { name, message });
+    }
+
+    pub fn git_stash_push(&self, message: Option<String>, include_untracked: bool) {
+        self.notification(ProxyNotification::GitStashPush {
+            message,
+            include_untracked,
+        });
+    }
+
+    pub fn git_stash_apply(&self, index: usize) {
+        self.notification(ProxyNotification::GitStashApply { index });
+    }
+
+    pub fn git_stash_pop(&self, index: usize) {
+        self.notification(ProxyNotification::GitStashPop { index });
+    }
+
+    pub fn git_stash_drop(&self, index: usize) {
+        self.notification(ProxyNotification::GitStashDrop { index });
+    }
+
+    pub fn git_remote_operation(
+        &self,
+        operation: RemoteOperation,
+        credentials: Option<GitCredentials>,
+    ) {
+        self.notification(ProxyNotification::GitRemoteOperation {
+            operation,
+            credentials,
+        });
+    }
+
     pub fn get_selection_range(
         &self,
         path: PathBuf,
@@ -1,4 +1,4 @@
-use std::path::PathBuf;
+use std::{ops::Range, path::PathBuf};
 
 use serde::{Deserialize, Serialize};
 
@@ -7,7 +7,10 @@ pub struct DiffInfo {
     pub head: String,
     pub branches: Vec<String>,
     pub tags: Vec<String>,
-    pub diffs: Vec<FileDiff>,
+    /// The changes in the index, which go into the next commit.
+    pub staged: Vec<FileDiff>,
+    /// The changes in the work tree which aren't in the index.
+    pub unstaged: Vec<FileDiff>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
@@ -45,3 +48,108 @@ pub enum FileDiffKind {
     Deleted,
     Renamed,
 }
+
+/// The commit which last changed some lines of a file.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub struct BlameHunk {
+    /// The lines, as they are in the editor.
+    pub lines: Range<usize>,
+    /// `None` for changes which aren't committed yet.
+    pub commit: Option<CommitInfo>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub struct CommitInfo {
+    pub id: String,
+    pub author: String,
+    pub email: String,
+    /// Seconds since the Unix epoch.
+    pub time: i64,
+    /// The first line of the message.
+    pub summary: String,
+}
+
+impl CommitInfo {
+    /// The abbreviated id, the way git shows it.
+    pub fn short_id(&self) -> &str {
+        &self.id[..self.id.len().min(7)]
+    }
+}
+
+/// Which commits the log shows. The filters which are set all have to match.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Default)]
+pub struct GitLogFilter {
+    /// The branch, or any other revision, to start from instead of HEAD.
+    pub branch: Option<String>,
+    /// Only the commits which changed this file, or a file in this directory.
+    pub path: Option<PathBuf>,
+    /// Matched against the name and the email of the author, ignoring case.
+    pub author: Option<String>,
+    /// Matched against the whole message, ignoring case.
+    pub message: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub struct LogEntry {
+    pub commit: CommitInfo,
+    /// The ids of the parents, the first parent first.
+    pub parents: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub struct BranchInfo {
+    pub name: String,
+    /// Whether it is the branch which is checked out.
+    pub is_head: bool,
+    /// The branch it tracks, like `origin/main`.
+    pub upstream: Option<String>,
+    /// How many commits it has which its upstream doesn't.
+    pub ahead: usize,
+    /// How many commits its upstream has which it doesn't.
+    pub behind: usize,
+    /// Whether all of its commits are in HEAD, so that deleting it loses none.
+    pub merged: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub struct StashEntry {
+    /// The position in the stash list, 0 for the latest.
+    pub index: usize,
+    pub message: String,
+    pub id: String,
+}
+
+/// How pulling brings the commits of the upstream branch into the current one.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub enum PullMode {
+    /// Only fast-forward, refusing to pull when the branches have diverged.
+    FastForward,
+    /// Rebase the commits of the current branch onto its upstream.
+    Rebase,
+}
+
+/// An operation on the remote of the current branch.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub enum RemoteOperation {
+    Fetch,
+    Pull { mode: PullMode },
+    Push,
+}
+
+impl RemoteOperation {
+    pub fn title(&self) -> &'static str {
+        match self {
+            RemoteOperation::Fetch => "Fetch",
+            RemoteOperation::Pull { .. } => "Pull",
+            RemoteOperation::Push => "Push",
+        }
+    }
+}
+
+/// A username and password to authenticate to a remote with, which the user
+/// typed in when neither the SSH agent nor a credential helper had any.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct GitCredentials {
+    pub username: String,
+    pub password: String,
+}
@@ -1,10 +1,10 @@
-use std::{collections::HashMap, path::PathBuf, sync::Arc};
+use std::{collections::HashMap, ops::Range, path::PathBuf, sync::Arc};
 
 use serde::{Deserialize, Serialize};
 
 pub type LineStyles = HashMap<usize, Arc<Vec<LineStyle>>>;
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
 pub struct LineStyle {
     pub start: usize,
     pub end: usize,
@@ -22,4 +22,9 @@ pub struct SemanticStyles {
     pub path: PathBuf,
     pub len: usize,
     pub styles: Vec<LineStyle>,
+    /// The part of the document the styles replace the styles of, or `None`
+    /// when they are for all of it.
+    pub range: Option<Range<usize>>,
+    /// The id to ask for the next styles as a delta of these with.
+    pub result_id: Option<String>,
 }
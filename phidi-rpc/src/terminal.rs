@@ -24,3 +24,21 @@ pub struct TerminalProfile {
 }
 
 impl TerminalProfile {}
+
+/// The outcome of a command run with `ProxyRequest::RunCommand`.
+#[derive(Eq, PartialEq, Clone, Debug, Serialize, Deserialize, Default)]
+pub struct CommandResult {
+    /// `None` if the process was killed, e.g. because it timed out.
+    pub exit_code: Option<i32>,
+    /// The output of the command, stripped of terminal escape sequences.
+    pub stdout: String,
+    /// Always empty for commands run in a visible terminal, where both
+    /// streams end up in `stdout`.
+    pub stderr: String,
+    pub duration_ms: u64,
+    pub timed_out: bool,
+    /// Whether the start of `stdout` was dropped because it was too long.
+    pub stdout_truncated: bool,
+    /// Whether the start of `stderr` was dropped because it was too long.
+    pub stderr_truncated: bool,
+}